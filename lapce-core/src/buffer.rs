@@ -813,6 +813,79 @@ impl Buffer {
             .slice_to_cow(self.offset_of_line(line)..self.offset_of_line(line + 1))
     }
 
+    /// The edits which clean up the whitespace of the buffer before it's
+    /// saved. Lines which don't need any changes are left untouched.
+    pub fn whitespace_cleanup_edits(
+        &self,
+        trim_trailing_whitespace: bool,
+        insert_final_newline: bool,
+        trim_final_newlines: bool,
+    ) -> Vec<(Selection, &'static str)> {
+        let is_trailing = |c: char| {
+            c == '\n'
+                || c == '\r'
+                || (trim_trailing_whitespace && (c == ' ' || c == '\t'))
+        };
+
+        // Everything after `content_end` belongs to the final newlines.
+        let mut content_end = 0;
+        for line in (0..=self.last_line()).rev() {
+            let line_content = self.line_content(line);
+            let trimmed = line_content.trim_end_matches(is_trailing);
+            if !trimmed.is_empty() {
+                content_end = self.offset_of_line(line) + trimmed.len();
+                break;
+            }
+        }
+        let tail = self.slice_to_cow(content_end..self.len());
+        let newlines = tail.matches('\n').count();
+        let new_newlines = if trim_final_newlines {
+            newlines.min(if content_end == 0 { 0 } else { 1 })
+        } else if insert_final_newline && content_end > 0 {
+            newlines.max(1)
+        } else {
+            newlines
+        };
+        // If the number of final newlines stays the same, the final lines
+        // are trimmed one by one like all the others.
+        let replace_tail = new_newlines != newlines;
+        let end = if replace_tail {
+            content_end
+        } else {
+            self.len()
+        };
+
+        let mut edits = Vec::new();
+        if trim_trailing_whitespace {
+            for line in 0..=self.line_of_offset(end) {
+                let line_start = self.offset_of_line(line);
+                let line_content = self.line_content(line);
+                let line_content =
+                    line_content.trim_end_matches(|c| c == '\n' || c == '\r');
+                let trimmed =
+                    line_content.trim_end_matches(|c| c == ' ' || c == '\t');
+                let start = line_start + trimmed.len();
+                if trimmed.len() < line_content.len() && start < end {
+                    edits.push((
+                        Selection::region(start, line_start + line_content.len()),
+                        "",
+                    ));
+                }
+            }
+        }
+
+        if replace_tail {
+            let line_ending = if self.line_content(0).ends_with("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            };
+            let new_tail = if new_newlines == 0 { "" } else { line_ending };
+            edits.push((Selection::region(content_end, self.len()), new_tail));
+        }
+        edits
+    }
+
     pub fn prev_grapheme_offset(
         &self,
         offset: usize,
@@ -15,6 +15,35 @@ mod editing {
         buffer.do_undo();
         assert!(buffer.is_pristine());
     }
+
+    fn cleanup(
+        text: &str,
+        trim_trailing_whitespace: bool,
+        insert_final_newline: bool,
+        trim_final_newlines: bool,
+    ) -> String {
+        let mut buffer = Buffer::new(text);
+        let edits = buffer.whitespace_cleanup_edits(
+            trim_trailing_whitespace,
+            insert_final_newline,
+            trim_final_newlines,
+        );
+        buffer.edit(&edits, EditType::Other);
+        buffer.text().to_string()
+    }
+
+    #[test]
+    fn whitespace_cleanup() {
+        assert_eq!(cleanup("a  \nb\t\n\n\n", true, true, true), "a\nb\n");
+        assert_eq!(cleanup("a  \n  \n", true, false, false), "a\n\n");
+        assert_eq!(cleanup("a  ", false, true, false), "a  \n");
+        assert_eq!(cleanup("a  ", true, true, false), "a\n");
+        assert_eq!(cleanup("a\n\n\n", false, false, true), "a\n");
+        assert_eq!(cleanup("a\r\nb", false, true, false), "a\r\nb\r\n");
+
+        let buffer = Buffer::new("a\nb\n");
+        assert!(buffer.whitespace_cleanup_edits(true, true, true).is_empty());
+    }
 }
 
 mod motion {
@@ -62,10 +62,23 @@ pub enum EditCommand {
     #[strum(message = "Paste")]
     #[strum(serialize = "clipboard_paste")]
     ClipboardPaste,
+    #[strum(message = "Paste Without Formatting")]
+    #[strum(serialize = "clipboard_paste_without_formatting")]
+    ClipboardPasteWithoutFormatting,
     #[strum(serialize = "yank")]
     Yank,
     #[strum(serialize = "paste")]
     Paste,
+    #[strum(message = "Increment Number")]
+    #[strum(serialize = "increment_number")]
+    IncrementNumber,
+    #[strum(message = "Decrement Number")]
+    #[strum(serialize = "decrement_number")]
+    DecrementNumber,
+    #[strum(serialize = "increment_number_sequence")]
+    IncrementNumberSequence,
+    #[strum(serialize = "decrement_number_sequence")]
+    DecrementNumberSequence,
 
     #[strum(serialize = "normal_mode")]
     NormalMode,
@@ -244,6 +257,16 @@ pub enum FocusCommand {
     ShowCodeActions,
     #[strum(serialize = "get_completion")]
     GetCompletion,
+    #[strum(message = "Trigger Parameter Hints")]
+    #[strum(serialize = "get_signature_help")]
+    GetSignatureHelp,
+    #[strum(serialize = "next_signature")]
+    NextSignature,
+    #[strum(serialize = "previous_signature")]
+    PreviousSignature,
+    #[strum(message = "Insert Inlay Hint")]
+    #[strum(serialize = "insert_inlay_hint")]
+    InsertInlayHint,
     /// This will close a modal, such as the settings window or completion
     #[strum(message = "Close Modal")]
     #[strum(serialize = "modal.close")]
@@ -254,6 +277,45 @@ pub enum FocusCommand {
     #[strum(message = "Go to Type Definition")]
     #[strum(serialize = "goto_type_definition")]
     GotoTypeDefinition,
+    #[strum(message = "Go to Implementation")]
+    #[strum(serialize = "goto_implementation")]
+    GotoImplementation,
+    #[strum(message = "Go to Declaration")]
+    #[strum(serialize = "goto_declaration")]
+    GotoDeclaration,
+    #[strum(message = "Find References")]
+    #[strum(serialize = "get_references")]
+    GetReferences,
+    #[strum(message = "Peek Definition")]
+    #[strum(serialize = "peek_definition")]
+    PeekDefinition,
+    #[strum(message = "Peek Implementation")]
+    #[strum(serialize = "peek_implementation")]
+    PeekImplementation,
+    #[strum(message = "Peek References")]
+    #[strum(serialize = "peek_references")]
+    PeekReferences,
+    #[strum(serialize = "next_peek_location")]
+    NextPeekLocation,
+    #[strum(serialize = "previous_peek_location")]
+    PreviousPeekLocation,
+    #[strum(serialize = "open_peek_location")]
+    OpenPeekLocation,
+    #[strum(message = "Rename Symbol")]
+    #[strum(serialize = "rename_symbol")]
+    Rename,
+    #[strum(message = "Show Incoming Calls")]
+    #[strum(serialize = "show_incoming_calls")]
+    ShowIncomingCalls,
+    #[strum(message = "Show Outgoing Calls")]
+    #[strum(serialize = "show_outgoing_calls")]
+    ShowOutgoingCalls,
+    #[strum(message = "Show Supertypes")]
+    #[strum(serialize = "show_supertypes")]
+    ShowSupertypes,
+    #[strum(message = "Show Subtypes")]
+    #[strum(serialize = "show_subtypes")]
+    ShowSubtypes,
     #[strum(serialize = "jump_location_backward")]
     JumpLocationBackward,
     #[strum(serialize = "jump_location_forward")]
@@ -263,6 +325,12 @@ pub enum FocusCommand {
     NextError,
     #[strum(serialize = "previous_error")]
     PreviousError,
+    #[strum(message = "Go to Next Occurrence")]
+    #[strum(serialize = "next_occurrence")]
+    NextOccurrence,
+    #[strum(message = "Go to Previous Occurrence")]
+    #[strum(serialize = "previous_occurrence")]
+    PreviousOccurrence,
     #[strum(message = "Go to Next Difference")]
     #[strum(serialize = "next_diff")]
     NextDiff,
@@ -275,6 +343,9 @@ pub enum FocusCommand {
     #[strum(serialize = "format_document")]
     #[strum(message = "Format Document")]
     FormatDocument,
+    #[strum(serialize = "format_selection")]
+    #[strum(message = "Format Selection")]
+    FormatSelection,
     #[strum(serialize = "search")]
     Search,
     #[strum(serialize = "inline_find_right")]
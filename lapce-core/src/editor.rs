@@ -7,6 +7,7 @@ use crate::{
     buffer::{Buffer, InvalLines},
     command::EditCommand,
     cursor::{get_first_selection_after, Cursor, CursorMode},
+    increment::increment_in_line,
     mode::{Mode, MotionMode, VisualMode},
     register::{Clipboard, Register, RegisterData, RegisterKind},
     selection::{InsertDrift, SelRegion, Selection},
@@ -46,6 +47,18 @@ impl EditType {
     }
 }
 
+/// How an edit command is run
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EditOptions {
+    /// Whether the editor is in modal, i.e. vim, mode
+    pub modal: bool,
+    /// The count typed before the command, for the commands which repeat
+    pub count: Option<usize>,
+    /// Whether multi-line pastes are re-indented to the indentation of the
+    /// line they're pasted on
+    pub reindent_paste: bool,
+}
+
 pub struct Editor {}
 
 impl Editor {
@@ -404,6 +417,7 @@ impl Editor {
         cursor: &mut Cursor,
         buffer: &mut Buffer,
         data: &RegisterData,
+        reindent: bool,
     ) -> Vec<(RopeDelta, InvalLines)> {
         let mut deltas = Vec::new();
         match data.mode {
@@ -419,8 +433,21 @@ impl Editor {
                     }
                 };
                 let after = cursor.is_insert() || !data.content.contains('\n');
-                let (delta, inval_lines) =
-                    buffer.edit(&[(&selection, &data.content)], EditType::Paste);
+                let edits = Self::paste_edits(
+                    buffer,
+                    &selection,
+                    &data.content,
+                    false,
+                    None,
+                    reindent,
+                );
+                let (delta, inval_lines) = buffer.edit(
+                    &edits
+                        .iter()
+                        .map(|(selection, content)| (selection, content.as_str()))
+                        .collect::<Vec<_>>(),
+                    EditType::Paste,
+                );
                 let selection =
                     selection.apply_delta(&delta, after, InsertDrift::Default);
                 deltas.push((delta, inval_lines));
@@ -443,11 +470,11 @@ impl Editor {
                 }
             }
             VisualMode::Linewise | VisualMode::Blockwise => {
-                let (selection, content) = match &cursor.mode {
+                let (selection, content, indent_line) = match &cursor.mode {
                     CursorMode::Normal(offset) => {
                         let line = buffer.line_of_offset(*offset);
                         let offset = buffer.offset_of_line(line + 1);
-                        (Selection::caret(offset), data.content.clone())
+                        (Selection::caret(offset), data.content.clone(), Some(line))
                     }
                     CursorMode::Insert(selection) => {
                         let mut selection = selection.clone();
@@ -459,7 +486,7 @@ impl Editor {
                                 region.end = start;
                             }
                         }
-                        (selection, data.content.clone())
+                        (selection, data.content.clone(), None)
                     }
                     CursorMode::Visual { mode, .. } => {
                         let selection = cursor.edit_selection(buffer);
@@ -467,11 +494,27 @@ impl Editor {
                             VisualMode::Linewise => data.content.clone(),
                             _ => "\n".to_string() + &data.content,
                         };
-                        (selection, data)
+                        (selection, data, None)
                     }
                 };
-                let (delta, inval_lines) =
-                    buffer.edit(&[(&selection, &content)], EditType::Paste);
+                // Pasting over a characterwise selection puts the lines after
+                // a newline, which is handled like a characterwise paste.
+                let linewise = !content.starts_with('\n');
+                let edits = Self::paste_edits(
+                    buffer,
+                    &selection,
+                    &content,
+                    linewise,
+                    indent_line,
+                    reindent,
+                );
+                let (delta, inval_lines) = buffer.edit(
+                    &edits
+                        .iter()
+                        .map(|(selection, content)| (selection, content.as_str()))
+                        .collect::<Vec<_>>(),
+                    EditType::Paste,
+                );
                 let selection = selection.apply_delta(
                     &delta,
                     cursor.is_insert(),
@@ -499,6 +542,39 @@ impl Editor {
         deltas
     }
 
+    /// The edits for pasting `content` over `selection`. When `reindent` is
+    /// set, multi-line content is re-indented for each region to match the
+    /// indentation of its line, or of `indent_line` if given.
+    fn paste_edits(
+        buffer: &Buffer,
+        selection: &Selection,
+        content: &str,
+        linewise: bool,
+        indent_line: Option<usize>,
+        reindent: bool,
+    ) -> Vec<(Selection, String)> {
+        if !reindent || !content.trim_end().contains('\n') {
+            return vec![(selection.clone(), content.to_string())];
+        }
+
+        let style = crate::indent::IndentStyle::from_str(buffer.indent_unit());
+        selection
+            .regions()
+            .iter()
+            .map(|region| {
+                let line = indent_line
+                    .unwrap_or_else(|| buffer.line_of_offset(region.min()));
+                let indent = buffer.indent_on_line(line);
+                let at_indent =
+                    region.min() <= buffer.offset_of_line(line) + indent.len();
+                let content = crate::indent::reindent(
+                    content, &indent, style, linewise, at_indent,
+                );
+                (Selection::region(region.start, region.end), content)
+            })
+            .collect()
+    }
+
     fn do_indent(
         buffer: &mut Buffer,
         selection: Selection,
@@ -572,16 +648,123 @@ impl Editor {
         buffer.edit(&edits, EditType::Outdent)
     }
 
+    /// Add `amount` to the first number, date or boolean at or after each
+    /// cursor on its line. In visual mode every line of the selection is
+    /// changed, and with `sequence` the nth match gets `n * amount` added.
+    pub fn do_increment(
+        cursor: &mut Cursor,
+        buffer: &mut Buffer,
+        amount: i64,
+        sequence: bool,
+    ) -> Vec<(RopeDelta, InvalLines)> {
+        let mut edits = Vec::new();
+        let mut add_edit = |start: usize, end: usize, col: usize, amount: i64| {
+            let content = buffer.slice_to_cow(start..end);
+            if let Some((s, e, new_text)) = increment_in_line(&content, col, amount)
+            {
+                edits.push((Selection::region(start + s, start + e), new_text));
+                true
+            } else {
+                false
+            }
+        };
+
+        match &cursor.mode {
+            CursorMode::Normal(offset) => {
+                let line = buffer.line_of_offset(*offset);
+                let start = buffer.offset_of_line(line);
+                let end = buffer.line_end_offset(line, true);
+                add_edit(start, end, offset - start, amount);
+            }
+            CursorMode::Insert(selection) => {
+                for region in selection.regions() {
+                    let line = buffer.line_of_offset(region.end);
+                    let start = buffer.offset_of_line(line);
+                    let end = buffer.line_end_offset(line, true);
+                    add_edit(start, end, region.end - start, amount);
+                }
+            }
+            CursorMode::Visual { .. } => {
+                let mut n = 1;
+                for region in cursor.edit_selection(buffer).regions() {
+                    let start_line = buffer.line_of_offset(region.min());
+                    let end_line = buffer.line_of_offset(region.max());
+                    for line in start_line..=end_line {
+                        let start = buffer.offset_of_line(line).max(region.min());
+                        let end =
+                            buffer.line_end_offset(line, true).min(region.max());
+                        if start >= end {
+                            continue;
+                        }
+                        let amount = if sequence {
+                            amount.saturating_mul(n)
+                        } else {
+                            amount
+                        };
+                        if add_edit(start, end, 0, amount) {
+                            n += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Several carets can be on the same number, and the edits have to be
+        // sorted and can't overlap.
+        edits.sort_by_key(|(selection, _)| selection.min_offset());
+        let mut last_end = None;
+        edits.retain(|(selection, _)| {
+            if last_end.map_or(false, |end| selection.min_offset() < end) {
+                return false;
+            }
+            last_end = Some(selection.max_offset());
+            true
+        });
+
+        if edits.is_empty() {
+            return vec![];
+        }
+
+        let edits = edits
+            .iter()
+            .map(|(selection, content)| (selection, content.as_str()))
+            .collect::<Vec<_>>();
+        let (delta, inval_lines) = buffer.edit(&edits, EditType::Other);
+        match cursor.mode.clone() {
+            CursorMode::Normal(_) => {
+                // Vim leaves the cursor on the last character of the number.
+                let (selection, new_text) = &edits[0];
+                let offset = selection.min_offset() + new_text.len() - 1;
+                cursor.mode = CursorMode::Normal(offset);
+            }
+            CursorMode::Insert(_) => {
+                cursor.apply_delta(&delta);
+            }
+            CursorMode::Visual { .. } => {
+                cursor.apply_delta(&delta);
+                if let CursorMode::Visual { start, end, .. } = cursor.mode {
+                    cursor.mode = CursorMode::Normal(start.min(end));
+                }
+            }
+        }
+        vec![(delta, inval_lines)]
+    }
+
     pub fn do_edit<T: Clipboard>(
         cursor: &mut Cursor,
         buffer: &mut Buffer,
         cmd: &EditCommand,
         syntax: Option<&Syntax>,
         clipboard: &mut T,
-        modal: bool,
         register: &mut Register,
+        options: EditOptions,
     ) -> Vec<(RopeDelta, InvalLines)> {
         use crate::command::EditCommand::*;
+        let EditOptions {
+            modal,
+            count,
+            reindent_paste,
+        } = options;
         match cmd {
             MoveLineUp => {
                 let mut deltas = Vec::new();
@@ -894,7 +1077,7 @@ impl Editor {
                         VisualMode::Normal
                     };
                     let data = RegisterData { content: s, mode };
-                    Self::do_paste(cursor, buffer, &data)
+                    Self::do_paste(cursor, buffer, &data, reindent_paste)
                 } else {
                     vec![]
                 }
@@ -915,9 +1098,38 @@ impl Editor {
                 }
                 vec![]
             }
+            ClipboardPasteWithoutFormatting => {
+                if let Some(s) = clipboard.get_string() {
+                    let mode = if s.ends_with('\n') {
+                        VisualMode::Linewise
+                    } else {
+                        VisualMode::Normal
+                    };
+                    let data = RegisterData { content: s, mode };
+                    Self::do_paste(cursor, buffer, &data, false)
+                } else {
+                    vec![]
+                }
+            }
             Paste => {
                 let data = register.unnamed.clone();
-                Self::do_paste(cursor, buffer, &data)
+                Self::do_paste(cursor, buffer, &data, reindent_paste)
+            }
+            IncrementNumber => {
+                let amount = count.unwrap_or(1) as i64;
+                Self::do_increment(cursor, buffer, amount, false)
+            }
+            DecrementNumber => {
+                let amount = count.unwrap_or(1) as i64;
+                Self::do_increment(cursor, buffer, -amount, false)
+            }
+            IncrementNumberSequence => {
+                let amount = count.unwrap_or(1) as i64;
+                Self::do_increment(cursor, buffer, amount, true)
+            }
+            DecrementNumberSequence => {
+                let amount = count.unwrap_or(1) as i64;
+                Self::do_increment(cursor, buffer, -amount, true)
             }
             NewLineAbove => {
                 let offset = cursor.offset();
@@ -1258,6 +1470,8 @@ mod test {
     use crate::buffer::Buffer;
     use crate::cursor::{Cursor, CursorMode};
     use crate::editor::Editor;
+    use crate::mode::VisualMode;
+    use crate::register::RegisterData;
     use crate::selection::{SelRegion, Selection};
 
     #[test]
@@ -1313,4 +1527,116 @@ mod test {
         Editor::insert(&mut cursor, &mut buffer, "}", None);
         assert_eq!("a{} bc\ne{} fg\n", buffer.slice_to_cow(0..buffer.len()));
     }
+
+    #[test]
+    fn test_increment_normal() {
+        let mut buffer = Buffer::new("let x = 0x0f;\nlet y = -1;\n");
+        let mut cursor = Cursor::new(CursorMode::Normal(0), None, None);
+
+        Editor::do_increment(&mut cursor, &mut buffer, 1, false);
+        assert_eq!(
+            "let x = 0x10;\nlet y = -1;\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(CursorMode::Normal(11), cursor.mode);
+
+        cursor.mode = CursorMode::Normal(14);
+        Editor::do_increment(&mut cursor, &mut buffer, 3, false);
+        assert_eq!(
+            "let x = 0x10;\nlet y = 2;\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(CursorMode::Normal(22), cursor.mode);
+
+        // Nothing after the cursor on this line.
+        cursor.mode = CursorMode::Normal(23);
+        let deltas = Editor::do_increment(&mut cursor, &mut buffer, 1, false);
+        assert!(deltas.is_empty());
+    }
+
+    #[test]
+    fn test_increment_visual_sequence() {
+        let mut buffer = Buffer::new("a[0]\na[0]\nb\na[0]\n");
+        let mut cursor = Cursor::new(
+            CursorMode::Visual {
+                start: 0,
+                end: 15,
+                mode: VisualMode::Linewise,
+            },
+            None,
+            None,
+        );
+
+        Editor::do_increment(&mut cursor, &mut buffer, 1, true);
+        assert_eq!(
+            "a[1]\na[2]\nb\na[3]\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(CursorMode::Normal(0), cursor.mode);
+
+        cursor.mode = CursorMode::Visual {
+            start: 0,
+            end: 15,
+            mode: VisualMode::Linewise,
+        };
+        Editor::do_increment(&mut cursor, &mut buffer, -1, false);
+        assert_eq!(
+            "a[0]\na[1]\nb\na[2]\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+    }
+
+    #[test]
+    fn test_increment_multiple_carets() {
+        let mut buffer = Buffer::new("a = 1 + 2;\nb = 9;\n");
+        let mut selection = Selection::new();
+        // Two carets on the `1` and one on the `2` of the same line.
+        selection.add_region(SelRegion::caret(4));
+        selection.add_region(SelRegion::caret(5));
+        selection.add_region(SelRegion::caret(0));
+        selection.add_region(SelRegion::caret(15));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+
+        Editor::do_increment(&mut cursor, &mut buffer, 1, false);
+        assert_eq!(
+            "a = 2 + 3;\nb = 10;\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+    }
+
+    #[test]
+    fn test_paste_reindent() {
+        let mut buffer = Buffer::new("fn main() {\n    \n}\n");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(16)), None, None);
+        let data = RegisterData {
+            content: "if a {\n\tb();\n}".to_string(),
+            mode: VisualMode::Normal,
+        };
+        Editor::do_paste(&mut cursor, &mut buffer, &data, true);
+        assert_eq!(
+            "fn main() {\n    if a {\n        b();\n    }\n}\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+
+        let mut buffer = Buffer::new("fn main() {\n    let a = 1;\n}\n");
+        let mut cursor = Cursor::new(CursorMode::Normal(16), None, None);
+        let data = RegisterData {
+            content: "        foo();\n            bar();\n".to_string(),
+            mode: VisualMode::Linewise,
+        };
+        Editor::do_paste(&mut cursor, &mut buffer, &data, true);
+        assert_eq!(
+            "fn main() {\n    let a = 1;\n    foo();\n        bar();\n}\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+
+        let mut buffer = Buffer::new("fn main() {\n    let a = 1;\n}\n");
+        let mut cursor = Cursor::new(CursorMode::Normal(16), None, None);
+        Editor::do_paste(&mut cursor, &mut buffer, &data, false);
+        assert_eq!(
+            "fn main() {\n    let a = 1;\n        foo();\n            bar();\n}\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+    }
 }
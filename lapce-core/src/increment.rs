@@ -0,0 +1,391 @@
+//! Finding and incrementing numbers, dates and booleans within a line, as done
+//! by `<C-a>`/`<C-x>` in modal editors.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Decimal,
+    Hex,
+    Binary,
+    Boolean,
+    Date,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+/// Find the first incrementable token in `line` which ends after `col`, and
+/// return its byte range within the line together with the replacement text
+/// after adding `amount` to it.
+///
+/// A token is a decimal number (optionally negative), a `0x` hexadecimal or
+/// `0b` binary number, a `YYYY-MM-DD` date or a boolean literal. The date
+/// component under `col` is the one that changes, defaulting to the day.
+pub fn increment_in_line(
+    line: &str,
+    col: usize,
+    amount: i64,
+) -> Option<(usize, usize, String)> {
+    let token = find_token(line, col)?;
+    let text = &line[token.start..token.end];
+    let new_text = match token.kind {
+        TokenKind::Decimal => increment_decimal(text, amount)?,
+        TokenKind::Hex => increment_radix(text, 16, amount)?,
+        TokenKind::Binary => increment_radix(text, 2, amount)?,
+        TokenKind::Boolean => toggle_boolean(text, amount),
+        TokenKind::Date => {
+            increment_date(text, col.checked_sub(token.start), amount)?
+        }
+    };
+    Some((token.start, token.end, new_text))
+}
+
+fn find_token(line: &str, col: usize) -> Option<Token> {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let token = token_at(bytes, i);
+        match token {
+            Some(token) => {
+                if token.end > col {
+                    return Some(token);
+                }
+                i = token.end;
+            }
+            None => {
+                // Skip over the rest of an identifier so that e.g. the `b`
+                // in `ab0` isn't treated as the start of a word.
+                if is_word_byte(bytes[i]) && !bytes[i].is_ascii_digit() {
+                    while i < bytes.len()
+                        && is_word_byte(bytes[i])
+                        && !bytes[i].is_ascii_digit()
+                    {
+                        i += 1;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+    None
+}
+
+fn token_at(bytes: &[u8], i: usize) -> Option<Token> {
+    let prev = if i > 0 { Some(bytes[i - 1]) } else { None };
+
+    if prev.map(|b| !is_word_byte(b)).unwrap_or(true) {
+        if let Some(end) = date_end(bytes, i) {
+            return Some(Token {
+                kind: TokenKind::Date,
+                start: i,
+                end,
+            });
+        }
+        for word in ["true", "false", "True", "False", "TRUE", "FALSE"] {
+            let end = i + word.len();
+            if bytes[i..].starts_with(word.as_bytes())
+                && bytes.get(end).map(|b| !is_word_byte(*b)).unwrap_or(true)
+            {
+                return Some(Token {
+                    kind: TokenKind::Boolean,
+                    start: i,
+                    end,
+                });
+            }
+        }
+    }
+
+    if bytes[i] == b'-' {
+        if bytes
+            .get(i + 1)
+            .map(|b| b.is_ascii_digit())
+            .unwrap_or(false)
+            && prev.map(|b| !b.is_ascii_alphanumeric()).unwrap_or(true)
+        {
+            let mut token = token_at(bytes, i + 1)?;
+            if token.kind == TokenKind::Decimal {
+                token.start = i;
+            }
+            return Some(token);
+        }
+        return None;
+    }
+
+    if !bytes[i].is_ascii_digit() {
+        return None;
+    }
+
+    if bytes[i] == b'0' {
+        let radix = match bytes.get(i + 1) {
+            Some(b'x' | b'X') => Some((TokenKind::Hex, 16)),
+            Some(b'b' | b'B') => Some((TokenKind::Binary, 2)),
+            _ => None,
+        };
+        if let Some((kind, radix)) = radix {
+            let end = digits_end(bytes, i + 2, radix);
+            if end > i + 2 {
+                return Some(Token {
+                    kind,
+                    start: i,
+                    end,
+                });
+            }
+        }
+    }
+
+    Some(Token {
+        kind: TokenKind::Decimal,
+        start: i,
+        end: digits_end(bytes, i, 10),
+    })
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn digits_end(bytes: &[u8], start: usize, radix: u32) -> usize {
+    let mut end = start;
+    while end < bytes.len() && (bytes[end] as char).is_digit(radix) {
+        end += 1;
+    }
+    end
+}
+
+/// Returns the end of a valid `YYYY-MM-DD` date starting at `i`, if there is
+/// one.
+fn date_end(bytes: &[u8], i: usize) -> Option<usize> {
+    let end = i + 10;
+    let date = bytes.get(i..end)?;
+    let is_date = date.iter().enumerate().all(|(j, b)| match j {
+        4 | 7 => *b == b'-',
+        _ => b.is_ascii_digit(),
+    });
+    if !is_date || bytes.get(end).map(|b| b.is_ascii_digit()).unwrap_or(false) {
+        return None;
+    }
+    let (year, month, day) = parse_date(std::str::from_utf8(date).ok()?)?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(end)
+}
+
+fn increment_decimal(text: &str, amount: i64) -> Option<String> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value: i128 = digits.parse().ok()?;
+    let value = if negative { -value } else { value };
+    let value = value.checked_add(amount as i128)?;
+
+    // Leading zeros mean the number has a fixed width, which is kept.
+    let width = if digits.len() > 1 && digits.starts_with('0') {
+        digits.len()
+    } else {
+        0
+    };
+    let sign = if value < 0 { "-" } else { "" };
+    Some(format!("{sign}{:0width$}", value.unsigned_abs()))
+}
+
+/// Hexadecimal and binary numbers are unsigned and wrap around at 64 bits,
+/// keeping their prefix, digit count and letter case.
+fn increment_radix(text: &str, radix: u32, amount: i64) -> Option<String> {
+    let (prefix, digits) = text.split_at(2);
+    let value = u64::from_str_radix(digits, radix).ok()?;
+    let value = value.wrapping_add(amount as u64);
+    let width = digits.len();
+    let new_digits = match radix {
+        16 if digits.chars().any(|c| c.is_ascii_uppercase()) => {
+            format!("{value:0width$X}")
+        }
+        16 => format!("{value:0width$x}"),
+        _ => format!("{value:0width$b}"),
+    };
+    Some(format!("{prefix}{new_digits}"))
+}
+
+fn toggle_boolean(text: &str, amount: i64) -> String {
+    if amount % 2 == 0 {
+        return text.to_string();
+    }
+    match text {
+        "true" => "false",
+        "false" => "true",
+        "True" => "False",
+        "False" => "True",
+        "TRUE" => "FALSE",
+        _ => "TRUE",
+    }
+    .to_string()
+}
+
+fn parse_date(text: &str) -> Option<(i64, i64, i64)> {
+    let year = text[0..4].parse().ok()?;
+    let month = text[5..7].parse().ok()?;
+    let day = text[8..10].parse().ok()?;
+    Some((year, month, day))
+}
+
+/// Increments the year, month or day of a date depending on which of them is
+/// at `col`. A cursor before the date changes the day.
+fn increment_date(text: &str, col: Option<usize>, amount: i64) -> Option<String> {
+    let (year, month, day) = parse_date(text)?;
+    let (year, month, day) = match col.unwrap_or(usize::MAX) {
+        0..=4 => {
+            let year = year.checked_add(amount)?;
+            (year, month, day.min(days_in_month(year, month)))
+        }
+        5..=7 => {
+            let months = (year * 12 + month - 1).checked_add(amount)?;
+            let (year, month) = (months.div_euclid(12), months.rem_euclid(12) + 1);
+            (year, month, day.min(days_in_month(year, month)))
+        }
+        _ => {
+            civil_from_days(days_from_civil(year, month, day).checked_add(amount)?)?
+        }
+    };
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Number of days since 1970-01-01 of the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> Option<(i64, i64, i64)> {
+    let days = days.checked_add(719468)?;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    Some((year, month, day))
+}
+
+#[cfg(test)]
+mod test {
+    use super::increment_in_line;
+
+    fn increment(line: &str, col: usize, amount: i64) -> Option<String> {
+        increment_in_line(line, col, amount).map(|(start, end, new_text)| {
+            format!("{}{}{}", &line[..start], new_text, &line[end..])
+        })
+    }
+
+    #[test]
+    fn test_increment_decimal() {
+        assert_eq!(increment("a 41 b", 0, 1).unwrap(), "a 42 b");
+        assert_eq!(increment("a 41 b", 3, 1).unwrap(), "a 42 b");
+        assert_eq!(increment("a 41 b", 4, 1), None);
+        assert_eq!(increment("10 20", 2, 5).unwrap(), "10 25");
+        assert_eq!(increment("9", 0, 1).unwrap(), "10");
+        assert_eq!(increment("x = 5;", 0, -7).unwrap(), "x = -2;");
+    }
+
+    #[test]
+    fn test_increment_negative() {
+        assert_eq!(increment("-1", 0, 1).unwrap(), "0");
+        assert_eq!(increment("-1", 1, 1).unwrap(), "0");
+        assert_eq!(increment("0", 0, -1).unwrap(), "-1");
+        assert_eq!(increment("x = -3", 0, -1).unwrap(), "x = -4");
+        assert_eq!(increment("(-10)", 0, 15).unwrap(), "(5)");
+        // A dash after a word is a separator, not a sign.
+        assert_eq!(increment("item-1", 0, 1).unwrap(), "item-2");
+    }
+
+    #[test]
+    fn test_increment_leading_zeros() {
+        assert_eq!(increment("007", 0, 1).unwrap(), "008");
+        assert_eq!(increment("009", 0, 1).unwrap(), "010");
+        assert_eq!(increment("099", 0, 1).unwrap(), "100");
+        assert_eq!(increment("0999", 0, 1).unwrap(), "1000");
+        assert_eq!(increment("010", 0, -11).unwrap(), "-001");
+        assert_eq!(increment("0", 0, 1).unwrap(), "1");
+    }
+
+    #[test]
+    fn test_increment_hex() {
+        assert_eq!(increment("0x0f", 0, 1).unwrap(), "0x10");
+        assert_eq!(increment("0x0f", 3, 1).unwrap(), "0x10");
+        assert_eq!(increment("0x0F", 0, 1).unwrap(), "0x10");
+        assert_eq!(increment("0x0E", 0, 1).unwrap(), "0x0F");
+        assert_eq!(increment("0xff", 0, 1).unwrap(), "0x100");
+        assert_eq!(increment("0x00", 0, -1).unwrap(), "0xffffffffffffffff");
+        assert_eq!(
+            increment("0xffffffffffffffff", 0, 1).unwrap(),
+            "0x0000000000000000"
+        );
+        assert_eq!(increment("0X1a", 0, 16).unwrap(), "0X2a");
+    }
+
+    #[test]
+    fn test_increment_binary() {
+        assert_eq!(increment("0b0111", 0, 1).unwrap(), "0b1000");
+        assert_eq!(increment("0b1", 0, 1).unwrap(), "0b10");
+        assert_eq!(increment("0b10", 0, -2).unwrap(), "0b00");
+    }
+
+    #[test]
+    fn test_increment_in_identifier() {
+        assert_eq!(increment("utf8", 0, 1).unwrap(), "utf9");
+        assert_eq!(increment("ab0x1", 0, 1).unwrap(), "ab0x2");
+        assert_eq!(increment("foo", 0, 1), None);
+    }
+
+    #[test]
+    fn test_increment_boolean() {
+        assert_eq!(increment("x = true;", 0, 1).unwrap(), "x = false;");
+        assert_eq!(increment("False", 0, -1).unwrap(), "True");
+        assert_eq!(increment("TRUE", 0, 2).unwrap(), "TRUE");
+        assert_eq!(increment("untrue 1", 0, 1).unwrap(), "untrue 2");
+    }
+
+    #[test]
+    fn test_increment_date() {
+        assert_eq!(increment("2022-02-28", 0, 1).unwrap(), "2023-02-28");
+        assert_eq!(increment("2022-02-28", 9, 1).unwrap(), "2022-03-01");
+        assert_eq!(increment("2024-02-28", 9, 1).unwrap(), "2024-02-29");
+        assert_eq!(increment("2024-01-01", 8, -1).unwrap(), "2023-12-31");
+        assert_eq!(increment("2024-01-31", 5, 1).unwrap(), "2024-02-29");
+        assert_eq!(increment("2024-12-15", 6, 1).unwrap(), "2025-01-15");
+        assert_eq!(increment("2024-02-29", 2, 1).unwrap(), "2025-02-28");
+        assert_eq!(increment("on 2024-12-31.", 0, 1).unwrap(), "on 2025-01-01.");
+        // An invalid date falls back to the plain numbers.
+        assert_eq!(increment("2024-13-01", 0, 1).unwrap(), "2025-13-01");
+    }
+}
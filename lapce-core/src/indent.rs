@@ -87,6 +87,85 @@ pub fn create_outdent<'s>(
     Some((Selection::region(start, offset), ""))
 }
 
+/// Re-indents the lines of `text` so that the block sits at `indent`, keeping
+/// the relative indentation between its lines. The new indentation is written
+/// in `style`.
+///
+/// If the text isn't `linewise`, its first line is pasted after the existing
+/// content of a line, so it's only touched when `at_indent` says that there
+/// is nothing but indentation in front of it.
+pub fn reindent(
+    text: &str,
+    indent: &str,
+    style: IndentStyle,
+    linewise: bool,
+    at_indent: bool,
+) -> String {
+    let tab_width = match style {
+        IndentStyle::Spaces(n) => n as usize,
+        IndentStyle::Tabs => 4,
+    };
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let first_is_indented = linewise || text.starts_with(|c| c == ' ' || c == '\t');
+
+    let base = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, line)| (*i > 0 || first_is_indented) && !line.trim().is_empty())
+        .map(|(_, line)| indent_width(leading_whitespace(line), tab_width))
+        .min();
+    let base = match base {
+        Some(base) => base,
+        None => return text.to_string(),
+    };
+    let target = indent_width(indent, tab_width);
+
+    let mut result = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        let whitespace = leading_whitespace(line);
+        let content = &line[whitespace.len()..];
+        let width = indent_width(whitespace, tab_width).saturating_sub(base);
+        if i == 0 && !linewise {
+            if at_indent && first_is_indented {
+                result.push_str(&indent_string(width, style, tab_width));
+                result.push_str(content);
+            } else {
+                result.push_str(line);
+            }
+        } else if line.trim().is_empty() {
+            result.push_str(content);
+        } else {
+            result.push_str(&indent_string(target + width, style, tab_width));
+            result.push_str(content);
+        }
+    }
+    result
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    let end = line.find(|c| c != ' ' && c != '\t').unwrap_or(line.len());
+    &line[..end]
+}
+
+fn indent_width(indent: &str, tab_width: usize) -> usize {
+    indent.chars().fold(0, |width, c| {
+        if c == '\t' {
+            (width / tab_width + 1) * tab_width
+        } else {
+            width + 1
+        }
+    })
+}
+
+fn indent_string(width: usize, style: IndentStyle, tab_width: usize) -> String {
+    match style {
+        IndentStyle::Tabs => {
+            "\t".repeat(width / tab_width) + &" ".repeat(width % tab_width)
+        }
+        IndentStyle::Spaces(_) => " ".repeat(width),
+    }
+}
+
 /// Attempts to detect the indentation style used in a document.
 ///
 /// Returns the indentation style if the auto-detect confidence is
@@ -4,6 +4,7 @@ pub mod command;
 pub mod cursor;
 pub mod editor;
 pub mod encoding;
+pub mod increment;
 pub mod indent;
 pub mod language;
 pub mod lens;
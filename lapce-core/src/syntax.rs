@@ -19,6 +19,13 @@ use crate::{
     style::{Highlight, HighlightEvent, Highlighter, SCOPES},
 };
 
+/// The kinds of the opening and closing tags of the grammars with markup,
+/// which are HTML and JSX in JavaScript and TypeScript
+const TAG_KINDS: &[(&str, &str)] = &[
+    ("start_tag", "end_tag"),
+    ("jsx_opening_element", "jsx_closing_element"),
+];
+
 thread_local! {
    static PARSER: RefCell<HashMap<LapceLanguage, Parser>> = RefCell::new(HashMap::new());
    static HIGHLIGHTS: RefCell<HashMap<LapceLanguage, crate::style::HighlightConfiguration>> = RefCell::new(HashMap::new());
@@ -333,6 +340,118 @@ impl Syntax {
         None
     }
 
+    /// The ranges of the name of the tag at `offset` and of the name of its
+    /// matching opening or closing tag, which are renamed together when no
+    /// language server provides linked editing ranges
+    pub fn find_linked_tag_names(
+        &self,
+        offset: usize,
+    ) -> Option<Vec<(usize, usize)>> {
+        let tree = self.tree.as_ref()?;
+        let mut node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+        let (tag, matching_kind, previous) = loop {
+            if let Some((open, close)) = TAG_KINDS
+                .iter()
+                .find(|(open, close)| node.kind() == *open || node.kind() == *close)
+            {
+                let previous = node.kind() == *close;
+                break (node, if previous { *open } else { *close }, previous);
+            }
+            node = node.parent()?;
+        };
+
+        // The cursor can be right before or after the name
+        let name = Self::tag_name(tag)?;
+        if offset < name.start_byte() || offset > name.end_byte() {
+            return None;
+        }
+
+        // The matching tag has to be of the same element, rather than of one
+        // that it's nested in
+        let matching_offset =
+            self.find_tag(name.start_byte(), previous, matching_kind)?;
+        let element = tag.parent()?;
+        let matching = (0..element.child_count())
+            .filter_map(|i| element.child(i))
+            .find(|child| {
+                child.kind() == matching_kind
+                    && child.start_byte() == matching_offset
+            })?;
+        let matching_name = Self::tag_name(matching)?;
+
+        let mut ranges = vec![
+            (name.start_byte(), name.end_byte()),
+            (matching_name.start_byte(), matching_name.end_byte()),
+        ];
+        ranges.sort_unstable();
+        Some(ranges)
+    }
+
+    /// The name of a tag, which is a `tag_name` node in HTML and the `name`
+    /// field in JSX
+    fn tag_name(tag: Node) -> Option<Node> {
+        tag.child_by_field_name("name")
+            .or_else(|| tag.named_child(0))
+    }
+
+    /// Find the identifiers in `range` that have the same text as the
+    /// identifier at `offset`, which is used when no language server can
+    /// highlight the occurrences of a symbol
+    pub fn find_identifier_occurrences(
+        &self,
+        offset: usize,
+        range: std::ops::Range<usize>,
+    ) -> Vec<(usize, usize)> {
+        let node = match self.identifier_at(offset).or_else(|| {
+            // The cursor can be right after the end of the identifier
+            offset
+                .checked_sub(1)
+                .and_then(|offset| self.identifier_at(offset))
+        }) {
+            Some(node) => node,
+            None => return Vec::new(),
+        };
+        let word = self.text.slice_to_cow(node.start_byte()..node.end_byte());
+
+        let mut occurrences = Vec::new();
+        let mut cursor = self.tree.as_ref().unwrap().walk();
+        loop {
+            let node = cursor.node();
+            if node.end_byte() > range.start && node.start_byte() < range.end {
+                if Self::is_identifier(&node) {
+                    if self.text.slice_to_cow(node.start_byte()..node.end_byte())
+                        == word
+                    {
+                        occurrences.push((node.start_byte(), node.end_byte()));
+                    }
+                } else if cursor.goto_first_child() {
+                    continue;
+                }
+            }
+
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return occurrences;
+                }
+            }
+        }
+    }
+
+    fn identifier_at(&self, offset: usize) -> Option<Node> {
+        let node = self
+            .tree
+            .as_ref()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset + 1)?;
+        Self::is_identifier(&node).then_some(node)
+    }
+
+    /// Grammars name their identifiers like `identifier`, `field_identifier`
+    /// or `type_identifier`
+    fn is_identifier(node: &Node) -> bool {
+        node.child_count() == 0 && node.kind().ends_with("identifier")
+    }
+
     fn find_tag_in_siblings(
         &self,
         node: Node,
@@ -455,6 +574,90 @@ mod tests {
         assert_eq!(33, lens.height_of_line(5));
     }
 
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_find_identifier_occurrences() {
+        let text =
+            "fn foo(a: usize) -> usize {\n    let b = a + 1;\n    foo(b + a)\n}\n";
+        let syntax = Syntax::from_language(LapceLanguage::Rust).parse(
+            0,
+            Rope::from(text),
+            None,
+        );
+
+        // The cursor is on the `a` parameter
+        let occurrences = syntax.find_identifier_occurrences(7, 0..text.len());
+        assert_eq!(occurrences, vec![(7, 8), (40, 41), (59, 60)]);
+
+        // The cursor is right after `foo` in the call
+        let occurrences = syntax.find_identifier_occurrences(54, 0..text.len());
+        assert_eq!(occurrences, vec![(3, 6), (51, 54)]);
+
+        // Only the occurrences in the range are returned
+        let occurrences = syntax.find_identifier_occurrences(7, 30..text.len());
+        assert_eq!(occurrences, vec![(40, 41), (59, 60)]);
+
+        // Keywords aren't identifiers
+        assert!(syntax
+            .find_identifier_occurrences(33, 0..text.len())
+            .is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "lang-html")]
+    fn test_find_linked_tag_names() {
+        let text = "<div><div>hi</div></div>";
+        let syntax = Syntax::from_language(LapceLanguage::Html).parse(
+            0,
+            Rope::from(text),
+            None,
+        );
+
+        // The cursor is in the name of the outer opening tag
+        assert_eq!(
+            syntax.find_linked_tag_names(2),
+            Some(vec![(1, 4), (20, 23)])
+        );
+
+        // The cursor is in the name of the inner closing tag
+        assert_eq!(
+            syntax.find_linked_tag_names(15),
+            Some(vec![(6, 9), (14, 17)])
+        );
+
+        // The cursor is right after the name of the inner opening tag
+        assert_eq!(
+            syntax.find_linked_tag_names(9),
+            Some(vec![(6, 9), (14, 17)])
+        );
+
+        // The cursor is in the text of the element
+        assert_eq!(syntax.find_linked_tag_names(11), None);
+    }
+
+    #[test]
+    #[cfg(feature = "lang-typescript")]
+    fn test_find_linked_jsx_tag_names() {
+        let text = "const a = <Foo.Bar x={1}>hi</Foo.Bar>;";
+        let syntax = Syntax::from_language(LapceLanguage::Tsx).parse(
+            0,
+            Rope::from(text),
+            None,
+        );
+
+        assert_eq!(
+            syntax.find_linked_tag_names(13),
+            Some(vec![(11, 18), (29, 36)])
+        );
+        assert_eq!(
+            syntax.find_linked_tag_names(36),
+            Some(vec![(11, 18), (29, 36)])
+        );
+
+        // The cursor is in an attribute
+        assert_eq!(syntax.find_linked_tag_names(20), None);
+    }
+
     #[test]
     fn test_lens_iter() {
         let lens = Syntax::lens_from_normal_lines(5, 25, 2, &[0, 2, 4]);
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use druid::{Command, Env, EventCtx, Modifiers, Target, WidgetId};
 use lapce_core::{command::FocusCommand, mode::Mode};
+use lapce_rpc::RequestId;
 
 use crate::{
     command::{CommandExecuted, CommandKind, LapceCommand, LAPCE_COMMAND},
@@ -21,6 +22,9 @@ pub struct AlertData {
     pub widget_id: WidgetId,
     pub active: bool,
     pub content: AlertContentData,
+    /// The `window/showMessageRequest` that the alert is showing, which is
+    /// answered with null if the alert is closed without picking an action
+    pub message_request: Option<RequestId>,
 }
 
 pub struct AlertFocusData {
@@ -43,6 +47,7 @@ impl AlertData {
                 msg: "".to_string(),
                 buttons: Vec::new(),
             },
+            message_request: None,
         }
     }
 }
@@ -0,0 +1,159 @@
+use druid::{ExtEventSink, Target, WidgetId};
+use lapce_rpc::buffer::BufferId;
+use lsp_types::{CallHierarchyItem, Range};
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    hierarchy::HierarchyTree,
+    proxy::LapceProxy,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallHierarchyDirection {
+    /// The functions that call the item
+    Incoming,
+    /// The functions that the item calls
+    Outgoing,
+}
+
+impl CallHierarchyDirection {
+    pub fn title(&self) -> &'static str {
+        match self {
+            CallHierarchyDirection::Incoming => "Incoming Calls",
+            CallHierarchyDirection::Outgoing => "Outgoing Calls",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CallHierarchyData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    pub direction: CallHierarchyDirection,
+    /// The buffer the hierarchy was prepared in, which decides the language
+    /// server that the calls are requested from
+    pub buffer_id: BufferId,
+    /// Incremented for every new hierarchy, so that calls that arrive for
+    /// an old one are ignored
+    pub request_id: usize,
+    pub tree: HierarchyTree,
+}
+
+impl CallHierarchyData {
+    pub fn new() -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            direction: CallHierarchyDirection::Incoming,
+            buffer_id: BufferId(0),
+            request_id: 0,
+            tree: HierarchyTree::default(),
+        }
+    }
+
+    /// Replace the tree with the items from `prepareCallHierarchy`, and
+    /// expand them
+    pub fn start(
+        &mut self,
+        direction: CallHierarchyDirection,
+        buffer_id: BufferId,
+        items: Vec<CallHierarchyItem>,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        self.request_id += 1;
+        self.direction = direction;
+        self.buffer_id = buffer_id;
+        self.tree = HierarchyTree::new(items);
+        for i in 0..self.tree.roots.len() {
+            self.toggle(&[i], proxy, event_sink.clone());
+        }
+    }
+
+    /// Expand or collapse the node, requesting its calls the first time it
+    /// is expanded
+    pub fn toggle(
+        &mut self,
+        path: &[usize],
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        if let Some(item) = self.tree.toggle(path) {
+            self.request_calls(path.to_vec(), item, proxy, event_sink);
+        }
+    }
+
+    fn request_calls(
+        &self,
+        path: Vec<usize>,
+        item: CallHierarchyItem,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let request_id = self.request_id;
+        let widget_id = self.widget_id;
+        let send = move |calls: Vec<(CallHierarchyItem, Vec<Range>)>| {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::CallHierarchyCalls {
+                    request_id,
+                    path,
+                    calls,
+                },
+                Target::Widget(widget_id),
+            );
+        };
+        match self.direction {
+            CallHierarchyDirection::Incoming => {
+                proxy.call_hierarchy_incoming_calls(
+                    self.buffer_id,
+                    item,
+                    move |result| {
+                        let calls = result
+                            .ok()
+                            .flatten()
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|call| (call.from, call.from_ranges))
+                            .collect();
+                        send(calls);
+                    },
+                );
+            }
+            CallHierarchyDirection::Outgoing => {
+                proxy.call_hierarchy_outgoing_calls(
+                    self.buffer_id,
+                    item,
+                    move |result| {
+                        let calls = result
+                            .ok()
+                            .flatten()
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|call| (call.to, call.from_ranges))
+                            .collect();
+                        send(calls);
+                    },
+                );
+            }
+        }
+    }
+
+    /// Fill in the calls of the node at `path`
+    pub fn receive_calls(
+        &mut self,
+        request_id: usize,
+        path: &[usize],
+        calls: Vec<(CallHierarchyItem, Vec<Range>)>,
+    ) {
+        if request_id == self.request_id {
+            self.tree.set_children(path, calls);
+        }
+    }
+}
+
+impl Default for CallHierarchyData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,318 @@
+use std::path::PathBuf;
+
+use lapce_core::buffer::Buffer;
+use lsp_types::{CodeLens, Command, Location};
+use serde_json::Value;
+use xi_rope::{RopeDelta, Transformer};
+
+#[derive(Clone)]
+struct CodeLensEntry {
+    /// Where the lens is, kept up to date with the edits
+    offset: usize,
+    line: usize,
+    lens: CodeLens,
+}
+
+/// The LSP code lenses of a document. Every line with lenses gets a phantom
+/// line above it in the normal editor view, so the lines are moved down by
+/// the phantom lines above them. A row is a line on the screen, either a
+/// phantom line or a line of the buffer.
+#[derive(Clone, Default)]
+pub struct CodeLenses {
+    /// Sorted by offset
+    entries: Vec<CodeLensEntry>,
+    /// The lines that have lenses, sorted and without duplicates
+    lines: Vec<usize>,
+}
+
+impl CodeLenses {
+    pub fn new(buffer: &Buffer, lenses: Vec<CodeLens>) -> Self {
+        let mut entries: Vec<CodeLensEntry> = lenses
+            .into_iter()
+            .filter_map(|lens| {
+                let offset = buffer.offset_of_position(&lens.range.start)?;
+                Some(CodeLensEntry {
+                    offset,
+                    line: buffer.line_of_offset(offset),
+                    lens,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.offset);
+        let mut code_lenses = Self {
+            entries,
+            lines: Vec::new(),
+        };
+        code_lenses.update_lines();
+        code_lenses
+    }
+
+    fn update_lines(&mut self) {
+        self.lines = self.entries.iter().map(|entry| entry.line).collect();
+        self.lines.dedup();
+    }
+
+    /// Move the lenses with the edit, `buffer` already has the edit applied
+    pub fn apply_delta(&mut self, buffer: &Buffer, delta: &RopeDelta) {
+        let mut transformer = Transformer::new(delta);
+        for entry in self.entries.iter_mut() {
+            entry.offset = transformer.transform(entry.offset, false);
+            entry.line = buffer.line_of_offset(entry.offset);
+        }
+        self.update_lines();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of phantom lines
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The lenses shown above the line
+    pub fn lenses_of_line(&self, line: usize) -> impl Iterator<Item = &CodeLens> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.line == line)
+            .map(|entry| &entry.lens)
+    }
+
+    /// The lenses in the lines from `start_line` to `end_line` whose command
+    /// is yet to be resolved
+    pub fn unresolved(
+        &self,
+        start_line: usize,
+        end_line: usize,
+    ) -> impl Iterator<Item = &CodeLens> {
+        self.entries
+            .iter()
+            .filter(move |entry| {
+                entry.lens.command.is_none()
+                    && entry.line >= start_line
+                    && entry.line <= end_line
+            })
+            .map(|entry| &entry.lens)
+    }
+
+    /// Replace the unresolved lens that the resolved one was requested for
+    pub fn resolve(&mut self, lens: CodeLens) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| {
+            entry.lens.command.is_none()
+                && entry.lens.range == lens.range
+                && entry.lens.data == lens.data
+        }) {
+            entry.lens = lens;
+        }
+    }
+
+    /// The row that the text of the line is on
+    pub fn row_of_line(&self, line: usize) -> usize {
+        line + self.lines.partition_point(|l| *l <= line)
+    }
+
+    /// The line at the row, and if the row is the phantom line of its lenses
+    pub fn line_of_row(&self, row: usize) -> (usize, bool) {
+        let mut line = row;
+        for (i, lens_line) in self.lines.iter().enumerate() {
+            // The phantom line of `lens_line` is on row `lens_line + i`
+            if lens_line + i >= row {
+                return (line, lens_line + i == row);
+            }
+            line = row - (i + 1);
+        }
+        (line, false)
+    }
+}
+
+/// What clicking on a code lens does
+pub enum CodeLensAction {
+    /// Run a shell command in a new terminal
+    RunInTerminal {
+        cwd: Option<PathBuf>,
+        command: String,
+    },
+    ShowReferences(Vec<Location>),
+    /// Let the language server run the command
+    Execute(Command),
+}
+
+impl CodeLensAction {
+    /// Some well-known commands are meant to be run by the editor, the rest
+    /// go to `workspace/executeCommand`
+    pub fn new(command: &Command) -> Self {
+        let arguments = command.arguments.as_deref().unwrap_or(&[]);
+        let action = match command.command.as_str() {
+            "rust-analyzer.runSingle" => {
+                arguments.first().and_then(Self::rust_analyzer_runnable)
+            }
+            "rust-analyzer.showReferences" => arguments
+                .get(2)
+                .and_then(|locations| serde_json::from_value(locations.clone()).ok())
+                .map(CodeLensAction::ShowReferences),
+            _ => None,
+        };
+        action.unwrap_or_else(|| CodeLensAction::Execute(command.clone()))
+    }
+
+    /// The cargo command line of a rust-analyzer runnable
+    fn rust_analyzer_runnable(runnable: &Value) -> Option<Self> {
+        let args = runnable.get("args")?;
+        let strings = |key: &str| -> Vec<String> {
+            args.get(key)
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|s| s.as_str())
+                        .map(shell_quote)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut command = vec![args
+            .get("overrideCargo")
+            .and_then(|v| v.as_str())
+            .unwrap_or("cargo")
+            .to_string()];
+        command.extend(strings("cargoArgs"));
+        command.extend(strings("cargoExtraArgs"));
+        let executable_args = strings("executableArgs");
+        if !executable_args.is_empty() {
+            command.push("--".to_string());
+            command.extend(executable_args);
+        }
+
+        Some(CodeLensAction::RunInTerminal {
+            cwd: args
+                .get("workspaceRoot")
+                .and_then(|v| v.as_str())
+                .map(PathBuf::from),
+            command: command.join(" "),
+        })
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=:./,@+".contains(c))
+    {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_core::{editor::EditType, selection::Selection};
+    use lsp_types::{Position, Range};
+    use serde_json::json;
+
+    use super::*;
+
+    fn lens(line: u32) -> CodeLens {
+        CodeLens {
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+            command: None,
+            data: None,
+        }
+    }
+
+    #[test]
+    fn test_rows() {
+        let buffer = Buffer::new("a\nb\nc\nd\ne\n");
+        let lenses = CodeLenses::new(&buffer, vec![lens(3), lens(1), lens(1)]);
+        assert_eq!(lenses.len(), 2);
+        assert_eq!(lenses.lenses_of_line(1).count(), 2);
+
+        let rows: Vec<usize> = (0..5).map(|line| lenses.row_of_line(line)).collect();
+        assert_eq!(rows, vec![0, 2, 3, 5, 6]);
+
+        let lines: Vec<(usize, bool)> =
+            (0..7).map(|row| lenses.line_of_row(row)).collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0, false),
+                (1, true),
+                (1, false),
+                (2, false),
+                (3, true),
+                (3, false),
+                (4, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let mut buffer = Buffer::new("a\nb\nc\n");
+        let mut lenses = CodeLenses::new(&buffer, vec![lens(2)]);
+        let (delta, _) =
+            buffer.edit(&[(Selection::caret(0), "x\n")], EditType::InsertChars);
+        lenses.apply_delta(&buffer, &delta);
+        assert_eq!(lenses.row_of_line(3), 4);
+        assert_eq!(lenses.line_of_row(3), (3, true));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let buffer = Buffer::new("a\nb\nc\nd\n");
+        let mut first = lens(1);
+        first.data = Some(json!(1));
+        let mut second = lens(1);
+        second.data = Some(json!(2));
+        let mut lenses =
+            CodeLenses::new(&buffer, vec![lens(0), first, second.clone(), lens(3)]);
+        assert_eq!(lenses.unresolved(1, 2).count(), 2);
+
+        second.command = Some(Command {
+            title: "2 references".to_string(),
+            command: "references".to_string(),
+            arguments: None,
+        });
+        lenses.resolve(second);
+        let unresolved: Vec<&CodeLens> = lenses.unresolved(1, 2).collect();
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(unresolved[0].data, Some(json!(1)));
+        assert_eq!(
+            lenses
+                .lenses_of_line(1)
+                .filter_map(|lens| lens.command.as_ref())
+                .map(|command| command.title.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["2 references"]
+        );
+    }
+
+    #[test]
+    fn test_run_single() {
+        let command = Command {
+            title: "▶\u{fe0e} Run Test".to_string(),
+            command: "rust-analyzer.runSingle".to_string(),
+            arguments: Some(vec![json!({
+                "label": "test test_rows",
+                "kind": "cargo",
+                "args": {
+                    "workspaceRoot": "/tmp/crate",
+                    "cargoArgs": ["test", "--package", "crate", "--lib"],
+                    "cargoExtraArgs": [],
+                    "executableArgs": ["test::test_rows", "--exact", "--nocapture"],
+                },
+            })]),
+        };
+        match CodeLensAction::new(&command) {
+            CodeLensAction::RunInTerminal { cwd, command } => {
+                assert_eq!(cwd, Some(PathBuf::from("/tmp/crate")));
+                assert_eq!(
+                    command,
+                    "cargo test --package crate --lib -- test::test_rows --exact --nocapture"
+                );
+            }
+            _ => panic!("runSingle should run in the terminal"),
+        }
+    }
+}
@@ -11,22 +11,31 @@ use lapce_core::command::{
 };
 use lapce_core::syntax::Syntax;
 use lapce_rpc::{
-    buffer::BufferId, file::FileNodeItem, plugin::PluginDescription,
-    source_control::DiffInfo, style::Style, terminal::TermId,
+    buffer::BufferId,
+    core::{LanguageServerStatus, LspTraceEntry, SignatureTriggers},
+    file::FileNodeItem,
+    plugin::PluginDescription,
+    source_control::DiffInfo,
+    style::Style,
+    terminal::TermId,
+    RequestId,
 };
 use lsp_types::{
-    CodeActionOrCommand, CodeActionResponse, CompletionItem, CompletionResponse,
-    InlayHint, Location, Position, ProgressParams, PublishDiagnosticsParams,
-    TextEdit,
+    CallHierarchyItem, CodeActionOrCommand, CodeActionResponse, CodeLens,
+    CompletionItem, CompletionResponse, InlayHint, Location, LogMessageParams,
+    Position, PrepareRenameResponse, ProgressParams, PublishDiagnosticsParams,
+    Range, ShowMessageParams, ShowMessageRequestParams, SignatureHelp, TextEdit,
+    WorkspaceEdit,
 };
 use serde_json::Value;
 use strum::{self, EnumMessage, IntoEnumIterator};
 use strum_macros::{Display, EnumIter, EnumMessage, EnumString, IntoStaticStr};
-use xi_rope::{spans::Spans, Rope};
+use xi_rope::{spans::Spans, Interval, Rope};
 
 use crate::alert::AlertContentData;
+use crate::call_hierarchy::CallHierarchyDirection;
 use crate::data::{LapceMainSplitData, LapceTabData, LapceWorkspace};
-use crate::document::BufferContent;
+use crate::document::{BufferContent, Occurrence};
 use crate::editor::{EditorPosition, Line, LineCol};
 use crate::menu::MenuKind;
 use crate::rich_text::RichText;
@@ -38,12 +47,15 @@ use crate::{
     proxy::ProxyStatus,
     search::Match,
     split::{SplitDirection, SplitMoveDirection},
+    type_hierarchy::{TypeHierarchyDirection, TypeHierarchyItem},
 };
 
 pub const LAPCE_OPEN_FOLDER: Selector<FileInfo> = Selector::new("lapce.open-folder");
 pub const LAPCE_OPEN_FILE: Selector<FileInfo> = Selector::new("lapce.open-file");
 pub const LAPCE_SAVE_FILE_AS: Selector<FileInfo> =
     Selector::new("lapce.save-file-as");
+pub const LAPCE_EXPORT_LSP_TRACE: Selector<FileInfo> =
+    Selector::new("lapce.export-lsp-trace");
 pub const LAPCE_COMMAND: Selector<LapceCommand> = Selector::new("lapce.new-command");
 pub const LAPCE_UI_COMMAND: Selector<LapceUICommand> =
     Selector::new("lapce.ui_command");
@@ -99,6 +111,7 @@ impl LapceCommand {
                 | LapceWorkbenchCommand::PaletteSymbol
                 | LapceWorkbenchCommand::PaletteCommand
                 | LapceWorkbenchCommand::ChangeFileLanguage
+                | LapceWorkbenchCommand::RestartLanguageServer
                 | LapceWorkbenchCommand::ChangeTheme
                 | LapceWorkbenchCommand::ConnectSshHost
                 | LapceWorkbenchCommand::ConnectWsl
@@ -340,6 +353,22 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_focus")]
     ToggleSearchFocus,
 
+    #[strum(message = "Toggle Call Hierarchy Focus")]
+    #[strum(serialize = "toggle_call_hierarchy_focus")]
+    ToggleCallHierarchyFocus,
+
+    #[strum(message = "Toggle Type Hierarchy Focus")]
+    #[strum(serialize = "toggle_type_hierarchy_focus")]
+    ToggleTypeHierarchyFocus,
+
+    #[strum(message = "Toggle Output Focus")]
+    #[strum(serialize = "toggle_output_focus")]
+    ToggleOutputFocus,
+
+    #[strum(message = "Toggle LSP Trace Focus")]
+    #[strum(serialize = "toggle_lsp_trace_focus")]
+    ToggleLspTraceFocus,
+
     // Visual toggle commands
     #[strum(serialize = "toggle_terminal_visual")]
     ToggleTerminalVisual,
@@ -359,6 +388,18 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_visual")]
     ToggleSearchVisual,
 
+    #[strum(serialize = "toggle_call_hierarchy_visual")]
+    ToggleCallHierarchyVisual,
+
+    #[strum(serialize = "toggle_type_hierarchy_visual")]
+    ToggleTypeHierarchyVisual,
+
+    #[strum(serialize = "toggle_output_visual")]
+    ToggleOutputVisual,
+
+    #[strum(serialize = "toggle_lsp_trace_visual")]
+    ToggleLspTraceVisual,
+
     #[strum(serialize = "focus_editor")]
     FocusEditor,
 
@@ -392,6 +433,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Change current file language")]
     ChangeFileLanguage,
 
+    #[strum(serialize = "restart_language_server")]
+    #[strum(message = "Restart Language Server")]
+    RestartLanguageServer,
+
     #[strum(serialize = "next_editor_tab")]
     #[strum(message = "Next editor tab")]
     NextEditorTab,
@@ -403,6 +448,16 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_inlay_hints")]
     #[strum(message = "Toggle Inlay Hints")]
     ToggleInlayHints,
+
+    /// Save the messages shown in the LSP trace panel as JSON Lines
+    #[strum(serialize = "export_lsp_trace")]
+    #[strum(message = "Export LSP Trace")]
+    ExportLspTrace,
+
+    /// Answer a `window/showMessageRequest` with one of its actions, the
+    /// data is the `request_id` and the `action`
+    #[strum(serialize = "message_request_action")]
+    MessageRequestAction,
 }
 
 #[derive(Debug, Clone)]
@@ -471,12 +526,139 @@ pub enum LapceUICommand {
     CancelCompletion(usize),
     ResolveCompletion(BufferId, u64, usize, Box<CompletionItem>),
     UpdateCompletion(usize, String, CompletionResponse),
+    UpdateLocalCompletion(usize, Vec<CompletionItem>),
     UpdateHover(usize, Arc<Vec<RichText>>),
+    UpdateSignature {
+        request_id: usize,
+        help: Option<Arc<SignatureHelp>>,
+    },
+    UpdatePeek {
+        request_id: usize,
+        locations: Vec<Location>,
+    },
+    /// Show the rename input for the result of a `prepareRename` request
+    StartRename {
+        editor_view_id: WidgetId,
+        path: PathBuf,
+        rev: u64,
+        offset: usize,
+        response: PrepareRenameResponse,
+    },
+    RenamePreview {
+        request_id: usize,
+        edit: Option<WorkspaceEdit>,
+    },
+    /// The content of a file in the rename preview that isn't open
+    RenamePreviewFile {
+        request_id: usize,
+        path: PathBuf,
+        content: String,
+    },
+    CancelRename,
+    /// A `workspace/applyEdit` request from a language server, which is
+    /// responded to once the edit has been applied
+    ApplyWorkspaceEdit {
+        request_id: RequestId,
+        edit: WorkspaceEdit,
+    },
+    /// A `window/showMessage` from the language server named `title`
+    ShowMessage {
+        title: String,
+        message: ShowMessageParams,
+    },
+    /// A `window/showMessageRequest`, which is responded to with the action
+    /// that is picked in the alert
+    ShowMessageRequest {
+        request_id: RequestId,
+        title: String,
+        message: ShowMessageRequestParams,
+    },
+    /// A `window/logMessage` for the output panel
+    LogMessage {
+        server: String,
+        message: LogMessageParams,
+    },
+    LanguageServerStatus {
+        name: String,
+        status: LanguageServerStatus,
+    },
+    /// A message between the proxy and a server for the LSP trace panel
+    LspTrace {
+        server: String,
+        entry: LspTraceEntry,
+    },
+    /// Request the semantic styles and the inlay hints of the open documents
+    /// again
+    LspRefresh,
+    /// Only the characters of the server's triggers ask it for the signature
+    /// help in the document at `path`
+    SignatureHelpTriggers {
+        path: PathBuf,
+        server: String,
+        triggers: SignatureTriggers,
+    },
+    DismissNotification(usize),
+    /// Show the items of a `prepareCallHierarchy` request in the call hierarchy panel
+    ShowCallHierarchy {
+        direction: CallHierarchyDirection,
+        buffer_id: BufferId,
+        items: Vec<CallHierarchyItem>,
+    },
+    /// The calls of the node at `path` in the call hierarchy tree
+    CallHierarchyCalls {
+        request_id: usize,
+        path: Vec<usize>,
+        calls: Vec<(CallHierarchyItem, Vec<Range>)>,
+    },
+    /// Show the items of a `prepareTypeHierarchy` request in the type hierarchy panel
+    ShowTypeHierarchy {
+        direction: TypeHierarchyDirection,
+        buffer_id: BufferId,
+        items: Vec<TypeHierarchyItem>,
+    },
+    /// The supertypes or subtypes of the node at `path` in the type hierarchy tree
+    TypeHierarchyTypes {
+        request_id: usize,
+        path: Vec<usize>,
+        items: Vec<TypeHierarchyItem>,
+    },
+    /// The inlay hints of the interval, relative to its start
     UpdateInlayHints {
         path: PathBuf,
         rev: u64,
+        interval: Interval,
         hints: Spans<InlayHint>,
     },
+    UpdateCodeLens {
+        path: PathBuf,
+        rev: u64,
+        lenses: Vec<CodeLens>,
+    },
+    /// A code lens with the command that the server left out
+    UpdateResolvedCodeLens {
+        path: PathBuf,
+        rev: u64,
+        lens: CodeLens,
+    },
+    /// Run the shell command in a new terminal
+    RunInTerminal {
+        cwd: Option<PathBuf>,
+        command: String,
+    },
+    /// The occurrences of the symbol under the cursor
+    UpdateOccurrences {
+        path: PathBuf,
+        rev: u64,
+        occurrences: Vec<Occurrence>,
+    },
+    /// The ranges that are edited together with the one at the cursor of
+    /// the editor
+    UpdateLinkedEditing {
+        view_id: WidgetId,
+        path: PathBuf,
+        rev: u64,
+        ranges: Vec<(usize, usize)>,
+    },
     UpdateCodeActions(PathBuf, u64, usize, CodeActionResponse),
     CancelPalette,
     RunCodeAction(CodeActionOrCommand),
@@ -538,6 +720,13 @@ pub enum LapceUICommand {
     DocumentSave(PathBuf, Option<WidgetId>),
     BufferSave(PathBuf, u64, Option<WidgetId>),
     UpdateSemanticStyles(BufferId, PathBuf, u64, Arc<Spans<Style>>),
+    /// The semantic styles of the interval, relative to its start
+    UpdateSemanticStylesRange {
+        path: PathBuf,
+        rev: u64,
+        interval: Interval,
+        styles: Spans<Style>,
+    },
     UpdateTerminalTitle(TermId, String),
     UpdateHistoryStyle {
         id: BufferId,
@@ -650,6 +839,8 @@ pub enum LapceUICommand {
         apply_naming: bool,
     },
     SetLanguage(String),
+    /// Restart the language server with the name
+    RestartLanguageServer(String),
 }
 
 /// This can't be an `FnOnce` because we only ever get a reference to
@@ -1,14 +1,24 @@
-use std::{fmt::Display, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
 
 use anyhow::Error;
 use druid::{ExtEventSink, Size, Target, WidgetId};
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use itertools::Itertools;
 use lapce_core::movement::Movement;
-use lapce_rpc::buffer::BufferId;
-use lsp_types::{CompletionItem, CompletionResponse, Position};
+use lapce_rpc::{buffer::BufferId, file::FileNodeItem};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionResponse, CompletionTextEdit,
+    Position, TextEdit,
+};
 use regex::Regex;
 use std::str::FromStr;
+use xi_rope::Rope;
 
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
@@ -96,33 +106,26 @@ impl Snippet {
         escs: Vec<&str>,
         loose_escs: Vec<&str>,
     ) -> Option<(SnippetElement, usize)> {
-        let mut s = &s[pos..];
+        let is_any = |escs: &[&str], c: char| escs.iter().any(|e| e.chars().eq([c]));
+
         let mut ele = "".to_string();
         let mut end = pos;
 
-        while !s.is_empty() {
-            if s.len() >= 2 {
-                let esc = &s[..2];
-                let mut new_escs = escs.clone();
-                new_escs.extend_from_slice(&loose_escs);
-
-                if new_escs
-                    .iter()
-                    .map(|e| format!("\\{}", e))
-                    .any(|x| x == *esc)
-                {
-                    ele = ele + &s[1..2].to_string();
-                    end += 2;
-                    s = &s[2..];
-                    continue;
+        while let Some(c) = s[end..].chars().next() {
+            if c == '\\' {
+                if let Some(next) = s[end + 1..].chars().next() {
+                    if is_any(&escs, next) || is_any(&loose_escs, next) {
+                        ele.push(next);
+                        end += 1 + next.len_utf8();
+                        continue;
+                    }
                 }
             }
-            if escs.contains(&&s[0..1]) {
+            if is_any(&escs, c) {
                 break;
             }
-            ele = ele + &s[0..1].to_string();
-            end += 1;
-            s = &s[1..];
+            ele.push(c);
+            end += c.len_utf8();
         }
         if ele.is_empty() {
             return None;
@@ -245,7 +248,8 @@ pub struct CompletionData {
     pub input: String,
     pub index: usize,
     pub input_items: im::HashMap<String, Arc<Vec<ScoredCompletionItem>>>,
-    empty: Arc<Vec<ScoredCompletionItem>>,
+    /// Items which don't come from the language server, such as user snippets
+    local_items: Arc<Vec<ScoredCompletionItem>>,
     pub filtered_items: Arc<Vec<ScoredCompletionItem>>,
     pub matcher: Arc<SkimMatcherV2>,
     /// The size of the completion list
@@ -272,7 +276,7 @@ impl CompletionData {
             size: Size::new(400.0, 300.0),
             // TODO: Make this configurable
             documentation_size: Size::new(400.0, 300.0),
-            empty: Arc::new(Vec::new()),
+            local_items: Arc::new(Vec::new()),
         }
     }
 
@@ -322,7 +326,9 @@ impl CompletionData {
         self.input_items
             .get(&self.input)
             .filter(|items| !items.is_empty())
-            .unwrap_or_else(move || self.input_items.get("").unwrap_or(&self.empty))
+            .unwrap_or_else(move || {
+                self.input_items.get("").unwrap_or(&self.local_items)
+            })
     }
 
     pub fn current_item(&self) -> &CompletionItem {
@@ -360,6 +366,52 @@ impl CompletionData {
         );
     }
 
+    /// Collect the words of the buffers off the UI thread, which are added
+    /// to the local items once they're ready
+    pub fn request_buffer_words(
+        &self,
+        current: Rope,
+        offset: usize,
+        others: Vec<Rope>,
+        event_sink: ExtEventSink,
+    ) {
+        let request_id = self.request_id;
+        let completion_widget_id = self.id;
+        thread::spawn(move || {
+            let items = buffer_word_items(&current, offset, others.iter());
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::UpdateLocalCompletion(request_id, items),
+                Target::Widget(completion_widget_id),
+            );
+        });
+    }
+
+    /// List the folder of the path that is typed with the proxy, so that
+    /// it's the folder of the remote workspace if there's one, and add its
+    /// entries to the local items
+    pub fn request_paths(
+        &self,
+        proxy: &LapceProxy,
+        dir: PathBuf,
+        fragment: String,
+        range: lsp_types::Range,
+        event_sink: ExtEventSink,
+    ) {
+        let request_id = self.request_id;
+        let completion_widget_id = self.id;
+        proxy.read_dir(&dir, move |result| {
+            if let Ok(resp) = result {
+                let items = path_items(resp.items.values(), &fragment, range);
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateLocalCompletion(request_id, items),
+                    Target::Widget(completion_widget_id),
+                );
+            }
+        });
+    }
+
     pub fn cancel(&mut self) {
         if self.status == CompletionStatus::Inactive {
             return;
@@ -367,9 +419,68 @@ impl CompletionData {
         self.status = CompletionStatus::Inactive;
         self.input = "".to_string();
         self.input_items.clear();
+        self.local_items = Arc::new(Vec::new());
         self.index = 0;
     }
 
+    /// Set the items offered alongside the language server's items for the
+    /// current completion session.
+    pub fn set_local_items(&mut self, items: Vec<CompletionItem>) {
+        self.local_items = Arc::new(
+            items
+                .into_iter()
+                .map(|item| ScoredCompletionItem {
+                    item,
+                    score: 0,
+                    label_score: 0,
+                    indices: Vec::new(),
+                })
+                .collect(),
+        );
+        self.filter_items();
+    }
+
+    /// Add the local items that came after the completion started, to the
+    /// items of the language server that were already received too
+    pub fn receive_local_items(
+        &mut self,
+        request_id: usize,
+        items: Vec<CompletionItem>,
+    ) {
+        if self.status == CompletionStatus::Inactive || self.request_id != request_id
+        {
+            return;
+        }
+
+        let items: Vec<ScoredCompletionItem> = items
+            .into_iter()
+            .map(|item| ScoredCompletionItem {
+                item,
+                score: 0,
+                label_score: 0,
+                indices: Vec::new(),
+            })
+            .collect();
+        for (_, received) in self.input_items.iter_mut() {
+            // Buffer words which the language server already offers are noise.
+            let new_items: Vec<ScoredCompletionItem> = {
+                let labels: HashSet<&str> =
+                    received.iter().map(|i| i.item.label.as_str()).collect();
+                items
+                    .iter()
+                    .filter(|i| {
+                        i.item.kind != Some(CompletionItemKind::TEXT)
+                            || !labels.contains(i.item.label.as_str())
+                    })
+                    .cloned()
+                    .collect()
+            };
+            Arc::make_mut(received).extend(new_items);
+        }
+        Arc::make_mut(&mut self.local_items).extend(items);
+        self.filter_items();
+    }
+
     pub fn update_input(&mut self, input: String) {
         self.input = input;
         self.index = 0;
@@ -394,6 +505,12 @@ impl CompletionData {
             CompletionResponse::Array(items) => items,
             CompletionResponse::List(list) => list.items,
         };
+        // Buffer words which the language server already offers are noise.
+        let labels: HashSet<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        let local_items = self.local_items.iter().filter(|i| {
+            i.item.kind != Some(CompletionItemKind::TEXT)
+                || !labels.contains(i.item.label.as_str())
+        });
         let items: Vec<ScoredCompletionItem> = items
             .iter()
             .map(|i| ScoredCompletionItem {
@@ -402,6 +519,7 @@ impl CompletionData {
                 label_score: 0,
                 indices: Vec::new(),
             })
+            .chain(local_items.cloned())
             .collect();
 
         self.input_items.insert(input, Arc::new(items));
@@ -447,6 +565,7 @@ impl CompletionData {
             b.score
                 .cmp(&a.score)
                 .then_with(|| b.label_score.cmp(&a.label_score))
+                .then_with(|| a.item.sort_text.cmp(&b.item.sort_text))
                 .then_with(|| a.item.label.len().cmp(&b.item.label.len()))
         });
         self.filtered_items = Arc::new(items);
@@ -482,6 +601,174 @@ pub struct ScoredCompletionItem {
     pub indices: Vec<usize>,
 }
 
+/// Don't collect words from buffers larger than this.
+const MAX_WORD_BUFFER_LEN: usize = 1_000_000;
+/// The maximum number of words offered from open buffers.
+const MAX_WORD_ITEMS: usize = 500;
+
+/// Completion items for the words in the open buffers, ranked by their
+/// distance to the cursor in the current buffer and how often they appear.
+/// The word at `offset` itself is left out.
+pub fn buffer_word_items<'a>(
+    current: &Rope,
+    offset: usize,
+    others: impl Iterator<Item = &'a Rope>,
+) -> Vec<CompletionItem> {
+    // (line distance, count) for each word
+    let mut words: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut add_word = |word: &str, distance: usize| {
+        if word.chars().count() < 2 || word.starts_with(|c: char| c.is_ascii_digit())
+        {
+            return;
+        }
+        let entry = words.entry(word.to_string()).or_insert((distance, 0));
+        entry.0 = entry.0.min(distance);
+        entry.1 += 1;
+    };
+
+    if current.len() <= MAX_WORD_BUFFER_LEN {
+        let cursor_line = current.line_of_offset(offset);
+        let cursor_col = offset - current.offset_of_line(cursor_line);
+        for (line, content) in current.lines(..).enumerate() {
+            for (col, word) in line_words(&content) {
+                if line == cursor_line
+                    && col <= cursor_col
+                    && cursor_col <= col + word.len()
+                {
+                    continue;
+                }
+                let distance = if line > cursor_line {
+                    line - cursor_line
+                } else {
+                    cursor_line - line
+                };
+                add_word(word, distance);
+            }
+        }
+    }
+    for rope in others {
+        if rope.len() > MAX_WORD_BUFFER_LEN {
+            continue;
+        }
+        for content in rope.lines(..) {
+            for (_, word) in line_words(&content) {
+                add_word(word, usize::MAX);
+            }
+        }
+    }
+
+    let mut words: Vec<(String, (usize, usize))> = words.into_iter().collect();
+    words.sort_by(
+        |(a_word, (a_distance, a_count)), (b_word, (b_distance, b_count))| {
+            a_distance
+                .cmp(b_distance)
+                .then_with(|| b_count.cmp(a_count))
+                .then_with(|| a_word.cmp(b_word))
+        },
+    );
+    words
+        .into_iter()
+        .take(MAX_WORD_ITEMS)
+        .enumerate()
+        .map(|(rank, (word, _))| CompletionItem {
+            label: word,
+            kind: Some(CompletionItemKind::TEXT),
+            sort_text: Some(format!("{rank:05}")),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The words in a line with their byte offsets
+fn line_words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - line.as_ptr() as usize, word))
+}
+
+/// If `line` ends inside a string literal which looks like a file path,
+/// returns the path typed so far.
+pub fn path_completion_fragment(line: &str) -> Option<&str> {
+    let mut quote = None;
+    let mut start = 0;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (_, '\\') if quote.is_some() => {
+                chars.next();
+            }
+            (None, '"' | '\'' | '`') => {
+                quote = Some(c);
+                start = i + 1;
+            }
+            (Some(q), c) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    quote?;
+
+    let fragment = &line[start..];
+    if !fragment.contains('/') || fragment.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(fragment)
+}
+
+/// The folder of the entries that complete `fragment`, a path relative to
+/// `base`, or to `home` if it starts with `~/`
+pub fn path_completion_dir(
+    base: &Path,
+    fragment: &str,
+    home: Option<&Path>,
+) -> Option<PathBuf> {
+    let (dir, _) = fragment.rsplit_once('/')?;
+    let dir = format!("{dir}/");
+    match dir.strip_prefix("~/") {
+        Some(rest) => home.map(|home| home.join(rest)),
+        None => Some(base.join(dir)),
+    }
+}
+
+/// Completion items for the entries of the folder that `fragment` is in.
+/// `range` is the range of the last path component, which gets replaced by
+/// the completion.
+pub fn path_items<'a>(
+    entries: impl Iterator<Item = &'a FileNodeItem>,
+    fragment: &str,
+    range: lsp_types::Range,
+) -> Vec<CompletionItem> {
+    let name = fragment.rsplit('/').next().unwrap_or("");
+    let mut items: Vec<CompletionItem> = entries
+        .filter_map(|entry| {
+            let file_name = entry.path_buf.file_name()?.to_str()?.to_string();
+            if file_name.starts_with('.') && !name.starts_with('.') {
+                return None;
+            }
+            let new_text = if entry.is_dir {
+                format!("{file_name}/")
+            } else {
+                file_name.clone()
+            };
+            Some(CompletionItem {
+                label: new_text.clone(),
+                kind: Some(if entry.is_dir {
+                    CompletionItemKind::FOLDER
+                } else {
+                    CompletionItemKind::FILE
+                }),
+                filter_text: Some(file_name),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text,
+                })),
+                ..Default::default()
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+    items
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -500,4 +787,69 @@ mod tests {
             parsed.tabs(0)
         );
     }
+
+    #[test]
+    fn test_snippet_non_ascii() {
+        let s = "héllo ${1:wörld \\$ü} 日本$0";
+        let parsed = Snippet::from_str(s).unwrap();
+        assert_eq!("héllo wörld $ü 日本", parsed.text());
+        assert_eq!(vec![(1, (7, 17)), (0, (24, 24))], parsed.tabs(0));
+    }
+
+    #[test]
+    fn test_buffer_word_items() {
+        let current = Rope::from("foo bar\nbaz foo\nfo\nqux qux 42\n");
+        let other = Rope::from("other foo");
+        let offset = current.offset_of_line(2) + 1;
+        let items = buffer_word_items(&current, offset, [&other].into_iter());
+        let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(vec!["foo", "qux", "baz", "bar", "other"], labels);
+    }
+
+    #[test]
+    fn test_path_items() {
+        let base = Path::new("/work/src");
+        assert_eq!(
+            Some(PathBuf::from("/work/src/./lib/")),
+            path_completion_dir(base, "./lib/ma", None)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/home/me/notes/")),
+            path_completion_dir(base, "~/notes/a", Some(Path::new("/home/me")))
+        );
+        assert_eq!(None, path_completion_dir(base, "~/notes/a", None));
+        assert_eq!(None, path_completion_dir(base, "main", None));
+
+        let entry = |name: &str, is_dir| FileNodeItem {
+            path_buf: Path::new("/work/src/lib").join(name),
+            is_dir,
+            read: false,
+            open: false,
+            children: HashMap::new(),
+            children_open_count: 0,
+        };
+        let entries = [
+            entry("main.rs", false),
+            entry("util", true),
+            entry(".hidden", false),
+        ];
+        let items = path_items(entries.iter(), "./lib/ma", Default::default());
+        let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(vec!["main.rs", "util/"], labels);
+        let items = path_items(entries.iter(), "./lib/.h", Default::default());
+        assert_eq!(3, items.len());
+    }
+
+    #[test]
+    fn test_path_completion_fragment() {
+        assert_eq!(
+            Some("./src/ma"),
+            path_completion_fragment("include \"./src/ma")
+        );
+        assert_eq!(Some("../"), path_completion_fragment("import '../"));
+        assert_eq!(None, path_completion_fragment("let a = b / c"));
+        assert_eq!(None, path_completion_fragment("\"a/b\" + x/"));
+        assert_eq!(None, path_completion_fragment("\"no path"));
+        assert_eq!(Some("a\\\"/b"), path_completion_fragment("\"a\\\"/b"));
+    }
 }
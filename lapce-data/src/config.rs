@@ -11,6 +11,7 @@ use druid::{
     Color, ExtEventSink, FontFamily, Size, Target,
 };
 use indexmap::IndexMap;
+use lapce_core::language::LapceLanguage;
 use lapce_proxy::plugin::PluginCatalog;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -22,6 +23,7 @@ use toml_edit::easy as toml;
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     data::{LapceWorkspace, LapceWorkspaceType},
+    snippet::UserSnippets,
 };
 
 const DEFAULT_SETTINGS: &str = include_str!("../../defaults/settings.toml");
@@ -48,6 +50,8 @@ impl LapceTheme {
     pub const EDITOR_SELECTION: &'static str = "editor.selection";
     pub const EDITOR_CURRENT_LINE: &'static str = "editor.current_line";
     pub const EDITOR_LINK: &'static str = "editor.link";
+    pub const EDITOR_OCCURRENCE_READ: &'static str = "editor.occurrence.read";
+    pub const EDITOR_OCCURRENCE_WRITE: &'static str = "editor.occurrence.write";
 
     pub const INLAY_HINT_FOREGROUND: &'static str = "inlay_hint.foreground";
     pub const INLAY_HINT_BACKGROUND: &'static str = "inlay_hint.background";
@@ -176,6 +180,14 @@ pub struct EditorConfig {
         desc = "How long (in ms) it should take before the hover information appears"
     )]
     pub hover_delay: u64,
+    #[field_names(
+        desc = "If the occurrences of the symbol under the cursor should be highlighted"
+    )]
+    pub highlight_occurrences: bool,
+    #[field_names(
+        desc = "How long (in ms) the cursor should stay still before the occurrences are highlighted"
+    )]
+    pub highlight_occurrences_delay: u64,
     #[field_names(
         desc = "If modal mode should have relative line numbers (though, not in insert mode)"
     )]
@@ -184,8 +196,36 @@ pub struct EditorConfig {
         desc = "Whether it should format the document on save (if there is an available formatter)"
     )]
     pub format_on_save: bool,
+    #[field_names(
+        desc = "Only format the lines changed since the last commit on save (needs a formatter with range formatting)"
+    )]
+    pub format_on_save_modified_lines: bool,
+    #[field_names(
+        desc = "Let the language server format as you type its trigger characters"
+    )]
+    pub format_on_type: bool,
+    #[field_names(
+        desc = "Format the pasted text (needs a formatter with range formatting)"
+    )]
+    pub format_on_paste: bool,
+    #[field_names(
+        desc = "If multi-line pastes should be re-indented to match the indentation at the cursor"
+    )]
+    pub reindent_on_paste: bool,
+    #[field_names(desc = "Remove trailing whitespace from every line on save")]
+    pub trim_trailing_whitespace: bool,
+    #[field_names(desc = "Make sure the file ends with a newline on save")]
+    pub insert_final_newline: bool,
+    #[field_names(
+        desc = "Remove all but one of the newlines at the end of the file on save"
+    )]
+    pub trim_final_newlines: bool,
     #[field_names(desc = "If inlay hints should be displayed")]
     pub enable_inlay_hints: bool,
+    #[field_names(
+        desc = "If the code lenses of the language server should be displayed above the lines"
+    )]
+    pub enable_code_lens: bool,
     #[field_names(
         desc = "Set the inlay hint font family. If empty, it uses the editor font family."
     )]
@@ -598,6 +638,23 @@ pub struct Config {
     pub editor: EditorConfig,
     pub terminal: TerminalConfig,
     pub theme: ThemeConfig,
+    /// Overrides of the editor settings for a language, keyed by the
+    /// language name, e.g. `[language.markdown]`.
+    #[serde(default)]
+    pub language: HashMap<String, serde_json::Map<String, serde_json::Value>>,
+    /// The settings for the language servers from the `[lsp.<name>]` tables,
+    /// keyed by the server name or the configuration section it asks for.
+    /// They are read separately because `config` lowercases the keys, and
+    /// the servers expect them as they are written. `[lsp.formatter]` picks
+    /// the server that formats a language when several of them can, e.g.
+    /// `typescript = "eslint-lsp"`. `[lsp.servers.<name>]` starts a server
+    /// that has no plugin, with its `command`, `args`, `env`, `languages`,
+    /// `extensions`, `root-markers` and `initialization-options`; it is only
+    /// read from the user settings.
+    /// `trace = "messages"` or `"verbose"` records the messages of the
+    /// servers in the LSP trace panel.
+    #[serde(skip)]
+    pub lsp: serde_json::Value,
     #[serde(skip)]
     pub default_theme: ThemeConfig,
     #[serde(skip)]
@@ -605,6 +662,8 @@ pub struct Config {
     #[serde(skip)]
     pub available_themes: HashMap<String, (String, config::Config)>,
     #[serde(skip)]
+    pub snippets: Arc<UserSnippets>,
+    #[serde(skip)]
     tab_layout_info: Arc<RwLock<HashMap<(FontFamily, usize), f64>>>,
 }
 
@@ -668,12 +727,44 @@ impl Config {
         }
         config.update_id();
         config.available_themes = available_themes;
+        config.snippets = Arc::new(UserSnippets::load(workspace));
+        config.lsp = Self::load_lsp_settings(workspace);
         config.resolve_colors(Some(&default_config));
         config.default_theme = default_config.theme.clone();
 
         Ok(config)
     }
 
+    /// The editor settings for the file at `path`, with the overrides from
+    /// the `[language.<name>]` table of its language applied.
+    pub fn editor_config(&self, path: &Path) -> EditorConfig {
+        let overrides = LapceLanguage::from_path(path).and_then(|language| {
+            let language = language.to_string();
+            self.language
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&language))
+                .map(|(_, overrides)| overrides)
+        });
+        let overrides = match overrides {
+            Some(overrides) => overrides,
+            None => return self.editor.clone(),
+        };
+
+        let mut editor = match serde_json::to_value(&self.editor) {
+            Ok(serde_json::Value::Object(editor)) => editor,
+            _ => return self.editor.clone(),
+        };
+        for (key, value) in overrides {
+            editor.insert(key.replace('_', "-"), value.clone());
+        }
+        serde_json::from_value(serde_json::Value::Object(editor)).unwrap_or_else(
+            |e| {
+                log::error!("Invalid language settings for {path:?}: {e}");
+                self.editor.clone()
+            },
+        )
+    }
+
     fn merge_settings(
         mut settings: config::Config,
         workspace: &LapceWorkspace,
@@ -703,6 +794,39 @@ impl Config {
         settings
     }
 
+    /// The `[lsp]` tables of the user settings and the workspace settings,
+    /// with the workspace taking precedence. `[lsp.servers]` is only read
+    /// from the user settings, because its commands are started as soon as
+    /// a matching file opens, and opening a workspace shouldn't run the
+    /// programs it names.
+    fn load_lsp_settings(workspace: &LapceWorkspace) -> serde_json::Value {
+        let mut paths = Vec::new();
+        if let Some(path) = Self::settings_file() {
+            paths.push((path, true));
+        }
+        if let LapceWorkspaceType::Local = workspace.kind {
+            if let Some(path) = workspace.path.as_ref() {
+                paths.push((path.join("./.lapce/settings.toml"), false));
+            }
+        }
+
+        let mut settings = serde_json::Value::Object(serde_json::Map::new());
+        for (path, is_user) in paths {
+            let lsp = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+                .and_then(|value| value.get("lsp").cloned())
+                .and_then(|lsp| serde_json::to_value(lsp).ok());
+            if let Some(mut lsp) = lsp {
+                if !is_user {
+                    strip_workspace_servers(&mut lsp, &path);
+                }
+                merge_json(&mut settings, lsp);
+            }
+        }
+        settings
+    }
+
     fn resolve_colors(&mut self, default_config: Option<&Config>) {
         self.color.base = self
             .theme
@@ -879,6 +1003,18 @@ impl Config {
         Some(path)
     }
 
+    /// Get the path to the snippets folder
+    /// Snippets are stored within as VS Code style json files
+    pub fn snippets_folder() -> Option<PathBuf> {
+        let path = Self::dir()?.join("snippets");
+
+        if !path.exists() {
+            let _ = std::fs::create_dir_all(&path);
+        }
+
+        Some(path)
+    }
+
     fn get_file_table() -> Option<toml_edit::Document> {
         let path = Self::settings_file()?;
         let content = std::fs::read_to_string(path).ok()?;
@@ -1210,3 +1346,33 @@ impl Config {
         self.editor.tab_width as f64 * width
     }
 }
+
+/// Merge `other` into `base`, where the tables are merged key by key and
+/// any other value of `other` replaces the one in `base`
+/// Drops `[lsp.servers]` from the `[lsp]` table of a workspace settings file
+fn strip_workspace_servers(lsp: &mut serde_json::Value, path: &Path) {
+    if let Some(lsp) = lsp.as_object_mut() {
+        if lsp.remove("servers").is_some() {
+            log::warn!(
+                "Ignoring [lsp.servers] in {path:?}, language servers can only be \
+                 configured in the user settings"
+            );
+        }
+    }
+}
+
+fn merge_json(base: &mut serde_json::Value, other: serde_json::Value) {
+    match (base, other) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_json(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
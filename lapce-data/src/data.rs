@@ -11,7 +11,7 @@ use std::{
 #[cfg(target_os = "windows")]
 use std::env;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use directories::BaseDirs;
 use druid::{
@@ -31,14 +31,18 @@ use lapce_core::{
     selection::Selection,
 };
 use lapce_rpc::{
-    buffer::BufferId, plugin::PluginDescription, source_control::FileDiff,
-    terminal::TermId,
+    buffer::BufferId, core::LanguageServerStatus, plugin::PluginDescription,
+    source_control::FileDiff, terminal::TermId,
 };
 
 use lapce_proxy::plugin::PluginCatalog;
 
-use lsp_types::{Diagnostic, DiagnosticSeverity, Position, ProgressToken, TextEdit};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentChangeOperation, DocumentChanges,
+    Position, ProgressToken, TextDocumentEdit, TextEdit, WorkspaceEdit,
+};
 use notify::Watcher;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use toml_edit::easy as toml;
@@ -46,10 +50,12 @@ use xi_rope::{Rope, RopeDelta};
 
 use crate::{
     alert::{AlertContentData, AlertData},
+    call_hierarchy::CallHierarchyData,
     command::{
         CommandKind, EnsureVisiblePosition, InitBufferContentCb, LapceCommand,
         LapceUICommand, LapceWorkbenchCommand, PluginLoadingStatus, LAPCE_COMMAND,
-        LAPCE_OPEN_FILE, LAPCE_OPEN_FOLDER, LAPCE_UI_COMMAND,
+        LAPCE_EXPORT_LSP_TRACE, LAPCE_OPEN_FILE, LAPCE_OPEN_FOLDER,
+        LAPCE_UI_COMMAND,
     },
     completion::CompletionData,
     config::{Config, ConfigWatcher, GetConfig, LapceTheme},
@@ -58,24 +64,35 @@ use crate::{
         SplitInfo, TabsInfo, WindowInfo, WorkspaceInfo,
     },
     document::{BufferContent, Document, LocalBufferKind},
-    editor::{EditorLocation, EditorPosition, LapceEditorBufferData, Line, TabRect},
+    editor::{
+        apply_text_edits, workspace_edits, EditorLocation, EditorPosition,
+        LapceEditorBufferData, Line, TabRect,
+    },
     explorer::FileExplorerData,
     find::Find,
     hover::HoverData,
     keypress::KeyPressData,
+    linked_editing::LinkedEditing,
+    lsp_trace::LspTraceData,
+    notification::NotificationData,
+    output::OutputData,
     palette::{PaletteData, PaletteType, PaletteViewData},
     panel::{
         PanelContainerPosition, PanelData, PanelKind, PanelOrder, PanelPosition,
     },
+    peek::{PeekData, PEEK_LINES},
     picker::FilePickerData,
     plugin::PluginData,
     problem::ProblemData,
-    proxy::{LapceProxy, ProxyStatus, TermEvent},
+    proxy::{path_from_url, LapceProxy, ProxyStatus, TermEvent},
+    rename::RenameData,
     search::SearchData,
     settings::LapceSettingsPanelData,
+    signature::SignatureData,
     source_control::SourceControlData,
     split::{SplitDirection, SplitMoveDirection},
     terminal::TerminalSplitData,
+    type_hierarchy::TypeHierarchyData,
 };
 
 /// `LapceData` is the topmost structure in a tree of structures that holds
@@ -106,6 +123,7 @@ impl LapceData {
         let keypress = Arc::new(KeyPressData::new(&config, event_sink.clone()));
         let panel_orders = db
             .get_panel_orders()
+            .map(Self::add_missing_panels)
             .unwrap_or_else(|_| Self::default_panel_orders());
 
         if let Some(path) = path {
@@ -261,12 +279,33 @@ impl LapceData {
         );
         order.insert(
             PanelPosition::BottomLeft,
-            im::vector![PanelKind::Terminal, PanelKind::Search, PanelKind::Problem,],
+            im::vector![
+                PanelKind::Terminal,
+                PanelKind::Search,
+                PanelKind::Problem,
+                PanelKind::CallHierarchy,
+                PanelKind::TypeHierarchy,
+                PanelKind::Output,
+                PanelKind::LspTrace,
+            ],
         );
 
         order
     }
 
+    /// Add the panels that are missing from a saved order, which happens
+    /// when new panels were added since it was saved, at their default position
+    fn add_missing_panels(mut order: PanelOrder) -> PanelOrder {
+        for (position, kinds) in Self::default_panel_orders().iter() {
+            for kind in kinds.iter() {
+                if !order.values().any(|panels| panels.contains(kind)) {
+                    order.entry(*position).or_default().push_back(*kind);
+                }
+            }
+        }
+        order
+    }
+
     pub fn reload_env(&self, env: &mut Env) {
         env.set(theme::SCROLLBAR_WIDTH, 10.0);
         env.set(theme::SCROLLBAR_EDGE_WIDTH, 0.0);
@@ -428,6 +467,9 @@ impl LapceWindowData {
         if let Some(path) = Config::themes_folder() {
             let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
         }
+        if let Some(path) = Config::snippets_folder() {
+            let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
+        }
         if let Some(path) = KeyPressData::file() {
             let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
         }
@@ -517,11 +559,19 @@ pub struct LapceTabData {
     pub main_split: LapceMainSplitData,
     pub completion: Arc<CompletionData>,
     pub hover: Arc<HoverData>,
+    pub signature: Arc<SignatureData>,
+    pub peek: Arc<PeekData>,
+    pub rename: Arc<RenameData>,
     pub terminal: Arc<TerminalSplitData>,
     pub palette: Arc<PaletteData>,
     pub find: Arc<Find>,
     pub source_control: Arc<SourceControlData>,
     pub problem: Arc<ProblemData>,
+    pub call_hierarchy: Arc<CallHierarchyData>,
+    pub type_hierarchy: Arc<TypeHierarchyData>,
+    pub output: Arc<OutputData>,
+    pub lsp_trace: Arc<LspTraceData>,
+    pub notification: Arc<NotificationData>,
     pub search: Arc<SearchData>,
     pub plugin: Arc<PluginData>,
     pub picker: Arc<FilePickerData>,
@@ -545,6 +595,8 @@ pub struct LapceTabData {
     pub focus_area: FocusArea,
     pub db: Arc<LapceDb>,
     pub progresses: im::Vector<WorkProgress>,
+    /// The status of the language servers, keyed by their name
+    pub language_servers: im::OrdMap<String, LanguageServerStatus>,
     pub drag: Arc<Option<(Vec2, Vec2, DragContent)>>,
 }
 
@@ -553,6 +605,9 @@ impl Data for LapceTabData {
         self.main_split.same(&other.main_split)
             && self.completion.same(&other.completion)
             && self.hover.same(&other.hover)
+            && self.signature.same(&other.signature)
+            && self.peek.same(&other.peek)
+            && self.rename.same(&other.rename)
             && self.palette.same(&other.palette)
             && self.workspace.same(&other.workspace)
             && self.source_control.same(&other.source_control)
@@ -565,9 +620,15 @@ impl Data for LapceTabData {
             && self.find.same(&other.find)
             && self.alert.same(&other.alert)
             && self.progresses.ptr_eq(&other.progresses)
+            && self.language_servers.ptr_eq(&other.language_servers)
             && self.file_explorer.same(&other.file_explorer)
             && self.plugin.same(&other.plugin)
             && self.problem.same(&other.problem)
+            && self.call_hierarchy.same(&other.call_hierarchy)
+            && self.type_hierarchy.same(&other.type_hierarchy)
+            && self.output.same(&other.output)
+            && self.lsp_trace.same(&other.lsp_trace)
+            && self.notification.same(&other.notification)
             && self.search.same(&other.search)
             && self
                 .installed_plugins_desc
@@ -615,9 +676,13 @@ impl LapceTabData {
             term_sender.clone(),
             event_sink.clone(),
         ));
+        proxy.update_lsp_settings(&config.lsp);
         let palette = Arc::new(PaletteData::new(proxy.clone()));
         let completion = Arc::new(CompletionData::new());
         let hover = Arc::new(HoverData::new());
+        let signature = Arc::new(SignatureData::new());
+        let peek = Arc::new(PeekData::new());
+        let rename = Arc::new(RenameData::new());
         let source_control = Arc::new(SourceControlData::new());
         let settings = Arc::new(LapceSettingsPanelData::new());
         let alert = Arc::new(AlertData::new());
@@ -643,6 +708,7 @@ impl LapceTabData {
             tab_id,
             workspace_info.as_ref(),
             palette.preview_editor,
+            peek.editor_id,
             proxy.clone(),
             &config,
             event_sink.clone(),
@@ -696,6 +762,11 @@ impl LapceTabData {
 
         let terminal = Arc::new(TerminalSplitData::new(proxy.clone()));
         let problem = Arc::new(ProblemData::new());
+        let call_hierarchy = Arc::new(CallHierarchyData::new());
+        let type_hierarchy = Arc::new(TypeHierarchyData::new());
+        let output = Arc::new(OutputData::new());
+        let lsp_trace = Arc::new(LspTraceData::new());
+        let notification = Arc::new(NotificationData::new());
         let panel = workspace_info
             .map(|i| {
                 let mut panel = i.panel;
@@ -715,9 +786,17 @@ impl LapceTabData {
             main_split,
             completion,
             hover,
+            signature,
+            peek,
+            rename,
             terminal,
             plugin,
             problem,
+            call_hierarchy,
+            type_hierarchy,
+            output,
+            lsp_trace,
+            notification,
             search,
             plugins: Arc::new(Vec::new()),
             disabled_plugins: Arc::new(HashMap::new()),
@@ -742,6 +821,7 @@ impl LapceTabData {
             focus_area: FocusArea::Editor,
             db,
             progresses: im::Vector::new(),
+            language_servers: im::OrdMap::new(),
             drag: Arc::new(None),
         };
         tab.start_update_process(event_sink);
@@ -811,6 +891,8 @@ impl LapceTabData {
             main_split: self.main_split.clone(),
             completion: self.completion.clone(),
             hover: self.hover.clone(),
+            signature: self.signature.clone(),
+            peek: self.peek.clone(),
             source_control: self.source_control.clone(),
             proxy: self.proxy.clone(),
             find: self.find.clone(),
@@ -857,6 +939,8 @@ impl LapceTabData {
     ) {
         self.completion = editor_buffer_data.completion.clone();
         self.hover = editor_buffer_data.hover.clone();
+        self.signature = editor_buffer_data.signature.clone();
+        self.peek = editor_buffer_data.peek.clone();
         self.main_split = editor_buffer_data.main_split.clone();
         self.find = editor_buffer_data.find.clone();
         if !editor_buffer_data.editor.same(editor) {
@@ -1000,6 +1084,117 @@ impl LapceTabData {
         }
     }
 
+    /// The signature help is shown above the cursor, so that it doesn't
+    /// cover the completion below it, unless there's no room there
+    pub fn signature_origin(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        config: &Config,
+    ) -> Point {
+        let editor = self.main_split.editors.get(&self.signature.editor_view_id);
+        let editor = match editor {
+            Some(editor) => editor,
+            None => return Point::ZERO,
+        };
+
+        let doc = self.main_split.editor_doc(editor.view_id);
+        let (point_above, point_below) =
+            doc.points_of_offset(text, self.signature.offset, &editor.view, config);
+        let content_height = self.signature.content_size.borrow().height;
+        let window_origin =
+            *editor.window_origin.borrow() - self.window_origin.borrow().to_vec2();
+        let mut origin =
+            window_origin + Vec2::new(point_above.x, point_above.y - content_height);
+        if origin.y < 0.0 {
+            origin.y = window_origin.y + point_below.y;
+        }
+        if origin.x + self.signature.size.width + 1.0 > tab_size.width {
+            origin.x = tab_size.width - self.signature.size.width - 1.0;
+        }
+        if origin.x <= 0.0 {
+            origin.x = 0.0;
+        }
+
+        origin
+    }
+
+    /// The peek view is as wide as the editor that it's displayed in, with
+    /// a header line above the lines of the read-only editor
+    pub fn peek_size(&self) -> Size {
+        let width = self
+            .main_split
+            .editors
+            .get(&self.peek.editor_view_id)
+            .map(|editor| editor.size.borrow().width)
+            .unwrap_or(0.0);
+        let line_height = self.config.editor.line_height as f64;
+        Size::new(width, (PEEK_LINES + 1) as f64 * line_height)
+    }
+
+    /// The peek view is shown below the line that it was opened on, unless
+    /// there's no room for it in the editor
+    pub fn peek_origin(
+        &self,
+        text: &mut PietText,
+        height: f64,
+        config: &Config,
+    ) -> Point {
+        let editor = self.main_split.editors.get(&self.peek.editor_view_id);
+        let editor = match editor {
+            Some(editor) => editor,
+            None => return Point::ZERO,
+        };
+
+        let doc = self.main_split.editor_doc(editor.view_id);
+        let (point_above, point_below) =
+            doc.points_of_offset(text, self.peek.offset, &editor.view, config);
+        let window_origin =
+            *editor.window_origin.borrow() - self.window_origin.borrow().to_vec2();
+        let top = window_origin.y + editor.scroll_offset.y;
+        let bottom = top + editor.size.borrow().height;
+        let mut y = window_origin.y + point_below.y;
+        if y + height > bottom {
+            y = (window_origin.y + point_above.y - height).max(top);
+        }
+        Point::new(window_origin.x + editor.scroll_offset.x, y)
+    }
+
+    pub fn rename_origin(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        rename_size: Size,
+        config: &Config,
+    ) -> Point {
+        let editor = match self.main_split.editors.get(&self.rename.editor_view_id) {
+            Some(editor) => editor,
+            None => return Point::ZERO,
+        };
+
+        let doc = self.main_split.editor_doc(editor.view_id);
+        let (point_above, point_below) =
+            doc.points_of_offset(text, self.rename.offset, &editor.view, config);
+
+        let mut origin = *editor.window_origin.borrow()
+            - self.window_origin.borrow().to_vec2()
+            + Vec2::new(point_below.x, point_below.y);
+        if origin.y + rename_size.height + 1.0 > tab_size.height {
+            origin.y = editor.window_origin.borrow().y
+                - self.window_origin.borrow().y
+                + point_above.y
+                - rename_size.height;
+        }
+        if origin.x + rename_size.width + 1.0 > tab_size.width {
+            origin.x = tab_size.width - rename_size.width - 1.0;
+        }
+        if origin.x <= 0.0 {
+            origin.x = 0.0;
+        }
+
+        origin
+    }
+
     pub fn palette_view_data(&self) -> PaletteViewData {
         PaletteViewData {
             palette: self.palette.clone(),
@@ -1278,6 +1473,18 @@ impl LapceTabData {
             LapceWorkbenchCommand::ToggleProblemVisual => {
                 self.toggle_panel_visual(ctx, PanelKind::Problem);
             }
+            LapceWorkbenchCommand::ToggleCallHierarchyVisual => {
+                self.toggle_panel_visual(ctx, PanelKind::CallHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleTypeHierarchyVisual => {
+                self.toggle_panel_visual(ctx, PanelKind::TypeHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleOutputVisual => {
+                self.toggle_panel_visual(ctx, PanelKind::Output);
+            }
+            LapceWorkbenchCommand::ToggleLspTraceVisual => {
+                self.toggle_panel_visual(ctx, PanelKind::LspTrace);
+            }
             LapceWorkbenchCommand::ToggleTerminalVisual => {
                 self.toggle_panel_visual(ctx, PanelKind::Terminal);
             }
@@ -1315,6 +1522,18 @@ impl LapceTabData {
             LapceWorkbenchCommand::ToggleProblemFocus => {
                 self.toggle_panel_focus(ctx, PanelKind::Problem);
             }
+            LapceWorkbenchCommand::ToggleCallHierarchyFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::CallHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleTypeHierarchyFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::TypeHierarchy);
+            }
+            LapceWorkbenchCommand::ToggleOutputFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::Output);
+            }
+            LapceWorkbenchCommand::ToggleLspTraceFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::LspTrace);
+            }
             LapceWorkbenchCommand::ToggleTerminalFocus => {
                 self.toggle_panel_focus(ctx, PanelKind::Terminal);
             }
@@ -1439,6 +1658,19 @@ impl LapceTabData {
                     Target::Auto,
                 ))
             }
+            LapceWorkbenchCommand::ExportLspTrace => {
+                let options = FileDialogOptions::new()
+                    .default_name("lsp-trace.jsonl")
+                    .accept_command(LAPCE_EXPORT_LSP_TRACE);
+                ctx.submit_command(druid::commands::SHOW_SAVE_PANEL.with(options));
+            }
+            LapceWorkbenchCommand::RestartLanguageServer => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::LanguageServer)),
+                    Target::Auto,
+                ))
+            }
             LapceWorkbenchCommand::NextEditorTab => {
                 if let Some(active) = *self.main_split.active_tab {
                     ctx.submit_command(Command::new(
@@ -1466,6 +1698,24 @@ impl LapceTabData {
                     toml_edit::Value::from(config.editor.enable_inlay_hints),
                 );
             }
+            LapceWorkbenchCommand::MessageRequestAction => {
+                let data = match data {
+                    Some(data) => data,
+                    None => return,
+                };
+                let request_id = data.get("request_id").and_then(|id| id.as_u64());
+                // Only the request that the alert is still showing is answered,
+                // closing the alert answers it with null
+                if request_id.is_some() && request_id == self.alert.message_request {
+                    if let Some(request_id) =
+                        Arc::make_mut(&mut self.alert).message_request.take()
+                    {
+                        let action =
+                            data.get("action").cloned().unwrap_or(Value::Null);
+                        self.proxy.respond(request_id, Ok(action));
+                    }
+                }
+            }
         }
     }
 
@@ -1586,6 +1836,10 @@ impl LapceTabData {
             PanelKind::Terminal => self.terminal.widget_id,
             PanelKind::Search => self.search.active,
             PanelKind::Problem => self.problem.widget_id,
+            PanelKind::CallHierarchy => self.call_hierarchy.widget_id,
+            PanelKind::TypeHierarchy => self.type_hierarchy.widget_id,
+            PanelKind::Output => self.output.widget_id,
+            PanelKind::LspTrace => self.lsp_trace.widget_id,
         };
         if let PanelKind::Search = kind {
             ctx.submit_command(Command::new(
@@ -1616,7 +1870,13 @@ impl LapceTabData {
 
     fn toggle_panel_focus(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         let should_hide = match kind {
-            PanelKind::FileExplorer | PanelKind::Plugin | PanelKind::Problem => {
+            PanelKind::FileExplorer
+            | PanelKind::Plugin
+            | PanelKind::Problem
+            | PanelKind::CallHierarchy
+            | PanelKind::TypeHierarchy
+            | PanelKind::Output
+            | PanelKind::LspTrace => {
                 // Some panels don't accept focus (yet). Fall back to visibility check
                 // in those cases.
                 self.panel.is_panel_visible(&kind)
@@ -1840,6 +2100,32 @@ impl SplitData {
 //     SplitActive,
 // }
 
+/// Responds to a `workspace/applyEdit` request when the last reference to it
+/// is dropped, which is once the last of the files was edited. A file that
+/// can't be loaded drops its reference without being edited, which makes the
+/// edit fail.
+struct WorkspaceEditProgress {
+    /// The number of files that weren't edited yet
+    remaining: usize,
+    respond: Option<Box<dyn FnOnce(Result<()>) + Send>>,
+}
+
+impl Drop for WorkspaceEditProgress {
+    fn drop(&mut self) {
+        if let Some(respond) = self.respond.take() {
+            let result = if self.remaining == 0 {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "{} of the files couldn't be edited",
+                    self.remaining
+                ))
+            };
+            respond(result);
+        }
+    }
+}
+
 #[derive(Clone, Data, Lens)]
 pub struct LapceMainSplitData {
     pub tab_id: Arc<WidgetId>,
@@ -1857,6 +2143,7 @@ pub struct LapceMainSplitData {
     pub register: Arc<Register>,
     pub proxy: Arc<LapceProxy>,
     pub palette_preview_editor: Arc<WidgetId>,
+    pub peek_editor: Arc<WidgetId>,
     pub diagnostics: im::HashMap<PathBuf, Arc<Vec<EditorDiagnostic>>>,
     pub error_count: usize,
     pub warning_count: usize,
@@ -2037,6 +2324,151 @@ impl LapceMainSplitData {
         Some(delta)
     }
 
+    /// Apply the LSP text edits to the file at `path`, and call `edited` once
+    /// they are.  
+    /// If the file isn't open, it is loaded without an editor, edited and then
+    /// saved, since there would be nothing to save it from. The document is
+    /// dropped again once it's saved, see [`Self::close_hidden_document`].
+    pub fn apply_edits_to_file(
+        &mut self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        edits: Vec<TextEdit>,
+        edited: impl Fn() + Send + 'static,
+    ) {
+        if let Some(doc) = self.open_docs.get(path).cloned() {
+            if !doc.loaded() {
+                log::error!("Can't edit {path:?} while it's being loaded");
+                return;
+            }
+            apply_text_edits(&doc, self, path, &edits);
+            // A file that is still only loaded to edit it is saved again
+            if !self.has_editor(path) {
+                self.document_save(ctx, path, None);
+            }
+            edited();
+            return;
+        }
+
+        let mut doc = Document::new(
+            BufferContent::File(path.to_path_buf()),
+            *self.tab_id,
+            ctx.get_external_handle(),
+            self.proxy.clone(),
+        );
+        let cb_path = path.to_path_buf();
+        let cb: InitBufferContentCb = Box::new(
+            move |ctx: &mut EventCtx, main_split: &mut LapceMainSplitData| {
+                if let Some(doc) = main_split.open_docs.get(&cb_path).cloned() {
+                    apply_text_edits(&doc, main_split, &cb_path, &edits);
+                    main_split.document_save(ctx, &cb_path, None);
+                    edited();
+                }
+            },
+        );
+        doc.retrieve_file::<usize>(Vec::new(), None, Some(cb));
+        self.open_docs.insert(path.to_path_buf(), Arc::new(doc));
+    }
+
+    /// Drop the document of the file if no editor shows it, which is the case
+    /// when it was only loaded to apply edits to it. Opening the file later
+    /// loads it again from the disk.
+    pub fn close_hidden_document(&mut self, path: &Path) {
+        if self.has_editor(path) {
+            return;
+        }
+        // It was edited again while it was being saved
+        if !self
+            .open_docs
+            .get(path)
+            .map_or(false, |doc| doc.buffer().is_pristine())
+        {
+            return;
+        }
+        if let Some(doc) = self.open_docs.remove(path) {
+            self.proxy.close_buffer(doc.id());
+        }
+    }
+
+    /// Whether an editor shows the file
+    fn has_editor(&self, path: &Path) -> bool {
+        let content = BufferContent::File(path.to_path_buf());
+        self.editors
+            .values()
+            .any(|editor| editor.content == content)
+    }
+
+    /// Apply a workspace edit that a language server sent with
+    /// `workspace/applyEdit` to all of the files it changes. `respond` is
+    /// called with the result once all of them are edited, which can be after
+    /// the files that aren't open were loaded.
+    pub fn apply_workspace_edit(
+        &mut self,
+        ctx: &mut EventCtx,
+        edit: &WorkspaceEdit,
+        respond: impl FnOnce(Result<()>) + Send + 'static,
+    ) {
+        // The edit is rejected as a whole rather than applied partly
+        if let Err(e) = self.check_workspace_edit(edit) {
+            respond(Err(e));
+            return;
+        }
+
+        let edits = workspace_edits(edit).unwrap_or_default();
+        let progress = Arc::new(Mutex::new(WorkspaceEditProgress {
+            remaining: edits.len(),
+            respond: Some(Box::new(respond)),
+        }));
+        for (url, edits) in edits {
+            let progress = progress.clone();
+            self.apply_edits_to_file(ctx, &path_from_url(&url), edits, move || {
+                progress.lock().remaining -= 1;
+            });
+        }
+    }
+
+    /// Whether the workspace edit can be applied: only text edits are
+    /// supported, and the versions of the documents that the edits were
+    /// computed for have to be the ones that are open
+    fn check_workspace_edit(&self, edit: &WorkspaceEdit) -> Result<()> {
+        let document_edits: Vec<&TextDocumentEdit> =
+            match edit.document_changes.as_ref() {
+                Some(DocumentChanges::Edits(edits)) => edits.iter().collect(),
+                Some(DocumentChanges::Operations(ops)) => {
+                    let mut edits = Vec::new();
+                    for op in ops {
+                        match op {
+                            DocumentChangeOperation::Edit(edit) => edits.push(edit),
+                            DocumentChangeOperation::Op(_) => {
+                                return Err(anyhow!(
+                                    "resource operations are not supported"
+                                ));
+                            }
+                        }
+                    }
+                    edits
+                }
+                None => Vec::new(),
+            };
+
+        for edit in document_edits {
+            let version = match edit.text_document.version {
+                Some(version) => version,
+                None => continue,
+            };
+            let path = path_from_url(&edit.text_document.uri);
+            if let Some(doc) = self.open_docs.get(&path) {
+                if doc.rev() as i32 != version {
+                    return Err(anyhow!(
+                        "{path:?} is at version {} and not {version}",
+                        doc.rev()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_active_tab_mut(
         &mut self,
         ctx: &mut EventCtx,
@@ -2597,7 +3029,10 @@ impl LapceMainSplitData {
                     LapceUICommand::ForceScrollTo(scroll_offset.x, scroll_offset.y),
                     Target::Widget(editor.view_id),
                 ));
-            } else if new_buffer || editor_view_id == *self.palette_preview_editor {
+            } else if new_buffer
+                || editor_view_id == *self.palette_preview_editor
+                || editor_view_id == *self.peek_editor
+            {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
                     LapceUICommand::EnsureCursorPosition(
@@ -2656,6 +3091,7 @@ impl LapceMainSplitData {
         tab_id: WidgetId,
         workspace_info: Option<&WorkspaceInfo>,
         palette_preview_editor: WidgetId,
+        peek_editor: WidgetId,
         proxy: Arc<LapceProxy>,
         config: &Config,
         event_sink: ExtEventSink,
@@ -2689,6 +3125,15 @@ impl LapceMainSplitData {
                 proxy.clone(),
             )),
         );
+        local_docs.insert(
+            LocalBufferKind::Rename,
+            Arc::new(Document::new(
+                BufferContent::Local(LocalBufferKind::Rename),
+                tab_id,
+                event_sink.clone(),
+                proxy.clone(),
+            )),
+        );
         let value_docs = im::HashMap::new();
         let scratch_docs = im::HashMap::new();
 
@@ -2701,6 +3146,15 @@ impl LapceMainSplitData {
         );
         editors.insert(editor.view_id, Arc::new(editor));
 
+        let editor = LapceEditorData::new(
+            Some(peek_editor),
+            None,
+            None,
+            BufferContent::Local(LocalBufferKind::Empty),
+            config,
+        );
+        editors.insert(editor.view_id, Arc::new(editor));
+
         let mut main_split_data = Self {
             tab_id: Arc::new(tab_id),
             split_id,
@@ -2717,6 +3171,7 @@ impl LapceMainSplitData {
             current_save_as: None,
             proxy,
             palette_preview_editor: Arc::new(palette_preview_editor),
+            peek_editor: Arc::new(peek_editor),
             diagnostics: im::HashMap::new(),
             error_count: 0,
             warning_count: 0,
@@ -3395,6 +3850,8 @@ pub struct LapceEditorData {
     pub size: Rc<RefCell<Size>>,
     pub window_origin: Rc<RefCell<Point>>,
     pub snippet: Option<Vec<(usize, (usize, usize))>>,
+    /// The ranges that are edited together while the cursor is in them
+    pub linked_editing: Option<LinkedEditing>,
     pub locations: Vec<EditorLocation>,
     pub current_location: usize,
     pub last_movement_new: Movement,
@@ -3436,6 +3893,7 @@ impl LapceEditorData {
             compare: None,
             window_origin: Rc::new(RefCell::new(Point::ZERO)),
             snippet: None,
+            linked_editing: None,
             locations: vec![],
             current_location: 0,
             last_movement_new: Movement::Left,
@@ -3454,9 +3912,38 @@ impl LapceEditorData {
             .map(|_| (WidgetId::next(), WidgetId::next()));
         new_editor.size = Rc::new(RefCell::new(Size::ZERO));
         new_editor.window_origin = Rc::new(RefCell::new(Point::ZERO));
+        new_editor.cancel_linked_editing();
         new_editor
     }
 
+    /// Put a caret in each of the linked editing ranges, if the cursor is
+    /// still in one of them
+    pub fn set_linked_editing(&mut self, ranges: Vec<(usize, usize)>) {
+        let region = match &self.cursor.mode {
+            CursorMode::Insert(selection) if selection.len() == 1 => {
+                selection.regions()[0]
+            }
+            _ => return,
+        };
+        if let Some(linked_editing) = LinkedEditing::new(ranges, &region) {
+            self.cursor.set_insert(linked_editing.selection(&region));
+            self.linked_editing = Some(linked_editing);
+        }
+    }
+
+    /// Drop the linked editing ranges, leaving only the caret in the range
+    /// that the cursor was in
+    pub fn cancel_linked_editing(&mut self) {
+        if let Some(linked_editing) = self.linked_editing.take() {
+            if let CursorMode::Insert(selection) = &self.cursor.mode {
+                if let Some(region) = linked_editing.primary_region(selection) {
+                    self.cursor
+                        .set_insert(Selection::region(region.start, region.end));
+                }
+            }
+        }
+    }
+
     pub fn is_code_lens(&self) -> bool {
         matches!(self.view, EditorView::Lens)
     }
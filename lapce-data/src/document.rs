@@ -12,7 +12,8 @@ use std::{
 
 use druid::{
     piet::{
-        PietText, PietTextLayout, Text, TextAttribute, TextLayout, TextLayoutBuilder,
+        HitTestPoint, PietText, PietTextLayout, Text, TextAttribute, TextLayout,
+        TextLayoutBuilder,
     },
     Color, ExtEventSink, Point, SingleUse, Size, Target, Vec2, WidgetId,
 };
@@ -20,7 +21,7 @@ use lapce_core::{
     buffer::{Buffer, DiffLines, InvalLines},
     command::{EditCommand, MultiSelectionCommand},
     cursor::{ColPosition, Cursor, CursorMode},
-    editor::{EditType, Editor},
+    editor::{EditOptions, EditType, Editor},
     language::LapceLanguage,
     mode::{Mode, MotionMode},
     movement::{LinePosition, Movement},
@@ -32,11 +33,12 @@ use lapce_core::{
 };
 use lapce_rpc::{
     buffer::BufferId,
+    core::SignatureTriggers,
     style::{LineStyle, LineStyles, Style},
 };
 use lsp_types::{
-    CodeActionOrCommand, CodeActionResponse, DiagnosticSeverity, InlayHint,
-    InlayHintLabel,
+    CodeActionOrCommand, CodeActionResponse, CodeLens, DiagnosticSeverity,
+    DocumentHighlightKind, InlayHint, InlayHintLabel,
 };
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
@@ -46,6 +48,7 @@ use xi_rope::{
 };
 
 use crate::{
+    code_lens::CodeLenses,
     command::{InitBufferContentCb, LapceUICommand, LAPCE_UI_COMMAND},
     config::{Config, LapceTheme},
     data::{EditorDiagnostic, EditorView},
@@ -118,6 +121,7 @@ pub enum LocalBufferKind {
     Keymap,
     Settings,
     PathName,
+    Rename,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -143,7 +147,8 @@ impl BufferContent {
                 | LocalBufferKind::FilePicker
                 | LocalBufferKind::Settings
                 | LocalBufferKind::Keymap
-                | LocalBufferKind::PathName => true,
+                | LocalBufferKind::PathName
+                | LocalBufferKind::Rename => true,
                 LocalBufferKind::Empty => false,
             },
             BufferContent::SettingsValue(..) => true,
@@ -160,7 +165,8 @@ impl BufferContent {
                 | LocalBufferKind::FilePicker
                 | LocalBufferKind::Settings
                 | LocalBufferKind::Keymap
-                | LocalBufferKind::PathName => true,
+                | LocalBufferKind::PathName
+                | LocalBufferKind::Rename => true,
                 LocalBufferKind::Empty | LocalBufferKind::SourceControl => false,
             },
             BufferContent::SettingsValue(..) => true,
@@ -382,6 +388,15 @@ impl<'hint, 'diag> PhantomTextLine<'hint, 'diag> {
     }
 }
 
+/// An occurrence of the symbol under the cursor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    pub start: usize,
+    pub end: usize,
+    /// If the symbol is written to here, rather than only read
+    pub write: bool,
+}
+
 #[derive(Clone)]
 pub struct Document {
     id: BufferId,
@@ -399,7 +414,14 @@ pub struct Document {
     pub scroll_offset: Vec2,
     pub code_actions: im::HashMap<usize, CodeActionResponse>,
     pub inlay_hints: Option<Spans<InlayHint>>,
+    lsp_refresh: u64,
+    /// The code lenses from the language server, shown above their lines
+    pub code_lenses: Arc<CodeLenses>,
+    /// The occurrences of the symbol under the cursor, sorted by offset
+    pub occurrences: Option<Arc<Vec<Occurrence>>>,
     pub diagnostics: Option<Arc<Vec<EditorDiagnostic>>>,
+    /// The signature help triggers of each language server of the document
+    signature_triggers: im::HashMap<String, SignatureTriggers>,
     pub find: Rc<RefCell<Find>>,
     find_progress: Rc<RefCell<FindProgress>>,
     pub event_sink: ExtEventSink,
@@ -440,7 +462,11 @@ impl Document {
             scroll_offset: Vec2::ZERO,
             code_actions: im::HashMap::new(),
             inlay_hints: None,
+            lsp_refresh: 0,
+            code_lenses: Arc::new(CodeLenses::default()),
+            occurrences: None,
             diagnostics: None,
+            signature_triggers: im::HashMap::new(),
             find: Rc::new(RefCell::new(Find::new(0))),
             find_progress: Rc::new(RefCell::new(FindProgress::Ready)),
             event_sink,
@@ -486,6 +512,23 @@ impl Document {
         self.syntax = Some(Syntax::from_language(language));
     }
 
+    pub fn set_signature_triggers(
+        &mut self,
+        server: &str,
+        triggers: SignatureTriggers,
+    ) {
+        self.signature_triggers.insert(server.to_string(), triggers);
+    }
+
+    /// Whether typing `c` asks a language server of the document for the
+    /// signature help, or for the signature help again if it's `active`
+    pub fn is_signature_trigger(&self, c: &str, active: bool) -> bool {
+        self.signature_triggers.values().any(|triggers| {
+            triggers.trigger_characters.iter().any(|t| t == c)
+                || (active && triggers.retrigger_characters.iter().any(|t| t == c))
+        })
+    }
+
     pub fn set_diagnostics(&mut self, diagnostics: &[EditorDiagnostic]) {
         self.clear_text_layout_cache();
         self.diagnostics = Some(Arc::new(
@@ -549,6 +592,8 @@ impl Document {
     pub fn reload(&mut self, content: Rope, set_pristine: bool) {
         self.code_actions.clear();
         self.inlay_hints = None;
+        self.code_lenses = Arc::new(CodeLenses::default());
+        self.occurrences = None;
         let delta = self.buffer.reload(content, set_pristine);
         self.apply_deltas(&[delta]);
     }
@@ -728,7 +773,7 @@ impl Document {
             return;
         }
         if let Some(history) = self.histories.get_mut(version) {
-            history.update_changes(changes);
+            history.update_changes(rev, changes);
         }
     }
 
@@ -742,88 +787,154 @@ impl Document {
         }
     }
 
-    fn get_semantic_styles(&self) {
+    /// The offsets and the LSP range of the lines from `start_line` to
+    /// `end_line`
+    fn lines_range(
+        &self,
+        start_line: usize,
+        end_line: usize,
+    ) -> Option<(Interval, lsp_types::Range)> {
+        let start = self.buffer.offset_of_line(start_line);
+        let end = self
+            .buffer
+            .offset_of_line(end_line.min(self.buffer.last_line()) + 1);
+        let range = lsp_types::Range {
+            start: self.buffer.offset_to_position(start)?,
+            end: self.buffer.offset_to_position(end)?,
+        };
+        Some((Interval::new(start, end), range))
+    }
+
+    /// Request the semantic styles of the document, the language server
+    /// only styles the lines from `start_line` to `end_line` if the document
+    /// is large
+    pub fn get_semantic_styles(&self, start_line: usize, end_line: usize) {
         if !self.loaded() {
             return;
         }
 
-        if !self.content().is_file() {
-            return;
-        }
         if let BufferContent::File(path) = self.content() {
+            let (interval, range) = match self.lines_range(start_line, end_line) {
+                Some(range) => range,
+                None => return,
+            };
             let tab_id = self.tab_id;
             let path = path.clone();
             let buffer_id = self.id();
             let rev = self.rev();
             let len = self.buffer().len();
             let event_sink = self.event_sink.clone();
-            self.proxy.get_semantic_tokens(buffer_id, move |result| {
-                if let Ok(resp) = result {
-                    rayon::spawn(move || {
-                        let mut styles_span = SpansBuilder::new(len);
-                        for style in resp.styles {
-                            styles_span.add_span(
-                                Interval::new(style.start, style.end),
-                                style.style,
+            self.proxy
+                .get_semantic_tokens(buffer_id, range, move |result| {
+                    if let Ok(resp) = result {
+                        rayon::spawn(move || {
+                            if resp.partial {
+                                let mut styles_span =
+                                    SpansBuilder::new(interval.size());
+                                for style in resp.styles {
+                                    if style.start < interval.start
+                                        || style.start >= interval.end
+                                    {
+                                        continue;
+                                    }
+                                    styles_span.add_span(
+                                        Interval::new(
+                                            style.start - interval.start,
+                                            style.end.min(interval.end)
+                                                - interval.start,
+                                        ),
+                                        style.style,
+                                    );
+                                }
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::UpdateSemanticStylesRange {
+                                        path,
+                                        rev,
+                                        interval,
+                                        styles: styles_span.build(),
+                                    },
+                                    Target::Widget(tab_id),
+                                );
+                                return;
+                            }
+
+                            let mut styles_span = SpansBuilder::new(len);
+                            for style in resp.styles {
+                                styles_span.add_span(
+                                    Interval::new(style.start, style.end),
+                                    style.style,
+                                );
+                            }
+                            let styles_span = Arc::new(styles_span.build());
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::UpdateSemanticStyles(
+                                    buffer_id,
+                                    path,
+                                    rev,
+                                    styles_span,
+                                ),
+                                Target::Widget(tab_id),
                             );
-                        }
-                        let styles_span = Arc::new(styles_span.build());
-                        let _ = event_sink.submit_command(
-                            LAPCE_UI_COMMAND,
-                            LapceUICommand::UpdateSemanticStyles(
-                                buffer_id,
-                                path,
-                                rev,
-                                styles_span,
-                            ),
-                            Target::Widget(tab_id),
-                        );
-                    });
-                }
-            });
+                        });
+                    }
+                });
         }
     }
 
-    pub fn get_inlay_hints(&self) {
+    /// Request the inlay hints of the lines from `start_line` to `end_line`
+    pub fn get_inlay_hints(&self, start_line: usize, end_line: usize) {
         if !self.loaded() {
             return;
         }
 
-        if !self.content().is_file() {
-            return;
-        }
-
         if let BufferContent::File(path) = self.content() {
+            let (interval, range) = match self.lines_range(start_line, end_line) {
+                Some(range) => range,
+                None => return,
+            };
             let tab_id = self.tab_id;
             let path = path.clone();
             let buffer_id = self.id();
             let rev = self.rev();
-            let len = self.buffer().len();
             let buffer = self.buffer().clone();
             let event_sink = self.event_sink.clone();
-            self.proxy.get_inlay_hints(buffer_id, move |result| {
+            self.proxy.get_inlay_hints(buffer_id, range, move |result| {
                 if let Ok(mut resp) = result {
                     // Sort the inlay hints by their position, as the LSP does not guarantee that it will
                     // provide them in the order that they are in within the file
                     // as well, Spans does not iterate in the order that they appear
                     resp.sort_by(|left, right| left.position.cmp(&right.position));
 
-                    let mut hints_span = SpansBuilder::new(len);
+                    // The hints are relative to the start of the lines, as
+                    // they replace the hints of those lines
+                    let mut hints_span = SpansBuilder::new(interval.size());
                     for hint in resp {
                         if let Some(offset) =
                             buffer.offset_of_position(&hint.position)
                         {
-                            let offset = offset.min(len);
+                            if offset < interval.start || offset >= interval.end {
+                                continue;
+                            }
                             hints_span.add_span(
-                                Interval::new(offset, (offset + 1).min(len)),
-                                hint.clone(),
+                                Interval::new(
+                                    offset - interval.start,
+                                    offset + 1 - interval.start,
+                                ),
+                                hint,
                             );
                         }
                     }
                     let hints = hints_span.build();
                     let _ = event_sink.submit_command(
                         LAPCE_UI_COMMAND,
-                        LapceUICommand::UpdateInlayHints { path, rev, hints },
+                        LapceUICommand::UpdateInlayHints {
+                            path,
+                            rev,
+                            interval,
+                            hints,
+                        },
                         Target::Widget(tab_id),
                     );
                 }
@@ -831,11 +942,218 @@ impl Document {
         }
     }
 
+    /// Request the code lenses of the document, and resolve the ones in the
+    /// lines from `start_line` to `end_line` that the server left unresolved
+    pub fn get_code_lens(&self, start_line: usize, end_line: usize) {
+        if !self.loaded() {
+            return;
+        }
+
+        if let BufferContent::File(path) = self.content() {
+            let tab_id = self.tab_id;
+            let path = path.clone();
+            let buffer_id = self.id();
+            let rev = self.rev();
+            let proxy = self.proxy.clone();
+            let event_sink = self.event_sink.clone();
+            self.proxy.get_code_lens(buffer_id, move |result| {
+                let lenses = match result {
+                    Ok(Some(lenses)) => lenses,
+                    _ => return,
+                };
+                let unresolved: Vec<CodeLens> = lenses
+                    .iter()
+                    .filter(|lens| {
+                        let line = lens.range.start.line as usize;
+                        lens.command.is_none()
+                            && line >= start_line
+                            && line <= end_line
+                    })
+                    .cloned()
+                    .collect();
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateCodeLens {
+                        path: path.clone(),
+                        rev,
+                        lenses,
+                    },
+                    Target::Widget(tab_id),
+                );
+                for lens in unresolved {
+                    resolve_code_lens(
+                        &proxy,
+                        event_sink.clone(),
+                        tab_id,
+                        buffer_id,
+                        path.clone(),
+                        rev,
+                        lens,
+                    );
+                }
+            });
+        }
+    }
+
+    /// Resolve the commands of the code lenses in the lines from `start_line`
+    /// to `end_line` that the server left out
+    pub fn resolve_code_lenses(&self, start_line: usize, end_line: usize) {
+        if let BufferContent::File(path) = self.content() {
+            for lens in self.code_lenses.unresolved(start_line, end_line) {
+                resolve_code_lens(
+                    &self.proxy,
+                    self.event_sink.clone(),
+                    self.tab_id,
+                    self.id(),
+                    path.clone(),
+                    self.rev(),
+                    lens.clone(),
+                );
+            }
+        }
+    }
+
+    /// Request the occurrences of the symbol at `offset` from the language
+    /// server. If no server can highlight them, the identifiers with the same
+    /// text in the lines from `start_line` to `end_line` are used instead.
+    pub fn get_occurrences(
+        &self,
+        offset: usize,
+        start_line: usize,
+        end_line: usize,
+    ) {
+        if !self.loaded() {
+            return;
+        }
+
+        if let BufferContent::File(path) = self.content() {
+            let position = match self.buffer.offset_to_position(offset) {
+                Some(position) => position,
+                None => return,
+            };
+            let tab_id = self.tab_id;
+            let path = path.clone();
+            let rev = self.rev();
+            let buffer = self.buffer().clone();
+            let syntax = self.syntax().filter(|s| s.rev == rev).cloned();
+            let event_sink = self.event_sink.clone();
+            self.proxy
+                .get_document_highlight(self.id(), position, move |result| {
+                    let mut occurrences: Vec<Occurrence> = match result {
+                        Ok(highlights) => highlights
+                            .unwrap_or_default()
+                            .iter()
+                            .filter_map(|highlight| {
+                                Some(Occurrence {
+                                    start: buffer.offset_of_position(
+                                        &highlight.range.start,
+                                    )?,
+                                    end: buffer
+                                        .offset_of_position(&highlight.range.end)?,
+                                    write: highlight.kind
+                                        == Some(DocumentHighlightKind::WRITE),
+                                })
+                            })
+                            .collect(),
+                        Err(_) => {
+                            let start = buffer.offset_of_line(start_line);
+                            let end = buffer.offset_of_line(end_line + 1);
+                            syntax
+                                .map(|syntax| {
+                                    syntax
+                                        .find_identifier_occurrences(
+                                            offset,
+                                            start..end,
+                                        )
+                                        .into_iter()
+                                        .map(|(start, end)| Occurrence {
+                                            start,
+                                            end,
+                                            write: false,
+                                        })
+                                        .collect()
+                                })
+                                .unwrap_or_default()
+                        }
+                    };
+                    occurrences.sort_by_key(|occurrence| occurrence.start);
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateOccurrences {
+                            path,
+                            rev,
+                            occurrences,
+                        },
+                        Target::Widget(tab_id),
+                    );
+                });
+        }
+    }
+
+    /// Request the ranges that are edited together with the one at `offset`
+    /// from the language server. If no server provides them, the names of
+    /// the tag at `offset` and of its matching tag are used instead.
+    pub fn get_linked_editing_ranges(&self, view_id: WidgetId, offset: usize) {
+        if !self.loaded() {
+            return;
+        }
+
+        if let BufferContent::File(path) = self.content() {
+            let position = match self.buffer.offset_to_position(offset) {
+                Some(position) => position,
+                None => return,
+            };
+            let tab_id = self.tab_id;
+            let path = path.clone();
+            let rev = self.rev();
+            let buffer = self.buffer().clone();
+            let syntax = self.syntax().filter(|s| s.rev == rev).cloned();
+            let event_sink = self.event_sink.clone();
+            self.proxy.get_linked_editing_range(
+                self.id(),
+                position,
+                move |result| {
+                    let ranges: Vec<(usize, usize)> = match result {
+                        Ok(ranges) => ranges
+                            .map(|ranges| {
+                                ranges
+                                    .ranges
+                                    .iter()
+                                    .filter_map(|range| {
+                                        Some((
+                                            buffer
+                                                .offset_of_position(&range.start)?,
+                                            buffer.offset_of_position(&range.end)?,
+                                        ))
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                        Err(_) => syntax
+                            .and_then(|syntax| syntax.find_linked_tag_names(offset))
+                            .unwrap_or_default(),
+                    };
+                    if ranges.len() < 2 {
+                        return;
+                    }
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateLinkedEditing {
+                            view_id,
+                            path,
+                            rev,
+                            ranges,
+                        },
+                        Target::Widget(tab_id),
+                    );
+                },
+            );
+        }
+    }
+
     fn on_update(&mut self, delta: Option<&RopeDelta>) {
         self.find.borrow_mut().unset();
         *self.find_progress.borrow_mut() = FindProgress::Started;
-        self.get_inlay_hints();
-        self.get_semantic_styles();
         self.clear_style_cache();
         self.trigger_syntax_change(delta);
         self.trigger_head_change();
@@ -887,7 +1205,7 @@ impl Document {
                             Target::Widget(self.tab_id),
                         );
                     }
-                    LocalBufferKind::PathName => {
+                    LocalBufferKind::PathName | LocalBufferKind::Rename => {
                         // TODO: anything to update with this?
                     }
                 }
@@ -908,6 +1226,38 @@ impl Document {
         self.clear_style_cache();
     }
 
+    /// Replace the semantic styles of the interval with new ones, whose
+    /// offsets are relative to the start of the interval. The syntax styles
+    /// stand in for the lines that weren't styled by the language server yet.
+    pub fn merge_semantic_styles(
+        &mut self,
+        interval: Interval,
+        styles: Spans<Style>,
+    ) {
+        let len = self.buffer.len();
+        let mut all_styles = match self.semantic_styles.take() {
+            Some(all_styles) => all_styles,
+            None => self
+                .syntax()
+                .and_then(|syntax| syntax.styles.clone())
+                .filter(|styles| styles.len() == len)
+                .unwrap_or_else(|| Arc::new(SpansBuilder::new(len).build())),
+        };
+        Arc::make_mut(&mut all_styles).edit(interval, styles);
+        self.semantic_styles = Some(all_styles);
+        self.clear_style_cache();
+    }
+
+    /// Bumped when the language servers ask for the semantic styles and the
+    /// inlay hints to be requested again
+    pub fn lsp_refresh(&self) -> u64 {
+        self.lsp_refresh
+    }
+
+    pub fn refresh_lsp(&mut self) {
+        self.lsp_refresh += 1;
+    }
+
     fn clear_style_cache(&self) {
         self.line_styles.borrow_mut().clear();
         self.clear_text_layout_cache();
@@ -946,13 +1296,65 @@ impl Document {
         }
     }
 
-    /// Update the inlay hints with new ones
+    /// Replace the inlay hints of the interval with new ones, whose offsets
+    /// are relative to the start of the interval
     /// Clears any caches that need to be updated after change
-    pub fn set_inlay_hints(&mut self, hints: Spans<InlayHint>) {
-        self.inlay_hints = Some(hints);
+    pub fn set_inlay_hints(&mut self, interval: Interval, hints: Spans<InlayHint>) {
+        let len = self.buffer.len();
+        self.inlay_hints
+            .get_or_insert_with(|| SpansBuilder::new(len).build())
+            .edit(interval, hints);
         self.clear_text_layout_cache();
     }
 
+    pub fn set_code_lenses(&mut self, lenses: Vec<CodeLens>) {
+        self.code_lenses = Arc::new(CodeLenses::new(&self.buffer, lenses));
+    }
+
+    pub fn set_resolved_code_lens(&mut self, lens: CodeLens) {
+        Arc::make_mut(&mut self.code_lenses).resolve(lens);
+    }
+
+    /// The code lenses that are shown in the normal view
+    fn visible_code_lenses(&self, config: &Config) -> Option<&CodeLenses> {
+        if config.editor.enable_code_lens && !self.code_lenses.is_empty() {
+            Some(&self.code_lenses)
+        } else {
+            None
+        }
+    }
+
+    /// The row of the normal view that the line is on, which is below the
+    /// phantom lines of the code lenses before it
+    pub fn row_of_line(&self, config: &Config, line: usize) -> usize {
+        match self.visible_code_lenses(config) {
+            Some(lenses) => lenses.row_of_line(line),
+            None => line,
+        }
+    }
+
+    /// The line at the row of the normal view, and if the row is the phantom
+    /// line of the code lenses of the line
+    pub fn line_of_row(&self, config: &Config, row: usize) -> (usize, bool) {
+        match self.visible_code_lenses(config) {
+            Some(lenses) => lenses.line_of_row(row),
+            None => (row, false),
+        }
+    }
+
+    /// The number of rows of the normal view
+    pub fn num_rows(&self, config: &Config) -> usize {
+        self.buffer.num_lines()
+            + self
+                .visible_code_lenses(config)
+                .map(|l| l.len())
+                .unwrap_or(0)
+    }
+
+    pub fn set_occurrences(&mut self, occurrences: Vec<Occurrence>) {
+        self.occurrences = Some(Arc::new(occurrences));
+    }
+
     pub fn buffer(&self) -> &Buffer {
         &self.buffer
     }
@@ -1038,6 +1440,11 @@ impl Document {
             self.update_styles(delta);
             self.update_inlay_hints(delta);
             self.update_diagnostics(delta);
+            if !self.code_lenses.is_empty() {
+                Arc::make_mut(&mut self.code_lenses)
+                    .apply_delta(&self.buffer, delta);
+            }
+            self.occurrences = None;
             if self.content.is_file() {
                 self.proxy.update(self.id, delta, rev + i as u64 + 1);
             }
@@ -1079,8 +1486,8 @@ impl Document {
         &mut self,
         cursor: &mut Cursor,
         cmd: &EditCommand,
-        modal: bool,
         register: &mut Register,
+        options: EditOptions,
     ) -> Vec<(RopeDelta, InvalLines)> {
         let mut clipboard = SystemClipboard {};
         let old_cursor = cursor.mode.clone();
@@ -1090,8 +1497,8 @@ impl Document {
             cmd,
             self.syntax.as_ref(),
             &mut clipboard,
-            modal,
             register,
+            options,
         );
         self.buffer_mut().set_cursor_before(old_cursor);
         self.buffer_mut().set_cursor_after(cursor.mode.clone());
@@ -1338,8 +1745,13 @@ impl Document {
         }
     }
 
-    pub fn do_paste(&mut self, cursor: &mut Cursor, data: &RegisterData) {
-        let deltas = Editor::do_paste(cursor, &mut self.buffer, data);
+    pub fn do_paste(
+        &mut self,
+        cursor: &mut Cursor,
+        data: &RegisterData,
+        reindent: bool,
+    ) {
+        let deltas = Editor::do_paste(cursor, &mut self.buffer, data, reindent);
         self.apply_deltas(&deltas)
     }
 
@@ -1365,14 +1777,15 @@ impl Document {
         self.line_styles.borrow().get(&line).cloned().unwrap()
     }
 
-    pub fn line_col_of_point(
+    /// The line at the point and where the point is in its text with the
+    /// phantom text
+    fn hit_test_point(
         &self,
         text: &mut PietText,
-        mode: Mode,
         point: Point,
         view: &EditorView,
         config: &Config,
-    ) -> ((usize, usize), bool) {
+    ) -> (usize, HitTestPoint) {
         let (line, font_size) = match view {
             EditorView::Diff(version) => {
                 if let Some(history) = self.get_history(version) {
@@ -1433,10 +1846,11 @@ impl Document {
                     )
                 }
             }
-            EditorView::Normal => (
-                (point.y / config.editor.line_height as f64).floor() as usize,
-                config.editor.font_size,
-            ),
+            EditorView::Normal => {
+                let row =
+                    (point.y / config.editor.line_height as f64).floor() as usize;
+                (self.line_of_row(config, row).0, config.editor.font_size)
+            }
         };
 
         let line = line.min(self.buffer.last_line());
@@ -1472,6 +1886,18 @@ impl Document {
         let hit_point = text_layout
             .text
             .hit_test_point(Point::new(point.x - x_shift, 0.0));
+        (line, hit_point)
+    }
+
+    pub fn line_col_of_point(
+        &self,
+        text: &mut PietText,
+        mode: Mode,
+        point: Point,
+        view: &EditorView,
+        config: &Config,
+    ) -> ((usize, usize), bool) {
+        let (line, hit_point) = self.hit_test_point(text, point, view, config);
         let phantom_text = self.line_phantom_text(config, line);
         let col = phantom_text.before_col(hit_point.idx);
         let max_col = self.buffer.line_end_col(line, mode != Mode::Normal);
@@ -1479,6 +1905,19 @@ impl Document {
         ((line, col), hit_point.is_inside)
     }
 
+    /// The first inlay hint at or after the offset on its line, if there's one
+    pub fn inlay_hint_after_offset(&self, offset: usize) -> Option<InlayHint> {
+        let line = self.buffer.line_of_offset(offset);
+        let end_offset = self.buffer.offset_of_line(line + 1);
+        self.inlay_hints
+            .as_ref()?
+            .iter_chunks(offset..end_offset)
+            .find(|(interval, _)| {
+                interval.start >= offset && interval.start < end_offset
+            })
+            .map(|(_, hint)| hint.clone())
+    }
+
     pub fn offset_of_point(
         &self,
         text: &mut PietText,
@@ -1565,7 +2004,7 @@ impl Document {
                 }
             }
             EditorView::Normal => (
-                config.editor.line_height * line,
+                config.editor.line_height * self.row_of_line(config, line),
                 config.editor.line_height,
                 config.editor.font_size,
             ),
@@ -2527,3 +2966,25 @@ impl Document {
         }
     }
 }
+
+/// Ask the language server for the command of the code lens, which is sent
+/// back to the document if it's still at revision `rev`
+fn resolve_code_lens(
+    proxy: &LapceProxy,
+    event_sink: ExtEventSink,
+    tab_id: WidgetId,
+    buffer_id: BufferId,
+    path: PathBuf,
+    rev: u64,
+    lens: CodeLens,
+) {
+    proxy.code_lens_resolve(buffer_id, lens, move |result| {
+        if let Ok(lens) = result {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::UpdateResolvedCodeLens { path, rev, lens },
+                Target::Widget(tab_id),
+            );
+        }
+    });
+}
@@ -1,9 +1,14 @@
+use crate::call_hierarchy::CallHierarchyDirection;
+use crate::code_lens::CodeLensAction;
 use crate::command::InitBufferContentCb;
 use crate::command::LapceCommand;
 use crate::command::LAPCE_COMMAND;
 use crate::command::LAPCE_SAVE_FILE_AS;
 use crate::command::{CommandExecuted, CommandKind};
-use crate::completion::{CompletionData, CompletionStatus, Snippet};
+use crate::completion::{
+    path_completion_dir, path_completion_fragment, CompletionData, CompletionStatus,
+    Snippet,
+};
 use crate::config::Config;
 use crate::data::EditorView;
 use crate::data::{
@@ -17,9 +22,14 @@ use crate::hover::HoverData;
 use crate::hover::HoverStatus;
 use crate::keypress::KeyMap;
 use crate::keypress::KeyPressFocus;
+use crate::linked_editing::is_linked_char;
 use crate::palette::PaletteData;
+use crate::peek::{PeekData, PeekKind, PeekStatus};
 use crate::proxy::path_from_url;
 use crate::proxy::RequestError;
+use crate::signature::{SignatureData, SignatureStatus};
+use crate::snippet::{resolve_variables, time_variable};
+use crate::type_hierarchy::TypeHierarchyDirection;
 use crate::{
     command::{
         EnsureVisiblePosition, InitBufferContent, LapceUICommand, LAPCE_UI_COMMAND,
@@ -40,15 +50,17 @@ use druid::{
 use druid::{ExtEventSink, MouseEvent};
 use indexmap::IndexMap;
 use lapce_core::buffer::Buffer;
-use lapce_core::buffer::{DiffLines, InvalLines};
+use lapce_core::buffer::{rope_diff, DiffLines, InvalLines};
 use lapce_core::command::{
     EditCommand, FocusCommand, MotionModeCommand, MultiSelectionCommand,
 };
-use lapce_core::editor::EditType;
+use lapce_core::editor::{EditOptions, EditType};
 use lapce_core::mode::{Mode, MotionMode};
 use lapce_core::selection::InsertDrift;
 use lapce_core::selection::Selection;
 pub use lapce_core::syntax::Syntax;
+use lapce_rpc::buffer::BufferId;
+use lapce_rpc::proxy::CancellableRequest;
 use lsp_types::request::GotoTypeDefinitionResponse;
 use lsp_types::CodeActionOrCommand;
 use lsp_types::CompletionTextEdit;
@@ -60,12 +72,15 @@ use lsp_types::Url;
 use lsp_types::WorkspaceEdit;
 use lsp_types::{
     CodeActionResponse, CompletionItem, DiagnosticSeverity, GotoDefinitionResponse,
-    Location, Position,
+    InlayHint, Location, Position,
 };
 use std::cmp::Ordering;
 use std::path::Path;
 use std::thread;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Arc},
+};
 use std::{iter::Iterator, path::PathBuf};
 use std::{str::FromStr, time::Duration};
 use xi_rope::Rope;
@@ -218,6 +233,8 @@ pub struct LapceEditorBufferData {
     pub doc: Arc<Document>,
     pub completion: Arc<CompletionData>,
     pub hover: Arc<HoverData>,
+    pub signature: Arc<SignatureData>,
+    pub peek: Arc<PeekData>,
     pub main_split: LapceMainSplitData,
     pub source_control: Arc<SourceControlData>,
     pub palette: Arc<PaletteData>,
@@ -232,6 +249,16 @@ impl LapceEditorBufferData {
         Arc::make_mut(&mut self.doc)
     }
 
+    /// The first and the last line that the editor shows
+    pub fn visible_lines(&self) -> (usize, usize) {
+        let line_height = self.config.editor.line_height as f64;
+        let scroll_offset = self.editor.scroll_offset;
+        let height = self.editor.size.borrow().height;
+        let start_line = (scroll_offset.y / line_height).floor() as usize;
+        let end_line = ((scroll_offset.y + height) / line_height).ceil() as usize;
+        (start_line, end_line)
+    }
+
     pub fn sync_buffer_position(&mut self, scroll_offset: Vec2) {
         let cursor_offset = self.editor.cursor.offset();
         if self.doc.cursor_offset != cursor_offset
@@ -329,7 +356,11 @@ impl LapceEditorBufferData {
             if view_id != &self.editor.view_id
                 && self.doc.content() == &editor.content
             {
-                Arc::make_mut(editor).cursor.apply_delta(delta);
+                let editor = Arc::make_mut(editor);
+                editor.cursor.apply_delta(delta);
+                if let Some(linked_editing) = editor.linked_editing.as_mut() {
+                    linked_editing.apply_delta(delta);
+                }
             }
         }
     }
@@ -348,6 +379,16 @@ impl LapceEditorBufferData {
         self.hover.status != HoverStatus::Inactive && !self.hover.is_empty()
     }
 
+    fn has_signature(&self) -> bool {
+        self.signature.status == SignatureStatus::Done
+            && self.signature.editor_view_id == self.editor.view_id
+    }
+
+    fn has_peek(&self) -> bool {
+        self.peek.status == PeekStatus::Done
+            && self.peek.editor_view_id == self.editor.view_id
+    }
+
     pub fn run_code_action(
         &mut self,
         ctx: &mut EventCtx,
@@ -355,7 +396,9 @@ impl LapceEditorBufferData {
     ) {
         if let BufferContent::File(path) = &self.editor.content {
             match action {
-                CodeActionOrCommand::Command(_cmd) => {}
+                CodeActionOrCommand::Command(command) => {
+                    self.execute_command(command.clone());
+                }
                 CodeActionOrCommand::CodeAction(action) => {
                     if let Some(edit) = action.edit.as_ref() {
                         if let Some(edits) = workspace_edits(edit) {
@@ -368,7 +411,7 @@ impl LapceEditorBufferData {
                                         .get(&path)
                                         .unwrap()
                                         .clone();
-                                    apply_code_action(
+                                    apply_text_edits(
                                         &doc,
                                         &mut self.main_split,
                                         &path,
@@ -406,7 +449,7 @@ impl LapceEditorBufferData {
                                                 return;
                                             };
 
-                                            apply_code_action(&doc, main_split, &url_path, &edits);
+                                            apply_text_edits(&doc, main_split, &url_path, &edits);
                                         }),
                                     );
                                 } else {
@@ -415,11 +458,27 @@ impl LapceEditorBufferData {
                             }
                         }
                     }
+                    // The command runs after the edit, and the server may send
+                    // more edits back with `workspace/applyEdit`
+                    if let Some(command) = action.command.as_ref() {
+                        self.execute_command(command.clone());
+                    }
                 }
             }
         }
     }
 
+    /// Let the language server run the command with `workspace/executeCommand`
+    fn execute_command(&self, command: lsp_types::Command) {
+        let title = command.title.clone();
+        self.proxy
+            .execute_command(self.doc.id(), command, move |result| {
+                if let Err(err) = result {
+                    log::error!("failed to run the command {title}: {err:?}");
+                }
+            });
+    }
+
     pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
         let additional_edit: Option<Option<Vec<_>>> =
             item.additional_text_edits.as_ref().map(|edits| {
@@ -507,50 +566,11 @@ impl LapceEditorBufferData {
                             return Ok(());
                         }
                         lsp_types::InsertTextFormat::SNIPPET => {
-                            let snippet = Snippet::from_str(&edit.new_text)?;
-                            let text = snippet.text();
-                            let (delta, inval_lines) = Arc::make_mut(&mut self.doc)
-                                .do_raw_edit(
-                                    &[
-                                        &[(&selection, text.as_str())][..],
-                                        &additional_edit[..],
-                                    ]
-                                    .concat(),
-                                    EditType::Completion,
-                                );
-                            let selection = selection.apply_delta(
-                                &delta,
-                                true,
-                                InsertDrift::Default,
+                            return self.apply_snippet(
+                                &selection,
+                                &edit.new_text,
+                                &additional_edit,
                             );
-
-                            let mut transformer = Transformer::new(&delta);
-                            let offset = transformer
-                                .transform(start_offset.min(edit_start), false);
-                            let snippet_tabs = snippet.tabs(offset);
-
-                            if snippet_tabs.is_empty() {
-                                Arc::make_mut(&mut self.editor)
-                                    .cursor
-                                    .update_selection(self.doc.buffer(), selection);
-                                self.apply_deltas(&[(delta, inval_lines)]);
-                                return Ok(());
-                            }
-
-                            let mut selection =
-                                lapce_core::selection::Selection::new();
-                            let (_tab, (start, end)) = &snippet_tabs[0];
-                            let region = lapce_core::selection::SelRegion::new(
-                                *start, *end, None,
-                            );
-                            selection.add_region(region);
-                            Arc::make_mut(&mut self.editor)
-                                .cursor
-                                .set_insert(selection);
-                            self.apply_deltas(&[(delta, inval_lines)]);
-                            Arc::make_mut(&mut self.editor)
-                                .add_snippet_placeholders(snippet_tabs);
-                            return Ok(());
                         }
                         _ => {}
                     }
@@ -564,6 +584,16 @@ impl LapceEditorBufferData {
         let end_offset = self.doc.buffer().next_code_boundary(offset);
         let selection = Selection::region(start_offset, end_offset);
 
+        if text_format == lsp_types::InsertTextFormat::SNIPPET {
+            if let Some(insert_text) = item.insert_text.as_deref() {
+                return self.apply_snippet(
+                    &selection,
+                    insert_text,
+                    &additional_edit,
+                );
+            }
+        }
+
         let (delta, inval_lines) = Arc::make_mut(&mut self.doc).do_raw_edit(
             &[
                 &[(
@@ -583,16 +613,217 @@ impl LapceEditorBufferData {
         Ok(())
     }
 
+    /// Replace `selection` with the snippet and select its first placeholder
+    fn apply_snippet(
+        &mut self,
+        selection: &Selection,
+        snippet: &str,
+        additional_edit: &[(&Selection, &str)],
+    ) -> Result<()> {
+        let snippet = Snippet::from_str(&self.resolve_snippet_variables(snippet))?;
+        let text = snippet.text();
+        let (delta, inval_lines) = Arc::make_mut(&mut self.doc).do_raw_edit(
+            &[&[(selection, text.as_str())][..], additional_edit].concat(),
+            EditType::Completion,
+        );
+        let new_selection =
+            selection.apply_delta(&delta, true, InsertDrift::Default);
+
+        let mut transformer = Transformer::new(&delta);
+        let offset = transformer.transform(selection.min_offset(), false);
+        let snippet_tabs = snippet.tabs(offset);
+
+        if snippet_tabs.is_empty() {
+            Arc::make_mut(&mut self.editor)
+                .cursor
+                .update_selection(self.doc.buffer(), new_selection);
+            self.apply_deltas(&[(delta, inval_lines)]);
+            return Ok(());
+        }
+
+        let mut selection = Selection::new();
+        let (_tab, (start, end)) = &snippet_tabs[0];
+        let region = lapce_core::selection::SelRegion::new(*start, *end, None);
+        selection.add_region(region);
+        Arc::make_mut(&mut self.editor).cursor.set_insert(selection);
+        self.apply_deltas(&[(delta, inval_lines)]);
+        Arc::make_mut(&mut self.editor).add_snippet_placeholders(snippet_tabs);
+        Ok(())
+    }
+
+    /// Resolve variables such as `$TM_FILENAME` or `$CLIPBOARD` in a snippet
+    fn resolve_snippet_variables(&self, snippet: &str) -> String {
+        let buffer = self.doc.buffer();
+        let offset = self.editor.cursor.offset();
+        let line = buffer.line_of_offset(offset);
+        let path = match self.doc.content() {
+            BufferContent::File(path) => Some(path.as_path()),
+            _ => None,
+        };
+        let workspace = self.main_split.workspace.path.as_deref();
+        resolve_variables(snippet, |name| {
+            Some(match name {
+                "TM_FILENAME" => path?.file_name()?.to_str()?.to_string(),
+                "TM_FILENAME_BASE" => path?.file_stem()?.to_str()?.to_string(),
+                "TM_DIRECTORY" => path?.parent()?.to_str()?.to_string(),
+                "TM_FILEPATH" => path?.to_str()?.to_string(),
+                "RELATIVE_FILEPATH" => {
+                    path?.strip_prefix(workspace?).ok()?.to_str()?.to_string()
+                }
+                "TM_LINE_INDEX" => line.to_string(),
+                "TM_LINE_NUMBER" => (line + 1).to_string(),
+                "TM_CURRENT_LINE" => buffer
+                    .line_content(line)
+                    .trim_end_matches(|c: char| c == '\r' || c == '\n')
+                    .to_string(),
+                "TM_CURRENT_WORD" => {
+                    let (start, end) = buffer.select_word(offset);
+                    buffer.slice_to_cow(start..end).to_string()
+                }
+                "WORKSPACE_NAME" => workspace?.file_name()?.to_str()?.to_string(),
+                "WORKSPACE_FOLDER" => workspace?.to_str()?.to_string(),
+                "CLIPBOARD" => {
+                    druid::Application::global().clipboard().get_string()?
+                }
+                "LINE_COMMENT" => self
+                    .doc
+                    .syntax()
+                    .map(|s| s.language.comment_token())
+                    .unwrap_or("//")
+                    .to_string(),
+                "UUID" => uuid::Uuid::new_v4().to_string(),
+                _ => return time_variable(name),
+            })
+        })
+    }
+
     pub fn cancel_completion(&mut self) {
+        if self.completion.status != CompletionStatus::Inactive {
+            self.proxy.cancel_request(
+                CancellableRequest::Completion,
+                self.completion.request_id,
+            );
+        }
         let completion = Arc::make_mut(&mut self.completion);
         completion.cancel();
     }
 
     pub fn cancel_hover(&mut self) {
+        if self.hover.status != HoverStatus::Inactive {
+            self.proxy
+                .cancel_request(CancellableRequest::Hover, self.hover.request_id);
+        }
         let hover = Arc::make_mut(&mut self.hover);
         hover.cancel();
     }
 
+    pub fn cancel_signature(&mut self) {
+        if self.signature.status == SignatureStatus::Inactive {
+            return;
+        }
+        self.proxy.cancel_request(
+            CancellableRequest::SignatureHelp,
+            self.signature.request_id,
+        );
+        let signature = Arc::make_mut(&mut self.signature);
+        signature.cancel();
+    }
+
+    /// Request the signature help at the cursor when `trigger` was typed, or
+    /// update the one that is shown, which the server closes when the cursor
+    /// left the call
+    fn update_signature(&mut self, ctx: &mut EventCtx, trigger: Option<&str>) {
+        if self.get_mode() != Mode::Insert
+            || self.signature.editor_view_id != self.editor.view_id
+        {
+            self.cancel_signature();
+            return;
+        }
+        if trigger.is_some() || self.has_signature() {
+            self.request_signature(ctx, trigger);
+        }
+    }
+
+    fn request_signature(&mut self, ctx: &mut EventCtx, trigger: Option<&str>) {
+        if !self.doc.loaded() {
+            return;
+        }
+        if !self.doc.content().is_file() {
+            return;
+        }
+        let offset = self.editor.cursor.offset();
+        let position = match self.doc.buffer().offset_to_position(offset) {
+            Some(position) => position,
+            None => return,
+        };
+        let signature = Arc::make_mut(&mut self.signature);
+        signature.request(
+            self.proxy.clone(),
+            self.doc.id(),
+            self.editor.view_id,
+            offset,
+            position,
+            trigger.map(|trigger| trigger.to_string()),
+            ctx.get_external_handle(),
+        );
+    }
+
+    /// Request the locations of the `kind` at the cursor, which are shown in
+    /// the peek view below the line of the cursor
+    fn peek(&mut self, ctx: &mut EventCtx, kind: PeekKind) {
+        let offset = self.editor.cursor.offset();
+        let position = match self.doc.buffer().offset_to_position(offset) {
+            Some(position) => position,
+            None => {
+                log::error!("Failed to convert offset {offset} to position in peek");
+                return;
+            }
+        };
+        let buffer_id = self.doc.id();
+        let peek = Arc::make_mut(&mut self.peek);
+        let request_id = peek.request(kind, self.editor.view_id, offset);
+        let widget_id = peek.id;
+        let event_sink = ctx.get_external_handle();
+        let update = move |locations: Vec<Location>| {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::UpdatePeek {
+                    request_id,
+                    locations,
+                },
+                Target::Widget(widget_id),
+            );
+        };
+        match kind {
+            PeekKind::Definition => {
+                self.proxy.get_definition(
+                    request_id,
+                    buffer_id,
+                    position,
+                    move |result| {
+                        update(result.map(response_locations).unwrap_or_default());
+                    },
+                );
+            }
+            PeekKind::Implementation => {
+                self.proxy.get_implementation(
+                    request_id,
+                    buffer_id,
+                    position,
+                    move |result| {
+                        update(result.map(response_locations).unwrap_or_default());
+                    },
+                );
+            }
+            PeekKind::References => {
+                self.proxy
+                    .get_references(buffer_id, position, move |result| {
+                        update(result.unwrap_or_default());
+                    });
+            }
+        }
+    }
+
     /// Update the displayed autocompletion box
     /// Sends a request to the LSP for completion information
     fn update_completion(
@@ -626,10 +857,24 @@ impl LapceEditorBufferData {
                 .slice_to_cow(start_offset - 1..start_offset)
                 .to_string()
         };
+        let path_fragment = {
+            let line = self.doc.buffer().line_of_offset(offset);
+            let line_start = self.doc.buffer().offset_of_line(line);
+            let line_content = self
+                .doc
+                .buffer()
+                .slice_to_cow(line_start..offset)
+                .to_string();
+            path_completion_fragment(&line_content).map(|s| s.to_string())
+        };
         let completion = Arc::make_mut(&mut self.completion);
-        if !display_if_empty_input && input.is_empty() && char != "." && char != ":"
+        if !display_if_empty_input
+            && input.is_empty()
+            && char != "."
+            && char != ":"
+            && !(char == "/" && path_fragment.is_some())
         {
-            completion.cancel();
+            self.cancel_completion();
             return;
         }
 
@@ -686,6 +931,45 @@ impl LapceEditorBufferData {
         completion.input_items.clear();
         completion.request_id += 1;
         let event_sink = ctx.get_external_handle();
+        if let BufferContent::File(path) = self.doc.content() {
+            completion.set_local_items(self.config.snippets.completion_items(path));
+            // The home folder of a remote workspace isn't this one
+            let home = if self.main_split.workspace.kind.is_remote() {
+                None
+            } else {
+                directories::BaseDirs::new()
+                    .map(|dirs| dirs.home_dir().to_path_buf())
+            };
+            if let (Some(fragment), Some(base)) = (path_fragment, path.parent()) {
+                let name_len = fragment.rsplit('/').next().unwrap_or("").len();
+                if let (Some(dir), Some(start), Some(end)) = (
+                    path_completion_dir(base, &fragment, home.as_deref()),
+                    self.doc.buffer().offset_to_position(offset - name_len),
+                    self.doc.buffer().offset_to_position(offset),
+                ) {
+                    completion.request_paths(
+                        &self.proxy,
+                        dir,
+                        fragment,
+                        lsp_types::Range { start, end },
+                        event_sink.clone(),
+                    );
+                }
+            }
+            let others = self
+                .main_split
+                .open_docs
+                .values()
+                .filter(|doc| doc.id() != self.doc.id())
+                .map(|doc| doc.buffer().text().clone())
+                .collect();
+            completion.request_buffer_words(
+                self.doc.buffer().text().clone(),
+                offset,
+                others,
+                event_sink.clone(),
+            );
+        }
         if let Some(start_pos) = self.doc.buffer().offset_to_position(start_offset) {
             completion.request(
                 self.proxy.clone(),
@@ -720,20 +1004,20 @@ impl LapceEditorBufferData {
         is_inside: bool,
         within_scroll: bool,
     ) -> bool {
-        let hover = Arc::make_mut(&mut self.hover);
-
-        if hover.status != HoverStatus::Inactive {
+        if self.hover.status != HoverStatus::Inactive {
             if !is_inside || !within_scroll {
-                hover.cancel();
+                self.cancel_hover();
                 return false;
             }
 
             let start_offset = self.doc.buffer().prev_code_boundary(offset);
-            if self.doc.id() == hover.buffer_id && start_offset == hover.offset {
+            if self.doc.id() == self.hover.buffer_id
+                && start_offset == self.hover.offset
+            {
                 return true;
             }
 
-            hover.cancel();
+            self.cancel_hover();
             return false;
         }
 
@@ -815,6 +1099,66 @@ impl LapceEditorBufferData {
         }
     }
 
+    fn update_linked_editing_offset(&mut self, delta: &RopeDelta) {
+        if let Some(linked_editing) = &self.editor.linked_editing {
+            let mut linked_editing = linked_editing.clone();
+            linked_editing.apply_delta(delta);
+            Arc::make_mut(&mut self.editor).linked_editing = Some(linked_editing);
+        }
+    }
+
+    /// Drop the linked editing ranges when the cursor left them, and
+    /// request the ones at the cursor when there are none
+    fn update_linked_editing(&mut self) {
+        if let Some(linked_editing) = self.editor.linked_editing.as_ref() {
+            if let lapce_core::cursor::CursorMode::Insert(selection) =
+                &self.editor.cursor.mode
+            {
+                if linked_editing.contains(selection) {
+                    return;
+                }
+            }
+            Arc::make_mut(&mut self.editor).cancel_linked_editing();
+        }
+
+        if let lapce_core::cursor::CursorMode::Insert(selection) =
+            &self.editor.cursor.mode
+        {
+            if selection.len() == 1 {
+                self.doc.get_linked_editing_ranges(
+                    self.editor.view_id,
+                    self.editor.cursor.offset(),
+                );
+            }
+        }
+    }
+
+    /// Whether the edit stays within the linked editing ranges, so that it's
+    /// made in each of them
+    fn is_linked_edit(&self, cmd: &EditCommand) -> bool {
+        let linked_editing = match self.editor.linked_editing.as_ref() {
+            Some(linked_editing) => linked_editing,
+            None => return false,
+        };
+        let region = match &self.editor.cursor.mode {
+            lapce_core::cursor::CursorMode::Insert(selection) => {
+                match linked_editing.primary_region(selection) {
+                    Some(region) => region,
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        let (start, end) = linked_editing.ranges[linked_editing.primary];
+        match cmd {
+            EditCommand::DeleteBackward => {
+                !region.is_caret() || region.min() > start
+            }
+            EditCommand::DeleteForward => !region.is_caret() || region.max() < end,
+            _ => false,
+        }
+    }
+
     fn next_diff(&mut self, ctx: &mut EventCtx) {
         if let BufferContent::File(buffer_path) = self.doc.content() {
             if self.source_control.file_diffs.is_empty() {
@@ -927,6 +1271,67 @@ impl LapceEditorBufferData {
         }
     }
 
+    /// Run the command of a clicked code lens
+    pub fn run_code_lens(&self, ctx: &mut EventCtx, command: &lsp_types::Command) {
+        match CodeLensAction::new(command) {
+            CodeLensAction::RunInTerminal { cwd, command } => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunInTerminal { cwd, command },
+                    Target::Widget(*self.main_split.tab_id),
+                ));
+            }
+            CodeLensAction::ShowReferences(locations) => {
+                let locations = locations
+                    .iter()
+                    .map(|location| EditorLocation {
+                        path: path_from_url(&location.uri),
+                        position: Some(location.range.start),
+                        scroll_offset: None,
+                        history: None,
+                    })
+                    .collect();
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPaletteReferences(locations),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
+            CodeLensAction::Execute(command) => {
+                self.execute_command(command);
+            }
+        }
+    }
+
+    /// Move the cursor to the next or previous highlighted occurrence of the
+    /// symbol under the cursor, wrapping around at the ends
+    fn next_occurrence(&mut self, previous: bool) {
+        let occurrences = match self.doc.occurrences.as_ref() {
+            Some(occurrences) if !occurrences.is_empty() => occurrences.clone(),
+            _ => return,
+        };
+        let offset = self.editor.cursor.offset();
+        let occurrence = if previous {
+            occurrences
+                .iter()
+                .rev()
+                .find(|occurrence| occurrence.end < offset)
+                .unwrap_or_else(|| occurrences.last().unwrap())
+        } else {
+            occurrences
+                .iter()
+                .find(|occurrence| occurrence.start > offset)
+                .unwrap_or_else(|| occurrences.first().unwrap())
+        };
+        Arc::make_mut(&mut self.editor).cursor.set_offset(
+            occurrence.start,
+            false,
+            false,
+        );
+        self.cancel_completion();
+        self.cancel_hover();
+    }
+
     fn jump_location_forward(&mut self, ctx: &mut EventCtx) -> Option<()> {
         if self.editor.locations.is_empty() {
             return None;
@@ -1093,7 +1498,8 @@ impl LapceEditorBufferData {
             let line = self.doc.history_actual_line_from_visual(compare, line);
             (line, config.editor_char_width(text))
         } else {
-            let line = (pos.y / config.editor.line_height as f64).floor() as usize;
+            let row = (pos.y / config.editor.line_height as f64).floor() as usize;
+            let (line, _) = self.doc.line_of_row(config, row);
             (line, config.editor_char_width(text))
         };
 
@@ -1136,6 +1542,8 @@ impl LapceEditorBufferData {
             mouse_event.mods.shift(),
             mouse_event.mods.alt(),
         );
+        self.update_signature(ctx, None);
+        self.update_linked_editing();
 
         let mut go_to_definition = false;
         #[cfg(target_os = "macos")]
@@ -1185,6 +1593,41 @@ impl LapceEditorBufferData {
         );
     }
 
+    /// Insert the text edits of the inlay hint, which the language server is
+    /// asked for first if it left them out
+    fn insert_inlay_hint(&self, ctx: &mut EventCtx, hint: InlayHint) {
+        let path = match self.doc.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+        let rev = self.doc.rev();
+        let tab_id = *self.main_split.tab_id;
+        if let Some(edits) = hint.text_edits.clone() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::DocumentFormat(path, rev, Ok(edits)),
+                Target::Widget(tab_id),
+            ));
+            return;
+        }
+
+        let event_sink = ctx.get_external_handle();
+        self.proxy
+            .resolve_inlay_hint(self.doc.id(), hint, move |result| {
+                if let Ok(InlayHint {
+                    text_edits: Some(edits),
+                    ..
+                }) = result
+                {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::DocumentFormat(path, rev, Ok(edits)),
+                        Target::Widget(tab_id),
+                    );
+                }
+            });
+    }
+
     pub fn triple_click(
         &mut self,
         ctx: &mut EventCtx,
@@ -1215,9 +1658,169 @@ impl LapceEditorBufferData {
         for (delta, _) in deltas {
             self.inactive_apply_delta(delta);
             self.update_snippet_offset(delta);
+            self.update_linked_editing_offset(delta);
+        }
+    }
+
+    /// The LSP range covering the whole lines between the two offsets
+    fn line_range(&self, start: usize, end: usize) -> Option<lsp_types::Range> {
+        let buffer = self.doc.buffer();
+        let start_line = buffer.line_of_offset(start);
+        let end_line = buffer.line_of_offset(end);
+        Some(lsp_types::Range {
+            start: buffer.offset_to_position(buffer.offset_of_line(start_line))?,
+            end: buffer
+                .offset_to_position(buffer.line_end_offset(end_line, true))?,
+        })
+    }
+
+    /// The offsets of the first and the last line of each run of lines that
+    /// were changed since the last commit, or `None` if the file has no
+    /// committed version
+    fn modified_line_offsets(&self) -> Option<Vec<(usize, usize)>> {
+        let history = self.doc.get_history("head")?;
+        let text = self.doc.buffer().text();
+        // The changes are computed in the background after an edit, and
+        // they're of an older revision until then
+        if history.changes_rev() == Some(self.doc.rev()) {
+            return Some(changed_line_offsets(text, history.changes()));
+        }
+        let changes = rope_diff(
+            history.text()?.clone(),
+            text.clone(),
+            0,
+            Arc::new(AtomicU64::new(0)),
+        )?;
+        Some(changed_line_offsets(text, &changes))
+    }
+
+    /// The ranges of the lines that were changed since the last commit
+    fn modified_line_ranges(&self) -> Vec<lsp_types::Range> {
+        self.modified_line_offsets()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(start, end)| self.line_range(start, end))
+            .collect()
+    }
+
+    /// The ranges to format for `FormatSelection`, which are the selected lines or,
+    /// if nothing is selected, the modified lines
+    fn format_selection_ranges(&self) -> Vec<lsp_types::Range> {
+        let selection = self.editor.cursor.edit_selection(self.doc.buffer());
+        let ranges: Vec<lsp_types::Range> = selection
+            .regions()
+            .iter()
+            .filter(|region| !region.is_caret())
+            .filter_map(|region| self.line_range(region.min(), region.max()))
+            .collect();
+        if ranges.is_empty() {
+            self.modified_line_ranges()
+        } else {
+            ranges
         }
     }
 
+    /// Format the ranges with `textDocument/rangeFormatting`.
+    /// The edits for all of the ranges are applied together, as a single undo.
+    fn format_ranges(&self, ctx: &mut EventCtx, ranges: Vec<lsp_types::Range>) {
+        let path = match self.doc.content() {
+            BufferContent::File(path) if !ranges.is_empty() => path.clone(),
+            _ => return,
+        };
+        let proxy = self.proxy.clone();
+        let buffer_id = self.doc.id();
+        let rev = self.doc.rev();
+        let event_sink = ctx.get_external_handle();
+        let tab_id = *self.main_split.tab_id;
+        thread::spawn(move || {
+            let result = request_range_formatting(&proxy, buffer_id, ranges);
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::DocumentFormat(path, rev, result),
+                Target::Widget(tab_id),
+            );
+        });
+    }
+
+    /// Format the text that the paste inserted, at each of the carets
+    fn format_pasted(&self, ctx: &mut EventCtx, deltas: &[(RopeDelta, InvalLines)]) {
+        let mut inserted: Vec<(usize, usize)> = Vec::new();
+        for (delta, _) in deltas {
+            let mut transformer = Transformer::new(delta);
+            for (start, end) in inserted.iter_mut() {
+                *start = transformer.transform(*start, false);
+                *end = transformer.transform(*end, true);
+            }
+            inserted.extend(
+                delta.iter_inserts().map(|region| {
+                    (region.new_offset, region.new_offset + region.len)
+                }),
+            );
+        }
+
+        // The pastes on the same lines are formatted together, so that the
+        // edits of their ranges don't overlap
+        let buffer = self.doc.buffer();
+        let mut lines: Vec<(usize, usize)> = inserted
+            .into_iter()
+            .map(|(start, end)| {
+                let end = end.saturating_sub(1).max(start);
+                (buffer.line_of_offset(start), buffer.line_of_offset(end))
+            })
+            .collect();
+        lines.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in lines {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let ranges = merged
+            .into_iter()
+            .filter_map(|(start, end)| {
+                self.line_range(
+                    buffer.offset_of_line(start),
+                    buffer.offset_of_line(end),
+                )
+            })
+            .collect();
+        self.format_ranges(ctx, ranges);
+    }
+
+    /// Ask the language server to format after typing `ch`, if it is one of
+    /// the server's trigger characters
+    fn format_on_type(&self, ctx: &mut EventCtx, ch: &str) {
+        let path = match self.doc.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+        let offset = self.editor.cursor.offset();
+        let position = match self.doc.buffer().offset_to_position(offset) {
+            Some(position) => position,
+            None => return,
+        };
+        let rev = self.doc.rev();
+        let event_sink = ctx.get_external_handle();
+        let tab_id = *self.main_split.tab_id;
+        self.proxy.get_on_type_formatting(
+            self.doc.id(),
+            position,
+            ch.to_string(),
+            move |result| {
+                if let Ok(edits) = result {
+                    if !edits.is_empty() {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::DocumentFormat(path, rev, Ok(edits)),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            },
+        );
+    }
+
     fn save(&mut self, ctx: &mut EventCtx, exit: bool) {
         if self.doc.buffer().is_pristine() && self.doc.content().is_file() {
             if exit {
@@ -1234,28 +1837,61 @@ impl LapceEditorBufferData {
         }
 
         if let BufferContent::File(path) = self.doc.content() {
-            let format_on_save = self.config.editor.format_on_save;
             let path = path.clone();
+            let editor_config = self.config.editor_config(&path);
+            let format_on_save = editor_config.format_on_save;
+            // The modified lines are found before the whitespace cleanup, which
+            // changes the lines without being a change of its own. The whole
+            // document is formatted if they can't be found.
+            let mut modified_lines =
+                if format_on_save && editor_config.format_on_save_modified_lines {
+                    self.modified_line_offsets()
+                } else {
+                    None
+                };
+            let edits = self.doc.buffer().whitespace_cleanup_edits(
+                editor_config.trim_trailing_whitespace,
+                editor_config.insert_final_newline,
+                editor_config.trim_final_newlines,
+            );
+            if !edits.is_empty() {
+                let (delta, inval_lines) = Arc::make_mut(&mut self.doc)
+                    .do_raw_edit(&edits, EditType::Other);
+                Arc::make_mut(&mut self.editor).cursor.apply_delta(&delta);
+                if let Some(offsets) = modified_lines.as_mut() {
+                    transform_line_offsets(offsets, &delta);
+                }
+                self.apply_deltas(&[(delta, inval_lines)]);
+            }
+            let modified_lines = modified_lines.map(|offsets| {
+                offsets
+                    .into_iter()
+                    .filter_map(|(start, end)| self.line_range(start, end))
+                    .collect::<Vec<lsp_types::Range>>()
+            });
             let proxy = self.proxy.clone();
             let buffer_id = self.doc.id();
             let rev = self.doc.rev();
             let event_sink = ctx.get_external_handle();
             let view_id = self.editor.view_id;
             let tab_id = self.main_split.tab_id.clone();
-            let (sender, receiver) = bounded(1);
             thread::spawn(move || {
-                proxy.get_document_formatting(
-                    buffer_id,
-                    Box::new(move |result| {
-                        let _ = sender.send(result);
-                    }),
-                );
+                let result = if let Some(ranges) = modified_lines {
+                    request_range_formatting(&proxy, buffer_id, ranges)
+                } else {
+                    let (sender, receiver) = bounded(1);
+                    proxy.get_document_formatting(
+                        buffer_id,
+                        Box::new(move |result| {
+                            let _ = sender.send(result);
+                        }),
+                    );
 
-                let result =
                     receiver.recv_timeout(Duration::from_secs(1)).map_or_else(
                         |e| Err(anyhow!("{}", e)),
                         |v| v.map_err(|e| anyhow!("{:?}", e)),
-                    );
+                    )
+                };
 
                 let exit = if exit { Some(view_id) } else { None };
                 let cmd = if format_on_save {
@@ -1321,6 +1957,8 @@ impl LapceEditorBufferData {
         }
         self.cancel_completion();
         self.cancel_hover();
+        self.update_signature(ctx, None);
+        self.update_linked_editing();
         CommandExecuted::Yes
     }
 
@@ -1328,8 +1966,19 @@ impl LapceEditorBufferData {
         &mut self,
         ctx: &mut EventCtx,
         cmd: &EditCommand,
+        count: Option<usize>,
     ) -> CommandExecuted {
-        let modal = self.config.lapce.modal && !self.editor.content.is_input();
+        if self.editor.linked_editing.is_some() && !self.is_linked_edit(cmd) {
+            Arc::make_mut(&mut self.editor).cancel_linked_editing();
+        }
+
+        let options = EditOptions {
+            modal: self.config.lapce.modal && !self.editor.content.is_input(),
+            count,
+            reindent_paste: self.config.editor.reindent_on_paste,
+        };
+        let format_on_type = self.config.editor.format_on_type;
+        let format_on_paste = self.config.editor.format_on_paste;
         let doc = Arc::make_mut(&mut self.doc);
         let register = Arc::make_mut(&mut self.main_split.register);
         let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
@@ -1340,7 +1989,7 @@ impl LapceEditorBufferData {
                 None
             };
 
-        let deltas = doc.do_edit(cursor, cmd, modal, register);
+        let deltas = doc.do_edit(cursor, cmd, register, options);
 
         if !deltas.is_empty() {
             if let Some(data) = yank_data {
@@ -1357,6 +2006,18 @@ impl LapceEditorBufferData {
         }
         self.apply_deltas(&deltas);
 
+        match cmd {
+            EditCommand::InsertNewLine if format_on_type => {
+                self.format_on_type(ctx, "\n");
+            }
+            EditCommand::ClipboardPaste if format_on_paste => {
+                self.format_pasted(ctx, &deltas);
+            }
+            _ => {}
+        }
+        self.update_signature(ctx, None);
+        self.update_linked_editing();
+
         CommandExecuted::Yes
     }
 
@@ -1386,6 +2047,12 @@ impl LapceEditorBufferData {
                 if self.has_hover() {
                     self.cancel_hover();
                 }
+                if self.has_signature() {
+                    self.cancel_signature();
+                }
+                if self.has_peek() {
+                    Arc::make_mut(&mut self.peek).cancel();
+                }
             }
             SplitVertical => {
                 self.main_split.split_editor(
@@ -1813,6 +2480,21 @@ impl LapceEditorBufferData {
                 // we allow empty inputs to allow for cases where the user wants to get the autocompletion beforehand
                 self.update_completion(ctx, true);
             }
+            GetSignatureHelp => {
+                self.request_signature(ctx, None);
+            }
+            NextSignature => {
+                Arc::make_mut(&mut self.signature).next();
+            }
+            PreviousSignature => {
+                Arc::make_mut(&mut self.signature).previous();
+            }
+            InsertInlayHint => {
+                let offset = self.editor.cursor.offset();
+                if let Some(hint) = self.doc.inlay_hint_after_offset(offset) {
+                    self.insert_inlay_hint(ctx, hint);
+                }
+            }
             GotoDefinition => {
                 let offset = self.editor.cursor.offset();
                 let start_offset = self.doc.buffer().prev_code_boundary(offset);
@@ -1967,6 +2649,209 @@ impl LapceEditorBufferData {
                     },
                 );
             }
+            GotoImplementation => {
+                let offset = self.editor.cursor.offset();
+                let event_sink = ctx.get_external_handle();
+                let buffer_id = self.doc.id();
+                let position = if let Some(position) =
+                    self.doc.buffer().offset_to_position(offset)
+                {
+                    position
+                } else {
+                    log::error!("Failed to convert offset {offset} to position in GotoImplementation");
+                    return CommandExecuted::Yes;
+                };
+                let editor_view_id = self.editor.view_id;
+                self.proxy.get_implementation(
+                    offset,
+                    buffer_id,
+                    position,
+                    move |result| {
+                        if let Ok(resp) = result {
+                            process_goto_locations(
+                                editor_view_id,
+                                offset,
+                                response_locations(resp),
+                                event_sink,
+                            );
+                        }
+                    },
+                );
+            }
+            GotoDeclaration => {
+                let offset = self.editor.cursor.offset();
+                let event_sink = ctx.get_external_handle();
+                let buffer_id = self.doc.id();
+                let position = if let Some(position) =
+                    self.doc.buffer().offset_to_position(offset)
+                {
+                    position
+                } else {
+                    log::error!("Failed to convert offset {offset} to position in GotoDeclaration");
+                    return CommandExecuted::Yes;
+                };
+                let editor_view_id = self.editor.view_id;
+                self.proxy.get_declaration(
+                    offset,
+                    buffer_id,
+                    position,
+                    move |result| {
+                        if let Ok(resp) = result {
+                            process_goto_locations(
+                                editor_view_id,
+                                offset,
+                                response_locations(resp),
+                                event_sink,
+                            );
+                        }
+                    },
+                );
+            }
+            GetReferences => {
+                let offset = self.editor.cursor.offset();
+                let event_sink = ctx.get_external_handle();
+                let buffer_id = self.doc.id();
+                let position = if let Some(position) =
+                    self.doc.buffer().offset_to_position(offset)
+                {
+                    position
+                } else {
+                    log::error!("Failed to convert offset {offset} to position in GetReferences");
+                    return CommandExecuted::Yes;
+                };
+                self.proxy
+                    .get_references(buffer_id, position, move |result| {
+                        let _ = process_get_references(offset, result, event_sink);
+                    });
+            }
+            PeekDefinition => {
+                self.peek(ctx, PeekKind::Definition);
+            }
+            PeekImplementation => {
+                self.peek(ctx, PeekKind::Implementation);
+            }
+            PeekReferences => {
+                self.peek(ctx, PeekKind::References);
+            }
+            NextPeekLocation => {
+                let peek = Arc::make_mut(&mut self.peek);
+                peek.next();
+                peek.show(ctx);
+            }
+            PreviousPeekLocation => {
+                let peek = Arc::make_mut(&mut self.peek);
+                peek.previous();
+                peek.show(ctx);
+            }
+            OpenPeekLocation => {
+                Arc::make_mut(&mut self.peek).open(ctx);
+            }
+            Rename => {
+                if let BufferContent::File(path) = self.doc.content() {
+                    let offset = self.editor.cursor.offset();
+                    let position = if let Some(position) =
+                        self.doc.buffer().offset_to_position(offset)
+                    {
+                        position
+                    } else {
+                        log::error!("Failed to convert offset {offset} to position in Rename");
+                        return CommandExecuted::Yes;
+                    };
+                    let editor_view_id = self.editor.view_id;
+                    let path = path.clone();
+                    let rev = self.doc.rev();
+                    let tab_id = *self.main_split.tab_id;
+                    let event_sink = ctx.get_external_handle();
+                    self.proxy.prepare_rename(
+                        self.doc.id(),
+                        position,
+                        move |result| {
+                            // A null response means there is nothing to rename here
+                            if let Ok(Some(response)) = result {
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::StartRename {
+                                        editor_view_id,
+                                        path,
+                                        rev,
+                                        offset,
+                                        response,
+                                    },
+                                    Target::Widget(tab_id),
+                                );
+                            }
+                        },
+                    );
+                }
+            }
+            ShowIncomingCalls | ShowOutgoingCalls => {
+                let direction = if matches!(cmd, ShowIncomingCalls) {
+                    CallHierarchyDirection::Incoming
+                } else {
+                    CallHierarchyDirection::Outgoing
+                };
+                let offset = self.editor.cursor.offset();
+                let position = match self.doc.buffer().offset_to_position(offset) {
+                    Some(position) => position,
+                    None => return CommandExecuted::Yes,
+                };
+                let buffer_id = self.doc.id();
+                let tab_id = *self.main_split.tab_id;
+                let event_sink = ctx.get_external_handle();
+                self.proxy.prepare_call_hierarchy(
+                    buffer_id,
+                    position,
+                    move |result| {
+                        if let Ok(Some(items)) = result {
+                            if !items.is_empty() {
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::ShowCallHierarchy {
+                                        direction,
+                                        buffer_id,
+                                        items,
+                                    },
+                                    Target::Widget(tab_id),
+                                );
+                            }
+                        }
+                    },
+                );
+            }
+            ShowSupertypes | ShowSubtypes => {
+                let direction = if matches!(cmd, ShowSupertypes) {
+                    TypeHierarchyDirection::Supertypes
+                } else {
+                    TypeHierarchyDirection::Subtypes
+                };
+                let offset = self.editor.cursor.offset();
+                let position = match self.doc.buffer().offset_to_position(offset) {
+                    Some(position) => position,
+                    None => return CommandExecuted::Yes,
+                };
+                let buffer_id = self.doc.id();
+                let tab_id = *self.main_split.tab_id;
+                let event_sink = ctx.get_external_handle();
+                self.proxy.prepare_type_hierarchy(
+                    buffer_id,
+                    position,
+                    move |result| {
+                        if let Ok(Some(items)) = result {
+                            if !items.is_empty() {
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::ShowTypeHierarchy {
+                                        direction,
+                                        buffer_id,
+                                        items,
+                                    },
+                                    Target::Widget(tab_id),
+                                );
+                            }
+                        }
+                    },
+                );
+            }
             JumpLocationBackward => {
                 self.jump_location_backward(ctx);
             }
@@ -1979,6 +2864,12 @@ impl LapceEditorBufferData {
             NextDiff => {
                 self.next_diff(ctx);
             }
+            NextOccurrence => {
+                self.next_occurrence(false);
+            }
+            PreviousOccurrence => {
+                self.next_occurrence(true);
+            }
             ToggleCodeLens => {
                 let editor = Arc::make_mut(&mut self.editor);
                 editor.view = match editor.view {
@@ -1987,6 +2878,10 @@ impl LapceEditorBufferData {
                     EditorView::Diff(_) => return CommandExecuted::Yes,
                 };
             }
+            FormatSelection => {
+                let ranges = self.format_selection_ranges();
+                self.format_ranges(ctx, ranges);
+            }
             FormatDocument => {
                 if let BufferContent::File(path) = self.doc.content() {
                     let path = path.clone();
@@ -2171,10 +3066,14 @@ impl KeyPressFocus for LapceEditorBufferData {
             "in_snippet" => self.editor.snippet.is_some(),
             "completion_focus" => self.has_completions(),
             "hover_focus" => self.has_hover(),
+            "signature_focus" => self.has_signature(),
+            "peek_focus" => self.has_peek(),
             "list_focus" => self.has_completions() || self.is_palette(),
             "modal_focus" => {
-                (self.has_completions() && !self.config.lapce.modal)
+                ((self.has_completions() || self.has_signature())
+                    && !self.config.lapce.modal)
                     || self.has_hover()
+                    || self.has_peek()
                     || self.is_palette()
             }
             _ => false,
@@ -2183,6 +3082,13 @@ impl KeyPressFocus for LapceEditorBufferData {
 
     fn receive_char(&mut self, ctx: &mut EventCtx, c: &str) {
         if self.get_mode() == Mode::Insert {
+            // Only the characters of a name are typed in all the linked
+            // ranges
+            if self.editor.linked_editing.is_some() && !c.chars().all(is_linked_char)
+            {
+                Arc::make_mut(&mut self.editor).cancel_linked_editing();
+            }
+
             let doc = Arc::make_mut(&mut self.doc);
             let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
             let deltas = doc.do_insert(cursor, c);
@@ -2195,6 +3101,17 @@ impl KeyPressFocus for LapceEditorBufferData {
             }
             self.cancel_hover();
             self.apply_deltas(&deltas);
+
+            // Trigger characters are punctuation, so don't bother the server
+            // with the rest
+            let is_trigger = c.chars().all(|c| c.is_ascii_punctuation());
+            if self.config.editor.format_on_type && is_trigger {
+                self.format_on_type(ctx, c);
+            }
+            let is_signature_trigger =
+                self.doc.is_signature_trigger(c, self.has_signature());
+            self.update_signature(ctx, is_signature_trigger.then_some(c));
+            self.update_linked_editing();
         } else if let Some(direction) = self.editor.inline_find.clone() {
             self.inline_find(ctx, direction.clone(), c);
             let editor = Arc::make_mut(&mut self.editor);
@@ -2213,7 +3130,7 @@ impl KeyPressFocus for LapceEditorBufferData {
     ) -> CommandExecuted {
         let old_doc = self.doc.clone();
         let executed = match &command.kind {
-            CommandKind::Edit(cmd) => self.run_edit_command(ctx, cmd),
+            CommandKind::Edit(cmd) => self.run_edit_command(ctx, cmd, count),
             CommandKind::Move(cmd) => {
                 let movement = cmd.to_movement(count);
                 self.run_move_command(ctx, &movement, count, mods)
@@ -2295,6 +3212,59 @@ fn next_in_file_errors_offset(
     ((*file_diagnostics[0].0).clone(), file_diagnostics[0].1[0])
 }
 
+/// The locations of a `textDocument/definition` response, or of the other
+/// requests that share its response type
+fn response_locations(resp: GotoDefinitionResponse) -> Vec<Location> {
+    match resp {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+    }
+}
+
+/// Go to the single location, or list them in the palette if there are
+/// several
+fn process_goto_locations(
+    editor_view_id: WidgetId,
+    offset: usize,
+    locations: Vec<Location>,
+    event_sink: ExtEventSink,
+) {
+    match locations.len() {
+        0 => {}
+        1 => {
+            let location = &locations[0];
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::GotoDefinition {
+                    editor_view_id,
+                    offset,
+                    location: EditorLocation {
+                        path: path_from_url(&location.uri),
+                        position: Some(location.range.start),
+                        scroll_offset: None,
+                        history: None,
+                    },
+                },
+                Target::Auto,
+            );
+        }
+        _ => {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::PaletteReferences(offset, locations),
+                Target::Auto,
+            );
+        }
+    }
+}
+
 fn process_get_references(
     offset: usize,
     result: Result<Vec<Location>, RequestError>,
@@ -2330,7 +3300,7 @@ fn process_get_references(
     Ok(())
 }
 
-fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
+pub fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
     if let Some(changes) = edit.changes.as_ref() {
         return Some(changes.clone());
     }
@@ -2372,6 +3342,65 @@ fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>>
     Some(edits)
 }
 
+/// The offsets of the first and the last line of each run of lines of `text`
+/// that the `changes` added
+fn changed_line_offsets(text: &Rope, changes: &[DiffLines]) -> Vec<(usize, usize)> {
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            DiffLines::Right(lines) if !lines.is_empty() => Some((
+                text.offset_of_line(lines.start),
+                text.offset_of_line(lines.end - 1),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Move the offsets of the lines by an edit that was made after they were
+/// found
+fn transform_line_offsets(offsets: &mut [(usize, usize)], delta: &RopeDelta) {
+    let mut transformer = Transformer::new(delta);
+    for (start, end) in offsets.iter_mut() {
+        *start = transformer.transform(*start, false);
+        *end = transformer.transform(*end, false);
+    }
+}
+
+/// Request range formatting for each of the ranges, waiting for all of the results
+/// so that they can be applied together.
+fn request_range_formatting(
+    proxy: &LapceProxy,
+    buffer_id: BufferId,
+    ranges: Vec<lsp_types::Range>,
+) -> Result<Vec<TextEdit>> {
+    let mut edits: Vec<TextEdit> = Vec::new();
+    for range in ranges {
+        let (sender, receiver) = bounded(1);
+        proxy.get_document_range_formatting(buffer_id, range, move |result| {
+            let _ = sender.send(result);
+        });
+        let result = receiver.recv_timeout(Duration::from_secs(1)).map_or_else(
+            |e| Err(anyhow!("{}", e)),
+            |v| v.map_err(|e| anyhow!("{:?}", e)),
+        )?;
+        edits.extend(result);
+    }
+
+    // Servers may format a bit outside of the requested range, so drop any
+    // edits that overlap with the result for a previous range
+    edits.sort_by(|a, b| a.range.start.cmp(&b.range.start));
+    let mut last_end: Option<Position> = None;
+    edits.retain(|edit| {
+        if last_end.map(|end| edit.range.start < end).unwrap_or(false) {
+            return false;
+        }
+        last_end = Some(edit.range.end);
+        true
+    });
+    Ok(edits)
+}
+
 /// Check if a [`Url`] matches the path
 fn url_matches_path(path: &Path, url: &Url) -> bool {
     // TODO: Neither of these methods work for paths
@@ -2390,7 +3419,7 @@ fn url_matches_path(path: &Path, url: &Url) -> bool {
     matches
 }
 
-fn apply_code_action(
+pub fn apply_text_edits(
     doc: &Document,
     main_split: &mut LapceMainSplitData,
     path: &Path,
@@ -2413,3 +3442,36 @@ fn apply_code_action(
         log::error!("Failed to convert code action edit Position to offset");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modified_lines_after_whitespace_cleanup() {
+        let head = Rope::from("fn a() {}\nfn b() {}\n");
+        let mut buffer = Buffer::new("fn a() {}   \nfn c() {}  \nfn b() {}\n");
+        let changes =
+            rope_diff(head, buffer.text().clone(), 0, Arc::new(AtomicU64::new(0)))
+                .unwrap();
+        let mut offsets = changed_line_offsets(buffer.text(), &changes);
+        assert_eq!(vec![(0, 13)], offsets);
+
+        // Saving trims the trailing whitespace, which moves the lines
+        let edits = buffer.whitespace_cleanup_edits(true, true, true);
+        let (delta, _) = buffer.edit(&edits, EditType::Other);
+        transform_line_offsets(&mut offsets, &delta);
+        assert_eq!(
+            "fn a() {}\nfn c() {}\nfn b() {}\n",
+            buffer.text().to_string()
+        );
+        assert_eq!(vec![(0, 10)], offsets);
+        let lines: Vec<(usize, usize)> = offsets
+            .iter()
+            .map(|(start, end)| {
+                (buffer.line_of_offset(*start), buffer.line_of_offset(*end))
+            })
+            .collect();
+        assert_eq!(vec![(0, 1)], lines);
+    }
+}
@@ -0,0 +1,184 @@
+use lsp_types::{CallHierarchyItem, Range};
+
+/// A node of the call or type hierarchy, type hierarchy items have the
+/// same fields as call hierarchy items
+#[derive(Clone)]
+pub struct HierarchyNode {
+    pub item: CallHierarchyItem,
+    /// Where the calls happen for the call hierarchy. These are in the caller
+    /// for incoming calls, and in the parent item for outgoing calls.
+    pub ranges: Vec<Range>,
+    /// The children of this item, `None` until they have been requested
+    pub children: Option<Vec<HierarchyNode>>,
+    pub expanded: bool,
+}
+
+impl HierarchyNode {
+    pub fn new(item: CallHierarchyItem, ranges: Vec<Range>) -> Self {
+        Self {
+            item,
+            ranges,
+            children: None,
+            expanded: false,
+        }
+    }
+}
+
+/// A visible row of the tree
+pub struct HierarchyRow<'a> {
+    /// The indices of the node, from the root down
+    pub path: Vec<usize>,
+    pub level: usize,
+    pub node: &'a HierarchyNode,
+}
+
+/// A tree whose children are requested from the language server when
+/// a node is first expanded
+#[derive(Clone, Default)]
+pub struct HierarchyTree {
+    pub roots: Vec<HierarchyNode>,
+}
+
+impl HierarchyTree {
+    pub fn new(items: Vec<CallHierarchyItem>) -> Self {
+        Self {
+            roots: items
+                .into_iter()
+                .map(|item| HierarchyNode::new(item, Vec::new()))
+                .collect(),
+        }
+    }
+
+    pub fn node(&self, path: &[usize]) -> Option<&HierarchyNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots.get(*first)?;
+        for i in rest {
+            node = node.children.as_ref()?.get(*i)?;
+        }
+        Some(node)
+    }
+
+    pub fn node_mut(&mut self, path: &[usize]) -> Option<&mut HierarchyNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots.get_mut(*first)?;
+        for i in rest {
+            node = node.children.as_mut()?.get_mut(*i)?;
+        }
+        Some(node)
+    }
+
+    /// Expand or collapse the node. Returns the item if its children still
+    /// need to be requested.
+    pub fn toggle(&mut self, path: &[usize]) -> Option<CallHierarchyItem> {
+        let node = self.node_mut(path)?;
+        node.expanded = !node.expanded;
+        if node.expanded && node.children.is_none() {
+            Some(node.item.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Fill in the children of the node at `path`
+    pub fn set_children(
+        &mut self,
+        path: &[usize],
+        children: Vec<(CallHierarchyItem, Vec<Range>)>,
+    ) {
+        if let Some(node) = self.node_mut(path) {
+            node.children = Some(
+                children
+                    .into_iter()
+                    .map(|(item, ranges)| HierarchyNode::new(item, ranges))
+                    .collect(),
+            );
+        }
+    }
+
+    /// The rows of the tree that aren't hidden in a collapsed node
+    pub fn rows(&self) -> Vec<HierarchyRow> {
+        fn push_rows<'a>(
+            rows: &mut Vec<HierarchyRow<'a>>,
+            nodes: &'a [HierarchyNode],
+            path: &mut Vec<usize>,
+        ) {
+            for (i, node) in nodes.iter().enumerate() {
+                path.push(i);
+                rows.push(HierarchyRow {
+                    path: path.clone(),
+                    level: path.len() - 1,
+                    node,
+                });
+                if node.expanded {
+                    if let Some(children) = node.children.as_ref() {
+                        push_rows(rows, children, path);
+                    }
+                }
+                path.pop();
+            }
+        }
+
+        let mut rows = Vec::new();
+        push_rows(&mut rows, &self.roots, &mut Vec::new());
+        rows
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::{Position, SymbolKind, Url};
+
+    use super::*;
+
+    fn item(name: &str) -> CallHierarchyItem {
+        CallHierarchyItem {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri: Url::parse("file:///main.rs").unwrap(),
+            range: Range::default(),
+            selection_range: Range::default(),
+            data: None,
+        }
+    }
+
+    fn names(tree: &HierarchyTree) -> Vec<(usize, String)> {
+        tree.rows()
+            .iter()
+            .map(|row| (row.level, row.node.item.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_set_children() {
+        let mut tree = HierarchyTree::new(vec![item("main")]);
+        assert_eq!(tree.toggle(&[0]).unwrap().name, "main");
+
+        let range = Range::new(Position::new(1, 4), Position::new(1, 7));
+        tree.set_children(
+            &[0],
+            vec![(item("foo"), vec![range]), (item("bar"), Vec::new())],
+        );
+        assert_eq!(
+            names(&tree),
+            vec![
+                (0, "main".to_string()),
+                (1, "foo".to_string()),
+                (1, "bar".to_string()),
+            ]
+        );
+        assert_eq!(tree.node(&[0, 0]).unwrap().ranges, vec![range]);
+
+        // The children of bar are hidden until it is expanded
+        tree.set_children(&[0, 1], vec![(item("baz"), Vec::new())]);
+        assert_eq!(names(&tree).len(), 3);
+        assert!(tree.toggle(&[0, 1]).is_none());
+        assert_eq!(names(&tree)[3], (2, "baz".to_string()));
+
+        // Collapsing doesn't request the children again
+        assert!(tree.toggle(&[0]).is_none());
+        assert_eq!(names(&tree), vec![(0, "main".to_string())]);
+        assert!(tree.toggle(&[0]).is_none());
+    }
+}
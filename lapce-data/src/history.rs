@@ -30,6 +30,9 @@ pub struct DocumentHistory {
     styles: Arc<Spans<Style>>,
     line_styles: Rc<RefCell<LineStyles>>,
     changes: Arc<Vec<DiffLines>>,
+    /// The revision of the document that the changes are of, which is
+    /// behind the document while they're computed after an edit
+    changes_rev: Option<u64>,
     text_layouts: Rc<RefCell<TextLayoutCache>>,
 }
 
@@ -62,6 +65,7 @@ impl DocumentHistory {
             line_styles: Rc::new(RefCell::new(LineStyles::new())),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             changes: Arc::new(Vec::new()),
+            changes_rev: None,
         }
     }
 
@@ -231,12 +235,22 @@ impl DocumentHistory {
         }
     }
 
+    /// The text of the version, once it's loaded
+    pub fn text(&self) -> Option<&Rope> {
+        self.buffer.as_ref().map(|buffer| buffer.text())
+    }
+
     pub fn changes(&self) -> &[DiffLines] {
         &self.changes
     }
 
-    pub fn update_changes(&mut self, changes: Arc<Vec<DiffLines>>) {
+    pub fn changes_rev(&self) -> Option<u64> {
+        self.changes_rev
+    }
+
+    pub fn update_changes(&mut self, rev: u64, changes: Arc<Vec<DiffLines>>) {
         self.changes = changes;
+        self.changes_rev = Some(rev);
     }
 
     pub fn update_styles(&mut self, styles: Arc<Spans<Style>>) {
@@ -1,4 +1,6 @@
 pub mod alert;
+pub mod call_hierarchy;
+pub mod code_lens;
 pub mod command;
 pub mod completion;
 pub mod config;
@@ -9,21 +11,30 @@ pub mod document;
 pub mod editor;
 pub mod explorer;
 pub mod find;
+pub mod hierarchy;
 pub mod history;
 pub mod hover;
 pub mod keypress;
+pub mod linked_editing;
+pub mod lsp_trace;
 pub mod markdown;
 pub mod menu;
+pub mod notification;
+pub mod output;
 pub mod palette;
 pub mod panel;
+pub mod peek;
 pub mod picker;
 pub mod plugin;
 pub mod problem;
 pub mod proxy;
+pub mod rename;
 pub mod rich_text;
 pub mod search;
 pub mod settings;
 pub mod signature;
+pub mod snippet;
 pub mod source_control;
 pub mod split;
 pub mod terminal;
+pub mod type_hierarchy;
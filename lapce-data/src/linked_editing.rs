@@ -0,0 +1,141 @@
+use lapce_core::selection::{SelRegion, Selection};
+use xi_rope::{RopeDelta, Transformer};
+
+/// Ranges of a document that are edited together, such as the names of an
+/// opening and a closing tag, which get a caret each while the cursor is in
+/// one of them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkedEditing {
+    /// The ranges, sorted by offset
+    pub ranges: Vec<(usize, usize)>,
+    /// The range that the cursor was in when they were installed, which it
+    /// goes back to when they're dropped
+    pub primary: usize,
+}
+
+impl LinkedEditing {
+    /// The linked editing ranges of the region, if it's in one of them and
+    /// there are others to mirror its edits in
+    pub fn new(mut ranges: Vec<(usize, usize)>, region: &SelRegion) -> Option<Self> {
+        ranges.sort_unstable();
+        ranges.dedup();
+        if ranges.len() < 2 {
+            return None;
+        }
+        let primary = ranges.iter().position(|(start, end)| {
+            *start <= region.min() && region.max() <= *end
+        })?;
+        Some(Self { ranges, primary })
+    }
+
+    /// The selection with the region of the primary range at the same place
+    /// in each of the ranges
+    pub fn selection(&self, region: &SelRegion) -> Selection {
+        let (primary_start, _) = self.ranges[self.primary];
+        let mut selection = Selection::new();
+        for (start, end) in self.ranges.iter() {
+            selection.add_region(SelRegion::new(
+                (region.start + start - primary_start).min(*end),
+                (region.end + start - primary_start).min(*end),
+                None,
+            ));
+        }
+        selection
+    }
+
+    /// Whether each of the ranges still has its region of the selection
+    pub fn contains(&self, selection: &Selection) -> bool {
+        selection.len() == self.ranges.len()
+            && selection.regions().iter().zip(self.ranges.iter()).all(
+                |(region, (start, end))| {
+                    *start <= region.min() && region.max() <= *end
+                },
+            )
+    }
+
+    /// The region of the selection in the primary range
+    pub fn primary_region(&self, selection: &Selection) -> Option<SelRegion> {
+        if selection.len() != self.ranges.len() {
+            return None;
+        }
+        selection.regions().get(self.primary).copied()
+    }
+
+    /// Move the ranges along with an edit, which grow with the text that is
+    /// inserted at their ends
+    pub fn apply_delta(&mut self, delta: &RopeDelta) {
+        let mut transformer = Transformer::new(delta);
+        for (start, end) in self.ranges.iter_mut() {
+            *start = transformer.transform(*start, false);
+            *end = transformer.transform(*end, true);
+        }
+    }
+}
+
+/// Whether a typed character can be part of a tag name, so that it's
+/// mirrored in the linked ranges instead of dropping them
+pub fn is_linked_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_core::selection::InsertDrift;
+    use xi_rope::{DeltaBuilder, Interval, Rope};
+
+    use super::*;
+
+    #[test]
+    fn test_selection() {
+        // The cursor is right after `di` in `<di></di>`
+        let region = SelRegion::caret(3);
+        let linked = LinkedEditing::new(vec![(6, 8), (1, 3)], &region).unwrap();
+        assert_eq!(linked.ranges, vec![(1, 3), (6, 8)]);
+        assert_eq!(linked.primary, 0);
+
+        let selection = linked.selection(&region);
+        let offsets: Vec<usize> = selection
+            .regions()
+            .iter()
+            .map(|region| region.end)
+            .collect();
+        assert_eq!(offsets, vec![3, 8]);
+        assert!(linked.contains(&selection));
+        assert_eq!(linked.primary_region(&selection).unwrap().end, 3);
+
+        // The cursor isn't in any of the ranges
+        assert!(
+            LinkedEditing::new(vec![(1, 3), (6, 8)], &SelRegion::caret(5)).is_none()
+        );
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let region = SelRegion::caret(3);
+        let mut linked = LinkedEditing::new(vec![(1, 3), (6, 8)], &region).unwrap();
+
+        // Typing `v` at both carets makes `<div></div>`
+        let mut builder = DeltaBuilder::new(9);
+        builder.replace(Interval::new(3, 3), Rope::from("v"));
+        builder.replace(Interval::new(8, 8), Rope::from("v"));
+        let delta = builder.build();
+        linked.apply_delta(&delta);
+        assert_eq!(linked.ranges, vec![(1, 4), (7, 10)]);
+
+        let selection = linked.selection(&region).apply_delta(
+            &delta,
+            true,
+            InsertDrift::Default,
+        );
+        assert!(linked.contains(&selection));
+        assert!(!linked.contains(&Selection::caret(4)));
+    }
+
+    #[test]
+    fn test_is_linked_char() {
+        assert!(is_linked_char('a'));
+        assert!(is_linked_char('-'));
+        assert!(!is_linked_char(' '));
+        assert!(!is_linked_char('>'));
+    }
+}
@@ -0,0 +1,202 @@
+use druid::WidgetId;
+use indexmap::IndexMap;
+use lapce_rpc::core::LspTraceEntry;
+use serde_json::json;
+
+/// The number of messages that are kept of the traffic with the servers
+const MAX_ENTRIES: usize = 10000;
+
+#[derive(Clone)]
+pub struct LspTraceItem {
+    /// Unique among the messages, so that an expanded message stays
+    /// expanded when older ones are dropped
+    pub id: u64,
+    pub server: String,
+    pub entry: LspTraceEntry,
+}
+
+impl LspTraceItem {
+    /// The text that the message is listed with, which is the message of a
+    /// `$/logTrace` and the method otherwise
+    pub fn summary(&self) -> String {
+        let method = self.entry.method.as_deref().unwrap_or("");
+        if method == "$/logTrace" {
+            if let Some(message) = self
+                .entry
+                .payload
+                .pointer("/params/message")
+                .and_then(|message| message.as_str())
+            {
+                return message.to_string();
+            }
+        }
+        method.to_string()
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.entry.payload.get("error").is_some()
+    }
+}
+
+/// The JSON-RPC messages between the proxy and the language servers, which
+/// are recorded when `[lsp] trace` is on, for the LSP trace panel
+#[derive(Clone)]
+pub struct LspTraceData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    pub methods_widget_id: WidgetId,
+    pub messages_widget_id: WidgetId,
+    /// The messages of all the servers, oldest first
+    pub items: im::Vector<LspTraceItem>,
+    /// The number of kept messages of each method, in the order that the
+    /// methods were first seen
+    pub methods: IndexMap<String, usize>,
+    /// Only the messages of this method are shown
+    pub filter: Option<String>,
+    /// The message whose payload is shown
+    pub expanded: Option<u64>,
+    next_id: u64,
+}
+
+impl LspTraceData {
+    pub fn new() -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            methods_widget_id: WidgetId::next(),
+            messages_widget_id: WidgetId::next(),
+            items: im::Vector::new(),
+            methods: IndexMap::new(),
+            filter: None,
+            expanded: None,
+            next_id: 0,
+        }
+    }
+
+    pub fn push(&mut self, server: String, entry: LspTraceEntry) {
+        if let Some(method) = entry.method.as_ref() {
+            *self.methods.entry(method.clone()).or_insert(0) += 1;
+        }
+        self.items.push_back(LspTraceItem {
+            id: self.next_id,
+            server,
+            entry,
+        });
+        self.next_id += 1;
+
+        while self.items.len() > MAX_ENTRIES {
+            if let Some(item) = self.items.pop_front() {
+                if let Some(method) = item.entry.method.as_ref() {
+                    if let Some(count) = self.methods.get_mut(method) {
+                        *count = count.saturating_sub(1);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the message passes the filter
+    pub fn is_visible(&self, item: &LspTraceItem) -> bool {
+        match self.filter.as_ref() {
+            Some(filter) => item.entry.method.as_ref() == Some(filter),
+            None => true,
+        }
+    }
+
+    /// The messages that pass the filter
+    pub fn visible(&self) -> impl Iterator<Item = &LspTraceItem> {
+        self.items.iter().filter(move |item| self.is_visible(item))
+    }
+
+    /// Show or hide the payload of the message
+    pub fn toggle_expanded(&mut self, id: u64) {
+        if self.expanded == Some(id) {
+            self.expanded = None;
+        } else {
+            self.expanded = Some(id);
+        }
+    }
+
+    /// The messages that pass the filter as JSON Lines, one object per
+    /// message with its server
+    pub fn to_jsonl(&self) -> String {
+        let mut jsonl = String::new();
+        for item in self.visible() {
+            let entry = &item.entry;
+            let line = json!({
+                "server": item.server,
+                "direction": entry.direction,
+                "timestamp": entry.timestamp,
+                "method": entry.method,
+                "latency": entry.latency,
+                "payload": entry.payload,
+            });
+            jsonl.push_str(&line.to_string());
+            jsonl.push('\n');
+        }
+        jsonl
+    }
+}
+
+impl Default for LspTraceData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_rpc::core::LspTraceDirection;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn entry(method: Option<&str>, payload: Value) -> LspTraceEntry {
+        LspTraceEntry {
+            direction: LspTraceDirection::Sent,
+            timestamp: 0,
+            method: method.map(|method| method.to_string()),
+            latency: None,
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_filter_and_export() {
+        let mut trace = LspTraceData::new();
+        trace.push(
+            "rust-analyzer".to_string(),
+            entry(Some("textDocument/hover"), json!({ "id": 1 })),
+        );
+        trace.push(
+            "rust-analyzer".to_string(),
+            entry(Some("$/logTrace"), json!({ "params": { "message": "ok" } })),
+        );
+        trace.push("gopls".to_string(), entry(None, json!({ "id": 7 })));
+        assert_eq!(trace.visible().count(), 3);
+        assert_eq!(trace.items[1].summary(), "ok");
+
+        trace.filter = Some("textDocument/hover".to_string());
+        assert_eq!(trace.visible().count(), 1);
+        let jsonl = trace.to_jsonl();
+        assert_eq!(jsonl.lines().count(), 1);
+        let line: Value = serde_json::from_str(jsonl.trim()).unwrap();
+        assert_eq!(line["server"], "rust-analyzer");
+        assert_eq!(line["direction"], "sent");
+        assert_eq!(line["payload"]["id"], 1);
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut trace = LspTraceData::new();
+        for _ in 0..MAX_ENTRIES + 1 {
+            trace.push(
+                "gopls".to_string(),
+                entry(Some("textDocument/didChange"), Value::Null),
+            );
+        }
+        assert_eq!(trace.items.len(), MAX_ENTRIES);
+        assert_eq!(trace.items[0].id, 1);
+        assert_eq!(trace.methods["textDocument/didChange"], MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,77 @@
+use druid::WidgetId;
+use lsp_types::MessageType;
+
+/// The number of notifications that are shown at once, the oldest ones are
+/// dropped first
+const MAX_NOTIFICATIONS: usize = 10;
+
+#[derive(Clone)]
+pub struct Notification {
+    pub id: usize,
+    pub kind: MessageType,
+    pub title: String,
+    pub message: String,
+}
+
+/// The dismissable messages shown above the status bar, like the
+/// `window/showMessage` notifications of the language servers
+#[derive(Clone)]
+pub struct NotificationData {
+    pub widget_id: WidgetId,
+    /// The oldest first
+    pub items: im::Vector<Notification>,
+    next_id: usize,
+}
+
+impl NotificationData {
+    pub fn new() -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            items: im::Vector::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn push(&mut self, kind: MessageType, title: String, message: String) {
+        self.items.push_back(Notification {
+            id: self.next_id,
+            kind,
+            title,
+            message,
+        });
+        self.next_id += 1;
+        while self.items.len() > MAX_NOTIFICATIONS {
+            self.items.pop_front();
+        }
+    }
+
+    pub fn dismiss(&mut self, id: usize) {
+        self.items.retain(|item| item.id != id);
+    }
+}
+
+impl Default for NotificationData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_push_and_dismiss() {
+        let mut notification = NotificationData::new();
+        for i in 0..MAX_NOTIFICATIONS + 2 {
+            notification.push(MessageType::INFO, "gopls".into(), i.to_string());
+        }
+        assert_eq!(notification.items.len(), MAX_NOTIFICATIONS);
+        assert_eq!(notification.items[0].message, "2");
+
+        let id = notification.items[1].id;
+        notification.dismiss(id);
+        assert_eq!(notification.items.len(), MAX_NOTIFICATIONS - 1);
+        assert!(notification.items.iter().all(|item| item.id != id));
+    }
+}
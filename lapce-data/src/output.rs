@@ -0,0 +1,92 @@
+use druid::WidgetId;
+use indexmap::IndexMap;
+use lsp_types::MessageType;
+
+/// The number of lines that are kept of the log of a server
+const MAX_LINES: usize = 10000;
+
+#[derive(Clone)]
+pub struct OutputLine {
+    pub kind: MessageType,
+    pub text: String,
+}
+
+/// The `window/logMessage` logs of the language servers, shown one server at
+/// a time in the output panel
+#[derive(Clone)]
+pub struct OutputData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    pub servers_widget_id: WidgetId,
+    pub log_widget_id: WidgetId,
+    /// The logs of the servers, in the order that they first logged
+    pub logs: IndexMap<String, im::Vector<OutputLine>>,
+    /// The server whose log is shown
+    pub active: usize,
+}
+
+impl OutputData {
+    pub fn new() -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            servers_widget_id: WidgetId::next(),
+            log_widget_id: WidgetId::next(),
+            logs: IndexMap::new(),
+            active: 0,
+        }
+    }
+
+    /// Add the message to the log of the server, a line at a time
+    pub fn log(&mut self, server: &str, kind: MessageType, message: &str) {
+        if !self.logs.contains_key(server) {
+            self.logs.insert(server.to_string(), im::Vector::new());
+        }
+        let lines = self.logs.get_mut(server).unwrap();
+        for text in message.lines() {
+            lines.push_back(OutputLine {
+                kind,
+                text: text.to_string(),
+            });
+        }
+        while lines.len() > MAX_LINES {
+            lines.pop_front();
+        }
+    }
+
+    /// The name and the log of the server that is shown
+    pub fn active_log(&self) -> Option<(&String, &im::Vector<OutputLine>)> {
+        self.logs.get_index(self.active)
+    }
+}
+
+impl Default for OutputData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_log() {
+        let mut output = OutputData::new();
+        output.log("rust-analyzer", MessageType::INFO, "first\nsecond");
+        output.log("gopls", MessageType::ERROR, "failed");
+        assert_eq!(output.logs.len(), 2);
+
+        let (server, lines) = output.active_log().unwrap();
+        assert_eq!(server, "rust-analyzer");
+        let lines: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(lines, vec!["first", "second"]);
+
+        for i in 0..MAX_LINES {
+            output.log("gopls", MessageType::LOG, &i.to_string());
+        }
+        let lines = &output.logs["gopls"];
+        assert_eq!(lines.len(), MAX_LINES);
+        assert_eq!(lines[0].text, "0");
+    }
+}
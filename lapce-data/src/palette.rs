@@ -48,6 +48,7 @@ pub enum PaletteType {
     Theme,
     SshHost,
     Language,
+    LanguageServer,
 }
 
 impl PaletteType {
@@ -64,6 +65,7 @@ impl PaletteType {
             PaletteType::Theme => "".to_string(),
             PaletteType::SshHost => "".to_string(),
             PaletteType::Language => "".to_string(),
+            PaletteType::LanguageServer => "".to_string(),
         }
     }
 
@@ -85,7 +87,8 @@ impl PaletteType {
             PaletteType::Reference
             | PaletteType::SshHost
             | PaletteType::Theme
-            | PaletteType::Language => {
+            | PaletteType::Language
+            | PaletteType::LanguageServer => {
                 return current_type.clone();
             }
             _ => (),
@@ -142,6 +145,7 @@ pub enum PaletteItemContent {
     Command(LapceCommand),
     Theme(String),
     Language(String),
+    LanguageServer(String),
 }
 
 impl PaletteItemContent {
@@ -235,6 +239,15 @@ impl PaletteItemContent {
                     ))
                 }
             }
+            PaletteItemContent::LanguageServer(name) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RestartLanguageServer(name.to_string()),
+                        Target::Auto,
+                    ))
+                }
+            }
             PaletteItemContent::Command(command) => {
                 if !preview {
                     ctx.submit_command(Command::new(
@@ -496,6 +509,7 @@ impl PaletteData {
             PaletteType::Reference => &self.input,
             PaletteType::Theme => &self.input,
             PaletteType::Language => &self.input,
+            PaletteType::LanguageServer => &self.input,
             PaletteType::SshHost => &self.input,
             PaletteType::Line => &self.input[1..],
             PaletteType::DocumentSymbol => &self.input[1..],
@@ -633,6 +647,9 @@ impl PaletteViewData {
             PaletteType::Language => {
                 self.get_languages(ctx);
             }
+            PaletteType::LanguageServer => {
+                self.get_language_servers(ctx);
+            }
         }
     }
 
@@ -658,6 +675,7 @@ impl PaletteViewData {
             PaletteType::Reference => 0,
             PaletteType::Theme => 0,
             PaletteType::Language => 0,
+            PaletteType::LanguageServer => 0,
             PaletteType::SshHost => 0,
             PaletteType::Line => 1,
             PaletteType::DocumentSymbol => 1,
@@ -922,6 +940,30 @@ impl PaletteViewData {
             .collect();
     }
 
+    fn get_language_servers(&mut self, ctx: &mut EventCtx) {
+        let widget_id = self.palette.widget_id;
+        let run_id = self.palette.run_id.clone();
+        let event_sink = ctx.get_external_handle();
+        self.palette.proxy.get_language_servers(move |result| {
+            if let Ok(names) = result {
+                let items = names
+                    .into_iter()
+                    .map(|name| PaletteItem {
+                        content: PaletteItemContent::LanguageServer(name.clone()),
+                        filter_text: name,
+                        score: 0,
+                        indices: vec![],
+                    })
+                    .collect();
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdatePaletteItems(run_id, items),
+                    Target::Widget(widget_id),
+                );
+            }
+        });
+    }
+
     fn get_commands(&mut self, _ctx: &mut EventCtx) {
         const EXCLUDED_ITEMS: &[&str] = &["palette.command"];
 
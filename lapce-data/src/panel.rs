@@ -11,6 +11,10 @@ pub enum PanelKind {
     Terminal,
     Search,
     Problem,
+    CallHierarchy,
+    TypeHierarchy,
+    Output,
+    LspTrace,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -32,6 +36,10 @@ impl PanelKind {
             PanelKind::Terminal => "terminal.svg",
             PanelKind::Search => "search.svg",
             PanelKind::Problem => "error.svg",
+            PanelKind::CallHierarchy => "symbol-method.svg",
+            PanelKind::TypeHierarchy => "symbol-class.svg",
+            PanelKind::Output => "symbol-file.svg",
+            PanelKind::LspTrace => "symbol-event.svg",
         }
     }
 }
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use druid::{Command, EventCtx, Target, WidgetId};
+use lsp_types::{Location, Position};
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    editor::EditorLocation,
+    proxy::path_from_url,
+};
+
+/// The number of lines of the target that the peek view shows
+pub const PEEK_LINES: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeekKind {
+    Definition,
+    Implementation,
+    References,
+}
+
+impl PeekKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PeekKind::Definition => "Definition",
+            PeekKind::Implementation => "Implementations",
+            PeekKind::References => "References",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeekStatus {
+    Inactive,
+    Started,
+    Done,
+}
+
+/// The locations that a peek command found, which are browsed in a
+/// read-only editor below the line of the cursor, without leaving it
+#[derive(Clone)]
+pub struct PeekData {
+    pub id: WidgetId,
+    /// The read-only editor that shows the location
+    pub editor_id: WidgetId,
+    /// The editor view id that the peek view is displayed in
+    pub editor_view_id: WidgetId,
+    /// The current request status
+    pub status: PeekStatus,
+    pub kind: PeekKind,
+    /// The offset of the cursor when peeking, which the view is shown below
+    pub offset: usize,
+    /// A counter to keep track of the active requests
+    pub request_id: usize,
+    pub locations: Arc<Vec<Location>>,
+    /// The location that is shown
+    pub index: usize,
+}
+
+impl PeekData {
+    pub fn new() -> Self {
+        Self {
+            id: WidgetId::next(),
+            editor_id: WidgetId::next(),
+            editor_view_id: WidgetId::next(),
+            status: PeekStatus::Inactive,
+            kind: PeekKind::Definition,
+            offset: 0,
+            request_id: 0,
+            locations: Arc::new(Vec::new()),
+            index: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// The location that is shown
+    pub fn location(&self) -> Option<&Location> {
+        self.locations.get(self.index)
+    }
+
+    /// Start a request for the locations to peek at, returning its id
+    pub fn request(
+        &mut self,
+        kind: PeekKind,
+        editor_view_id: WidgetId,
+        offset: usize,
+    ) -> usize {
+        self.status = PeekStatus::Started;
+        self.kind = kind;
+        self.editor_view_id = editor_view_id;
+        self.offset = offset;
+        self.request_id += 1;
+        self.request_id
+    }
+
+    /// Receive the locations of a peek request, returning whether there's
+    /// a location to show
+    pub fn receive(&mut self, request_id: usize, locations: Vec<Location>) -> bool {
+        if self.status == PeekStatus::Inactive || self.request_id != request_id {
+            return false;
+        }
+        if locations.is_empty() {
+            self.cancel();
+            return false;
+        }
+
+        self.status = PeekStatus::Done;
+        self.locations = Arc::new(locations);
+        self.index = 0;
+        true
+    }
+
+    /// Move to the next location, wrapping around to the first
+    pub fn next(&mut self) {
+        if !self.is_empty() {
+            self.index = (self.index + 1) % self.len();
+        }
+    }
+
+    /// Move to the previous location, wrapping around to the last
+    pub fn previous(&mut self) {
+        if !self.is_empty() {
+            self.index = (self.index + self.len() - 1) % self.len();
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if self.status == PeekStatus::Inactive {
+            return;
+        }
+
+        self.status = PeekStatus::Inactive;
+        self.locations = Arc::new(Vec::new());
+        self.index = 0;
+    }
+
+    fn editor_location(&self) -> Option<EditorLocation<Position>> {
+        let location = self.location()?;
+        Some(EditorLocation {
+            path: path_from_url(&location.uri),
+            position: Some(location.range.start),
+            scroll_offset: None,
+            history: None,
+        })
+    }
+
+    /// Load the location that is shown in the read-only editor
+    pub fn show(&self, ctx: &mut EventCtx) {
+        if let Some(location) = self.editor_location() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::JumpToLspLocation(Some(self.editor_id), location),
+                Target::Auto,
+            ));
+        }
+    }
+
+    /// Open the location that is shown in the editor that the peek view is
+    /// displayed in, and close the peek view
+    pub fn open(&mut self, ctx: &mut EventCtx) {
+        if let Some(location) = self.editor_location() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::JumpToLspLocation(None, location),
+                Target::Auto,
+            ));
+        }
+        self.cancel();
+    }
+}
+
+impl Default for PeekData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::{Range, Url};
+
+    use super::*;
+
+    fn location(line: u32) -> Location {
+        Location {
+            uri: Url::parse("file:///tmp/main.rs").unwrap(),
+            range: Range::new(Position::new(line, 0), Position::new(line, 4)),
+        }
+    }
+
+    #[test]
+    fn test_receive() {
+        let mut peek = PeekData::new();
+        let request_id = peek.request(PeekKind::References, WidgetId::next(), 0);
+
+        // The response to an older request is dropped
+        assert!(!peek.receive(request_id - 1, vec![location(0)]));
+        assert_eq!(peek.status, PeekStatus::Started);
+
+        assert!(peek.receive(request_id, vec![location(1), location(2)]));
+        assert_eq!(peek.status, PeekStatus::Done);
+        assert_eq!(peek.location().unwrap().range.start.line, 1);
+
+        let request_id = peek.request(PeekKind::Definition, WidgetId::next(), 0);
+        assert!(!peek.receive(request_id, Vec::new()));
+        assert_eq!(peek.status, PeekStatus::Inactive);
+        assert!(peek.is_empty());
+    }
+
+    #[test]
+    fn test_cycle_locations() {
+        let mut peek = PeekData::new();
+        let request_id = peek.request(PeekKind::References, WidgetId::next(), 0);
+        peek.receive(request_id, vec![location(1), location(2), location(3)]);
+        peek.previous();
+        assert_eq!(peek.index, 2);
+        peek.next();
+        assert_eq!(peek.index, 0);
+        peek.next();
+        assert_eq!(peek.location().unwrap().range.start.line, 2);
+    }
+}
@@ -17,19 +17,26 @@ use lapce_proxy::dispatch::Dispatcher;
 use lapce_rpc::buffer::{BufferHeadResponse, BufferId, NewBufferResponse};
 use lapce_rpc::core::{CoreNotification, CoreRequest};
 use lapce_rpc::plugin::PluginDescription;
-use lapce_rpc::proxy::{ProxyRequest, ReadDirResponse};
+use lapce_rpc::proxy::{
+    CancellableRequest, ProxyRequest, ReadDirResponse, ReadFileResponse,
+};
 use lapce_rpc::source_control::FileDiff;
 use lapce_rpc::style::SemanticStyles;
 use lapce_rpc::terminal::TermId;
-use lapce_rpc::RpcHandler;
 use lapce_rpc::{stdio_transport, Callback};
 use lapce_rpc::{ControlFlow, Handler};
-use lsp_types::request::GotoTypeDefinitionResponse;
+use lapce_rpc::{RequestId, RpcHandler};
+use lsp_types::request::{
+    GotoDeclarationResponse, GotoImplementationResponse, GotoTypeDefinitionResponse,
+};
 use lsp_types::{
-    CodeActionResponse, CompletionItem, CompletionResponse, DocumentSymbolResponse,
-    GotoDefinitionResponse, InlayHint, SymbolInformation, TextEdit,
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall,
+    CodeActionResponse, CodeLens, CompletionItem, CompletionResponse,
+    DocumentHighlight, DocumentSymbolResponse, GotoDefinitionResponse, InlayHint,
+    LinkedEditingRanges, PrepareRenameResponse, SignatureHelp, SymbolInformation,
+    TextEdit, WorkspaceEdit,
 };
-use lsp_types::{Hover, Position};
+use lsp_types::{Hover, Position, Range};
 use lsp_types::{Location, Url};
 use parking_lot::Mutex;
 use serde::de::DeserializeOwned;
@@ -43,6 +50,7 @@ use crate::command::LAPCE_UI_COMMAND;
 use crate::config::Config;
 use crate::data::{LapceWorkspace, LapceWorkspaceType};
 use crate::terminal::RawTerminal;
+use crate::type_hierarchy::TypeHierarchyItem;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -119,6 +127,56 @@ impl Handler for LapceProxy {
                     Target::Widget(self.tab_id),
                 );
             }
+            ShowMessage { title, message } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowMessage { title, message },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            LogMessage { server, message } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::LogMessage { server, message },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            LspTrace { server, entry } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::LspTrace { server, entry },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            LspRefresh {} => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::LspRefresh,
+                    Target::Widget(self.tab_id),
+                );
+            }
+            SignatureHelpTriggers {
+                path,
+                server,
+                triggers,
+            } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::SignatureHelpTriggers {
+                        path,
+                        server,
+                        triggers,
+                    },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            LanguageServerStatus { name, status } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::LanguageServerStatus { name, status },
+                    Target::Widget(self.tab_id),
+                );
+            }
             InstalledPlugins { plugins } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -184,8 +242,30 @@ impl Handler for LapceProxy {
         ControlFlow::Continue
     }
 
-    fn handle_request(&mut self, _rpc: Self::Request) -> Result<Value, Value> {
-        Err(json!("unimplemented"))
+    fn handle_request(&mut self, id: RequestId, rpc: Self::Request) {
+        match rpc {
+            CoreRequest::ApplyWorkspaceEdit { edit } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ApplyWorkspaceEdit {
+                        request_id: id,
+                        edit,
+                    },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            CoreRequest::ShowMessageRequest { title, message } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowMessageRequest {
+                        request_id: id,
+                        title,
+                        message,
+                    },
+                    Target::Widget(self.tab_id),
+                );
+            }
+        }
     }
 }
 
@@ -332,6 +412,36 @@ impl LapceProxy {
         )
     }
 
+    pub fn update_lsp_settings(&self, settings: &Value) {
+        self.rpc.send_rpc_notification(
+            "update_lsp_settings",
+            &json!({
+                "settings": settings,
+            }),
+        )
+    }
+
+    pub fn restart_language_server(&self, name: &str) {
+        self.rpc.send_rpc_notification(
+            "restart_language_server",
+            &json!({
+                "name": name,
+            }),
+        )
+    }
+
+    /// Let the language servers stop working on a request whose result isn't
+    /// needed anymore
+    pub fn cancel_request(&self, kind: CancellableRequest, request_id: usize) {
+        self.rpc.send_rpc_notification(
+            "cancel_request",
+            &json!({
+                "kind": kind,
+                "request_id": request_id,
+            }),
+        )
+    }
+
     pub fn terminal_close(&self, term_id: TermId) {
         self.rpc.send_rpc_notification(
             "terminal_close",
@@ -519,6 +629,15 @@ impl LapceProxy {
         )
     }
 
+    pub fn close_buffer(&self, buffer_id: BufferId) {
+        self.rpc.send_rpc_notification(
+            "close_buffer",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+        )
+    }
+
     pub fn save(&self, rev: u64, buffer_id: BufferId, f: Box<dyn Callback>) {
         self.rpc.send_rpc_request_async(
             "save",
@@ -630,17 +749,23 @@ impl LapceProxy {
 
     pub fn get_signature(
         &self,
+        request_id: usize,
         buffer_id: BufferId,
         position: Position,
-        f: Box<dyn Callback>,
+        trigger: Option<String>,
+        active: Option<Box<SignatureHelp>>,
+        f: impl FnOnce(Result<Option<SignatureHelp>, RequestError>) + Send + 'static,
     ) {
         self.rpc.send_rpc_request_async(
             "get_signature",
             &json!({
+                "request_id": request_id,
                 "buffer_id": buffer_id,
                 "position": position,
+                "trigger": trigger,
+                "active": active,
             }),
-            f,
+            box_json_cb(f),
         );
     }
 
@@ -687,6 +812,20 @@ impl LapceProxy {
         );
     }
 
+    pub fn read_file(
+        &self,
+        path: &Path,
+        f: impl FnOnce(Result<ReadFileResponse, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "read_file",
+            &json!({
+                "path": path,
+            }),
+            box_json_cb(f),
+        );
+    }
+
     pub fn get_definition(
         &self,
         request_id: usize,
@@ -723,6 +862,42 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_implementation(
+        &self,
+        request_id: usize,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<GotoImplementationResponse, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_implementation",
+            &json!({
+                "request_id": request_id,
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn get_declaration(
+        &self,
+        request_id: usize,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<GotoDeclarationResponse, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_declaration",
+            &json!({
+                "request_id": request_id,
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
     pub fn get_document_symbols(
         &self,
         buffer_id: BufferId,
@@ -758,12 +933,30 @@ impl LapceProxy {
     pub fn get_inlay_hints(
         &self,
         buffer_id: BufferId,
+        range: Range,
         f: impl FnOnce(Result<Vec<InlayHint>, RequestError>) + Send + 'static,
     ) {
         self.rpc.send_rpc_request_async(
             "get_inlay_hints",
             &json!({
                 "buffer_id": buffer_id,
+                "range": range,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn resolve_inlay_hint(
+        &self,
+        buffer_id: BufferId,
+        hint: InlayHint,
+        f: impl FnOnce(Result<InlayHint, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "resolve_inlay_hint",
+            &json!({
+                "buffer_id": buffer_id,
+                "hint": hint,
             }),
             box_json_cb(f),
         );
@@ -772,12 +965,14 @@ impl LapceProxy {
     pub fn get_semantic_tokens(
         &self,
         buffer_id: BufferId,
+        range: Range,
         f: impl FnOnce(Result<SemanticStyles, RequestError>) + Send + 'static,
     ) {
         self.rpc.send_rpc_request_async(
             "get_semantic_tokens",
             &json!({
                 "buffer_id": buffer_id,
+                "range": range,
             }),
             box_json_cb(f),
         );
@@ -813,6 +1008,285 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_document_range_formatting(
+        &self,
+        buffer_id: BufferId,
+        range: Range,
+        f: impl FnOnce(Result<Vec<TextEdit>, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_document_range_formatting",
+            &json!({
+                "buffer_id": buffer_id,
+                "range": range,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn get_on_type_formatting(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        ch: String,
+        f: impl FnOnce(Result<Vec<TextEdit>, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_on_type_formatting",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "ch": ch,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn prepare_rename(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<Option<PrepareRenameResponse>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_rename",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn rename(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        new_name: String,
+        f: impl FnOnce(Result<Option<WorkspaceEdit>, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "rename",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "new_name": new_name,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn prepare_call_hierarchy(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<Option<Vec<CallHierarchyItem>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_call_hierarchy",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn call_hierarchy_incoming_calls(
+        &self,
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+        f: impl FnOnce(Result<Option<Vec<CallHierarchyIncomingCall>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "call_hierarchy_incoming_calls",
+            &json!({
+                "buffer_id": buffer_id,
+                "item": item,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn call_hierarchy_outgoing_calls(
+        &self,
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+        f: impl FnOnce(Result<Option<Vec<CallHierarchyOutgoingCall>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "call_hierarchy_outgoing_calls",
+            &json!({
+                "buffer_id": buffer_id,
+                "item": item,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn prepare_type_hierarchy(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<Option<Vec<TypeHierarchyItem>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_type_hierarchy",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn type_hierarchy_supertypes(
+        &self,
+        buffer_id: BufferId,
+        item: TypeHierarchyItem,
+        f: impl FnOnce(Result<Option<Vec<TypeHierarchyItem>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "type_hierarchy_supertypes",
+            &json!({
+                "buffer_id": buffer_id,
+                "item": item,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn type_hierarchy_subtypes(
+        &self,
+        buffer_id: BufferId,
+        item: TypeHierarchyItem,
+        f: impl FnOnce(Result<Option<Vec<TypeHierarchyItem>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "type_hierarchy_subtypes",
+            &json!({
+                "buffer_id": buffer_id,
+                "item": item,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn get_document_highlight(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<Option<Vec<DocumentHighlight>>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_document_highlight",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn get_linked_editing_range(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: impl FnOnce(Result<Option<LinkedEditingRanges>, RequestError>)
+            + Send
+            + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_linked_editing_range",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn get_code_lens(
+        &self,
+        buffer_id: BufferId,
+        f: impl FnOnce(Result<Option<Vec<CodeLens>>, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_code_lens",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    pub fn code_lens_resolve(
+        &self,
+        buffer_id: BufferId,
+        code_lens: CodeLens,
+        f: impl FnOnce(Result<CodeLens, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "code_lens_resolve",
+            &json!({
+                "buffer_id": buffer_id,
+                "code_lens": code_lens,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    /// Run the command on the language server of the buffer, with
+    /// `workspace/executeCommand`
+    pub fn execute_command(
+        &self,
+        buffer_id: BufferId,
+        command: lsp_types::Command,
+        f: impl FnOnce(Result<Value, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "execute_command",
+            &json!({
+                "buffer_id": buffer_id,
+                "command": command,
+            }),
+            box_json_cb(f),
+        );
+    }
+
+    /// The names of the running language servers
+    pub fn get_language_servers(
+        &self,
+        f: impl FnOnce(Result<Vec<String>, RequestError>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_language_servers",
+            &json!({}),
+            box_json_cb(f),
+        );
+    }
+
+    /// Respond to a request from the proxy
+    pub fn respond(&self, id: RequestId, result: Result<Value, Value>) {
+        self.rpc.respond(id, result);
+    }
+
     pub fn stop(&self) {
         self.rpc.send_rpc_notification("shutdown", &json!({}));
         // self.core_sender.send(json!({
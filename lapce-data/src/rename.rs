@@ -0,0 +1,445 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use druid::{Command, EventCtx, ExtEventSink, Target, WidgetId};
+use lapce_core::{buffer::Buffer, cursor::CursorMode, selection::Selection};
+use lapce_rpc::buffer::BufferId;
+use lsp_types::{Position, PrepareRenameResponse, TextEdit, WorkspaceEdit};
+use xi_rope::Rope;
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    data::LapceMainSplitData,
+    document::LocalBufferKind,
+    editor::workspace_edits,
+    proxy::{path_from_url, LapceProxy},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameStatus {
+    Inactive,
+    /// The input is shown and the user is typing the new name
+    Input,
+    /// Waiting for the language server to send back the workspace edit
+    Requested,
+    /// The workspace edit is shown and waiting to be applied
+    Preview,
+}
+
+/// A line (or run of lines) that will be changed by the rename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameHunk {
+    /// The first line of the hunk
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Clone)]
+pub struct RenamePreviewFile {
+    pub path: PathBuf,
+    /// The hunks to display, empty until the file is loaded if it isn't open,
+    /// or if it could not be read
+    pub hunks: Vec<RenameHunk>,
+    pub edits: Vec<TextEdit>,
+}
+
+#[derive(Clone)]
+pub struct RenameData {
+    pub id: WidgetId,
+    /// The id of the editor (in `main_split.editors`) for the new name
+    pub input_view_id: WidgetId,
+    /// The editor view that the rename was started from
+    pub editor_view_id: WidgetId,
+    pub status: RenameStatus,
+    pub path: PathBuf,
+    pub buffer_id: BufferId,
+    /// The start of the symbol being renamed, the input is displayed below it
+    pub offset: usize,
+    pub position: Position,
+    /// A counter to keep track of the active requests
+    pub request_id: usize,
+    /// The name that the current preview was requested with
+    pub new_name: String,
+    pub preview: Arc<Vec<RenamePreviewFile>>,
+}
+
+impl RenameData {
+    pub fn new() -> Self {
+        Self {
+            id: WidgetId::next(),
+            input_view_id: WidgetId::next(),
+            editor_view_id: WidgetId::next(),
+            status: RenameStatus::Inactive,
+            path: PathBuf::new(),
+            buffer_id: BufferId(0),
+            offset: 0,
+            position: Position::default(),
+            request_id: 0,
+            new_name: String::new(),
+            preview: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Show the rename input for the symbol that `prepareRename` pointed us at
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+        editor_view_id: WidgetId,
+        path: &Path,
+        rev: u64,
+        offset: usize,
+        response: &PrepareRenameResponse,
+    ) {
+        let doc = match main_split.open_docs.get(path) {
+            // The document was edited while we were waiting on the server
+            Some(doc) if doc.rev() == rev => doc.clone(),
+            _ => return,
+        };
+        let buffer = doc.buffer();
+
+        let (start, end, placeholder) = match response {
+            PrepareRenameResponse::Range(range) => (
+                buffer.offset_of_position(&range.start),
+                buffer.offset_of_position(&range.end),
+                None,
+            ),
+            PrepareRenameResponse::RangeWithPlaceholder { range, placeholder } => (
+                buffer.offset_of_position(&range.start),
+                buffer.offset_of_position(&range.end),
+                Some(placeholder.clone()),
+            ),
+            PrepareRenameResponse::DefaultBehavior { .. } => {
+                let (start, end) = buffer.select_word(offset);
+                (Some(start), Some(end), None)
+            }
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if start < end => (start, end),
+            _ => return,
+        };
+        let position = match buffer.offset_to_position(offset) {
+            Some(position) => position,
+            None => return,
+        };
+        let text = placeholder
+            .unwrap_or_else(|| buffer.slice_to_cow(start..end).to_string());
+
+        self.cancel();
+        self.status = RenameStatus::Input;
+        self.editor_view_id = editor_view_id;
+        self.path = path.to_path_buf();
+        self.buffer_id = doc.id();
+        self.offset = start;
+        self.position = position;
+        self.new_name = text.clone();
+
+        // Set the text of the input and select all of it
+        let input = main_split
+            .local_docs
+            .get_mut(&LocalBufferKind::Rename)
+            .unwrap();
+        Arc::make_mut(input).reload(Rope::from(text), true);
+        let input_end = input.buffer().line_end_offset(0, true);
+        let editor = main_split.editors.get_mut(&self.input_view_id).unwrap();
+        Arc::make_mut(editor).cursor.mode =
+            CursorMode::Insert(Selection::region(0, input_end));
+
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(self.input_view_id),
+        ));
+    }
+
+    /// Stop renaming, discarding the preview
+    pub fn cancel(&mut self) {
+        if self.status == RenameStatus::Inactive {
+            return;
+        }
+
+        self.status = RenameStatus::Inactive;
+        self.preview = Arc::new(Vec::new());
+    }
+
+    /// Ask the language server for the workspace edit that renames the symbol to `new_name`
+    pub fn request(
+        &mut self,
+        proxy: &LapceProxy,
+        new_name: String,
+        event_sink: ExtEventSink,
+    ) {
+        self.request_id += 1;
+        self.status = RenameStatus::Requested;
+        self.new_name = new_name.clone();
+
+        let request_id = self.request_id;
+        let widget_id = self.id;
+        proxy.rename(self.buffer_id, self.position, new_name, move |result| {
+            let edit = match result {
+                Ok(edit) => edit,
+                Err(err) => {
+                    log::warn!("rename failed: {err:?}");
+                    None
+                }
+            };
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::RenamePreview { request_id, edit },
+                Target::Widget(widget_id),
+            );
+        });
+    }
+
+    /// Receive the workspace edit of a rename request and build the preview for it
+    pub fn receive(
+        &mut self,
+        request_id: usize,
+        edit: Option<&WorkspaceEdit>,
+        main_split: &LapceMainSplitData,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        if self.status != RenameStatus::Requested || self.request_id != request_id {
+            return;
+        }
+
+        let edits = match edit.and_then(workspace_edits) {
+            Some(edits) if !edits.is_empty() => edits,
+            _ => {
+                // Nothing to rename, let the user try another name
+                self.status = RenameStatus::Input;
+                return;
+            }
+        };
+
+        let mut files: Vec<RenamePreviewFile> = edits
+            .into_iter()
+            .map(|(url, edits)| {
+                let path = path_from_url(&url);
+                let hunks = if let Some(doc) = main_split.open_docs.get(&path) {
+                    preview_hunks(doc.buffer(), &edits)
+                } else {
+                    self.load_file(request_id, &path, proxy, event_sink.clone());
+                    Vec::new()
+                };
+                RenamePreviewFile { path, hunks, edits }
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        self.preview = Arc::new(files);
+        self.status = RenameStatus::Preview;
+    }
+
+    /// Ask the proxy for the content of a file that isn't open, to preview
+    /// the edits to it
+    fn load_file(
+        &self,
+        request_id: usize,
+        path: &Path,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let widget_id = self.id;
+        let file_path = path.to_path_buf();
+        proxy.read_file(path, move |result| match result {
+            Ok(resp) => {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RenamePreviewFile {
+                        request_id,
+                        path: file_path,
+                        content: resp.content,
+                    },
+                    Target::Widget(widget_id),
+                );
+            }
+            Err(err) => {
+                log::warn!(
+                    "failed to read {file_path:?} for the rename preview: {err:?}"
+                );
+            }
+        });
+    }
+
+    /// Receive the content of a file in the preview that isn't open, and
+    /// show the hunks of its edits
+    pub fn receive_file(&mut self, request_id: usize, path: &Path, content: &str) {
+        if self.status != RenameStatus::Preview || self.request_id != request_id {
+            return;
+        }
+
+        let preview = Arc::make_mut(&mut self.preview);
+        if let Some(file) = preview.iter_mut().find(|file| file.path == path) {
+            file.hunks = preview_hunks(&Buffer::new(content), &file.edits);
+        }
+    }
+
+    /// Apply the previewed workspace edit, then stop renaming
+    pub fn apply(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+    ) {
+        if self.status != RenameStatus::Preview {
+            return;
+        }
+
+        for file in self.preview.iter() {
+            main_split.apply_edits_to_file(
+                ctx,
+                &file.path,
+                file.edits.clone(),
+                || {},
+            );
+        }
+        self.cancel();
+    }
+}
+
+impl Default for RenameData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Group the edits into hunks of the lines they touch, with the content of
+/// those lines before and after the edits are applied.
+pub fn preview_hunks(buffer: &Buffer, edits: &[TextEdit]) -> Vec<RenameHunk> {
+    let mut edits: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .filter_map(|edit| {
+            Some((
+                buffer.offset_of_position(&edit.range.start)?,
+                buffer.offset_of_position(&edit.range.end)?,
+                edit.new_text.as_str(),
+            ))
+        })
+        .collect();
+    edits.sort_by_key(|(start, ..)| *start);
+
+    let is_newline = |c: char| c == '\n' || c == '\r';
+    let mut hunks = Vec::new();
+    let mut i = 0;
+    while i < edits.len() {
+        let first_line = buffer.line_of_offset(edits[i].0);
+        let mut last_line = buffer.line_of_offset(edits[i].1);
+        let mut j = i + 1;
+        while j < edits.len() && buffer.line_of_offset(edits[j].0) <= last_line {
+            last_line = last_line.max(buffer.line_of_offset(edits[j].1));
+            j += 1;
+        }
+
+        let start = buffer.offset_of_line(first_line);
+        let end = buffer.offset_of_line(last_line + 1);
+        let mut after = String::new();
+        let mut last = start;
+        for (edit_start, edit_end, text) in &edits[i..j] {
+            if *edit_start > last {
+                after.push_str(&buffer.slice_to_cow(last..*edit_start));
+            }
+            after.push_str(text);
+            last = last.max(*edit_end);
+        }
+        if end > last {
+            after.push_str(&buffer.slice_to_cow(last..end));
+        }
+
+        hunks.push(RenameHunk {
+            line: first_line,
+            before: buffer
+                .slice_to_cow(start..end)
+                .trim_end_matches(is_newline)
+                .to_string(),
+            after: after.trim_end_matches(is_newline).to_string(),
+        });
+        i = j;
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::Range;
+
+    use super::*;
+
+    fn edit(line: u32, start: u32, end: u32, text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: end,
+                },
+            },
+            new_text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_preview_hunks() {
+        let buffer = Buffer::new("fn foo() {}\nlet x = foo();\nfoo(foo);\n");
+        let edits = vec![
+            edit(2, 4, 7, "bar"),
+            edit(0, 3, 6, "bar"),
+            edit(1, 8, 11, "bar"),
+            edit(2, 0, 3, "bar"),
+        ];
+        let hunks = preview_hunks(&buffer, &edits);
+        assert_eq!(
+            hunks,
+            vec![
+                RenameHunk {
+                    line: 0,
+                    before: "fn foo() {}".to_string(),
+                    after: "fn bar() {}".to_string(),
+                },
+                RenameHunk {
+                    line: 1,
+                    before: "let x = foo();".to_string(),
+                    after: "let x = bar();".to_string(),
+                },
+                RenameHunk {
+                    line: 2,
+                    before: "foo(foo);".to_string(),
+                    after: "bar(bar);".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_receive_file() {
+        let mut rename = RenameData::new();
+        rename.request_id = 2;
+        rename.status = RenameStatus::Preview;
+        rename.preview = Arc::new(vec![RenamePreviewFile {
+            path: PathBuf::from("/work/src/lib.rs"),
+            hunks: Vec::new(),
+            edits: vec![edit(1, 4, 7, "bar")],
+        }]);
+
+        // The content of an older request is ignored
+        rename.receive_file(1, Path::new("/work/src/lib.rs"), "mod a;\nuse foo;\n");
+        assert!(rename.preview[0].hunks.is_empty());
+
+        rename.receive_file(2, Path::new("/work/src/lib.rs"), "mod a;\nuse foo;\n");
+        assert_eq!(
+            rename.preview[0].hunks,
+            vec![RenameHunk {
+                line: 1,
+                before: "use foo;".to_string(),
+                after: "use bar;".to_string(),
+            }]
+        );
+    }
+}
@@ -1,65 +1,372 @@
-use lsp_types::ParameterLabel;
-use lsp_types::SignatureHelp;
+use std::{cell::RefCell, rc::Rc, sync::Arc};
 
+use druid::{ExtEventSink, FontWeight, Size, Target, WidgetId};
+use lapce_core::encoding::offset_utf16_to_utf8_str;
+use lapce_rpc::buffer::BufferId;
+use lsp_types::{
+    Documentation, MarkupKind, ParameterInformation, ParameterLabel, Position,
+    SignatureHelp, SignatureInformation,
+};
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::{Config, LapceTheme},
+    markdown::parse_markdown,
+    proxy::LapceProxy,
+    rich_text::{RichText, RichTextBuilder},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Inactive,
+    Started,
+    Done,
+}
+
+/// The signature help of the call that the cursor is in, which is shown
+/// above the cursor while typing the arguments
 #[derive(Clone)]
-pub struct SignatureState {
-    pub offset: Option<usize>,
-    pub signature: Option<SignatureHelp>,
-    pub active: Option<(usize, usize)>,
+pub struct SignatureData {
+    pub id: WidgetId,
+    pub scroll_id: WidgetId,
+    /// The editor view id that the signature help is displayed for
+    pub editor_view_id: WidgetId,
+    /// The current request status
+    pub status: SignatureStatus,
+    /// The buffer that this signature help is for
+    pub buffer_id: BufferId,
+    /// The offset of the cursor when the signature help was requested
+    pub offset: usize,
+    /// A counter to keep track of the active requests
+    pub request_id: usize,
+    /// Stores the size of the signature help box
+    pub size: Size,
+    /// Stores the actual size of the signature help content
+    pub content_size: Rc<RefCell<Size>>,
+    /// The signature help that is shown
+    pub help: Option<Arc<SignatureHelp>>,
+    /// The overload that is shown, which starts at the one that the server
+    /// picked and can be cycled through
+    pub active_signature: usize,
 }
 
-impl SignatureState {
+impl SignatureData {
     pub fn new() -> Self {
         Self {
-            offset: None,
-            signature: None,
-            active: None,
+            id: WidgetId::next(),
+            scroll_id: WidgetId::next(),
+            editor_view_id: WidgetId::next(),
+            status: SignatureStatus::Inactive,
+            buffer_id: BufferId(0),
+            offset: 0,
+            request_id: 0,
+            size: Size::new(600.0, 250.0),
+            content_size: Rc::new(RefCell::new(Size::ZERO)),
+            help: None,
+            active_signature: 0,
         }
     }
 
-    pub fn update(&mut self, offset: usize, commas: Vec<usize>) -> Option<bool> {
-        let signature = self.signature.as_ref()?;
-        let params = signature.signatures[0].parameters.as_ref()?;
-        if params.is_empty() {
-            return None;
+    /// The number of overloads
+    pub fn len(&self) -> usize {
+        self.help
+            .as_ref()
+            .map(|help| help.signatures.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The overload that is shown
+    pub fn signature(&self) -> Option<&SignatureInformation> {
+        self.help.as_ref()?.signatures.get(self.active_signature)
+    }
+
+    /// Move to the next overload, wrapping around to the first
+    pub fn next(&mut self) {
+        let len = self.len();
+        if len > 0 {
+            self.active_signature = (self.active_signature + 1) % len;
         }
-        let mut index = commas.len();
-        for (i, c) in commas.iter().enumerate() {
-            if offset <= *c {
-                index = i;
-                break;
-            }
+    }
+
+    /// Move to the previous overload, wrapping around to the last
+    pub fn previous(&mut self) {
+        let len = self.len();
+        if len > 0 {
+            self.active_signature = (self.active_signature + len - 1) % len;
         }
+    }
 
-        let label = signature.signatures[0].label.clone();
-        let active = if index >= params.len() {
-            None
-        } else {
-            match &params[index].label {
-                ParameterLabel::Simple(s) => {
-                    let start = label.find(s)?;
-                    Some((start, start + s.len()))
-                }
-                ParameterLabel::LabelOffsets(offsets) => {
-                    Some((offsets[0] as usize, offsets[1] as usize))
+    /// Cancel the current signature help, clearing out held data
+    pub fn cancel(&mut self) {
+        if self.status == SignatureStatus::Inactive {
+            return;
+        }
+
+        self.status = SignatureStatus::Inactive;
+        self.help = None;
+        self.active_signature = 0;
+    }
+
+    /// Send a request for the signature help at the position, which is
+    /// `trigger`ed by a typed character, or updates the one that is shown
+    #[allow(clippy::too_many_arguments)]
+    pub fn request(
+        &mut self,
+        proxy: Arc<LapceProxy>,
+        buffer_id: BufferId,
+        editor_view_id: WidgetId,
+        offset: usize,
+        position: Position,
+        trigger: Option<String>,
+        event_sink: ExtEventSink,
+    ) {
+        if self.buffer_id != buffer_id || self.editor_view_id != editor_view_id {
+            self.cancel();
+        }
+        if self.status == SignatureStatus::Inactive {
+            self.status = SignatureStatus::Started;
+        }
+        self.buffer_id = buffer_id;
+        self.editor_view_id = editor_view_id;
+        self.offset = offset;
+        self.request_id += 1;
+
+        // The server keeps the overload that was cycled to
+        let active = self.help.as_ref().map(|help| {
+            let mut help = (**help).clone();
+            help.active_signature = Some(self.active_signature as u32);
+            Box::new(help)
+        });
+
+        let request_id = self.request_id;
+        let widget_id = self.id;
+        proxy.get_signature(
+            request_id,
+            buffer_id,
+            position,
+            trigger,
+            active,
+            move |result| {
+                if let Ok(help) = result {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateSignature {
+                            request_id,
+                            help: help.map(Arc::new),
+                        },
+                        Target::Widget(widget_id),
+                    );
                 }
+            },
+        );
+    }
+
+    /// Receive the result of a signature help request, where no signatures
+    /// mean that the cursor isn't in a call anymore
+    pub fn receive(&mut self, request_id: usize, help: Option<Arc<SignatureHelp>>) {
+        if self.status == SignatureStatus::Inactive || self.request_id != request_id
+        {
+            return;
+        }
+
+        match help {
+            Some(help) if !help.signatures.is_empty() => {
+                self.active_signature = (help.active_signature.unwrap_or(0)
+                    as usize)
+                    .min(help.signatures.len() - 1);
+                self.status = SignatureStatus::Done;
+                self.help = Some(help);
             }
+            _ => self.cancel(),
+        }
+    }
+
+    fn active_parameter_info(&self) -> Option<&ParameterInformation> {
+        let signature = self.signature()?;
+        let index = signature
+            .active_parameter
+            .or_else(|| self.help.as_ref()?.active_parameter)?;
+        signature.parameters.as_ref()?.get(index as usize)
+    }
+
+    /// The byte range of the active parameter in the label of the overload
+    /// that is shown
+    pub fn active_parameter(&self) -> Option<(usize, usize)> {
+        let label = &self.signature()?.label;
+        match &self.active_parameter_info()?.label {
+            ParameterLabel::Simple(param) => {
+                let start = label.find(param.as_str())?;
+                Some((start, start + param.len()))
+            }
+            ParameterLabel::LabelOffsets([start, end]) => Some((
+                offset_utf16_to_utf8_str(label, *start as usize)?,
+                offset_utf16_to_utf8_str(label, *end as usize)?,
+            )),
+        }
+    }
+
+    /// The label of the overload that is shown with the active parameter in
+    /// bold, after the position among the overloads if there are several
+    pub fn label(&self, config: &Config) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        let signature = match self.signature() {
+            Some(signature) => signature,
+            None => return builder.build(),
         };
 
-        let changed = self.active != active;
-        self.active = active;
-        Some(changed)
+        if self.len() > 1 {
+            builder
+                .push(&format!("{}/{} ", self.active_signature + 1, self.len()))
+                .text_color(
+                    config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone(),
+                );
+        }
+
+        let label = &signature.label;
+        match self.active_parameter() {
+            Some((start, end)) => {
+                builder.push(&label[..start]);
+                builder.push(&label[start..end]).weight(FontWeight::BOLD);
+                builder.push(&label[end..]);
+            }
+            None => {
+                builder.push(label);
+            }
+        }
+        builder.build()
     }
 
-    pub fn clear(&mut self) {
-        self.offset = None;
-        self.signature = None;
-        self.active = None;
+    /// The documentation of the active parameter and of the overload that
+    /// is shown
+    pub fn documentation(&self, config: &Config) -> Vec<RichText> {
+        self.active_parameter_info()
+            .and_then(|param| param.documentation.as_ref())
+            .into_iter()
+            .chain(
+                self.signature()
+                    .and_then(|signature| signature.documentation.as_ref()),
+            )
+            .map(|documentation| parse_documentation(documentation, config))
+            .collect()
     }
 }
 
-impl Default for SignatureState {
+impl Default for SignatureData {
     fn default() -> Self {
         Self::new()
     }
 }
+
+fn parse_documentation(documentation: &Documentation, config: &Config) -> RichText {
+    match documentation {
+        Documentation::MarkupContent(content)
+            if content.kind == MarkupKind::Markdown =>
+        {
+            parse_markdown(&content.value, config)
+        }
+        Documentation::MarkupContent(content) => plain_text(&content.value),
+        Documentation::String(text) => plain_text(text),
+    }
+}
+
+fn plain_text(text: &str) -> RichText {
+    let mut builder = RichTextBuilder::new();
+    builder.set_line_height(1.5);
+    builder.push(text);
+    builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn help(label: &str, params: Vec<ParameterLabel>, active: u32) -> SignatureHelp {
+        SignatureHelp {
+            signatures: vec![
+                SignatureInformation {
+                    label: label.to_string(),
+                    documentation: None,
+                    parameters: Some(
+                        params
+                            .into_iter()
+                            .map(|label| ParameterInformation {
+                                label,
+                                documentation: None,
+                            })
+                            .collect(),
+                    ),
+                    active_parameter: None,
+                },
+                SignatureInformation {
+                    label: "fn f()".to_string(),
+                    documentation: None,
+                    parameters: None,
+                    active_parameter: None,
+                },
+            ],
+            active_signature: Some(0),
+            active_parameter: Some(active),
+        }
+    }
+
+    fn received(help: SignatureHelp) -> SignatureData {
+        let mut signature = SignatureData::new();
+        signature.status = SignatureStatus::Started;
+        signature.receive(0, Some(Arc::new(help)));
+        signature
+    }
+
+    #[test]
+    fn test_active_parameter() {
+        let signature = received(help(
+            "fn f(a: u8, b: u8)",
+            vec![
+                ParameterLabel::Simple("a: u8".to_string()),
+                ParameterLabel::Simple("b: u8".to_string()),
+            ],
+            1,
+        ));
+        assert_eq!(signature.status, SignatureStatus::Done);
+        assert_eq!(signature.active_parameter(), Some((12, 17)));
+
+        // The offsets are in UTF-16 code units
+        let signature = received(help(
+            "fn f(é: u8, 𝔸: u8)",
+            vec![
+                ParameterLabel::LabelOffsets([5, 10]),
+                ParameterLabel::LabelOffsets([12, 18]),
+            ],
+            1,
+        ));
+        let (start, end) = signature.active_parameter().unwrap();
+        assert_eq!(&signature.signature().unwrap().label[start..end], "𝔸: u8");
+    }
+
+    #[test]
+    fn test_cycle_overloads() {
+        let mut signature = received(help("fn f(a: u8)", Vec::new(), 0));
+        assert_eq!(signature.len(), 2);
+        signature.next();
+        assert_eq!(signature.signature().unwrap().label, "fn f()");
+        assert_eq!(signature.active_parameter(), None);
+        signature.next();
+        assert_eq!(signature.active_signature, 0);
+        signature.previous();
+        assert_eq!(signature.active_signature, 1);
+    }
+
+    #[test]
+    fn test_receive() {
+        let mut signature = received(help("fn f(a: u8)", Vec::new(), 0));
+        // A stale response is ignored
+        signature.receive(1, None);
+        assert_eq!(signature.status, SignatureStatus::Done);
+        signature.receive(0, None);
+        assert_eq!(signature.status, SignatureStatus::Inactive);
+        assert!(signature.is_empty());
+    }
+}
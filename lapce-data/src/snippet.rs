@@ -0,0 +1,474 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, Local, Timelike};
+use lapce_core::language::LapceLanguage;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, InsertTextFormat,
+    MarkupContent, MarkupKind,
+};
+use serde::Deserialize;
+
+use crate::{
+    config::Config,
+    data::{LapceWorkspace, LapceWorkspaceType},
+};
+
+/// A snippet defined by the user in a VS Code style snippet file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserSnippet {
+    pub name: String,
+    pub prefixes: Vec<String>,
+    pub body: String,
+    pub description: Option<String>,
+    /// The languages the snippet applies to, or `None` for every language.
+    pub languages: Option<Vec<String>>,
+}
+
+impl UserSnippet {
+    fn applies_to(&self, names: &[String]) -> bool {
+        match self.languages.as_ref() {
+            Some(languages) => languages
+                .iter()
+                .any(|l| names.iter().any(|n| n.eq_ignore_ascii_case(l))),
+            None => true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(s) => vec![s],
+            OneOrMany::Many(v) => v,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SnippetDefinition {
+    prefix: Option<OneOrMany>,
+    body: OneOrMany,
+    description: Option<OneOrMany>,
+    scope: Option<String>,
+}
+
+/// The user snippets loaded from `Config::dir()/snippets` and the workspace's
+/// `.lapce/snippets` folder.
+///
+/// A file named `<language>.json` holds the snippets of that language, where
+/// the language is either a language name like `rust` or a file extension
+/// like `rs`. Snippets in `*.code-snippets` files apply to every language
+/// unless they list a `scope`.
+#[derive(Debug, Clone, Default)]
+pub struct UserSnippets {
+    snippets: Vec<UserSnippet>,
+}
+
+impl UserSnippets {
+    pub fn load(workspace: &LapceWorkspace) -> Self {
+        let mut snippets = Vec::new();
+        if let Some(folder) = Config::snippets_folder() {
+            snippets.extend(Self::load_folder(&folder));
+        }
+        match workspace.kind {
+            LapceWorkspaceType::Local => {
+                if let Some(path) = workspace.path.as_ref() {
+                    let folder = path.join("./.lapce/snippets");
+                    snippets.extend(Self::load_folder(&folder));
+                }
+            }
+            LapceWorkspaceType::RemoteSSH(_, _) => {}
+            LapceWorkspaceType::RemoteWSL => {}
+        }
+        Self { snippets }
+    }
+
+    fn load_folder(folder: &Path) -> Vec<UserSnippet> {
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(folder) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
+            Err(_) => return Vec::new(),
+        };
+        paths.sort();
+
+        let mut snippets = Vec::new();
+        for path in paths {
+            let language = match path.extension().and_then(|e| e.to_str()) {
+                Some("json") => path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_string()),
+                Some("code-snippets") => None,
+                _ => continue,
+            };
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    log::error!("Failed to read snippet file {path:?}: {e}");
+                    continue;
+                }
+            };
+            match Self::parse(&content, language.as_deref()) {
+                Ok(file_snippets) => snippets.extend(file_snippets),
+                Err(e) => {
+                    log::error!("Failed to parse snippet file {path:?}: {e}")
+                }
+            }
+        }
+        snippets
+    }
+
+    /// Parse the content of a snippet file. `language` is the language given
+    /// by the file name, if any.
+    pub fn parse(
+        content: &str,
+        language: Option<&str>,
+    ) -> Result<Vec<UserSnippet>, serde_json::Error> {
+        let definitions: HashMap<String, SnippetDefinition> =
+            serde_json::from_str(&strip_json_comments(content))?;
+        let mut snippets: Vec<UserSnippet> = definitions
+            .into_iter()
+            .filter_map(|(name, definition)| {
+                let prefixes = definition.prefix?.into_vec();
+                if prefixes.is_empty() {
+                    return None;
+                }
+                let languages = match definition.scope {
+                    Some(scope) => Some(
+                        scope
+                            .split(',')
+                            .map(|s| s.trim().to_string())
+                            .filter(|s| !s.is_empty())
+                            .collect(),
+                    ),
+                    None => language.map(|l| vec![l.to_string()]),
+                };
+                Some(UserSnippet {
+                    name,
+                    prefixes,
+                    body: definition.body.into_vec().join("\n"),
+                    description: definition
+                        .description
+                        .map(|d| d.into_vec().join("\n")),
+                    languages,
+                })
+            })
+            .collect();
+        snippets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(snippets)
+    }
+
+    /// The snippets for the file at `path` as completion items.
+    pub fn completion_items(&self, path: &Path) -> Vec<CompletionItem> {
+        let mut names = Vec::new();
+        if let Some(language) = LapceLanguage::from_path(path) {
+            names.push(language.to_string());
+        }
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            names.push(extension.to_string());
+        }
+
+        self.snippets
+            .iter()
+            .filter(|snippet| snippet.applies_to(&names))
+            .flat_map(|snippet| {
+                snippet.prefixes.iter().map(|prefix| CompletionItem {
+                    label: prefix.clone(),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    detail: Some(
+                        snippet
+                            .description
+                            .clone()
+                            .unwrap_or_else(|| snippet.name.clone()),
+                    ),
+                    documentation: Some(Documentation::MarkupContent(
+                        MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: format!("```\n{}\n```", snippet.body),
+                        },
+                    )),
+                    filter_text: Some(prefix.clone()),
+                    insert_text: Some(snippet.body.clone()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Remove `//` and `/* */` comments and trailing commas, which are allowed in
+/// VS Code's snippet files but not in JSON.
+fn strip_json_comments(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            result.push(c);
+            if c == '\\' {
+                if let Some(c) = chars.next() {
+                    result.push(c);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                result.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        result.push(c);
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '}' | ']' => {
+                let trimmed = result.trim_end().len();
+                if result[..trimmed].ends_with(',') {
+                    result.truncate(trimmed - 1);
+                }
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Replace the variables such as `$TM_FILENAME` or `${CURRENT_YEAR:2022}` in a
+/// snippet with the values given by `resolve`. Variables which can't be
+/// resolved are replaced by their default value, or by nothing. The values are
+/// escaped, so that e.g. a `$1` in the clipboard stays text when the snippet
+/// is parsed.
+pub fn resolve_variables(
+    snippet: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> String {
+    resolve_variables_with(snippet, &resolve)
+}
+
+fn resolve_variables_with(
+    snippet: &str,
+    resolve: &dyn Fn(&str) -> Option<String>,
+) -> String {
+    let is_name_char = |c: char| c.is_ascii_uppercase() || c == '_';
+
+    let mut result = String::with_capacity(snippet.len());
+    let mut rest = snippet;
+    while let Some(i) = rest.find(|c| c == '$' || c == '\\') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with('\\') {
+            // Keep escapes as they are for the snippet parser.
+            let len = rest[1..].chars().next().map(|c| c.len_utf8()).unwrap_or(0);
+            result.push_str(&rest[..1 + len]);
+            rest = &rest[1 + len..];
+            continue;
+        }
+
+        let after = &rest[1..];
+        let name_len = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+        if name_len > 0 {
+            let name = &after[..name_len];
+            result.push_str(&escape(&resolve(name).unwrap_or_default()));
+            rest = &after[name_len..];
+            continue;
+        }
+
+        if let Some(inner) = after.strip_prefix('{') {
+            let name_len = inner.find(|c| !is_name_char(c)).unwrap_or(inner.len());
+            let name = &inner[..name_len];
+            let tail = &inner[name_len..];
+            if name_len > 0 && tail.starts_with('}') {
+                result.push_str(&escape(&resolve(name).unwrap_or_default()));
+                rest = &tail[1..];
+                continue;
+            }
+            if name_len > 0 && tail.starts_with(':') {
+                if let Some(end) = tail.find('}') {
+                    let default = &tail[1..end];
+                    let value = resolve(name)
+                        .map(|value| escape(&value))
+                        .unwrap_or_else(|| resolve_variables_with(default, resolve));
+                    result.push_str(&value);
+                    rest = &tail[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        result.push('$');
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Escape the characters of the text that have a meaning in a snippet
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '$' | '}') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// The value of a snippet variable that only depends on the current time.
+pub fn time_variable(name: &str) -> Option<String> {
+    let now = Local::now();
+    Some(match name {
+        "CURRENT_YEAR" => now.year().to_string(),
+        "CURRENT_YEAR_SHORT" => format!("{:02}", now.year() % 100),
+        "CURRENT_MONTH" => format!("{:02}", now.month()),
+        "CURRENT_MONTH_NAME" => now.format("%B").to_string(),
+        "CURRENT_MONTH_NAME_SHORT" => now.format("%b").to_string(),
+        "CURRENT_DATE" => format!("{:02}", now.day()),
+        "CURRENT_DAY_NAME" => now.format("%A").to_string(),
+        "CURRENT_DAY_NAME_SHORT" => now.format("%a").to_string(),
+        "CURRENT_HOUR" => format!("{:02}", now.hour()),
+        "CURRENT_MINUTE" => format!("{:02}", now.minute()),
+        "CURRENT_SECOND" => format!("{:02}", now.second()),
+        "CURRENT_SECONDS_UNIX" => now.timestamp().to_string(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::completion::Snippet;
+
+    #[test]
+    fn test_parse_snippets() {
+        let content = r##"{
+            // A comment
+            "Print": {
+                "prefix": ["pr", "print"],
+                "body": ["println!(\"$1\");", "$0"],
+                "description": "Print a line",
+            },
+            /* no prefix */
+            "Empty": { "body": "x" },
+            "Test": {
+                "prefix": "test",
+                "body": "#[test]",
+                "scope": "rust, toml"
+            }
+        }"##;
+        let snippets = UserSnippets::parse(content, Some("rust")).unwrap();
+        assert_eq!(
+            vec![
+                UserSnippet {
+                    name: "Print".to_string(),
+                    prefixes: vec!["pr".to_string(), "print".to_string()],
+                    body: "println!(\"$1\");\n$0".to_string(),
+                    description: Some("Print a line".to_string()),
+                    languages: Some(vec!["rust".to_string()]),
+                },
+                UserSnippet {
+                    name: "Test".to_string(),
+                    prefixes: vec!["test".to_string()],
+                    body: "#[test]".to_string(),
+                    description: None,
+                    languages: Some(vec!["rust".to_string(), "toml".to_string()]),
+                },
+            ],
+            snippets
+        );
+    }
+
+    #[test]
+    fn test_completion_items_language() {
+        let content = r#"{ "A": { "prefix": "a", "body": "a" } }"#;
+        let snippets = UserSnippets {
+            snippets: [
+                UserSnippets::parse(content, Some("sh")).unwrap(),
+                UserSnippets::parse(content, None).unwrap(),
+            ]
+            .concat(),
+        };
+        assert_eq!(2, snippets.completion_items(Path::new("a.sh")).len());
+        assert_eq!(1, snippets.completion_items(Path::new("a.txt")).len());
+    }
+
+    #[test]
+    fn test_resolve_variables() {
+        let resolve = |name: &str| match name {
+            "TM_FILENAME" => Some("main.rs".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            "// main.rs\n$1 ${2:x} \\$TM_FILENAME main.rs",
+            resolve_variables(
+                "// $TM_FILENAME\n$1 ${2:x} \\$TM_FILENAME ${TM_FILENAME}",
+                resolve
+            )
+        );
+        assert_eq!(
+            "a default b",
+            resolve_variables("a ${UNKNOWN:default} b$UNKNOWN", resolve)
+        );
+        assert_eq!(
+            "${1:main.rs}",
+            resolve_variables("${1:$TM_FILENAME}", resolve)
+        );
+    }
+
+    #[test]
+    fn test_resolve_variables_escape() {
+        let resolve = |name: &str| match name {
+            "CLIPBOARD" => Some("a$1 ${b} \\c".to_string()),
+            _ => None,
+        };
+        let snippet = resolve_variables("$CLIPBOARD$0 ${1:$CLIPBOARD}", resolve);
+        assert_eq!("a\\$1 \\${b\\} \\\\c$0 ${1:a\\$1 \\${b\\} \\\\c}", snippet);
+
+        let snippet = Snippet::from_str(&snippet).unwrap();
+        assert_eq!("a$1 ${b} \\c a$1 ${b} \\c", snippet.text());
+        assert_eq!(vec![(0, (11, 11)), (1, (12, 23))], snippet.tabs(0));
+    }
+
+    #[test]
+    fn test_resolve_variables_non_ascii() {
+        let resolve = |name: &str| match name {
+            "CLIPBOARD" => Some("é$1 ✓}".to_string()),
+            _ => None,
+        };
+        let snippet = resolve_variables("«$CLIPBOARD»$0", resolve);
+        assert_eq!("«é\\$1 ✓\\}»$0", snippet);
+
+        let snippet = Snippet::from_str(&snippet).unwrap();
+        assert_eq!("«é$1 ✓}»", snippet.text());
+        assert_eq!(vec![(0, (13, 13))], snippet.tabs(0));
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use alacritty_terminal::{
     ansi,
@@ -360,7 +360,8 @@ impl KeyPressFocus for LapceTerminalViewData {
                     }
                     self.terminal.clear_selection(term);
                 }
-                EditCommand::ClipboardPaste => {
+                EditCommand::ClipboardPaste
+                | EditCommand::ClipboardPasteWithoutFormatting => {
                     if let Some(s) = Application::global().clipboard().get_string() {
                         self.receive_char(ctx, &s);
                     }
@@ -524,6 +525,32 @@ impl LapceTerminalData {
         config: &Config,
     ) -> Self {
         let cwd = workspace.path.as_ref().cloned();
+        Self::new_in(cwd, None, split_id, event_sink, proxy, config)
+    }
+
+    /// A terminal that runs the shell command once the shell has started,
+    /// in `cwd` or else the workspace
+    pub fn new_with_command(
+        workspace: Arc<LapceWorkspace>,
+        split_id: WidgetId,
+        event_sink: ExtEventSink,
+        proxy: Arc<LapceProxy>,
+        config: &Config,
+        cwd: Option<PathBuf>,
+        command: String,
+    ) -> Self {
+        let cwd = cwd.or_else(|| workspace.path.as_ref().cloned());
+        Self::new_in(cwd, Some(command), split_id, event_sink, proxy, config)
+    }
+
+    fn new_in(
+        cwd: Option<PathBuf>,
+        command: Option<String>,
+        split_id: WidgetId,
+        event_sink: ExtEventSink,
+        proxy: Arc<LapceProxy>,
+        config: &Config,
+    ) -> Self {
         let widget_id = WidgetId::next();
         let view_id = WidgetId::next();
         let term_id = TermId::next();
@@ -538,6 +565,9 @@ impl LapceTerminalData {
         let shell = config.terminal.shell.clone();
         std::thread::spawn(move || {
             local_proxy.new_terminal(term_id, cwd, shell, local_raw);
+            if let Some(command) = command {
+                local_proxy.terminal_write(term_id, &format!("{command}\n"));
+            }
         });
 
         Self {
@@ -0,0 +1,139 @@
+use druid::{ExtEventSink, Target, WidgetId};
+use lapce_rpc::buffer::BufferId;
+use lsp_types::CallHierarchyItem;
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    hierarchy::HierarchyTree,
+    proxy::LapceProxy,
+};
+
+/// lsp-types doesn't have the type hierarchy yet, but its items have the
+/// same fields as call hierarchy items
+pub type TypeHierarchyItem = CallHierarchyItem;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeHierarchyDirection {
+    /// The types that the item extends or implements
+    Supertypes,
+    /// The types that extend or implement the item
+    Subtypes,
+}
+
+impl TypeHierarchyDirection {
+    pub fn title(&self) -> &'static str {
+        match self {
+            TypeHierarchyDirection::Supertypes => "Supertypes",
+            TypeHierarchyDirection::Subtypes => "Subtypes",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TypeHierarchyData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    pub direction: TypeHierarchyDirection,
+    /// The buffer the hierarchy was prepared in, which decides the language
+    /// server that the types are requested from
+    pub buffer_id: BufferId,
+    /// Incremented for every new hierarchy, so that types that arrive for
+    /// an old one are ignored
+    pub request_id: usize,
+    pub tree: HierarchyTree,
+}
+
+impl TypeHierarchyData {
+    pub fn new() -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            direction: TypeHierarchyDirection::Supertypes,
+            buffer_id: BufferId(0),
+            request_id: 0,
+            tree: HierarchyTree::default(),
+        }
+    }
+
+    /// Replace the tree with the items from `prepareTypeHierarchy`, and
+    /// expand them
+    pub fn start(
+        &mut self,
+        direction: TypeHierarchyDirection,
+        buffer_id: BufferId,
+        items: Vec<TypeHierarchyItem>,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        self.request_id += 1;
+        self.direction = direction;
+        self.buffer_id = buffer_id;
+        self.tree = HierarchyTree::new(items);
+        for i in 0..self.tree.roots.len() {
+            self.toggle(&[i], proxy, event_sink.clone());
+        }
+    }
+
+    /// Expand or collapse the node, requesting its supertypes or subtypes
+    /// the first time it is expanded
+    pub fn toggle(
+        &mut self,
+        path: &[usize],
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        if let Some(item) = self.tree.toggle(path) {
+            self.request_types(path.to_vec(), item, proxy, event_sink);
+        }
+    }
+
+    fn request_types(
+        &self,
+        path: Vec<usize>,
+        item: TypeHierarchyItem,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let request_id = self.request_id;
+        let widget_id = self.widget_id;
+        let f = move |result: Result<Option<Vec<TypeHierarchyItem>>, _>| {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::TypeHierarchyTypes {
+                    request_id,
+                    path,
+                    items: result.ok().flatten().unwrap_or_default(),
+                },
+                Target::Widget(widget_id),
+            );
+        };
+        match self.direction {
+            TypeHierarchyDirection::Supertypes => {
+                proxy.type_hierarchy_supertypes(self.buffer_id, item, f);
+            }
+            TypeHierarchyDirection::Subtypes => {
+                proxy.type_hierarchy_subtypes(self.buffer_id, item, f);
+            }
+        }
+    }
+
+    /// Fill in the supertypes or subtypes of the node at `path`
+    pub fn receive_types(
+        &mut self,
+        request_id: usize,
+        path: &[usize],
+        items: Vec<TypeHierarchyItem>,
+    ) {
+        if request_id == self.request_id {
+            let children =
+                items.into_iter().map(|item| (item, Vec::new())).collect();
+            self.tree.set_children(path, children);
+        }
+    }
+}
+
+impl Default for TypeHierarchyData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,5 @@
 use crate::buffer::{get_mod_time, load_file, Buffer};
-use crate::lsp::LspCatalog;
+use crate::lsp::{FileOperation, LspCatalog};
 use crate::plugin::PluginCatalog;
 use crate::terminal::Terminal;
 use crate::watcher::{FileWatcher, Notify, WatchToken};
@@ -15,18 +15,21 @@ use grep_regex::RegexMatcherBuilder;
 use grep_searcher::sinks::UTF8;
 use grep_searcher::SearcherBuilder;
 use lapce_rpc::buffer::{BufferHeadResponse, BufferId, NewBufferResponse};
-use lapce_rpc::core::CoreNotification;
+use lapce_rpc::core::{CoreNotification, CoreRequest};
 use lapce_rpc::file::FileNodeItem;
-use lapce_rpc::proxy::{ProxyNotification, ProxyRequest, ReadDirResponse};
+use lapce_rpc::proxy::{
+    ProxyNotification, ProxyRequest, ReadDirResponse, ReadFileResponse,
+};
 use lapce_rpc::source_control::{DiffInfo, FileDiff};
 use lapce_rpc::terminal::TermId;
-use lapce_rpc::{self, Call, RequestId, RpcObject};
+use lapce_rpc::{self, Call, Callback, RequestId, RpcObject};
 use parking_lot::Mutex;
 use serde_json::json;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
@@ -51,6 +54,9 @@ pub struct Dispatcher {
     pub file_watcher: Arc<Mutex<Option<FileWatcher>>>,
     workspace_fs_change_handler: Arc<Mutex<Option<Sender<bool>>>>,
     last_diff: Arc<Mutex<DiffInfo>>,
+    /// The ids and response handlers of the requests sent to the core
+    core_request_id: Arc<AtomicU64>,
+    core_pending: Arc<Mutex<HashMap<RequestId, Box<dyn Callback>>>>,
 }
 
 impl Notify for Dispatcher {
@@ -73,6 +79,8 @@ impl Dispatcher {
             file_watcher: Arc::new(Mutex::new(None)),
             last_diff: Arc::new(Mutex::new(DiffInfo::default())),
             workspace_fs_change_handler: Arc::new(Mutex::new(None)),
+            core_request_id: Arc::new(AtomicU64::new(0)),
+            core_pending: Arc::new(Mutex::new(HashMap::new())),
         };
         *dispatcher.file_watcher.lock() = Some(FileWatcher::new(dispatcher.clone()));
         dispatcher.lsp.lock().dispatcher = Some(dispatcher.clone());
@@ -115,6 +123,12 @@ impl Dispatcher {
         for msg in receiver {
             let rpc: RpcObject = msg.into();
             if rpc.is_response() {
+                let id = rpc.get_id().unwrap();
+                let result = rpc.into_response().unwrap_or_else(|e| Err(json!(e)));
+                let handler = self.core_pending.lock().remove(&id);
+                if let Some(f) = handler {
+                    f.call(result);
+                }
             } else {
                 match rpc.into_rpc::<ProxyNotification, ProxyRequest>() {
                     Ok(Call::Request(id, request)) => {
@@ -181,6 +195,41 @@ impl Dispatcher {
         let _ = self.sender.send(resp);
     }
 
+    /// Make the file operation of the explorer, letting the language servers
+    /// update the workspace for it first, like the imports of a moved file
+    fn run_file_operation(
+        &self,
+        id: RequestId,
+        operation: FileOperation,
+        run: impl FnOnce() -> Result<()> + Send + 'static,
+    ) {
+        let local_dispatcher = self.clone();
+        thread::spawn(move || {
+            let clients = local_dispatcher.lsp.lock().clients();
+            for client in clients.iter() {
+                if let Some(edit) = client.will_operate_on_files(&operation) {
+                    // The edit is applied by the editor before the operation
+                    let (sender, receiver) = crossbeam_channel::bounded(1);
+                    local_dispatcher.send_core_request(
+                        CoreRequest::ApplyWorkspaceEdit { edit },
+                        move |_| {
+                            let _ = sender.send(());
+                        },
+                    );
+                    let _ = receiver.recv_timeout(Duration::from_secs(1));
+                }
+            }
+
+            let result = run();
+            if result.is_ok() {
+                for client in clients.iter() {
+                    client.did_operate_on_files(&operation);
+                }
+            }
+            local_dispatcher.respond(id, result.map(|_| json!({})));
+        });
+    }
+
     pub fn respond_rpc<T: serde::Serialize>(
         &self,
         id: RequestId,
@@ -205,6 +254,27 @@ impl Dispatcher {
             .send(serde_json::to_value(notification).unwrap());
     }
 
+    /// Send a request to the core, `f` is called with its response
+    pub fn send_core_request(
+        &self,
+        request: CoreRequest,
+        f: impl FnOnce(Result<Value, Value>) + Send + 'static,
+    ) {
+        let id = self.core_request_id.fetch_add(1, Ordering::Relaxed);
+        self.core_pending.lock().insert(id, Box::new(f));
+        let mut request = serde_json::to_value(request).unwrap();
+        request
+            .as_object_mut()
+            .unwrap()
+            .insert("id".to_string(), json!(id));
+        if self.sender.send(request).is_err() {
+            let handler = self.core_pending.lock().remove(&id);
+            if let Some(f) = handler {
+                f.call(Err(json!("io error")));
+            }
+        }
+    }
+
     pub fn send_notification(&self, method: &str, params: Value) {
         let _ = self.sender.send(json!({
             "method": method,
@@ -259,6 +329,7 @@ impl Dispatcher {
     }
 
     fn handle_workspace_fs_event(&self, event: notify::Event) {
+        self.lsp.lock().files_changed(&event);
         if let Some(workspace) = self.workspace.lock().clone() {
             let explorer_change = match &event.kind {
                 notify::EventKind::Create(_)
@@ -325,6 +396,21 @@ impl Dispatcher {
     fn handle_notification(&self, rpc: ProxyNotification) {
         use ProxyNotification::*;
         match rpc {
+            UpdateLspSettings { settings } => {
+                let buffers = self.buffers.lock();
+                let mut lsp = self.lsp.lock();
+                lsp.update_settings(settings);
+                // The servers that were added start for the open documents
+                for buffer in buffers.values() {
+                    lsp.start_configured_servers(buffer);
+                }
+            }
+            RestartLanguageServer { name } => {
+                self.lsp.lock().restart_server(&name);
+            }
+            CancelRequest { kind, request_id } => {
+                self.lsp.lock().cancel_request(kind, request_id);
+            }
             Initialize { workspace } => {
                 *self.workspace.lock() = Some(workspace.clone());
                 self.file_watcher.lock().as_mut().unwrap().watch(
@@ -354,6 +440,21 @@ impl Dispatcher {
                     self.lsp.lock().update(buffer, &content_change, buffer.rev);
                 }
             }
+            CloseBuffer { buffer_id } => {
+                if let Some(buffer) = self.buffers.lock().remove(&buffer_id) {
+                    let path = buffer.path.to_str().unwrap().to_string();
+                    let mut open_files = self.open_files.lock();
+                    if open_files.get(&path) == Some(&buffer_id) {
+                        open_files.remove(&path);
+                        self.file_watcher
+                            .lock()
+                            .as_mut()
+                            .unwrap()
+                            .unwatch(&buffer.path, OPEN_FILE_EVENT_TOKEN);
+                    }
+                    self.lsp.lock().close_buffer(&buffer);
+                }
+            }
             InstallPlugin { plugin } => {
                 let catalog = self.plugins.clone();
                 let dispatcher = self.clone();
@@ -556,6 +657,7 @@ impl Dispatcher {
                     .insert(path.to_str().unwrap().to_string(), buffer_id);
                 let buffer = Buffer::new(buffer_id, path);
                 let content = buffer.rope.to_string();
+                self.lsp.lock().start_configured_servers(&buffer);
                 self.buffers.lock().insert(buffer_id, buffer);
                 let resp = NewBufferResponse { content };
                 let _ = self.sender.send(json!({
@@ -609,12 +711,22 @@ impl Dispatcher {
                 self.lsp.lock().get_hover(id, request_id, buffer, position);
             }
             GetSignature {
+                request_id,
                 buffer_id,
                 position,
+                trigger,
+                active,
             } => {
                 let buffers = self.buffers.lock();
                 let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_signature(id, buffer, position);
+                self.lsp.lock().get_signature(
+                    id,
+                    request_id,
+                    buffer,
+                    position,
+                    trigger,
+                    active.map(|active| *active),
+                );
             }
             GetReferences {
                 buffer_id,
@@ -646,15 +758,42 @@ impl Dispatcher {
                     .lock()
                     .get_type_definition(id, request_id, buffer, position);
             }
-            GetInlayHints { buffer_id } => {
+            GetImplementation {
+                request_id,
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_implementation(id, request_id, buffer, position);
+            }
+            GetDeclaration {
+                request_id,
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_declaration(id, request_id, buffer, position);
+            }
+            GetInlayHints { buffer_id, range } => {
                 let buffers = self.buffers.lock();
                 let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_inlay_hints(id, buffer);
+                self.lsp.lock().get_inlay_hints(id, buffer, range);
             }
-            GetSemanticTokens { buffer_id } => {
+            ResolveInlayHint { buffer_id, hint } => {
                 let buffers = self.buffers.lock();
                 let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_semantic_tokens(id, buffer);
+                self.lsp.lock().resolve_inlay_hint(id, buffer, &hint);
+            }
+            GetSemanticTokens { buffer_id, range } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_semantic_tokens(id, buffer, range);
             }
             GetCodeActions {
                 buffer_id,
@@ -679,6 +818,121 @@ impl Dispatcher {
                 let buffer = buffers.get(&buffer_id).unwrap();
                 self.lsp.lock().get_document_formatting(id, buffer);
             }
+            GetDocumentRangeFormatting { buffer_id, range } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_document_range_formatting(id, buffer, range);
+            }
+            GetOnTypeFormatting {
+                buffer_id,
+                position,
+                ch,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_on_type_formatting(id, buffer, position, ch);
+            }
+            PrepareRename {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().prepare_rename(id, buffer, position);
+            }
+            Rename {
+                buffer_id,
+                position,
+                new_name,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().rename(id, buffer, position, new_name);
+            }
+            PrepareCallHierarchy {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().prepare_call_hierarchy(id, buffer, position);
+            }
+            CallHierarchyIncomingCalls { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .call_hierarchy_incoming_calls(id, buffer, item);
+            }
+            CallHierarchyOutgoingCalls { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .call_hierarchy_outgoing_calls(id, buffer, item);
+            }
+            PrepareTypeHierarchy {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().prepare_type_hierarchy(id, buffer, position);
+            }
+            TypeHierarchySupertypes { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().type_hierarchy_supertypes(id, buffer, item);
+            }
+            TypeHierarchySubtypes { buffer_id, item } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().type_hierarchy_subtypes(id, buffer, item);
+            }
+            GetDocumentHighlight {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_document_highlight(id, buffer, position);
+            }
+            GetLinkedEditingRange {
+                buffer_id,
+                position,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp
+                    .lock()
+                    .get_linked_editing_range(id, buffer, position);
+            }
+            GetCodeLens { buffer_id } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().get_code_lens(id, buffer);
+            }
+            CodeLensResolve {
+                buffer_id,
+                code_lens,
+            } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().code_lens_resolve(id, buffer, code_lens);
+            }
+            ExecuteCommand { buffer_id, command } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffers.get(&buffer_id).unwrap();
+                self.lsp.lock().execute_command(id, buffer, command);
+            }
+            GetLanguageServers {} => {
+                let names = self.lsp.lock().server_names();
+                self.respond(id, Ok(json!(names)));
+            }
             ReadDir { path } => {
                 let local_dispatcher = self.clone();
                 thread::spawn(move || {
@@ -711,6 +965,15 @@ impl Dispatcher {
                     local_dispatcher.respond_rpc(id, result);
                 });
             }
+            ReadFile { path } => {
+                let local_dispatcher = self.clone();
+                thread::spawn(move || {
+                    let result = fs::read_to_string(path)
+                        .map(|content| ReadFileResponse { content })
+                        .map_err(|e| anyhow!(e));
+                    local_dispatcher.respond_rpc(id, result);
+                });
+            }
             GetFiles { .. } => {
                 if let Some(workspace) = self.workspace.lock().clone() {
                     let local_dispatcher = self.clone();
@@ -759,25 +1022,37 @@ impl Dispatcher {
                 // Create the file, specifically choosing to error if it already exists
                 // We also throw away the file object because we only want to create it,
                 // and return any errors that occur
-                let resp = std::fs::OpenOptions::new()
-                    .write(true)
-                    .create_new(true)
-                    .open(path)
-                    .map(|_| json!({}))
-                    .map_err(anyhow::Error::from);
-                self.respond(id, resp);
+                let operation = FileOperation::Create {
+                    path: path.clone(),
+                    is_dir: false,
+                };
+                self.run_file_operation(id, operation, move || {
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(path)?;
+                    Ok(())
+                });
             }
             CreateDirectory { path } => {
-                let resp = std::fs::create_dir(path)
-                    .map(|_| json!({}))
-                    .map_err(anyhow::Error::from);
-                self.respond(id, resp);
+                let operation = FileOperation::Create {
+                    path: path.clone(),
+                    is_dir: true,
+                };
+                self.run_file_operation(id, operation, move || {
+                    std::fs::create_dir(path)?;
+                    Ok(())
+                });
             }
             TrashPath { path } => {
-                let resp = trash::delete(path)
-                    .map(|_| json!({}))
-                    .map_err(anyhow::Error::from);
-                self.respond(id, resp);
+                let operation = FileOperation::Delete {
+                    is_dir: path.is_dir(),
+                    path: path.clone(),
+                };
+                self.run_file_operation(id, operation, move || {
+                    trash::delete(path)?;
+                    Ok(())
+                });
             }
             RenamePath { from, to } => {
                 // We first check if the destination already exists, because rename can overwrite it
@@ -785,10 +1060,15 @@ impl Dispatcher {
                 if to.exists() {
                     self.respond(id, Err(anyhow!("{:?} already exists", to)));
                 } else {
-                    let resp = std::fs::rename(from, to)
-                        .map(|_| json!({}))
-                        .map_err(anyhow::Error::from);
-                    self.respond(id, resp);
+                    let operation = FileOperation::Rename {
+                        from: from.clone(),
+                        to: to.clone(),
+                        is_dir: from.is_dir(),
+                    };
+                    self.run_file_operation(id, operation, move || {
+                        std::fs::rename(from, to)?;
+                        Ok(())
+                    });
                 }
             }
             GlobalSearch { pattern } => {
@@ -1,17 +1,17 @@
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
     process::{self, Child, ChildStderr, ChildStdout, Command, Stdio},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::channel,
         Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Result};
@@ -19,20 +19,86 @@ use jsonrpc_lite::{Id, JsonRpc, Params};
 use lapce_core::encoding::offset_utf16_to_utf8;
 use lapce_rpc::{
     buffer::BufferId,
+    core::{
+        CoreNotification, CoreRequest, LanguageServerStatus, LspTraceDirection,
+        LspTraceEntry, SignatureTriggers,
+    },
+    proxy::CancellableRequest,
     style::{LineStyle, SemanticStyles, Style},
     RequestId,
 };
 use log::error;
-use lsp_types::{request::GotoTypeDefinitionParams, *};
+use lsp_types::{
+    request::{
+        GotoDeclarationParams, GotoImplementationParams, GotoTypeDefinitionParams,
+    },
+    *,
+};
 use parking_lot::Mutex;
+use serde::Deserialize;
 use serde_json::{json, to_value, Value};
 
 use crate::{buffer::Buffer, dispatch::Dispatcher};
 
 pub type Callback = Box<dyn Callable>;
+
+/// The id of the next server that is started
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+/// The diagnostics of the documents, keyed by the id of the server that
+/// published them
+type ServerDiagnostics = Arc<Mutex<HashMap<Url, HashMap<u64, Vec<Diagnostic>>>>>;
+
+/// The error code of a response to a request that was cancelled
+const REQUEST_CANCELLED: i64 = -32800;
+/// The error code of a response to a request whose result is stale because
+/// the document changed
+const CONTENT_MODIFIED: i64 = -32801;
+/// The error code of a response to a request that the server cancelled,
+/// which it may want to be sent again
+const SERVER_CANCELLED: i64 = -32802;
+
+/// How long the changes of the watched files are collected before they're
+/// sent together
+const WATCHED_FILES_DELAY: Duration = Duration::from_millis(300);
+/// How long the documents can go without a change before their diagnostics
+/// are pulled
+const DIAGNOSTICS_PULL_DELAY: Duration = Duration::from_millis(500);
+/// How long a server can take to answer a `workspace/will*Files` request
+/// before the explorer goes on without its edit
+const FILE_OPERATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Documents with more lines than this only have the semantic tokens of the
+/// range that the editor shows requested, when the server can do that
+const LARGE_FILE_LINES: usize = 10000;
+
+/// The number of times in a row that a server is restarted after it crashed
+const MAX_RESTARTS: u32 = 5;
+/// A server that ran for this long before it crashed is restarted as if it
+/// never crashed before
+const CRASH_RESET: Duration = Duration::from_secs(60);
+
+/// The number of traced requests that wait for their response, beyond which
+/// the oldest one is forgotten, for a server that never answers some
+const MAX_TRACED_REQUESTS: usize = 1000;
+
 const HEADER_CONTENT_LENGTH: &str = "content-length";
 const HEADER_CONTENT_TYPE: &str = "content-type";
 
+/// The error of a request that the server cancelled, with whether it wants
+/// the request to be sent again
+#[derive(Debug)]
+struct ServerCancelled {
+    retrigger: bool,
+}
+
+impl std::fmt::Display for ServerCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the server cancelled the request")
+    }
+}
+
+impl std::error::Error for ServerCancelled {}
+
 pub trait Callable: Send {
     fn call(self: Box<Self>, client: &LspClient, result: Result<Value>);
 }
@@ -45,7 +111,19 @@ impl<F: Send + FnOnce(&LspClient, Result<Value>)> Callable for F {
 
 pub struct LspCatalog {
     pub dispatcher: Option<Dispatcher>,
-    clients: HashMap<String, Arc<LspClient>>,
+    /// The servers in the order that they were started. A document can be
+    /// handled by several of them, picked by their document selectors, and
+    /// the requests with a single result go to the first one that can answer.
+    clients: Vec<Arc<LspClient>>,
+    /// The `[lsp]` settings of the editor, shared with the clients to answer
+    /// `workspace/configuration` requests
+    settings: Arc<Mutex<Value>>,
+    /// Shared with the clients, so that a document gets the diagnostics of
+    /// all its servers together
+    diagnostics: ServerDiagnostics,
+    /// The servers of the `[lsp.servers.<name>]` settings, keyed by their
+    /// name, which are started once a document that they handle is opened
+    configured_servers: HashMap<String, ServerConfig>,
 }
 
 pub struct LspState {
@@ -57,8 +135,87 @@ pub struct LspState {
     pub opened_documents: HashMap<BufferId, Url>,
     pub is_initialized: bool,
     pub did_save_capabilities: Vec<DidSaveCapability>,
+    /// lsp-types doesn't have the type hierarchy in `ServerCapabilities` yet,
+    /// so it's read from the initialize result directly
+    pub type_hierarchy_provider: bool,
+    /// lsp-types doesn't have the pull diagnostics of LSP 3.17 yet either,
+    /// this is the `diagnosticProvider` of a server that is asked for the
+    /// diagnostics instead of publishing them
+    diagnostic_provider: Option<DiagnosticProvider>,
+    /// The `resultId` of the last diagnostic report of each document, which
+    /// is sent with the next request so that the server can answer that the
+    /// diagnostics are unchanged
+    diagnostic_result_ids: HashMap<Url, String>,
+    /// The last semantic tokens of the whole of each document, which the
+    /// edits of a `semanticTokens/full/delta` result are applied to
+    semantic_tokens: HashMap<BufferId, SemanticTokensCache>,
+    status: LanguageServerStatus,
+    /// When the process was started
+    started: Instant,
+    /// The number of times that it crashed in a row, which doubles the wait
+    /// before it's restarted each time
+    crashes: u32,
+    /// The requests that are cancelled once they're stale, with the
+    /// `request_id` that the editor gave them and their id on the server
+    in_flight: HashMap<RequestKey, (usize, u64)>,
+    /// The `workspace/didChangeWatchedFiles` registrations of the server
+    watched_files: Vec<WatchedFiles>,
+    /// The changes of the watched files that are waiting to be sent
+    changed_files: Vec<FileEvent>,
+    /// The documents whose diagnostics are waiting to be pulled
+    diagnostic_pulls: HashSet<BufferId>,
+    /// When a document whose diagnostics are pulled last changed, which is
+    /// only set while the pulls are waiting to be sent
+    last_diagnostic_pull: Option<Instant>,
+    /// The method and the time of the traced requests that weren't answered
+    /// yet, keyed by the direction that they were sent in and their id
+    traced_requests: HashMap<(LspTraceDirection, String), (String, Instant)>,
+    /// When a watched file last changed, which is only set while the changes
+    /// are waiting to be sent
+    last_file_change: Option<Instant>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct DiagnosticProvider {
+    identifier: Option<String>,
+    /// Whether a change of a document can change the diagnostics of the
+    /// other documents
+    inter_file_dependencies: bool,
+    workspace_diagnostics: bool,
+}
+
+struct SemanticTokensCache {
+    /// The revision of the document that the tokens are of
+    rev: u64,
+    result_id: Option<String>,
+    data: Vec<SemanticToken>,
+}
+
+/// What a request is for. A newer request for the same thing makes the older
+/// one stale, so that the server can stop working on it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum RequestKey {
+    Completion,
+    Hover,
+    SignatureHelp,
+    InlayHints(BufferId),
+    SemanticTokens(BufferId),
+    Diagnostics(BufferId),
+    WorkspaceDiagnostics,
+}
+
+impl From<CancellableRequest> for RequestKey {
+    fn from(kind: CancellableRequest) -> Self {
+        match kind {
+            CancellableRequest::Completion => RequestKey::Completion,
+            CancellableRequest::Hover => RequestKey::Hover,
+            CancellableRequest::SignatureHelp => RequestKey::SignatureHelp,
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct DocumentFilter {
     /// The document must have this language id, if it exists
     pub language_id: Option<String>,
@@ -81,7 +238,392 @@ impl DocumentFilter {
                 .map(|x| globset::Glob::compile_matcher(&x)),
         }
     }
+
+    /// Whether a document with the language id and the path, relative to
+    /// the workspace, passes the filter
+    pub fn matches(&self, language_id: &str, path: &Path) -> bool {
+        if let Some(filter_language_id) = &self.language_id {
+            if filter_language_id != language_id {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(path) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A request that is sent to several servers, which is responded to once all
+/// of them answered, with their results merged
+struct MergedRequest {
+    id: RequestId,
+    /// The ids of the servers that were asked, in their order in the catalog
+    servers: Vec<u64>,
+    remaining: usize,
+    /// The results with the id of the server that sent them
+    results: Vec<(u64, Value)>,
+    error: Option<anyhow::Error>,
+    merge: fn(Vec<(u64, Value)>) -> Value,
+}
+
+impl MergedRequest {
+    fn new(
+        id: RequestId,
+        clients: &[&Arc<LspClient>],
+        merge: fn(Vec<(u64, Value)>) -> Value,
+    ) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            id,
+            servers: clients.iter().map(|client| client.id).collect(),
+            remaining: clients.len(),
+            results: Vec::new(),
+            error: None,
+            merge,
+        }))
+    }
+
+    fn receive(&mut self, client: &LspClient, result: Result<Value>) {
+        match result {
+            Ok(value) => self.results.push((client.id, value)),
+            Err(e) => self.error = Some(e),
+        }
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            return;
+        }
+
+        // The results are in the order of the servers in the catalog, where
+        // a restarted server keeps its place
+        let mut results = std::mem::take(&mut self.results);
+        results.sort_by_key(|(server, _)| {
+            self.servers.iter().position(|id| id == server)
+        });
+        let result = match self.error.take() {
+            Some(e) if results.is_empty() => Err(e),
+            _ => Ok((self.merge)(results)),
+        };
+        client.dispatcher.respond(self.id, result);
+    }
+}
+
+/// How a server is started, and the documents that it handles
+#[derive(Clone)]
+struct LspServer {
+    /// The name of the server in the messages it shows
+    name: String,
+    exec_path: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    /// The folder that the server runs in and is initialized with, which is
+    /// the workspace if there is none
+    root: Option<PathBuf>,
+    document_selector: Vec<DocumentFilter>,
+    /// Whether it's one of the `[lsp.servers]` rather than started by a
+    /// plugin
+    from_settings: bool,
+}
+
+impl LspServer {
+    /// Whether the server handles the document with the language id and path
+    fn handles(
+        &self,
+        workspace: Option<&Path>,
+        language_id: &str,
+        path: &Path,
+    ) -> bool {
+        // The patterns are relative to the workspace
+        let path = workspace
+            .and_then(|workspace| path.strip_prefix(workspace).ok())
+            .unwrap_or(path);
+        self.document_selector
+            .iter()
+            .any(|filter| filter.matches(language_id, path))
+    }
+}
+
+/// A server from an `[lsp.servers.<name>]` table of the settings, for the
+/// servers that don't have a plugin
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ServerConfig {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    /// The language ids of the documents that it handles
+    #[serde(default)]
+    languages: Vec<String>,
+    /// The file extensions of the documents that it handles, for the
+    /// languages that have no language id
+    #[serde(default)]
+    extensions: Vec<String>,
+    /// The files that are at the root of a project, like `Cargo.toml`. The
+    /// server runs in the nearest folder of the first document that has one.
+    #[serde(default)]
+    root_markers: Vec<String>,
+    initialization_options: Option<Value>,
+}
+
+impl ServerConfig {
+    /// The servers of the `servers` table of the `[lsp]` settings, leaving
+    /// out the ones that can't be read
+    fn from_settings(settings: &Value) -> HashMap<String, ServerConfig> {
+        let servers = match settings.get("servers").and_then(|s| s.as_object()) {
+            Some(servers) => servers,
+            None => return HashMap::new(),
+        };
+        servers
+            .iter()
+            .filter_map(|(name, config)| {
+                match serde_json::from_value(config.clone()) {
+                    Ok(config) => Some((name.to_string(), config)),
+                    Err(e) => {
+                        log::warn!(
+                            "invalid settings of language server {name}: {e}"
+                        );
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// The nearest folder of the document that has one of the root markers
+    fn root(&self, path: &Path) -> Option<PathBuf> {
+        path.ancestors()
+            .skip(1)
+            .find(|dir| {
+                self.root_markers
+                    .iter()
+                    .any(|marker| dir.join(marker).exists())
+            })
+            .map(|dir| dir.to_path_buf())
+    }
+
+    fn server(&self, name: &str, root: Option<PathBuf>) -> LspServer {
+        let languages = self.languages.iter().map(|language_id| DocumentFilter {
+            language_id: Some(language_id.to_string()),
+            pattern: None,
+        });
+        let extensions = self.extensions.iter().filter_map(|extension| {
+            let glob = format!("**/*.{}", extension.trim_start_matches('.'));
+            globset::Glob::new(&glob).ok().map(|glob| DocumentFilter {
+                language_id: None,
+                pattern: Some(glob.compile_matcher()),
+            })
+        });
+        LspServer {
+            name: name.to_string(),
+            exec_path: self.command.clone(),
+            args: self.args.clone(),
+            env: self.env.clone(),
+            root,
+            document_selector: languages.chain(extensions).collect(),
+            from_settings: true,
+        }
+    }
+}
+
+/// The globs of a `workspace/didChangeWatchedFiles` registration
+struct WatchedFiles {
+    /// The id of the registration, to unregister it
+    id: String,
+    watchers: Vec<(globset::GlobMatcher, WatchKind)>,
+}
+
+impl WatchedFiles {
+    fn new(id: String, options: DidChangeWatchedFilesRegistrationOptions) -> Self {
+        let watchers = options
+            .watchers
+            .into_iter()
+            .filter_map(|watcher| {
+                let glob = globset::Glob::new(&watcher.glob_pattern).ok()?;
+                // All the changes are watched if the kind is left out
+                let kind = watcher.kind.unwrap_or_else(WatchKind::all);
+                Some((glob.compile_matcher(), kind))
+            })
+            .collect();
+        Self { id, watchers }
+    }
+
+    fn matches(&self, path: &Path, typ: FileChangeType) -> bool {
+        let kind = match typ {
+            FileChangeType::CREATED => WatchKind::Create,
+            FileChangeType::CHANGED => WatchKind::Change,
+            _ => WatchKind::Delete,
+        };
+        self.watchers
+            .iter()
+            .any(|(glob, watch)| watch.contains(kind) && glob.is_match(path))
+    }
+}
+
+/// A change to the files of the workspace that is made by the explorer,
+/// which the servers can follow up on, like fixing the imports of a moved
+/// file
+pub enum FileOperation {
+    Create {
+        path: PathBuf,
+        is_dir: bool,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        is_dir: bool,
+    },
+    Delete {
+        path: PathBuf,
+        is_dir: bool,
+    },
+}
+
+impl FileOperation {
+    /// The path that is matched against the filters of the servers
+    fn path(&self) -> &Path {
+        match self {
+            FileOperation::Create { path, .. } => path,
+            FileOperation::Rename { from, .. } => from,
+            FileOperation::Delete { path, .. } => path,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        match self {
+            FileOperation::Create { is_dir, .. }
+            | FileOperation::Rename { is_dir, .. }
+            | FileOperation::Delete { is_dir, .. } => *is_dir,
+        }
+    }
+
+    /// The method of the request that is sent before the operation, or of
+    /// the notification that is sent after it
+    fn method(&self, will: bool) -> &'static str {
+        match (self, will) {
+            (FileOperation::Create { .. }, true) => "workspace/willCreateFiles",
+            (FileOperation::Create { .. }, false) => "workspace/didCreateFiles",
+            (FileOperation::Rename { .. }, true) => "workspace/willRenameFiles",
+            (FileOperation::Rename { .. }, false) => "workspace/didRenameFiles",
+            (FileOperation::Delete { .. }, true) => "workspace/willDeleteFiles",
+            (FileOperation::Delete { .. }, false) => "workspace/didDeleteFiles",
+        }
+    }
+
+    fn params(&self) -> Params {
+        let uri = |path: &Path| {
+            Url::from_file_path(path)
+                .map(|uri| uri.to_string())
+                .unwrap_or_default()
+        };
+        let params = match self {
+            FileOperation::Create { path, .. } => to_value(CreateFilesParams {
+                files: vec![FileCreate { uri: uri(path) }],
+            }),
+            FileOperation::Rename { from, to, .. } => to_value(RenameFilesParams {
+                files: vec![FileRename {
+                    old_uri: uri(from),
+                    new_uri: uri(to),
+                }],
+            }),
+            FileOperation::Delete { path, .. } => to_value(DeleteFilesParams {
+                files: vec![FileDelete { uri: uri(path) }],
+            }),
+        };
+        Params::from(params.unwrap())
+    }
+
+    /// The registration of the server for the request before the operation,
+    /// or for the notification after it
+    fn options<'a>(
+        &self,
+        operations: &'a WorkspaceFileOperationsServerCapabilities,
+        will: bool,
+    ) -> Option<&'a FileOperationRegistrationOptions> {
+        match (self, will) {
+            (FileOperation::Create { .. }, true) => operations.will_create.as_ref(),
+            (FileOperation::Create { .. }, false) => operations.did_create.as_ref(),
+            (FileOperation::Rename { .. }, true) => operations.will_rename.as_ref(),
+            (FileOperation::Rename { .. }, false) => operations.did_rename.as_ref(),
+            (FileOperation::Delete { .. }, true) => operations.will_delete.as_ref(),
+            (FileOperation::Delete { .. }, false) => operations.did_delete.as_ref(),
+        }
+    }
+}
+
+/// Whether the file or the folder at the path is one that the filter of a
+/// file operation registration is for
+fn file_operation_filter_matches(
+    filter: &FileOperationFilter,
+    workspace: Option<&Path>,
+    path: &Path,
+    is_dir: bool,
+) -> bool {
+    if filter.scheme.as_deref().unwrap_or("file") != "file" {
+        return false;
+    }
+    let pattern = &filter.pattern;
+    match pattern.matches {
+        Some(FileOperationPatternKind::File) if is_dir => return false,
+        Some(FileOperationPatternKind::Folder) if !is_dir => return false,
+        _ => {}
+    }
+    let ignore_case = pattern
+        .options
+        .as_ref()
+        .and_then(|options| options.ignore_case)
+        .unwrap_or(false);
+    // The patterns are relative to the workspace, like the document filters
+    let path = workspace
+        .and_then(|workspace| path.strip_prefix(workspace).ok())
+        .unwrap_or(path);
+    globset::GlobBuilder::new(&pattern.glob)
+        .case_insensitive(ignore_case)
+        .build()
+        .map(|glob| glob.compile_matcher().is_match(path))
+        .unwrap_or(false)
+}
+
+/// The changes of the files in an event of the file watcher
+fn file_changes(event: &notify::Event) -> Vec<(PathBuf, FileChangeType)> {
+    use notify::event::{EventKind, ModifyKind, RenameMode};
+
+    let typ = match event.kind {
+        EventKind::Create(_) => FileChangeType::CREATED,
+        EventKind::Remove(_) => FileChangeType::DELETED,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            // The paths are the old one and the new one
+            return event
+                .paths
+                .iter()
+                .cloned()
+                .zip([FileChangeType::DELETED, FileChangeType::CREATED])
+                .collect();
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            // Either side of a rename, which is told by whether the path is
+            // still there
+            return event
+                .paths
+                .iter()
+                .map(|path| {
+                    let typ = if path.exists() {
+                        FileChangeType::CREATED
+                    } else {
+                        FileChangeType::DELETED
+                    };
+                    (path.clone(), typ)
+                })
+                .collect();
+        }
+        EventKind::Modify(_) => FileChangeType::CHANGED,
+        _ => return Vec::new(),
+    };
+    event.paths.iter().map(|path| (path.clone(), typ)).collect()
 }
+
 pub struct DidSaveCapability {
     /// A filter on what documents this applies to
     filter: DocumentFilter,
@@ -91,9 +633,14 @@ pub struct DidSaveCapability {
 
 #[derive(Clone)]
 pub struct LspClient {
-    exec_path: String,
-    args: Vec<String>,
+    /// Unique among the servers, so that the results of a server can be told
+    /// apart from the others' when they are merged
+    id: u64,
+    language_id: String,
+    server: LspServer,
     options: Option<Value>,
+    settings: Arc<Mutex<Value>>,
+    diagnostics: ServerDiagnostics,
     state: Arc<Mutex<LspState>>,
     dispatcher: Dispatcher,
     active: Arc<AtomicBool>,
@@ -103,25 +650,150 @@ impl LspCatalog {
     pub fn new() -> LspCatalog {
         LspCatalog {
             dispatcher: None,
-            clients: HashMap::new(),
+            clients: Vec::new(),
+            settings: Arc::new(Mutex::new(json!({}))),
+            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            configured_servers: HashMap::new(),
+        }
+    }
+
+    /// The servers that handle the document
+    fn buffer_clients<'a>(
+        &'a self,
+        buffer: &'a Buffer,
+    ) -> impl Iterator<Item = &'a Arc<LspClient>> + 'a {
+        self.clients
+            .iter()
+            .filter(move |client| client.handles(&buffer.language_id, &buffer.path))
+    }
+
+    /// The initialized servers of the document that have the capability
+    fn capable_clients(
+        &self,
+        buffer: &Buffer,
+        is_capable: impl Fn(&LspState) -> bool,
+    ) -> Vec<&Arc<LspClient>> {
+        self.buffer_clients(buffer)
+            .filter(|client| {
+                let state = client.state.lock();
+                state.is_initialized && is_capable(&state)
+            })
+            .collect()
+    }
+
+    /// The first initialized server of the document that has the capability
+    fn capable_client(
+        &self,
+        buffer: &Buffer,
+        is_capable: impl Fn(&LspState) -> bool,
+    ) -> Option<&Arc<LspClient>> {
+        self.capable_clients(buffer, is_capable).into_iter().next()
+    }
+
+    /// The server that formats the document, which is the one named for its
+    /// language in `[lsp.formatter]` if it can, or else the first that can
+    fn formatting_client(
+        &self,
+        buffer: &Buffer,
+        is_capable: impl Fn(&LspState) -> bool,
+    ) -> Option<&Arc<LspClient>> {
+        let clients = self.capable_clients(buffer, is_capable);
+        let preferred = self
+            .settings
+            .lock()
+            .get("formatter")
+            .and_then(|formatter| formatter.get(&buffer.language_id))
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_string());
+        preferred
+            .and_then(|name| {
+                clients
+                    .iter()
+                    .find(|client| client.server.name == name)
+                    .copied()
+            })
+            .or_else(|| clients.first().copied())
+    }
+
+    /// Replace the `[lsp]` settings, and tell the servers if they changed
+    pub fn update_settings(&mut self, settings: Value) {
+        let trace_changed = {
+            let mut current = self.settings.lock();
+            if *current == settings {
+                return;
+            }
+            let trace_changed = current.get("trace") != settings.get("trace");
+            *current = settings.clone();
+            trace_changed
+        };
+
+        // The servers whose settings changed are started again with the new
+        // ones, or stopped if they were removed
+        let configured_servers = ServerConfig::from_settings(&settings);
+        let changed: Vec<String> = self
+            .configured_servers
+            .keys()
+            .filter(|name| {
+                self.configured_servers.get(*name) != configured_servers.get(*name)
+            })
+            .cloned()
+            .collect();
+        self.configured_servers = configured_servers;
+        for name in changed {
+            self.restart_server(&name);
+        }
+
+        for client in self.clients.iter() {
+            if client.state.lock().is_initialized {
+                client.send_did_change_configuration(&settings);
+                if trace_changed {
+                    client.send_set_trace();
+                }
+            }
+        }
+    }
+
+    /// Tell the servers about the files that changed in the workspace, if
+    /// they watch them
+    pub fn files_changed(&self, event: &notify::Event) {
+        let changes = file_changes(event);
+        if changes.is_empty() {
+            return;
         }
+        for client in self.clients.iter() {
+            client.files_changed(&changes);
+        }
+    }
+
+    /// The servers, for the file operations of the explorer, which wait on
+    /// them without holding the catalog
+    pub fn clients(&self) -> Vec<Arc<LspClient>> {
+        self.clients.clone()
     }
 
     pub fn stop(&mut self) {
-        for (_, client) in self.clients.iter() {
+        for client in self.clients.iter() {
             client.stop();
         }
         self.clients.clear();
         self.dispatcher.take();
     }
 
+    /// Stop the servers that were started for the language
     pub fn stop_language_lsp(&mut self, lang: &String) {
-        if let Some(lsp) = self.clients.get(lang) {
-            lsp.stop();
-        }
+        self.clients.retain(|client| {
+            if &client.language_id == lang {
+                client.stop();
+                client.clear_diagnostics();
+                false
+            } else {
+                true
+            }
+        });
     }
 
-    pub fn start_server(
+    /// Start a server for a plugin
+    pub fn start_plugin_server(
         &mut self,
         exec_path: &str,
         language_id: &str,
@@ -130,14 +802,139 @@ impl LspCatalog {
         let args = self
             .get_plugin_binary_args(options.clone())
             .unwrap_or_default();
-        let client = LspClient::new(
+        let name = Path::new(exec_path)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(exec_path)
+            .to_string();
+        let server = LspServer {
+            name,
+            exec_path: exec_path.to_string(),
+            args,
+            env: HashMap::new(),
+            root: None,
+            document_selector: LspClient::document_selector(
+                language_id,
+                options.as_ref(),
+            ),
+            from_settings: false,
+        };
+        self.start_server(language_id, server, options);
+    }
+
+    /// Start the servers of the `[lsp.servers]` settings that handle the
+    /// document, if they aren't running yet
+    pub fn start_configured_servers(&mut self, buffer: &Buffer) {
+        let workspace = match self.dispatcher.as_ref() {
+            Some(dispatcher) => dispatcher.workspace.lock().clone(),
+            None => return,
+        };
+        let mut servers: Vec<(LspServer, Option<Value>)> = self
+            .configured_servers
+            .iter()
+            .filter(|(name, _)| {
+                !self
+                    .clients
+                    .iter()
+                    .any(|client| &&client.server.name == name)
+            })
+            .map(|(name, config)| {
+                let server = config.server(name, config.root(&buffer.path));
+                (server, config.initialization_options.clone())
+            })
+            .filter(|(server, _)| {
+                server.handles(
+                    workspace.as_deref(),
+                    &buffer.language_id,
+                    &buffer.path,
+                )
+            })
+            .collect();
+        servers.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        for (server, options) in servers {
+            let language_id = server.name.clone();
+            self.start_server(&language_id, server, options);
+        }
+    }
+
+    fn start_server(
+        &mut self,
+        language_id: &str,
+        server: LspServer,
+        options: Option<Value>,
+    ) {
+        if let Some(client) = self.new_client(language_id, server, options) {
+            self.clients.push(client);
+        }
+    }
+
+    fn new_client(
+        &self,
+        language_id: &str,
+        server: LspServer,
+        options: Option<Value>,
+    ) -> Option<Arc<LspClient>> {
+        let dispatcher = self.dispatcher.clone()?;
+        let name = server.name.clone();
+        match LspClient::new(
             language_id.to_string(),
-            exec_path,
+            server,
             options,
-            args,
-            self.dispatcher.clone().unwrap(),
-        );
-        self.clients.insert(language_id.to_string(), client);
+            self.settings.clone(),
+            self.diagnostics.clone(),
+            dispatcher.clone(),
+        ) {
+            Ok(client) => Some(client),
+            Err(e) => {
+                show_start_error(&dispatcher, &name, &e);
+                None
+            }
+        }
+    }
+
+    /// Start the server again, with its current settings if it's one of the
+    /// `[lsp.servers]`, which stops it if it was removed from them
+    pub fn restart_server(&mut self, name: &str) {
+        let index = match self
+            .clients
+            .iter()
+            .position(|client| client.server.name == name)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let client = self.clients.remove(index);
+        client.stop();
+        client.clear_diagnostics();
+
+        let (server, options) = match self.configured_servers.get(name) {
+            Some(config) => (
+                config.server(name, client.server.root.clone()),
+                config.initialization_options.clone(),
+            ),
+            None if client.server.from_settings => return,
+            None => (client.server.clone(), client.options.clone()),
+        };
+        // It keeps its place, so that it's still asked before the others
+        if let Some(client) = self.new_client(&client.language_id, server, options) {
+            self.clients.insert(index, client);
+        }
+    }
+
+    /// Cancel the request of the editor on the servers, if they are still
+    /// working on it
+    pub fn cancel_request(&self, kind: CancellableRequest, request_id: usize) {
+        for client in self.clients.iter() {
+            client.cancel_request(kind.into(), request_id);
+        }
+    }
+
+    /// The names of the running servers
+    pub fn server_names(&self) -> Vec<String> {
+        self.clients
+            .iter()
+            .map(|client| client.server.name.clone())
+            .collect()
     }
 
     fn get_plugin_binary_args(
@@ -171,26 +968,30 @@ impl LspCatalog {
         language_id: &str,
         text: String,
     ) {
-        if let Some(client) = self.clients.get(language_id) {
+        for client in self.clients.iter() {
+            if !client.state.lock().is_initialized
+                || !client.handles(language_id, Path::new(path))
             {
-                let state = client.state.lock();
-                if !state.is_initialized {
-                    return;
-                }
+                continue;
             }
 
             let document_uri = Url::from_file_path(path).unwrap();
-            client.send_did_open(buffer_id, document_uri, language_id, text);
+            // The revision that a new buffer starts at
+            let version = if text.is_empty() { 0 } else { 1 };
+            client.send_did_open(
+                buffer_id,
+                document_uri,
+                language_id,
+                version,
+                text.clone(),
+            );
         }
     }
 
     pub fn save_buffer(&self, buffer: &Buffer, workspace_path: &Path) {
-        for (client_language_id, client) in self.clients.iter() {
-            {
-                let state = client.state.lock();
-                if !state.is_initialized {
-                    return;
-                }
+        for client in self.clients.iter() {
+            if !client.state.lock().is_initialized {
+                continue;
             }
 
             // Get rid of the workspace path prefix so that it can be used with the filters
@@ -199,7 +1000,8 @@ impl LspCatalog {
                 .strip_prefix(workspace_path)
                 .unwrap_or(&buffer.path);
 
-            let mut passed_filter = client_language_id == &buffer.language_id;
+            let mut passed_filter =
+                client.handles(&buffer.language_id, &buffer.path);
             let mut include_text = false;
             if !passed_filter {
                 let lsp_state = client.state.lock();
@@ -208,16 +1010,8 @@ impl LspCatalog {
                 // can overwrite old ones?
                 // Find the first capability that wants this file, if any.
                 for cap in &lsp_state.did_save_capabilities {
-                    if let Some(language_id) = &cap.filter.language_id {
-                        if language_id != &buffer.language_id {
-                            continue;
-                        }
-                    }
-
-                    if let Some(pattern) = &cap.filter.pattern {
-                        if !pattern.is_match(buffer_path) {
-                            continue;
-                        }
+                    if !cap.filter.matches(&buffer.language_id, buffer_path) {
+                        continue;
                     }
 
                     passed_filter = true;
@@ -241,94 +1035,111 @@ impl LspCatalog {
         }
     }
 
-    pub fn get_semantic_tokens(&self, id: RequestId, buffer: &Buffer) {
-        let buffer = buffer.clone();
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
+    /// Get the semantic tokens of the whole document, or only of the range
+    /// that the editor shows when the document is large or the server can't
+    /// do the whole document
+    pub fn get_semantic_tokens(&self, id: RequestId, buffer: &Buffer, range: Range) {
+        let client = match self.capable_client(buffer, |state| {
+            semantic_tokens_options(state)
+                .map(|opts| semantic_tokens_full(opts) || opts.range == Some(true))
+                .unwrap_or(false)
+        }) {
+            Some(client) => client,
+            None => return,
+        };
 
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.semantic_tokens_provider.as_ref())
-                    .map(|prov| match prov {
-                        SemanticTokensServerCapabilities::SemanticTokensOptions(opts) => opts,
-                        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(reg) => &reg.semantic_tokens_options,
-                    }).map(|opts| {
-                        // TODO: handle opts.full delta
-                        opts.full.is_some()
-                    }).unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
+        let uri = client.get_uri(buffer);
+        let (full, delta, ranged) = {
+            let state = client.state.lock();
+            match semantic_tokens_options(&state) {
+                Some(opts) => (
+                    semantic_tokens_full(opts),
+                    matches!(
+                        opts.full,
+                        Some(SemanticTokensFullOptions::Delta { delta: Some(true) })
+                    ),
+                    opts.range == Some(true),
+                ),
+                None => return,
             }
+        };
+        let large = buffer.line_of_offset(buffer.len()) > LARGE_FILE_LINES;
+        let old_buffer = buffer.clone();
 
-            let uri = client.get_uri(&buffer);
-            let old_buffer = buffer;
-
-            client.request_semantic_tokens(uri, move |lsp_client, result| {
-                let buffers = lsp_client.dispatcher.buffers.lock();
-                let buffer = buffers.get(&old_buffer.id).unwrap();
-                // If the revision changed while we were requesting, then we refuse the request as it will have made another one
-                if buffer.rev != old_buffer.rev {
-                    lsp_client
-                        .dispatcher
-                        .respond(id, Err(anyhow!("revision changed")));
-                    return;
-                }
+        if ranged && (large || !full) {
+            client.request_semantic_tokens_range(
+                buffer.id,
+                uri,
+                range,
+                move |lsp_client, result| {
+                    let result = result.and_then(|value| {
+                        let tokens: SemanticTokens = serde_json::from_value(value)?;
+                        lsp_client.semantic_styles(&old_buffer, &tokens.data, true)
+                    });
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+            return;
+        }
 
-                let lsp_state = lsp_client.state.lock();
-                let semantic_tokens_provider = &lsp_state
-                    .server_capabilities
-                    .as_ref()
-                    .unwrap()
-                    .semantic_tokens_provider;
+        let (unchanged, previous_result_id) = {
+            let state = client.state.lock();
+            let cache = state.semantic_tokens.get(&buffer.id);
+            (
+                cache.map(|cache| cache.rev == buffer.rev).unwrap_or(false),
+                cache
+                    .filter(|_| delta)
+                    .and_then(|cache| cache.result_id.clone()),
+            )
+        };
+        if unchanged {
+            // The editor already has the styles of this revision
+            client
+                .dispatcher
+                .respond(id, Err(anyhow!("semantic tokens unchanged")));
+            return;
+        }
+
+        client.request_semantic_tokens(
+            buffer.id,
+            uri,
+            previous_result_id.clone(),
+            move |lsp_client, result| {
                 let result = result.and_then(|value| {
-                    format_semantic_styles(buffer, semantic_tokens_provider, value)
-                        .map(|styles| {
-                            serde_json::to_value(SemanticStyles {
-                                rev: buffer.rev,
-                                buffer_id: buffer.id,
-                                path: buffer.path.clone(),
-                                styles,
-                                len: buffer.len(),
-                            })
-                            .unwrap()
-                        })
-                        .ok_or_else(|| anyhow!("can't format semantic styles"))
+                    let previous = lsp_client
+                        .state
+                        .lock()
+                        .semantic_tokens
+                        .remove(&old_buffer.id)
+                        .filter(|cache| cache.result_id == previous_result_id);
+                    let (result_id, data) =
+                        semantic_tokens_of_result(value, previous)?;
+                    let styles =
+                        lsp_client.semantic_styles(&old_buffer, &data, false);
+                    lsp_client.state.lock().semantic_tokens.insert(
+                        old_buffer.id,
+                        SemanticTokensCache {
+                            rev: old_buffer.rev,
+                            result_id,
+                            data,
+                        },
+                    );
+                    styles
                 });
-
                 lsp_client.dispatcher.respond(id, result);
-            });
-        }
+            },
+        );
     }
 
     pub fn get_document_symbols(&self, id: RequestId, buffer: &Buffer) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.document_symbol_provider.as_ref())
-                    .map(|prov| prov != &OneOf::Left(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
-
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.document_symbol_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
             client.request_document_symbols(uri, move |lsp_client, result| {
                 lsp_client.dispatcher.respond(id, result);
@@ -343,26 +1154,14 @@ impl LspCatalog {
         query: String,
     ) {
         // TODO: We could collate workspace symbols from all the lsps?
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.workspace_symbol_provider.as_ref())
-                    .map(|prov| prov != &OneOf::Left(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
-
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.workspace_symbol_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
             client.request_workspace_symbols(query, move |lsp_client, result| {
                 lsp_client.dispatcher.respond(id, result);
             });
@@ -370,79 +1169,481 @@ impl LspCatalog {
     }
 
     pub fn get_document_formatting(&self, id: RequestId, buffer: &Buffer) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
+        if let Some(client) = self.formatting_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.document_formatting_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_document_formatting(uri, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no document formatting")));
+        }
+    }
 
-                if !state.is_initialized {
-                    return;
-                }
+    pub fn get_document_range_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        range: Range,
+    ) {
+        if let Some(client) = self.formatting_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.document_range_formatting_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_document_range_formatting(
+                uri,
+                range,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+            return;
+        }
 
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.document_formatting_provider.as_ref())
-                    .map(|prov| prov != &OneOf::Left(false))
-                    .unwrap_or(false);
+        self.dispatcher
+            .as_ref()
+            .unwrap()
+            .respond(id, Err(anyhow!("no document range formatting")));
+    }
 
-                if !is_enabled {
-                    return;
-                }
+    pub fn get_on_type_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        ch: String,
+    ) {
+        if let Some(client) = self.formatting_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.document_on_type_formatting_provider.as_ref())
+                .map(|options| {
+                    options.first_trigger_character == ch
+                        || options
+                            .more_trigger_character
+                            .as_ref()
+                            .map(|more| more.contains(&ch))
+                            .unwrap_or(false)
+                })
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_on_type_formatting(
+                uri,
+                position,
+                ch,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+            return;
+        }
+
+        // Most typed characters aren't triggers, so this isn't an error
+        self.dispatcher
+            .as_ref()
+            .unwrap()
+            .respond(id, Ok(Value::Array(Vec::new())));
+    }
+
+    pub fn prepare_rename(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        let dispatcher = self.dispatcher.as_ref().unwrap();
+        let client = match self.capable_client(buffer, has_rename_provider) {
+            Some(client) => client,
+            None => {
+                dispatcher.respond(id, Err(anyhow!("no rename provider")));
+                return;
             }
+        };
+
+        let prepare_provider = match client
+            .state
+            .lock()
+            .server_capabilities
+            .as_ref()
+            .and_then(|cap| cap.rename_provider.as_ref())
+        {
+            Some(OneOf::Right(options)) => options.prepare_provider.unwrap_or(false),
+            _ => false,
+        };
+
+        if !prepare_provider {
+            // The server can rename but can't tell us the range, so the
+            // editor falls back to the word under the cursor.
+            dispatcher.respond(id, Ok(json!({ "defaultBehavior": true })));
+            return;
+        }
 
+        let uri = client.get_uri(buffer);
+        client.request_prepare_rename(uri, position, move |lsp_client, result| {
+            lsp_client.dispatcher.respond(id, result);
+        });
+    }
+
+    pub fn rename(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        new_name: String,
+    ) {
+        if let Some(client) = self.capable_client(buffer, has_rename_provider) {
             let uri = client.get_uri(buffer);
-            client.request_document_formatting(uri, move |lsp_client, result| {
-                lsp_client.dispatcher.respond(id, result);
-            });
+            client.request_rename(
+                uri,
+                position,
+                new_name,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
         } else {
             self.dispatcher
                 .as_ref()
                 .unwrap()
-                .respond(id, Err(anyhow!("no document formatting")));
+                .respond(id, Err(anyhow!("no rename provider")));
         }
     }
 
-    pub fn get_completion(
+    pub fn prepare_call_hierarchy(
         &self,
         id: RequestId,
-        _request_id: usize,
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
+        if let Some(client) =
+            self.capable_client(buffer, has_call_hierarchy_provider)
+        {
+            let uri = client.get_uri(buffer);
+            client.request_prepare_call_hierarchy(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no call hierarchy provider")));
+        }
+    }
 
-                if !state.is_initialized {
-                    return;
-                }
+    pub fn call_hierarchy_incoming_calls(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        item: CallHierarchyItem,
+    ) {
+        // The same server that prepared the hierarchy
+        if let Some(client) =
+            self.capable_client(buffer, has_call_hierarchy_provider)
+        {
+            client.request_call_hierarchy_incoming_calls(
+                item,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no call hierarchy provider")));
+        }
+    }
 
-                // TODO: pay attention to trigger characters
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .map(|cap| cap.completion_provider.is_some())
-                    .unwrap_or(false);
+    pub fn call_hierarchy_outgoing_calls(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        item: CallHierarchyItem,
+    ) {
+        if let Some(client) =
+            self.capable_client(buffer, has_call_hierarchy_provider)
+        {
+            client.request_call_hierarchy_outgoing_calls(
+                item,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no call hierarchy provider")));
+        }
+    }
 
-                if !is_enabled {
-                    return;
-                }
-            }
+    pub fn prepare_type_hierarchy(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) =
+            self.capable_client(buffer, |state| state.type_hierarchy_provider)
+        {
+            let uri = client.get_uri(buffer);
+            client.request_prepare_type_hierarchy(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no type hierarchy provider")));
+        }
+    }
+
+    pub fn type_hierarchy_supertypes(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        item: CallHierarchyItem,
+    ) {
+        if let Some(client) =
+            self.capable_client(buffer, |state| state.type_hierarchy_provider)
+        {
+            client.request_type_hierarchy(
+                "typeHierarchy/supertypes",
+                item,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no type hierarchy provider")));
+        }
+    }
 
+    pub fn type_hierarchy_subtypes(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        item: CallHierarchyItem,
+    ) {
+        if let Some(client) =
+            self.capable_client(buffer, |state| state.type_hierarchy_provider)
+        {
+            client.request_type_hierarchy(
+                "typeHierarchy/subtypes",
+                item,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no type hierarchy provider")));
+        }
+    }
+
+    pub fn get_document_highlight(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.document_highlight_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
-            client.request_completion(uri, position, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
+            client.request_document_highlight(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no document highlight provider")));
+        }
+    }
+
+    pub fn get_linked_editing_range(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.linked_editing_range_provider.as_ref())
+                .map(|prov| {
+                    prov != &LinkedEditingRangeServerCapabilities::Simple(false)
+                })
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_linked_editing_range(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no linked editing range provider")));
+        }
+    }
+
+    pub fn get_code_lens(&self, id: RequestId, buffer: &Buffer) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .map(|cap| cap.code_lens_provider.is_some())
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_code_lens(uri, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no code lens provider")));
+        }
+    }
+
+    pub fn code_lens_resolve(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        code_lens: CodeLens,
+    ) {
+        // The lenses come from the first server with a code lens provider,
+        // so that's the one that can resolve them
+        let client = self
+            .capable_client(buffer, |state| {
+                state
+                    .server_capabilities
+                    .as_ref()
+                    .map(|cap| cap.code_lens_provider.is_some())
+                    .unwrap_or(false)
+            })
+            .filter(|client| {
+                client
+                    .state
+                    .lock()
+                    .server_capabilities
+                    .as_ref()
+                    .and_then(|cap| cap.code_lens_provider.as_ref())
+                    .and_then(|prov| prov.resolve_provider)
+                    .unwrap_or(false)
             });
+        if let Some(client) = client {
+            client.request_code_lens_resolve(
+                code_lens,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no code lens resolve provider")));
+        }
+    }
+
+    pub fn execute_command(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        command: lsp_types::Command,
+    ) {
+        // The server that registered the command
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.execute_command_provider.as_ref())
+                .map(|prov| prov.commands.contains(&command.command))
+                .unwrap_or(false)
+        }) {
+            client.request_execute_command(command, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        } else {
+            self.dispatcher.as_ref().unwrap().respond(
+                id,
+                Err(anyhow!("{} isn't supported by the server", command.command)),
+            );
+        }
+    }
+
+    pub fn get_completion(
+        &self,
+        id: RequestId,
+        request_id: usize,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        // TODO: pay attention to trigger characters
+        let clients = self.capable_clients(buffer, has_completion_provider);
+        if clients.is_empty() {
+            return;
+        }
+
+        let merged = MergedRequest::new(id, &clients, merge_completions);
+        for client in clients {
+            let merged = merged.clone();
+            let uri = client.get_uri(buffer);
+            client.request_completion(
+                request_id,
+                uri,
+                position,
+                move |lsp_client, result| {
+                    merged.lock().receive(lsp_client, result);
+                },
+            );
         }
     }
 
@@ -452,103 +1653,107 @@ impl LspCatalog {
         buffer: &Buffer,
         completion_item: &CompletionItem,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            client.completion_resolve(completion_item, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
-            });
+        let mut completion_item = completion_item.clone();
+        let client = match untag_completion_item(&mut completion_item) {
+            Some(server) => self.clients.iter().find(|client| client.id == server),
+            None => self.capable_client(buffer, has_completion_provider),
+        };
+        if let Some(client) = client {
+            client.completion_resolve(
+                &completion_item,
+                move |lsp_client, result| {
+                    let result = result.map(|mut item| {
+                        tag_completion_item(&mut item, lsp_client.id);
+                        item
+                    });
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
         }
     }
 
     pub fn get_hover(
         &self,
         id: RequestId,
-        _request_id: usize,
+        request_id: usize,
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.hover_provider.as_ref())
-                    .map(|prov| prov != &HoverProviderCapability::Simple(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
-
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.hover_provider.as_ref())
+                .map(|prov| prov != &HoverProviderCapability::Simple(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
-            client.request_hover(uri, position, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}", e),
-                        })
+            client.request_hover(
+                request_id,
+                uri,
+                position,
+                move |lsp_client, result| {
+                    let mut resp = json!({ "id": id });
+                    match result {
+                        Ok(v) => resp["result"] = v,
+                        Err(e) => {
+                            resp["error"] = json!({
+                                "code": 0,
+                                "message": format!("{}", e),
+                            })
+                        }
                     }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
-            });
+                    let _ = lsp_client.dispatcher.sender.send(resp);
+                },
+            );
         }
     }
 
-    pub fn get_signature(&self, id: RequestId, buffer: &Buffer, position: Position) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                // TODO: use the trigger characters fields
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .map(|cap| cap.signature_help_provider.is_some())
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
+    pub fn get_signature(
+        &self,
+        id: RequestId,
+        request_id: usize,
+        buffer: &Buffer,
+        position: Position,
+        trigger: Option<String>,
+        active: Option<SignatureHelp>,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .map(|cap| cap.signature_help_provider.is_some())
+                .unwrap_or(false)
+        }) {
+            let context = client
+                .state
+                .lock()
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.signature_help_provider.as_ref())
+                .and_then(|options| {
+                    signature_help_context(options, trigger, active)
+                });
+            if let Some(context) = context {
+                let uri = client.get_uri(buffer);
+                client.request_signature(
+                    request_id,
+                    uri,
+                    position,
+                    context,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+                return;
             }
-
-            let uri = client.get_uri(buffer);
-            client.request_signature(uri, position, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
-            });
         }
+
+        // Most typed characters don't trigger the signature help
+        self.dispatcher
+            .as_ref()
+            .unwrap()
+            .respond(id, Ok(Value::Null));
     }
 
     pub fn get_references(
@@ -557,26 +1762,14 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.references_provider.as_ref())
-                    .map(|prov| prov != &OneOf::Left(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
-
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.references_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
             client.request_references(uri, position, move |lsp_client, result| {
                 let mut resp = json!({ "id": id });
@@ -594,46 +1787,63 @@ impl LspCatalog {
         }
     }
 
-    pub fn get_inlay_hints(&self, id: RequestId, buffer: &Buffer) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.inlay_hint_provider.as_ref())
-                    .map(|prov| prov != &OneOf::Left(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
-
+    /// Get the inlay hints of the range that the editor shows
+    pub fn get_inlay_hints(&self, id: RequestId, buffer: &Buffer, range: Range) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.inlay_hint_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
-            // Range over the entire buffer
-            let range = Range {
-                start: Position::new(0, 0),
-                end: buffer.offset_to_position(buffer.len()).unwrap(),
-            };
-            client.request_inlay_hints(uri, range, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}", e),
-                        })
+            client.request_inlay_hints(
+                buffer.id,
+                uri,
+                range,
+                move |lsp_client, result| {
+                    let mut resp = json!({ "id": id });
+                    match result {
+                        Ok(v) => resp["result"] = v,
+                        Err(e) => {
+                            resp["error"] = json!({
+                                "code": 0,
+                                "message": format!("{}", e),
+                            })
+                        }
                     }
-                }
 
-                let _ = lsp_client.dispatcher.sender.send(resp);
+                    let _ = lsp_client.dispatcher.sender.send(resp);
+                },
+            );
+        }
+    }
+
+    pub fn resolve_inlay_hint(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        hint: &InlayHint,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.inlay_hint_provider.as_ref())
+                .map(|prov| match prov {
+                    OneOf::Left(_) => false,
+                    OneOf::Right(InlayHintServerCapabilities::Options(options)) => {
+                        options.resolve_provider == Some(true)
+                    }
+                    OneOf::Right(
+                        InlayHintServerCapabilities::RegistrationOptions(options),
+                    ) => options.inlay_hint_options.resolve_provider == Some(true),
+                })
+                .unwrap_or(false)
+        }) {
+            client.resolve_inlay_hint(hint, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
             });
         }
     }
@@ -644,43 +1854,29 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.code_action_provider.as_ref())
-                    .map(|prov| prov != &CodeActionProviderCapability::Simple(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
+        // Every server has its own fixes for the diagnostics it published
+        let clients = self.capable_clients(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.code_action_provider.as_ref())
+                .map(|prov| prov != &CodeActionProviderCapability::Simple(false))
+                .unwrap_or(false)
+        });
+        if clients.is_empty() {
+            return;
+        }
 
+        let range = Range {
+            start: position,
+            end: position,
+        };
+        let merged = MergedRequest::new(id, &clients, merge_code_actions);
+        for client in clients {
+            let merged = merged.clone();
             let uri = client.get_uri(buffer);
-            let range = Range {
-                start: position,
-                end: position,
-            };
             client.request_code_actions(uri, range, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
+                merged.lock().receive(lsp_client, result);
             });
         }
     }
@@ -692,26 +1888,14 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.definition_provider.as_ref())
-                    .map(|prov| prov != &OneOf::Left(false))
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
-                }
-            }
-
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.definition_provider.as_ref())
+                .map(|prov| prov != &OneOf::Left(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
             client.request_definition(uri, position, move |lsp_client, result| {
                 let mut resp = json!({ "id": id });
@@ -721,43 +1905,29 @@ impl LspCatalog {
                         resp["error"] = json!({
                             "code": 0,
                             "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
-            });
-        }
-    }
-
-    pub fn get_type_definition(
-        &self,
-        id: RequestId,
-        _request_id: usize,
-        buffer: &Buffer,
-        position: Position,
-    ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            {
-                let state = client.state.lock();
-
-                if !state.is_initialized {
-                    return;
-                }
-
-                let is_enabled = state
-                    .server_capabilities
-                    .as_ref()
-                    .and_then(|cap| cap.type_definition_provider.as_ref())
-                    .map(|prov| {
-                        prov != &TypeDefinitionProviderCapability::Simple(false)
-                    })
-                    .unwrap_or(false);
-
-                if !is_enabled {
-                    return;
+                        })
+                    }
                 }
-            }
+                let _ = lsp_client.dispatcher.sender.send(resp);
+            });
+        }
+    }
 
+    pub fn get_type_definition(
+        &self,
+        id: RequestId,
+        _request_id: usize,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.type_definition_provider.as_ref())
+                .map(|prov| prov != &TypeDefinitionProviderCapability::Simple(false))
+                .unwrap_or(false)
+        }) {
             let uri = client.get_uri(buffer);
             client.request_type_definition(
                 uri,
@@ -780,16 +1950,79 @@ impl LspCatalog {
         }
     }
 
+    pub fn get_implementation(
+        &self,
+        id: RequestId,
+        _request_id: usize,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.implementation_provider.as_ref())
+                .map(|prov| prov != &ImplementationProviderCapability::Simple(false))
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_implementation(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        }
+    }
+
+    pub fn get_declaration(
+        &self,
+        id: RequestId,
+        _request_id: usize,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) = self.capable_client(buffer, |state| {
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|cap| cap.declaration_provider.as_ref())
+                .map(|prov| prov != &DeclarationCapability::Simple(false))
+                .unwrap_or(false)
+        }) {
+            let uri = client.get_uri(buffer);
+            client.request_declaration(uri, position, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        }
+    }
+
     pub fn update(
         &self,
         buffer: &Buffer,
         content_change: &TextDocumentContentChangeEvent,
         rev: u64,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        for client in self.buffer_clients(buffer) {
             client.update(buffer, content_change, rev);
         }
     }
+
+    /// Let the servers that the document was opened in know that it's closed
+    pub fn close_buffer(&self, buffer: &Buffer) {
+        for client in self.clients.iter() {
+            let uri = {
+                let mut state = client.state.lock();
+                state.semantic_tokens.remove(&buffer.id);
+                state.opened_documents.remove(&buffer.id)
+            };
+            if let Some(uri) = uri {
+                client.state.lock().diagnostic_result_ids.remove(&uri);
+                client.send_did_close(uri);
+            }
+        }
+    }
 }
 
 impl Default for LspCatalog {
@@ -799,25 +2032,29 @@ impl Default for LspCatalog {
 }
 
 impl LspClient {
-    pub fn new(
+    fn new(
         language_id: String,
-        exec_path: &str,
+        server: LspServer,
         options: Option<Value>,
-        args: Vec<String>,
+        settings: Arc<Mutex<Value>>,
+        diagnostics: ServerDiagnostics,
         dispatcher: Dispatcher,
-    ) -> Arc<LspClient> {
+    ) -> Result<Arc<LspClient>> {
         //TODO: better handling of binary args in plugin
         let workspace = dispatcher.workspace.lock().clone();
-        let mut process = Self::process(workspace, exec_path, args.clone());
+        let mut process = Self::process(workspace, &server)?;
         let writer = Box::new(BufWriter::new(process.stdin.take().unwrap()));
         let stdout = process.stdout.take().unwrap();
         let stderr = process.stderr.take().unwrap();
 
         let lsp_client = Arc::new(LspClient {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            language_id: language_id.clone(),
+            server,
             dispatcher,
-            exec_path: exec_path.to_string(),
-            args,
             options,
+            settings,
+            diagnostics,
             state: Arc::new(Mutex::new(LspState {
                 next_id: 0,
                 writer,
@@ -827,15 +2064,31 @@ impl LspClient {
                 opened_documents: HashMap::new(),
                 is_initialized: false,
                 did_save_capabilities: Vec::new(),
+                type_hierarchy_provider: false,
+                diagnostic_provider: None,
+                diagnostic_result_ids: HashMap::new(),
+                semantic_tokens: HashMap::new(),
+                status: LanguageServerStatus::Starting,
+                started: Instant::now(),
+                crashes: 0,
+                in_flight: HashMap::new(),
+                watched_files: Vec::new(),
+                changed_files: Vec::new(),
+                diagnostic_pulls: HashSet::new(),
+                last_diagnostic_pull: None,
+                last_file_change: None,
+                traced_requests: HashMap::new(),
             })),
             active: Arc::new(AtomicBool::new(true)),
         });
 
+        lsp_client.set_status(LanguageServerStatus::Starting);
+
         lsp_client.handle_stdout(stdout);
         lsp_client.handle_stderr(stderr, language_id);
         lsp_client.initialize();
 
-        lsp_client
+        Ok(lsp_client)
     }
 
     fn handle_stdout(&self, stdout: ChildStdout) {
@@ -851,8 +2104,7 @@ impl LspClient {
                         if !local_lsp_client.active.load(Ordering::Acquire) {
                             return;
                         }
-                        local_lsp_client.stop();
-                        local_lsp_client.reload();
+                        local_lsp_client.handle_crash();
                         return;
                     }
                 };
@@ -860,6 +2112,83 @@ impl LspClient {
         });
     }
 
+    /// The documents of the language, and the ones matched by the
+    /// `documentSelector` of the plugin options, for servers like tailwind
+    /// that work on the files of several languages
+    fn document_selector(
+        language_id: &str,
+        options: Option<&Value>,
+    ) -> Vec<DocumentFilter> {
+        let mut selector = vec![DocumentFilter {
+            language_id: Some(language_id.to_string()),
+            pattern: None,
+        }];
+        let filters = options
+            .and_then(|options| options.get("documentSelector"))
+            .and_then(|filters| {
+                serde_json::from_value::<Vec<lsp_types::DocumentFilter>>(
+                    filters.clone(),
+                )
+                .ok()
+            });
+        if let Some(filters) = filters {
+            selector.extend(
+                filters
+                    .into_iter()
+                    .map(DocumentFilter::from_lsp_filter_loose),
+            );
+        }
+        selector
+    }
+
+    /// Whether the server handles the document with the language id and path
+    fn handles(&self, language_id: &str, path: &Path) -> bool {
+        let workspace = self.dispatcher.workspace.lock().clone();
+        self.server.handles(workspace.as_deref(), language_id, path)
+    }
+
+    /// Replace the diagnostics that the server published for the document,
+    /// and send the ones of all the servers of the document to the editor
+    fn publish_diagnostics(&self, uri: Url, mut diagnostics: Vec<Diagnostic>) {
+        for diagnostic in diagnostics.iter_mut() {
+            if diagnostic.source.is_none() {
+                diagnostic.source = Some(self.server.name.clone());
+            }
+        }
+
+        let merged = merge_server_diagnostics(
+            &mut self.diagnostics.lock(),
+            &uri,
+            self.id,
+            diagnostics,
+        );
+
+        self.dispatcher.send_notification(
+            "publish_diagnostics",
+            json!({
+                "diagnostics": PublishDiagnosticsParams {
+                    uri,
+                    diagnostics: merged,
+                    version: None,
+                },
+            }),
+        );
+    }
+
+    /// Remove the diagnostics of the server from all the documents
+    fn clear_diagnostics(&self) {
+        let uris: Vec<Url> = self
+            .diagnostics
+            .lock()
+            .iter()
+            .filter(|(_, servers)| servers.contains_key(&self.id))
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        for uri in uris {
+            self.publish_diagnostics(uri, Vec::new());
+        }
+    }
+
     fn handle_stderr(&self, stderr: ChildStderr, language_id: String) {
         thread::spawn(move || {
             let mut reader = Box::new(BufReader::new(stderr));
@@ -883,17 +2212,14 @@ impl LspClient {
         });
     }
 
-    fn process(
-        workspace: Option<PathBuf>,
-        exec_path: &str,
-        args: Vec<String>,
-    ) -> Child {
-        let mut process = Command::new(exec_path);
-        if let Some(workspace) = workspace {
-            process.current_dir(&workspace);
+    fn process(workspace: Option<PathBuf>, server: &LspServer) -> Result<Child> {
+        let mut process = Command::new(&server.exec_path);
+        if let Some(dir) = server.root.clone().or(workspace) {
+            process.current_dir(&dir);
         }
 
-        process.args(args);
+        process.args(&server.args);
+        process.envs(&server.env);
 
         #[cfg(target_os = "windows")]
         let process = process.creation_flags(0x08000000);
@@ -902,36 +2228,116 @@ impl LspClient {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .expect("Error Occurred")
+            .map_err(|e| anyhow!("can't run {}: {e}", server.exec_path))
+    }
+
+    /// Restart the server after its process exited, waiting longer the more
+    /// times it crashed in a row, and give up after `MAX_RESTARTS`
+    fn handle_crash(&self) {
+        let (crashes, pending) = {
+            let mut state = self.state.lock();
+            let _ = state.process.kill();
+            let _ = state.process.wait();
+            state.is_initialized = false;
+            if state.started.elapsed() > CRASH_RESET {
+                state.crashes = 0;
+            }
+            state.crashes += 1;
+            state.traced_requests.clear();
+            (state.crashes, std::mem::take(&mut state.pending))
+        };
+        // Nothing will answer the requests that were sent
+        for (_, callback) in pending {
+            callback.call(self, Err(anyhow!("the language server crashed")));
+        }
+        self.clear_diagnostics();
+
+        if crashes > MAX_RESTARTS {
+            self.set_status(LanguageServerStatus::Crashed);
+            return;
+        }
+        self.set_status(LanguageServerStatus::Restarting);
+        thread::sleep(Duration::from_secs(1 << (crashes - 1)));
+        // It could have been stopped or restarted by hand in the meantime
+        if !self.active.load(Ordering::Acquire) {
+            return;
+        }
+        self.reload();
     }
 
     fn reload(&self) {
-        //TODO: avoid clone using a &[String] ?
-        let mut process = Self::process(
-            self.dispatcher.workspace.lock().clone(),
-            &self.exec_path,
-            self.args.clone(),
-        );
+        let workspace = self.dispatcher.workspace.lock().clone();
+        let mut process = match Self::process(workspace, &self.server) {
+            Ok(process) => process,
+            Err(e) => {
+                show_start_error(&self.dispatcher, &self.server.name, &e);
+                self.set_status(LanguageServerStatus::Crashed);
+                return;
+            }
+        };
         let writer = Box::new(BufWriter::new(process.stdin.take().unwrap()));
         let stdout = process.stdout.take().unwrap();
 
         let mut state = self.state.lock();
         state.next_id = 0;
         state.pending.clear();
+        state.in_flight.clear();
         state.opened_documents.clear();
+        state.watched_files.clear();
+        state.changed_files.clear();
+        state.traced_requests.clear();
         state.server_capabilities = None;
         state.is_initialized = false;
+        state.type_hierarchy_provider = false;
+        state.diagnostic_provider = None;
+        state.diagnostic_result_ids.clear();
+        state.semantic_tokens.clear();
         state.writer = writer;
         state.process = process;
+        state.started = Instant::now();
+
+        drop(state);
+        self.clear_diagnostics();
+        self.set_status(LanguageServerStatus::Starting);
 
         self.handle_stdout(stdout);
         self.initialize();
+
+        // The documents that were open before are opened again
+        if self.state.lock().is_initialized {
+            let buffers = self.dispatcher.buffers.lock();
+            for buffer in buffers.values() {
+                if self.handles(&buffer.language_id, &buffer.path) {
+                    self.get_uri(buffer);
+                }
+            }
+        }
     }
 
     fn stop(&self) {
         self.active.store(false, Ordering::Release);
         let _ = self.state.lock().process.kill();
         let _ = self.state.lock().process.wait();
+        self.set_status(LanguageServerStatus::Stopped);
+    }
+
+    fn set_status(&self, status: LanguageServerStatus) {
+        self.state.lock().status = status;
+        self.dispatcher.send_notification(
+            "language_server_status",
+            json!({
+                "name": self.server.name,
+                "status": status,
+            }),
+        );
+    }
+
+    /// Whether the process isn't running, so nothing should be written to it
+    fn is_down(&self) -> bool {
+        !matches!(
+            self.state.lock().status,
+            LanguageServerStatus::Starting | LanguageServerStatus::Running
+        )
     }
 
     pub fn get_uri(&self, buffer: &Buffer) -> Url {
@@ -948,6 +2354,7 @@ impl LspClient {
                 &buffer.id,
                 document_uri,
                 &buffer.language_id,
+                buffer.rev as i32,
                 buffer.get_document(),
             );
         }
@@ -960,6 +2367,11 @@ impl LspClient {
     }
 
     pub fn handle_message(&self, message: &str) {
+        if self.trace_value() != TraceValue::Off {
+            if let Ok(value) = serde_json::from_str::<Value>(message) {
+                self.trace(LspTraceDirection::Received, &value);
+            }
+        }
         match JsonRpc::parse(message) {
             Ok(value @ JsonRpc::Request(_)) => {
                 let id = value.get_id().unwrap();
@@ -983,7 +2395,25 @@ impl LspClient {
             Ok(value @ JsonRpc::Error(_)) => {
                 let id = number_from_id(&value.get_id().unwrap());
                 let error = value.get_error().unwrap();
-                self.handle_response(id, Err(anyhow!("{}", error)));
+                let error = match error.code {
+                    // Not failures, the result would be stale anyway, and the
+                    // editor asks again if it still needs it
+                    REQUEST_CANCELLED => anyhow!("request cancelled"),
+                    CONTENT_MODIFIED => {
+                        anyhow!("the document changed during the request")
+                    }
+                    SERVER_CANCELLED => anyhow::Error::new(ServerCancelled {
+                        // Sent again unless the server says otherwise
+                        retrigger: error
+                            .data
+                            .as_ref()
+                            .and_then(|data| data.get("retriggerRequest"))
+                            .and_then(|retrigger| retrigger.as_bool())
+                            .unwrap_or(true),
+                    }),
+                    _ => anyhow!("{}", error),
+                };
+                self.handle_response(id, Err(error));
             }
             Err(_err) => {}
         }
@@ -1027,24 +2457,137 @@ impl LspClient {
                                 }
                                 // TODO: report error?
                             }
+                            "workspace/didChangeWatchedFiles" => {
+                                if let Some(options) = registration.register_options.and_then(|options| serde_json::from_value::<DidChangeWatchedFilesRegistrationOptions>(options).ok()) {
+                                    let watched_files = WatchedFiles::new(registration.id, options);
+                                    self.state.lock().watched_files.push(watched_files);
+                                }
+                            }
                             _ => println!("Received unhandled client/registerCapability request {}", registration.method),
                         }
                     }
                 }
+                self.send_success_response(id, &Value::Null);
+            }
+            "client/unregisterCapability" => {
+                if let Ok(params) =
+                    serde_json::from_value::<UnregistrationParams>(json!(params))
+                {
+                    let mut state = self.state.lock();
+                    for unregistration in params.unregisterations {
+                        state
+                            .watched_files
+                            .retain(|watched| watched.id != unregistration.id);
+                    }
+                }
+                self.send_success_response(id, &Value::Null);
+            }
+            "workspace/diagnostic/refresh" => {
+                self.send_success_response(id, &Value::Null);
+                self.pull_document_diagnostics(None);
+                self.pull_workspace_diagnostics();
+            }
+            "workspace/semanticTokens/refresh" | "workspace/inlayHint/refresh" => {
+                // Otherwise the cache would answer that the editor's styles
+                // are up to date
+                self.state.lock().semantic_tokens.clear();
+                self.send_success_response(id, &Value::Null);
+                self.dispatcher
+                    .send_rpc_notification(CoreNotification::LspRefresh {});
             }
             "workspace/configuration" => {
                 if let Ok(config) =
                     serde_json::from_value::<ConfigurationParams>(json!(params))
                 {
-                    let items = config
-                        .items
-                        .into_iter()
-                        .map(|_item| Value::Null)
-                        .collect::<Vec<Value>>();
+                    let items = {
+                        let settings = self.settings.lock();
+                        config
+                            .items
+                            .into_iter()
+                            .map(|item| {
+                                configuration_section(
+                                    &settings,
+                                    item.section.as_deref(),
+                                )
+                            })
+                            .collect::<Vec<Value>>()
+                    };
 
                     self.send_success_response(id, &Value::Array(items));
                 }
             }
+            "window/showMessageRequest" => {
+                match serde_json::from_value::<ShowMessageRequestParams>(json!(
+                    params
+                )) {
+                    Ok(message) => {
+                        // Answered with the action that the user picks, or
+                        // null if the message is dismissed
+                        let client = self.clone();
+                        self.dispatcher.send_core_request(
+                            CoreRequest::ShowMessageRequest {
+                                title: self.server.name.clone(),
+                                message,
+                            },
+                            move |result| {
+                                client.send_success_response(
+                                    id,
+                                    &result.unwrap_or(Value::Null),
+                                );
+                            },
+                        );
+                    }
+                    Err(_) => {
+                        self.send_error_response(
+                            id,
+                            jsonrpc_lite::Error::invalid_params(),
+                        );
+                    }
+                }
+            }
+            "workspace/applyEdit" => {
+                match serde_json::from_value::<ApplyWorkspaceEditParams>(json!(
+                    params
+                )) {
+                    Ok(params) => {
+                        // The edit is applied by the editor, which reports back
+                        // with the response for the server
+                        let client = self.clone();
+                        self.dispatcher.send_core_request(
+                            CoreRequest::ApplyWorkspaceEdit { edit: params.edit },
+                            move |result| {
+                                let response = result
+                                    .map_err(|e| e.to_string())
+                                    .and_then(|value| {
+                                        serde_json::from_value::<
+                                            ApplyWorkspaceEditResponse,
+                                        >(
+                                            value
+                                        )
+                                        .map_err(|e| e.to_string())
+                                    })
+                                    .unwrap_or_else(|e| {
+                                        ApplyWorkspaceEditResponse {
+                                            applied: false,
+                                            failure_reason: Some(e),
+                                            failed_change: None,
+                                        }
+                                    });
+                                client.send_success_response(
+                                    id,
+                                    &to_value(response).unwrap(),
+                                );
+                            },
+                        );
+                    }
+                    Err(_) => {
+                        self.send_error_response(
+                            id,
+                            jsonrpc_lite::Error::invalid_params(),
+                        );
+                    }
+                }
+            }
             method => {
                 println!("Received unhandled request {method}");
             }
@@ -1054,12 +2597,11 @@ impl LspClient {
     pub fn handle_notification(&self, method: &str, params: Params) {
         match method {
             "textDocument/publishDiagnostics" => {
-                self.dispatcher.send_notification(
-                    "publish_diagnostics",
-                    json!({
-                        "diagnostics": params,
-                    }),
-                );
+                if let Ok(params) =
+                    serde_json::from_value::<PublishDiagnosticsParams>(json!(params))
+                {
+                    self.publish_diagnostics(params.uri, params.diagnostics);
+                }
             }
             "$/progress" => {
                 self.dispatcher.send_notification(
@@ -1070,15 +2612,29 @@ impl LspClient {
                 );
             }
             "window/showMessage" => {
-                // TODO: send message to display
+                self.dispatcher.send_notification(
+                    "show_message",
+                    json!({
+                        "title": self.server.name.clone(),
+                        "message": params,
+                    }),
+                );
             }
             "window/logMessage" => {
-                // TODO: We should log the message here. Waiting for
-                // the discussion about handling plugins logs before doing anything
+                self.dispatcher.send_notification(
+                    "log_message",
+                    json!({
+                        "server": self.server.name.clone(),
+                        "message": params,
+                    }),
+                );
             }
             "experimental/serverStatus" => {
                 //TODO: Logging of server status
             }
+            "$/logTrace" => {
+                // Recorded by the trace like every other message
+            }
             method => {
                 println!("Received unhandled notification {}", method);
             }
@@ -1086,13 +2642,16 @@ impl LspClient {
     }
 
     pub fn handle_response(&self, id: u64, result: Result<Value>) {
-        let callback =
-            {
-                self.state.lock().pending.remove(&id).unwrap_or_else(|| {
-                    panic!("id {} missing from request table", id)
-                })
-            };
-        callback.call(self, result);
+        let callback = {
+            let mut state = self.state.lock();
+            state.in_flight.retain(|_, (_, request)| *request != id);
+            state.pending.remove(&id)
+        };
+        match callback {
+            Some(callback) => callback.call(self, result),
+            // The server answered a request that was sent before it restarted
+            None => log::warn!("id {id} missing from request table"),
+        }
     }
 
     pub fn write(&self, msg: &str) -> Result<()> {
@@ -1103,6 +2662,12 @@ impl LspClient {
     }
 
     fn send_rpc(&self, value: &Value) {
+        if self.is_down() {
+            return;
+        }
+        if self.trace_value() != TraceValue::Off {
+            self.trace(LspTraceDirection::Sent, value);
+        }
         let rpc = match prepare_lsp_json(value) {
             Ok(r) => r,
             Err(err) => panic!("Encoding Error {:?}", err),
@@ -1111,6 +2676,90 @@ impl LspClient {
         let _ = self.write(rpc.as_ref());
     }
 
+    /// The `[lsp] trace` setting, which is off unless it's `"messages"` or
+    /// `"verbose"`
+    fn trace_value(&self) -> TraceValue {
+        self.settings
+            .lock()
+            .get("trace")
+            .and_then(|trace| serde_json::from_value(trace.clone()).ok())
+            .unwrap_or(TraceValue::Off)
+    }
+
+    /// Send the message to the editor for the LSP trace panel, with the
+    /// time that the server took to answer if it's a response
+    fn trace(&self, direction: LspTraceDirection, message: &Value) {
+        let id = message.get("id").map(|id| id.to_string());
+        let method = message
+            .get("method")
+            .and_then(|method| method.as_str())
+            .map(|method| method.to_string());
+        let (method, latency) = match (id, method) {
+            (Some(id), Some(method)) => {
+                let mut state = self.state.lock();
+                if state.traced_requests.len() >= MAX_TRACED_REQUESTS {
+                    let oldest = state
+                        .traced_requests
+                        .iter()
+                        .min_by_key(|(_, (_, sent))| *sent)
+                        .map(|(key, _)| key.clone());
+                    if let Some(oldest) = oldest {
+                        state.traced_requests.remove(&oldest);
+                    }
+                }
+                state
+                    .traced_requests
+                    .insert((direction, id), (method.clone(), Instant::now()));
+                (Some(method), None)
+            }
+            (Some(id), None) => {
+                // A response goes the other way of its request
+                let request_direction = match direction {
+                    LspTraceDirection::Sent => LspTraceDirection::Received,
+                    LspTraceDirection::Received => LspTraceDirection::Sent,
+                };
+                match self
+                    .state
+                    .lock()
+                    .traced_requests
+                    .remove(&(request_direction, id))
+                {
+                    Some((method, sent)) => {
+                        (Some(method), Some(sent.elapsed().as_millis() as u64))
+                    }
+                    None => (None, None),
+                }
+            }
+            (None, method) => (method, None),
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or(0);
+        self.dispatcher
+            .send_rpc_notification(CoreNotification::LspTrace {
+                server: self.server.name.clone(),
+                entry: LspTraceEntry {
+                    direction,
+                    timestamp,
+                    method,
+                    latency,
+                    payload: message.clone(),
+                },
+            });
+    }
+
+    /// Tell the server about a change of the `[lsp] trace` setting
+    fn send_set_trace(&self) {
+        let params = SetTraceParams {
+            value: self.trace_value(),
+        };
+        self.send_notification(
+            "$/setTrace",
+            Params::from(to_value(params).unwrap()),
+        );
+    }
+
     pub fn send_notification(&self, method: &str, params: Params) {
         let notification = JsonRpc::notification_with_params(method, params);
         let res = to_value(&notification).unwrap();
@@ -1118,18 +2767,81 @@ impl LspClient {
     }
 
     pub fn send_request(&self, method: &str, params: Params, completion: Callback) {
-        let request = {
+        self.send_keyed_request(method, params, None, completion);
+    }
+
+    /// Send a request, which cancels the one that was sent before with the
+    /// same key if the server didn't answer it yet
+    fn send_keyed_request(
+        &self,
+        method: &str,
+        params: Params,
+        key: Option<(RequestKey, usize)>,
+        completion: Callback,
+    ) {
+        if self.is_down() {
+            completion.call(self, Err(anyhow!("the language server isn't running")));
+            return;
+        }
+        let (request, stale) = {
             let mut state = self.state.lock();
             let next_id = state.next_id;
             state.pending.insert(next_id, completion);
             state.next_id += 1;
-
-            JsonRpc::request_with_params(Id::Num(next_id as i64), method, params)
+            let stale = key
+                .and_then(|(key, request_id)| {
+                    state.in_flight.insert(key, (request_id, next_id))
+                })
+                .map(|(_, id)| id);
+
+            (
+                JsonRpc::request_with_params(
+                    Id::Num(next_id as i64),
+                    method,
+                    params,
+                ),
+                stale,
+            )
         };
 
+        if let Some(id) = stale {
+            self.send_cancel(id);
+        }
         self.send_rpc(&to_value(&request).unwrap());
     }
 
+    /// Cancel the request if it's the one with the key that the server is
+    /// working on
+    fn cancel_request(&self, key: RequestKey, request_id: usize) {
+        let id = {
+            let mut state = self.state.lock();
+            match state.in_flight.get(&key) {
+                Some((in_flight, id)) if *in_flight == request_id => {
+                    let id = *id;
+                    state.in_flight.remove(&key);
+                    id
+                }
+                _ => return,
+            }
+        };
+        self.send_cancel(id);
+    }
+
+    fn send_cancel(&self, id: u64) {
+        // The server doesn't have to answer it anymore
+        self.state
+            .lock()
+            .traced_requests
+            .remove(&(LspTraceDirection::Sent, id.to_string()));
+        let params = CancelParams {
+            id: NumberOrString::Number(id as i32),
+        };
+        self.send_notification(
+            "$/cancelRequest",
+            Params::from(serde_json::to_value(params).unwrap()),
+        );
+    }
+
     pub fn send_success_response(&self, id: Id, result: &Value) {
         let response = JsonRpc::success(id, result);
 
@@ -1147,19 +2859,50 @@ impl LspClient {
     }
 
     fn initialize(&self) {
-        if let Some(workspace) = self.dispatcher.workspace.lock().clone() {
-            let root_url = Url::from_directory_path(workspace).unwrap();
+        let root = self
+            .server
+            .root
+            .clone()
+            .or_else(|| self.dispatcher.workspace.lock().clone());
+        if let Some(root) = root {
+            let root_url = Url::from_directory_path(root).unwrap();
             let (sender, receiver) = channel();
             self.send_initialize(Some(root_url), move |lsp_client, result| {
                 if let Ok(result) = result {
                     {
+                        let type_hierarchy_provider = match result
+                            .pointer("/capabilities/typeHierarchyProvider")
+                        {
+                            Some(Value::Bool(enabled)) => *enabled,
+                            Some(Value::Object(_)) => true,
+                            _ => false,
+                        };
+                        let diagnostic_provider = result
+                            .pointer("/capabilities/diagnosticProvider")
+                            .and_then(|provider| {
+                                serde_json::from_value(provider.clone()).ok()
+                            });
                         let init_result: InitializeResult =
                             serde_json::from_value(result).unwrap();
                         let mut state = lsp_client.state.lock();
                         state.server_capabilities = Some(init_result.capabilities);
+                        state.type_hierarchy_provider = type_hierarchy_provider;
+                        state.diagnostic_provider = diagnostic_provider;
                         state.is_initialized = true;
                     }
                     lsp_client.send_initialized();
+                    let uris: Vec<Url> = lsp_client
+                        .state
+                        .lock()
+                        .opened_documents
+                        .values()
+                        .cloned()
+                        .collect();
+                    for uri in uris {
+                        lsp_client.send_signature_help_triggers(&uri);
+                    }
+                    lsp_client.pull_workspace_diagnostics();
+                    lsp_client.set_status(LanguageServerStatus::Running);
                 }
                 let _ = sender.send(true);
             });
@@ -1167,11 +2910,15 @@ impl LspClient {
         }
     }
 
+    /// Open the document on the server, at the version that is the revision
+    /// of the buffer, as the versions of `didChange` and of the workspace
+    /// edits that the editor checks are
     pub fn send_did_open(
         &self,
         buffer_id: &BufferId,
         document_uri: Url,
         language_id: &str,
+        version: i32,
         document_text: String,
     ) {
         let is_initialized = {
@@ -1187,33 +2934,200 @@ impl LspClient {
             return;
         }
 
-        let text_document_did_open_params = DidOpenTextDocumentParams {
-            text_document: TextDocumentItem {
-                language_id: language_id.to_string(),
-                uri: document_uri,
-                version: 0,
-                text: document_text,
-            },
-        };
-        let params = Params::from(
-            serde_json::to_value(text_document_did_open_params).unwrap(),
+        let text_document_did_open_params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                language_id: language_id.to_string(),
+                uri: document_uri,
+                version,
+                text: document_text,
+            },
+        };
+        let uri = text_document_did_open_params.text_document.uri.clone();
+        let params = Params::from(
+            serde_json::to_value(text_document_did_open_params).unwrap(),
+        );
+        self.send_notification("textDocument/didOpen", params);
+        self.send_signature_help_triggers(&uri);
+        self.pull_document_diagnostics(Some(*buffer_id));
+    }
+
+    /// Tell the editor which characters that are typed in the document
+    /// trigger the signature help of the server, so that it only asks for
+    /// the signature help when one of them is typed
+    fn send_signature_help_triggers(&self, uri: &Url) {
+        let options = self
+            .state
+            .lock()
+            .server_capabilities
+            .as_ref()
+            .and_then(|cap| cap.signature_help_provider.clone());
+        if let (Some(options), Ok(path)) = (options, uri.to_file_path()) {
+            self.dispatcher.send_rpc_notification(
+                CoreNotification::SignatureHelpTriggers {
+                    path,
+                    server: self.server.name.clone(),
+                    triggers: SignatureTriggers {
+                        trigger_characters: options
+                            .trigger_characters
+                            .unwrap_or_default(),
+                        retrigger_characters: options
+                            .retrigger_characters
+                            .unwrap_or_default(),
+                    },
+                },
+            );
+        }
+    }
+
+    pub fn send_did_close(&self, uri: Url) {
+        let params = DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri },
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_notification("textDocument/didClose", params);
+    }
+
+    pub fn send_did_save(&self, uri: Url, text: Option<String>) {
+        let params = DidSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri },
+            text,
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_notification("textDocument/didSave", params);
+    }
+
+    /// Queue the changes of the files that the server watches, which are
+    /// sent together once no more changes come in for a while
+    fn files_changed(&self, changes: &[(PathBuf, FileChangeType)]) {
+        let schedule = {
+            let mut state = self.state.lock();
+            let events: Vec<FileEvent> = changes
+                .iter()
+                .filter(|(path, typ)| {
+                    state
+                        .watched_files
+                        .iter()
+                        .any(|watched| watched.matches(path, *typ))
+                })
+                .filter_map(|(path, typ)| {
+                    Some(FileEvent {
+                        uri: Url::from_file_path(path).ok()?,
+                        typ: *typ,
+                    })
+                })
+                .collect();
+            if events.is_empty() {
+                return;
+            }
+            for event in events {
+                if !state.changed_files.contains(&event) {
+                    state.changed_files.push(event);
+                }
+            }
+            let schedule = state.last_file_change.is_none();
+            state.last_file_change = Some(Instant::now());
+            schedule
+        };
+        if !schedule {
+            return;
+        }
+
+        let client = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(WATCHED_FILES_DELAY);
+            let changes = {
+                let mut state = client.state.lock();
+                if let Some(last) = state.last_file_change {
+                    if last.elapsed() < WATCHED_FILES_DELAY {
+                        continue;
+                    }
+                }
+                state.last_file_change = None;
+                std::mem::take(&mut state.changed_files)
+            };
+            if !changes.is_empty() {
+                let params = DidChangeWatchedFilesParams { changes };
+                client.send_notification(
+                    "workspace/didChangeWatchedFiles",
+                    Params::from(to_value(params).unwrap()),
+                );
+            }
+            return;
+        });
+    }
+
+    /// Whether the server registered for the request before the file
+    /// operation, or for the notification after it
+    fn handles_file_operation(&self, operation: &FileOperation, will: bool) -> bool {
+        let workspace = self.dispatcher.workspace.lock().clone();
+        let state = self.state.lock();
+        if !state.is_initialized {
+            return false;
+        }
+        state
+            .server_capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.workspace.as_ref())
+            .and_then(|workspace| workspace.file_operations.as_ref())
+            .and_then(|operations| operation.options(operations, will))
+            .map(|options| {
+                options.filters.iter().any(|filter| {
+                    file_operation_filter_matches(
+                        filter,
+                        workspace.as_deref(),
+                        operation.path(),
+                        operation.is_dir(),
+                    )
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    /// Ask the server for the edit that goes with the file operation before
+    /// it's made, waiting for a short while at most
+    pub fn will_operate_on_files(
+        &self,
+        operation: &FileOperation,
+    ) -> Option<WorkspaceEdit> {
+        if !self.handles_file_operation(operation, true) {
+            return None;
+        }
+        let (sender, receiver) = channel();
+        self.send_request(
+            operation.method(true),
+            operation.params(),
+            Box::new(move |_: &LspClient, result: Result<Value>| {
+                let _ = sender.send(result);
+            }),
         );
-        self.send_notification("textDocument/didOpen", params);
+        let result = receiver.recv_timeout(FILE_OPERATION_TIMEOUT).ok()?.ok()?;
+        serde_json::from_value::<Option<WorkspaceEdit>>(result)
+            .ok()
+            .flatten()
     }
 
-    pub fn send_did_save(&self, uri: Url, text: Option<String>) {
-        let params = DidSaveTextDocumentParams {
-            text_document: TextDocumentIdentifier { uri },
-            text,
-        };
-        let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_notification("textDocument/didSave", params);
+    /// Tell the server about the file operation once it's made
+    pub fn did_operate_on_files(&self, operation: &FileOperation) {
+        if self.handles_file_operation(operation, false) {
+            self.send_notification(operation.method(false), operation.params());
+        }
     }
 
     pub fn send_initialized(&self) {
         self.send_notification("initialized", Params::from(json!({})));
     }
 
+    /// Send the server only its own section of the `[lsp]` settings, under
+    /// its name, so that it doesn't see the other servers' settings
+    pub fn send_did_change_configuration(&self, settings: &Value) {
+        let name = &self.server.name;
+        let settings = json!({ name: configuration_section(settings, Some(name)) });
+        let params = Params::from(
+            serde_json::to_value(DidChangeConfigurationParams { settings }).unwrap(),
+        );
+        self.send_notification("workspace/didChangeConfiguration", params);
+    }
+
     pub fn send_initialize<CB>(&self, root_uri: Option<Url>, on_init: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
@@ -1237,19 +3151,20 @@ impl LspClient {
                     }),
                     ..Default::default()
                 }),
-                // signature_help: Some(SignatureHelpCapability {
-                //     signature_information: Some(SignatureInformationSettings {
-                //         parameter_information: Some(ParameterInformationSettings {
-                //             label_offset_support: Some(true),
-                //         }),
-                //         active_parameter_support: Some(true),
-                //         documentation_format: Some(vec![
-                //             MarkupKind::Markdown,
-                //             MarkupKind::PlainText,
-                //         ]),
-                //     }),
-                //     ..Default::default()
-                // }),
+                signature_help: Some(SignatureHelpClientCapabilities {
+                    signature_information: Some(SignatureInformationSettings {
+                        parameter_information: Some(ParameterInformationSettings {
+                            label_offset_support: Some(true),
+                        }),
+                        active_parameter_support: Some(true),
+                        documentation_format: Some(vec![
+                            MarkupKind::Markdown,
+                            MarkupKind::PlainText,
+                        ]),
+                    }),
+                    context_support: Some(true),
+                    ..Default::default()
+                }),
                 hover: Some(HoverClientCapabilities {
                     content_format: Some(vec![
                         MarkupKind::Markdown,
@@ -1258,8 +3173,18 @@ impl LspClient {
                     ..Default::default()
                 }),
                 inlay_hint: Some(InlayHintClientCapabilities {
+                    resolve_support: Some(InlayHintResolveClientCapabilities {
+                        properties: vec!["textEdits".to_string()],
+                    }),
                     ..Default::default()
                 }),
+                document_highlight: Some(
+                    DocumentHighlightClientCapabilities::default(),
+                ),
+                linked_editing_range: Some(
+                    LinkedEditingRangeClientCapabilities::default(),
+                ),
+                code_lens: Some(CodeLensClientCapabilities::default()),
                 code_action: Some(CodeActionClientCapabilities {
                     code_action_literal_support: Some(CodeActionLiteralSupport {
                         code_action_kind: CodeActionKindLiteralSupport {
@@ -1284,14 +3209,39 @@ impl LspClient {
                     ..Default::default()
                 }),
                 semantic_tokens: Some(SemanticTokensClientCapabilities {
+                    requests: SemanticTokensClientCapabilitiesRequests {
+                        range: Some(true),
+                        full: Some(SemanticTokensFullOptions::Delta {
+                            delta: Some(true),
+                        }),
+                    },
+                    ..Default::default()
+                }),
+                range_formatting: Some(
+                    DocumentRangeFormattingClientCapabilities::default(),
+                ),
+                on_type_formatting: Some(
+                    DocumentOnTypeFormattingClientCapabilities::default(),
+                ),
+                rename: Some(RenameClientCapabilities {
+                    prepare_support: Some(true),
                     ..Default::default()
                 }),
+                call_hierarchy: Some(CallHierarchyClientCapabilities::default()),
                 type_definition: Some(GotoCapability {
                     // Note: This is explicitly specified rather than left to the Default because
                     // of a bug in lsp-types https://github.com/gluon-lang/lsp-types/pull/244
                     link_support: Some(false),
                     ..Default::default()
                 }),
+                implementation: Some(GotoCapability {
+                    link_support: Some(false),
+                    ..Default::default()
+                }),
+                declaration: Some(GotoCapability {
+                    link_support: Some(false),
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             window: Some(WindowClientCapabilities {
@@ -1308,6 +3258,37 @@ impl LspClient {
                     ..Default::default()
                 }),
                 configuration: Some(true),
+                did_change_configuration: Some(
+                    DynamicRegistrationClientCapabilities::default(),
+                ),
+                apply_edit: Some(true),
+                did_change_watched_files: Some(
+                    DidChangeWatchedFilesClientCapabilities {
+                        dynamic_registration: Some(true),
+                    },
+                ),
+                file_operations: Some(WorkspaceFileOperationsClientCapabilities {
+                    dynamic_registration: Some(false),
+                    did_create: Some(true),
+                    will_create: Some(true),
+                    did_rename: Some(true),
+                    will_rename: Some(true),
+                    did_delete: Some(true),
+                    will_delete: Some(true),
+                }),
+                workspace_edit: Some(WorkspaceEditClientCapabilities {
+                    document_changes: Some(true),
+                    ..Default::default()
+                }),
+                execute_command: Some(
+                    DynamicRegistrationClientCapabilities::default(),
+                ),
+                semantic_tokens: Some(SemanticTokensWorkspaceClientCapabilities {
+                    refresh_support: Some(true),
+                }),
+                inlay_hint: Some(InlayHintWorkspaceClientCapabilities {
+                    refresh_support: Some(true),
+                }),
                 ..Default::default()
             }),
 
@@ -1318,6 +3299,13 @@ impl LspClient {
             // So, currently, we only send/receive UTF16 positions/offsets from the LSP clients
             experimental: Some(json!({
                 "serverStatusNotification": true,
+                // The code lens commands that are run by the editor
+                "commands": {
+                    "commands": [
+                        "rust-analyzer.runSingle",
+                        "rust-analyzer.showReferences",
+                    ],
+                },
             })),
             ..Default::default()
         };
@@ -1328,7 +3316,7 @@ impl LspClient {
             root_uri: root_uri.clone(),
             initialization_options: self.options.clone(),
             capabilities: client_capabilities,
-            trace: Some(TraceValue::Verbose),
+            trace: Some(self.trace_value()),
             workspace_folders: root_uri.map(|uri| {
                 vec![WorkspaceFolder {
                     name: uri.as_str().to_string(),
@@ -1340,7 +3328,18 @@ impl LspClient {
             locale: None,
         };
 
-        let params = Params::from(serde_json::to_value(init_params).unwrap());
+        let mut params = serde_json::to_value(init_params).unwrap();
+        // lsp-types doesn't have the type hierarchy client capability yet
+        params["capabilities"]["textDocument"]["typeHierarchy"] =
+            json!({ "dynamicRegistration": false });
+        // Nor the pull diagnostics
+        params["capabilities"]["textDocument"]["diagnostic"] = json!({
+            "dynamicRegistration": false,
+            "relatedDocumentSupport": true,
+        });
+        params["capabilities"]["workspace"]["diagnostics"] =
+            json!({ "refreshSupport": true });
+        let params = Params::from(params);
         self.send_request("initialize", params, Box::new(on_init));
     }
 
@@ -1375,33 +3374,351 @@ impl LspClient {
     {
         let params = DocumentFormattingParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
-            options: FormattingOptions {
-                tab_size: 4,
-                insert_spaces: true,
-                ..Default::default()
-            },
+            options: formatting_options(),
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
         self.send_request("textDocument/formatting", params, Box::new(cb));
     }
 
-    pub fn request_semantic_tokens<CB>(&self, document_uri: Url, cb: CB)
+    pub fn request_document_range_formatting<CB>(
+        &self,
+        document_uri: Url,
+        range: Range,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            range,
+            options: formatting_options(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/rangeFormatting", params, Box::new(cb));
+    }
+
+    pub fn request_on_type_formatting<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        ch: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentOnTypeFormattingParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            ch,
+            options: formatting_options(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/onTypeFormatting", params, Box::new(cb));
+    }
+
+    pub fn request_prepare_rename<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            position,
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareRename", params, Box::new(cb));
+    }
+
+    pub fn request_rename<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        new_name: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            new_name,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/rename", params, Box::new(cb));
+    }
+
+    pub fn request_prepare_call_hierarchy<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareCallHierarchy", params, Box::new(cb));
+    }
+
+    pub fn request_call_hierarchy_incoming_calls<CB>(
+        &self,
+        item: CallHierarchyItem,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = CallHierarchyIncomingCallsParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("callHierarchy/incomingCalls", params, Box::new(cb));
+    }
+
+    pub fn request_call_hierarchy_outgoing_calls<CB>(
+        &self,
+        item: CallHierarchyItem,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = CallHierarchyOutgoingCallsParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("callHierarchy/outgoingCalls", params, Box::new(cb));
+    }
+
+    pub fn request_prepare_type_hierarchy<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        // The params are the same as for the call hierarchy
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareTypeHierarchy", params, Box::new(cb));
+    }
+
+    /// Request the supertypes or subtypes of the item, which has the same
+    /// fields as a call hierarchy item
+    pub fn request_type_hierarchy<CB>(
+        &self,
+        method: &str,
+        item: CallHierarchyItem,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = Params::from(json!({ "item": item }));
+        self.send_request(method, params, Box::new(cb));
+    }
+
+    pub fn request_document_highlight<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentHighlightParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/documentHighlight", params, Box::new(cb));
+    }
+
+    pub fn request_linked_editing_range<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = LinkedEditingRangeParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/linkedEditingRange", params, Box::new(cb));
+    }
+
+    pub fn request_code_lens<CB>(&self, document_uri: Url, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
-        let params = SemanticTokensParams {
+        let params = CodeLensParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/semanticTokens/full", params, Box::new(cb));
+        self.send_request("textDocument/codeLens", params, Box::new(cb));
     }
 
-    pub fn request_inlay_hints<CB>(&self, document_uri: Url, range: Range, cb: CB)
+    pub fn request_code_lens_resolve<CB>(&self, code_lens: CodeLens, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = Params::from(serde_json::to_value(code_lens).unwrap());
+        self.send_request("codeLens/resolve", params, Box::new(cb));
+    }
+
+    pub fn request_execute_command<CB>(&self, command: lsp_types::Command, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = ExecuteCommandParams {
+            command: command.command,
+            arguments: command.arguments.unwrap_or_default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("workspace/executeCommand", params, Box::new(cb));
+    }
+
+    /// Request the semantic tokens of the whole document, as the edits of the
+    /// tokens of the previous result if there's one
+    pub fn request_semantic_tokens<CB>(
+        &self,
+        buffer_id: BufferId,
+        document_uri: Url,
+        previous_result_id: Option<String>,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let text_document = TextDocumentIdentifier { uri: document_uri };
+        let (method, params) = match previous_result_id {
+            Some(previous_result_id) => (
+                "textDocument/semanticTokens/full/delta",
+                serde_json::to_value(SemanticTokensDeltaParams {
+                    text_document,
+                    previous_result_id,
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                }),
+            ),
+            None => (
+                "textDocument/semanticTokens/full",
+                serde_json::to_value(SemanticTokensParams {
+                    text_document,
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                }),
+            ),
+        };
+        let params = Params::from(params.unwrap());
+        self.send_keyed_request(
+            method,
+            params,
+            Some((RequestKey::SemanticTokens(buffer_id), 0)),
+            Box::new(cb),
+        );
+    }
+
+    pub fn request_semantic_tokens_range<CB>(
+        &self,
+        buffer_id: BufferId,
+        document_uri: Url,
+        range: Range,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = SemanticTokensRangeParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            range,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_keyed_request(
+            "textDocument/semanticTokens/range",
+            params,
+            Some((RequestKey::SemanticTokens(buffer_id), 0)),
+            Box::new(cb),
+        );
+    }
+
+    /// The styles of the semantic tokens of the buffer, unless it changed
+    /// since they were requested, as another request is made for the new
+    /// revision then
+    fn semantic_styles(
+        &self,
+        old_buffer: &Buffer,
+        tokens: &[SemanticToken],
+        partial: bool,
+    ) -> Result<Value> {
+        let buffers = self.dispatcher.buffers.lock();
+        let buffer = buffers
+            .get(&old_buffer.id)
+            .ok_or_else(|| anyhow!("buffer not found"))?;
+        if buffer.rev != old_buffer.rev {
+            return Err(anyhow!("revision changed"));
+        }
+
+        let legend = semantic_tokens_options(&self.state.lock())
+            .map(|opts| opts.legend.clone())
+            .ok_or_else(|| anyhow!("no semantic tokens legend"))?;
+        let styles = format_semantic_styles(buffer, &legend, tokens)
+            .ok_or_else(|| anyhow!("can't format semantic styles"))?;
+        Ok(serde_json::to_value(SemanticStyles {
+            rev: buffer.rev,
+            buffer_id: buffer.id,
+            path: buffer.path.clone(),
+            styles,
+            len: buffer.len(),
+            partial,
+        })?)
+    }
+
+    pub fn request_inlay_hints<CB>(
+        &self,
+        buffer_id: BufferId,
+        document_uri: Url,
+        range: Range,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
         let params = InlayHintParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
@@ -1409,7 +3726,20 @@ impl LspClient {
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/inlayHint", params, Box::new(cb));
+        self.send_keyed_request(
+            "textDocument/inlayHint",
+            params,
+            Some((RequestKey::InlayHints(buffer_id), 0)),
+            Box::new(cb),
+        );
+    }
+
+    pub fn resolve_inlay_hint<CB>(&self, hint: &InlayHint, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = Params::from(serde_json::to_value(hint).unwrap());
+        self.send_request("inlayHint/resolve", params, Box::new(cb));
     }
 
     pub fn request_code_actions<CB>(&self, document_uri: Url, range: Range, cb: CB)
@@ -1490,8 +3820,49 @@ impl LspClient {
         self.send_request("textDocument/typeDefinition", params, Box::new(cb));
     }
 
+    pub fn request_implementation<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = GotoImplementationParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/implementation", params, Box::new(cb));
+    }
+
+    pub fn request_declaration<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = GotoDeclarationParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/declaration", params, Box::new(cb));
+    }
+
     pub fn request_completion<CB>(
         &self,
+        request_id: usize,
         document_uri: Url,
         position: Position,
         on_completion: CB,
@@ -1508,9 +3879,10 @@ impl LspClient {
             context: None,
         };
         let params = Params::from(serde_json::to_value(completion_params).unwrap());
-        self.send_request(
+        self.send_keyed_request(
             "textDocument/completion",
             params,
+            Some((RequestKey::Completion, request_id)),
             Box::new(on_completion),
         );
     }
@@ -1526,8 +3898,13 @@ impl LspClient {
         self.send_request("completionItem/resolve", params, Box::new(on_result));
     }
 
-    pub fn request_hover<CB>(&self, document_uri: Url, position: Position, cb: CB)
-    where
+    pub fn request_hover<CB>(
+        &self,
+        request_id: usize,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
         let hover_params = HoverParams {
@@ -1538,13 +3915,20 @@ impl LspClient {
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
         let params = Params::from(serde_json::to_value(hover_params).unwrap());
-        self.send_request("textDocument/hover", params, Box::new(cb));
+        self.send_keyed_request(
+            "textDocument/hover",
+            params,
+            Some((RequestKey::Hover, request_id)),
+            Box::new(cb),
+        );
     }
 
     pub fn request_signature<CB>(
         &self,
+        request_id: usize,
         document_uri: Url,
         position: Position,
+        context: SignatureHelpContext,
         cb: CB,
     ) where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
@@ -1555,10 +3939,15 @@ impl LspClient {
                 position,
             },
             work_done_progress_params: WorkDoneProgressParams::default(),
-            context: None,
+            context: Some(context),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/signatureHelp", params, Box::new(cb));
+        self.send_keyed_request(
+            "textDocument/signatureHelp",
+            params,
+            Some((RequestKey::SignatureHelp, request_id)),
+            Box::new(cb),
+        );
     }
 
     pub fn send_did_change(
@@ -1604,8 +3993,262 @@ impl LspClient {
         let changes = get_change_for_sync_kind(sync_kind, buffer, content_change);
         if let Some(changes) = changes {
             self.send_did_change(buffer, changes, rev);
+            self.pull_document_diagnostics(Some(buffer.id));
+        }
+    }
+
+    /// Request the diagnostics of the open documents from a server that
+    /// is asked for them, either all of them or the one that changed, once
+    /// the documents stop changing for a while. The others are asked again
+    /// after a change too if the server says that their diagnostics depend
+    /// on each other.
+    fn pull_document_diagnostics(&self, changed: Option<BufferId>) {
+        let schedule = {
+            let mut state = self.state.lock();
+            let inter_file_dependencies = match state.diagnostic_provider.as_ref() {
+                Some(provider) => provider.inter_file_dependencies,
+                None => return,
+            };
+            let buffer_ids: Vec<BufferId> = state
+                .opened_documents
+                .keys()
+                .filter(|buffer_id| {
+                    inter_file_dependencies
+                        || changed.is_none()
+                        || changed == Some(**buffer_id)
+                })
+                .copied()
+                .collect();
+            state.diagnostic_pulls.extend(buffer_ids);
+            let schedule = state.last_diagnostic_pull.is_none();
+            state.last_diagnostic_pull = Some(Instant::now());
+            schedule
+        };
+        if !schedule {
+            return;
         }
+
+        let client = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(DIAGNOSTICS_PULL_DELAY);
+            let buffer_ids = {
+                let mut state = client.state.lock();
+                if let Some(last) = state.last_diagnostic_pull {
+                    if last.elapsed() < DIAGNOSTICS_PULL_DELAY {
+                        continue;
+                    }
+                }
+                state.last_diagnostic_pull = None;
+                std::mem::take(&mut state.diagnostic_pulls)
+            };
+            client.send_diagnostic_pulls(buffer_ids);
+            return;
+        });
+    }
+
+    /// Request the diagnostics of the documents that are still open. A
+    /// request of a document cancels the one before it if the server is
+    /// still working on that.
+    fn send_diagnostic_pulls(&self, buffer_ids: HashSet<BufferId>) {
+        let requests: Vec<(BufferId, Value)> = {
+            let state = self.state.lock();
+            let provider = match state.diagnostic_provider.as_ref() {
+                Some(provider) => provider,
+                None => return,
+            };
+            buffer_ids
+                .into_iter()
+                .filter_map(|buffer_id| {
+                    let uri = state.opened_documents.get(&buffer_id)?;
+                    let mut params = json!({ "textDocument": { "uri": uri } });
+                    if let Some(identifier) = provider.identifier.as_ref() {
+                        params["identifier"] = json!(identifier);
+                    }
+                    if let Some(result_id) = state.diagnostic_result_ids.get(uri) {
+                        params["previousResultId"] = json!(result_id);
+                    }
+                    Some((buffer_id, params))
+                })
+                .collect()
+        };
+
+        for (buffer_id, params) in requests {
+            self.send_keyed_request(
+                "textDocument/diagnostic",
+                Params::from(params.clone()),
+                Some((RequestKey::Diagnostics(buffer_id), 0)),
+                Box::new(move |client: &LspClient, result: Result<Value>| {
+                    let uri = params
+                        .pointer("/textDocument/uri")
+                        .and_then(|uri| uri.as_str())
+                        .and_then(|uri| Url::parse(uri).ok());
+                    match (uri, result) {
+                        (Some(uri), Ok(report)) => {
+                            client.apply_diagnostic_report(uri, &report);
+                        }
+                        (_, Err(e)) if retrigger(&e) => {
+                            client.pull_document_diagnostics(Some(buffer_id));
+                        }
+                        _ => {}
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Request the diagnostics of all the documents of the workspace, the
+    /// ones that aren't open too, if the server can report them
+    fn pull_workspace_diagnostics(&self) {
+        let params = {
+            let state = self.state.lock();
+            let provider = match state.diagnostic_provider.as_ref() {
+                Some(provider) if provider.workspace_diagnostics => provider,
+                _ => return,
+            };
+            let previous_result_ids: Vec<Value> = state
+                .diagnostic_result_ids
+                .iter()
+                .map(|(uri, result_id)| json!({ "uri": uri, "value": result_id }))
+                .collect();
+            let mut params = json!({ "previousResultIds": previous_result_ids });
+            if let Some(identifier) = provider.identifier.as_ref() {
+                params["identifier"] = json!(identifier);
+            }
+            params
+        };
+
+        self.send_keyed_request(
+            "workspace/diagnostic",
+            Params::from(params),
+            Some((RequestKey::WorkspaceDiagnostics, 0)),
+            Box::new(move |client: &LspClient, result: Result<Value>| {
+                if let Err(e) = result.as_ref() {
+                    if retrigger(e) {
+                        let client = client.clone();
+                        thread::spawn(move || {
+                            thread::sleep(DIAGNOSTICS_PULL_DELAY);
+                            client.pull_workspace_diagnostics();
+                        });
+                    }
+                    return;
+                }
+                let reports = result
+                    .ok()
+                    .and_then(|result| result.get("items").cloned())
+                    .and_then(|items| items.as_array().cloned())
+                    .unwrap_or_default();
+                for report in reports {
+                    let uri = report
+                        .get("uri")
+                        .and_then(|uri| uri.as_str())
+                        .and_then(|uri| Url::parse(uri).ok());
+                    if let Some(uri) = uri {
+                        client.apply_diagnostic_report(uri, &report);
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Apply a pulled diagnostic report of the document, which is either
+    /// `full` with the diagnostics, or `unchanged` since the `resultId`
+    /// that was sent with the request
+    fn apply_diagnostic_report(&self, uri: Url, report: &Value) {
+        for report in diagnostic_reports(uri, report) {
+            if let Some(result_id) = report.result_id {
+                self.state
+                    .lock()
+                    .diagnostic_result_ids
+                    .insert(report.uri.clone(), result_id);
+            }
+            if let Some(diagnostics) = report.diagnostics {
+                self.publish_diagnostics(report.uri, diagnostics);
+            }
+        }
+    }
+}
+
+/// Whether the error is of a request that the server cancelled and wants to
+/// be sent again
+fn retrigger(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ServerCancelled>()
+        .map(|cancelled| cancelled.retrigger)
+        .unwrap_or(false)
+}
+
+/// The pulled diagnostic report of a document
+#[derive(Debug, PartialEq)]
+struct DiagnosticReport {
+    uri: Url,
+    result_id: Option<String>,
+    /// The diagnostics of a `full` report, which are `None` when they're
+    /// `unchanged`
+    diagnostics: Option<Vec<Diagnostic>>,
+}
+
+/// The reports of the document and of its `relatedDocuments` in a pulled
+/// diagnostic report
+fn diagnostic_reports(uri: Url, report: &Value) -> Vec<DiagnosticReport> {
+    let diagnostics = if report.get("kind").and_then(|kind| kind.as_str())
+        == Some("full")
+    {
+        let diagnostics = report
+            .get("items")
+            .cloned()
+            .and_then(|items| serde_json::from_value::<Vec<Diagnostic>>(items).ok())
+            .unwrap_or_default();
+        Some(diagnostics)
+    } else {
+        None
+    };
+    let mut reports = vec![DiagnosticReport {
+        uri,
+        result_id: report
+            .get("resultId")
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string()),
+        diagnostics,
+    }];
+
+    if let Some(related) = report
+        .get("relatedDocuments")
+        .and_then(|related| related.as_object())
+    {
+        for (related_uri, related_report) in related {
+            if let Ok(related_uri) = Url::parse(related_uri) {
+                reports.extend(diagnostic_reports(related_uri, related_report));
+            }
+        }
+    }
+    reports
+}
+
+/// Replace the diagnostics that the server with the id has for the document,
+/// and return the ones of all of its servers, in the order that they were
+/// started
+fn merge_server_diagnostics(
+    all_diagnostics: &mut HashMap<Url, HashMap<u64, Vec<Diagnostic>>>,
+    uri: &Url,
+    id: u64,
+    diagnostics: Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    let servers = all_diagnostics.entry(uri.clone()).or_default();
+    if diagnostics.is_empty() {
+        servers.remove(&id);
+    } else {
+        servers.insert(id, diagnostics);
+    }
+    let mut ids: Vec<u64> = servers.keys().copied().collect();
+    ids.sort_unstable();
+    let merged: Vec<Diagnostic> = ids
+        .iter()
+        .flat_map(|id| servers[id].iter().cloned())
+        .collect();
+    if servers.is_empty() {
+        all_diagnostics.remove(uri);
     }
+    merged
 }
 
 pub enum LspHeader {
@@ -1613,6 +4256,214 @@ pub enum LspHeader {
     ContentLength(usize),
 }
 
+fn has_rename_provider(state: &LspState) -> bool {
+    matches!(
+        state
+            .server_capabilities
+            .as_ref()
+            .and_then(|cap| cap.rename_provider.as_ref()),
+        Some(OneOf::Left(true)) | Some(OneOf::Right(_))
+    )
+}
+
+fn has_call_hierarchy_provider(state: &LspState) -> bool {
+    state
+        .server_capabilities
+        .as_ref()
+        .and_then(|cap| cap.call_hierarchy_provider.as_ref())
+        .map(|prov| prov != &CallHierarchyServerCapability::Simple(false))
+        .unwrap_or(false)
+}
+
+fn has_completion_provider(state: &LspState) -> bool {
+    state
+        .server_capabilities
+        .as_ref()
+        .map(|cap| cap.completion_provider.is_some())
+        .unwrap_or(false)
+}
+
+/// Tell the user that the server couldn't be started
+fn show_start_error(dispatcher: &Dispatcher, name: &str, error: &anyhow::Error) {
+    dispatcher.send_notification(
+        "show_message",
+        json!({
+            "title": name,
+            "message": ShowMessageParams {
+                typ: MessageType::ERROR,
+                message: format!("Failed to start the language server: {error}"),
+            },
+        }),
+    );
+}
+
+/// The key in the data of a completion item that has the id of its server
+const COMPLETION_SERVER_KEY: &str = "lapceServer";
+
+/// Remember the server of the completion item in its data, so that it's
+/// resolved by the same server
+fn tag_completion_item(item: &mut Value, server: u64) {
+    if let Value::Object(item) = item {
+        let data = item.remove("data").unwrap_or(Value::Null);
+        item.insert(
+            "data".to_string(),
+            json!({
+                COMPLETION_SERVER_KEY: server,
+                "data": data,
+            }),
+        );
+    }
+}
+
+/// Restore the data of a completion item tagged by `tag_completion_item`,
+/// returning the id of its server
+fn untag_completion_item(item: &mut CompletionItem) -> Option<u64> {
+    let data = item.data.take()?;
+    match data.get(COMPLETION_SERVER_KEY).and_then(|id| id.as_u64()) {
+        Some(server) => {
+            item.data = data.get("data").filter(|data| !data.is_null()).cloned();
+            Some(server)
+        }
+        None => {
+            item.data = Some(data);
+            None
+        }
+    }
+}
+
+/// Merge the completions of the servers into one list, leaving out the items
+/// that an earlier server already suggested
+fn merge_completions(results: Vec<(u64, Value)>) -> Value {
+    let mut is_incomplete = false;
+    let mut seen = HashSet::new();
+    let mut items = Vec::new();
+    for (server, result) in results {
+        let server_items = match result {
+            Value::Array(server_items) => server_items,
+            Value::Object(mut list) => {
+                is_incomplete |= list
+                    .get("isIncomplete")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                match list.remove("items") {
+                    Some(Value::Array(server_items)) => server_items,
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        };
+        for mut item in server_items {
+            let key = json!([
+                item.get("label"),
+                item.get("kind"),
+                item.get("insertText"),
+                item.pointer("/textEdit/newText"),
+            ])
+            .to_string();
+            if seen.insert(key) {
+                tag_completion_item(&mut item, server);
+                items.push(item);
+            }
+        }
+    }
+    json!({
+        "isIncomplete": is_incomplete,
+        "items": items,
+    })
+}
+
+/// The code actions of all the servers
+fn merge_code_actions(results: Vec<(u64, Value)>) -> Value {
+    let actions: Vec<Value> = results
+        .into_iter()
+        .flat_map(|(_, result)| match result {
+            Value::Array(actions) => actions,
+            _ => Vec::new(),
+        })
+        .collect();
+    Value::Array(actions)
+}
+
+/// The keys of the `[lsp]` table that are settings of the editor rather than
+/// of a server, which are never sent to the servers
+const RESERVED_LSP_SETTINGS: &[&str] = &["servers", "formatter", "trace"];
+
+/// The settings of a `workspace/configuration` item. The section is looked
+/// up as a key of the `[lsp]` table first, e.g. `[lsp.gopls]`, and then as a
+/// dotted path into it, e.g. `python.analysis` in `[lsp.python.analysis]`.
+fn configuration_section(settings: &Value, section: Option<&str>) -> Value {
+    let section = match section {
+        Some(section) if !section.is_empty() => section,
+        _ => {
+            let mut settings = settings.clone();
+            if let Some(settings) = settings.as_object_mut() {
+                for key in RESERVED_LSP_SETTINGS {
+                    settings.remove(*key);
+                }
+            }
+            return settings;
+        }
+    };
+    let key = section.split('.').next().unwrap_or(section);
+    if RESERVED_LSP_SETTINGS.contains(&key) {
+        return Value::Null;
+    }
+    if let Some(value) = settings.get(section) {
+        return value.clone();
+    }
+    section
+        .split('.')
+        .try_fold(settings, |value, key| value.get(key))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+/// The options sent with every formatting request
+fn formatting_options() -> FormattingOptions {
+    FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+        ..Default::default()
+    }
+}
+
+/// The context of a signature help request, which is `None` when the typed
+/// `trigger` neither triggers nor retriggers the signature help
+fn signature_help_context(
+    options: &SignatureHelpOptions,
+    trigger: Option<String>,
+    active: Option<SignatureHelp>,
+) -> Option<SignatureHelpContext> {
+    let is_retrigger = active.is_some();
+    let (trigger_kind, trigger_character) = match trigger {
+        Some(ch) => {
+            let contains = |chars: &Option<Vec<String>>| {
+                chars
+                    .as_ref()
+                    .map(|chars| chars.contains(&ch))
+                    .unwrap_or(false)
+            };
+            if contains(&options.trigger_characters)
+                || (is_retrigger && contains(&options.retrigger_characters))
+            {
+                (SignatureHelpTriggerKind::TRIGGER_CHARACTER, Some(ch))
+            } else if is_retrigger {
+                (SignatureHelpTriggerKind::CONTENT_CHANGE, None)
+            } else {
+                return None;
+            }
+        }
+        None if is_retrigger => (SignatureHelpTriggerKind::CONTENT_CHANGE, None),
+        None => (SignatureHelpTriggerKind::INVOKED, None),
+    };
+    Some(SignatureHelpContext {
+        trigger_kind,
+        trigger_character,
+        is_retrigger,
+        active_signature_help: active,
+    })
+}
+
 fn number_from_id(id: &Id) -> u64 {
     match *id {
         Id::Num(n) => n as u64,
@@ -1697,20 +4548,114 @@ pub fn get_change_for_sync_kind(
     }
 }
 
+fn semantic_tokens_options(state: &LspState) -> Option<&SemanticTokensOptions> {
+    state
+        .server_capabilities
+        .as_ref()?
+        .semantic_tokens_provider
+        .as_ref()
+        .map(|prov| match prov {
+            SemanticTokensServerCapabilities::SemanticTokensOptions(opts) => opts,
+            SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
+                reg,
+            ) => &reg.semantic_tokens_options,
+        })
+}
+
+/// Whether the server can give the semantic tokens of the whole document
+fn semantic_tokens_full(opts: &SemanticTokensOptions) -> bool {
+    matches!(
+        opts.full,
+        Some(SemanticTokensFullOptions::Bool(true))
+            | Some(SemanticTokensFullOptions::Delta { .. })
+    )
+}
+
+/// A `semanticTokens/full/delta` result. The positions and the data of its
+/// edits are of the integers that encode the tokens, five integers each, so
+/// that an edit doesn't have to replace whole tokens.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SemanticTokensDeltaEdits {
+    result_id: Option<String>,
+    edits: Vec<SemanticTokensIntegersEdit>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SemanticTokensIntegersEdit {
+    start: usize,
+    delete_count: usize,
+    #[serde(default)]
+    data: Vec<u32>,
+}
+
+/// The result id and the tokens of a `semanticTokens/full` or
+/// `semanticTokens/full/delta` result, whose edits are applied to the
+/// previous tokens
+fn semantic_tokens_of_result(
+    value: Value,
+    previous: Option<SemanticTokensCache>,
+) -> Result<(Option<String>, Vec<SemanticToken>)> {
+    if value.get("edits").is_none() {
+        let tokens: SemanticTokens = serde_json::from_value(value)?;
+        return Ok((tokens.result_id, tokens.data));
+    }
+
+    let delta: SemanticTokensDeltaEdits = serde_json::from_value(value)?;
+    let previous = previous
+        .map(|cache| cache.data)
+        .ok_or_else(|| anyhow!("no semantic tokens to apply the edits to"))?;
+    let mut data: Vec<u32> = previous
+        .iter()
+        .flat_map(|token| {
+            [
+                token.delta_line,
+                token.delta_start,
+                token.length,
+                token.token_type,
+                token.token_modifiers_bitset,
+            ]
+        })
+        .collect();
+    let mut edits = delta.edits;
+    // The edits are of the previous tokens, so the later ones are applied
+    // first to keep the positions of the earlier ones
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+    for edit in edits {
+        let end = edit
+            .start
+            .checked_add(edit.delete_count)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| anyhow!("bad semantic tokens edit"))?;
+        data.splice(edit.start..end, edit.data);
+    }
+    if data.len() % 5 != 0 {
+        return Err(anyhow!("bad semantic tokens edit"));
+    }
+    let tokens = data
+        .chunks_exact(5)
+        .map(|token| SemanticToken {
+            delta_line: token[0],
+            delta_start: token[1],
+            length: token[2],
+            token_type: token[3],
+            token_modifiers_bitset: token[4],
+        })
+        .collect();
+    Ok((delta.result_id, tokens))
+}
+
 fn format_semantic_styles(
     buffer: &Buffer,
-    semantic_tokens_provider: &Option<SemanticTokensServerCapabilities>,
-    value: Value,
+    semantic_legends: &SemanticTokensLegend,
+    tokens: &[SemanticToken],
 ) -> Option<Vec<LineStyle>> {
-    let semantic_tokens: SemanticTokens = serde_json::from_value(value).ok()?;
-    let semantic_tokens_provider = semantic_tokens_provider.as_ref()?;
-    let semantic_legends = semantic_tokens_legend(semantic_tokens_provider);
-
     let mut highlights = Vec::new();
     let mut line = 0;
     let mut start = 0;
     let mut last_start = 0;
-    for semantic_token in &semantic_tokens.data {
+    for semantic_token in tokens {
         if semantic_token.delta_line > 0 {
             line += semantic_token.delta_line as usize;
             start = buffer.offset_of_line(line);
@@ -1756,15 +4701,412 @@ fn format_semantic_styles(
     Some(highlights)
 }
 
-fn semantic_tokens_legend(
-    semantic_tokens_provider: &SemanticTokensServerCapabilities,
-) -> SemanticTokensLegend {
-    match semantic_tokens_provider {
-        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => {
-            options.legend.clone()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(message: &str) -> Diagnostic {
+        Diagnostic {
+            message: message.to_string(),
+            ..Default::default()
         }
-        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
-            options,
-        ) => options.semantic_tokens_options.legend.clone(),
+    }
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merge_completions() {
+        let merged = merge_completions(vec![
+            (1, json!([{ "label": "a" }, { "label": "b", "kind": 3 }])),
+            (
+                2,
+                json!({
+                    "isIncomplete": true,
+                    "items": [
+                        { "label": "b", "kind": 3 },
+                        { "label": "b", "kind": 6 },
+                        { "label": "c", "data": 7 },
+                    ],
+                }),
+            ),
+        ]);
+        assert_eq!(json!(true), merged["isIncomplete"]);
+
+        // The items of the first server come first, and the same item of a
+        // later server is left out
+        let items: Vec<(String, u64, Option<Value>)> = merged["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                let mut item: CompletionItem =
+                    serde_json::from_value(item.clone()).unwrap();
+                let server = untag_completion_item(&mut item).unwrap();
+                (item.label, server, item.data)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("a".to_string(), 1, None),
+                ("b".to_string(), 1, None),
+                ("b".to_string(), 2, None),
+                ("c".to_string(), 2, Some(json!(7))),
+            ],
+            items
+        );
+    }
+
+    #[test]
+    fn test_merge_server_diagnostics() {
+        let uri = Url::parse("file:///a.rs").unwrap();
+        let mut all = HashMap::new();
+
+        let merged =
+            merge_server_diagnostics(&mut all, &uri, 2, vec![diagnostic("b")]);
+        assert_eq!(vec!["b"], messages(&merged));
+        let merged =
+            merge_server_diagnostics(&mut all, &uri, 1, vec![diagnostic("a")]);
+        assert_eq!(vec!["a", "b"], messages(&merged));
+
+        // The new diagnostics of a server replace its old ones
+        let merged =
+            merge_server_diagnostics(&mut all, &uri, 2, vec![diagnostic("c")]);
+        assert_eq!(vec!["a", "c"], messages(&merged));
+
+        let merged = merge_server_diagnostics(&mut all, &uri, 1, Vec::new());
+        assert_eq!(vec!["c"], messages(&merged));
+        let merged = merge_server_diagnostics(&mut all, &uri, 2, Vec::new());
+        assert!(merged.is_empty());
+        assert!(all.is_empty());
+    }
+
+    #[test]
+    fn test_diagnostic_reports() {
+        let uri = Url::parse("file:///a.rs").unwrap();
+        let related = Url::parse("file:///b.rs").unwrap();
+        let range = json!({
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 0, "character": 1 },
+        });
+        let report = json!({
+            "kind": "full",
+            "resultId": "1",
+            "items": [{ "range": range, "message": "a" }],
+            "relatedDocuments": {
+                "file:///b.rs": { "kind": "unchanged", "resultId": "2" },
+            },
+        });
+
+        let reports = diagnostic_reports(uri.clone(), &report);
+        assert_eq!(2, reports.len());
+        assert_eq!(uri, reports[0].uri);
+        assert_eq!(Some("1".to_string()), reports[0].result_id);
+        assert_eq!(
+            vec!["a"],
+            messages(reports[0].diagnostics.as_ref().unwrap())
+        );
+        assert_eq!(
+            DiagnosticReport {
+                uri: related,
+                result_id: Some("2".to_string()),
+                diagnostics: None,
+            },
+            reports[1]
+        );
+    }
+
+    #[test]
+    fn test_configuration_section() {
+        let settings = json!({
+            "gopls": { "a": 1 },
+            "python": { "analysis": { "b": 2 } },
+            "rust-analyzer.cargo": { "c": 3 },
+            "servers": { "my-ls": { "command": "my-ls", "env": { "TOKEN": "t" } } },
+            "formatter": { "rust": "rust-analyzer" },
+            "trace": "messages",
+        });
+        assert_eq!(
+            json!({ "a": 1 }),
+            configuration_section(&settings, Some("gopls"))
+        );
+        assert_eq!(
+            json!({ "b": 2 }),
+            configuration_section(&settings, Some("python.analysis"))
+        );
+        assert_eq!(
+            json!({ "c": 3 }),
+            configuration_section(&settings, Some("rust-analyzer.cargo"))
+        );
+        assert_eq!(
+            Value::Null,
+            configuration_section(&settings, Some("missing"))
+        );
+        assert_eq!(
+            Value::Null,
+            configuration_section(&settings, Some("servers"))
+        );
+        assert_eq!(
+            Value::Null,
+            configuration_section(&settings, Some("servers.my-ls.env"))
+        );
+        assert_eq!(
+            Value::Null,
+            configuration_section(&settings, Some("formatter"))
+        );
+        assert_eq!(
+            json!({
+                "gopls": { "a": 1 },
+                "python": { "analysis": { "b": 2 } },
+                "rust-analyzer.cargo": { "c": 3 },
+            }),
+            configuration_section(&settings, None)
+        );
+    }
+
+    #[test]
+    fn test_server_config_from_settings() {
+        let settings = json!({
+            "servers": {
+                "ok-ls": {
+                    "command": "ok-ls",
+                    "args": ["--stdio"],
+                    "languages": ["rust"],
+                    "extensions": [".md"],
+                    "root-markers": ["Cargo.toml"],
+                },
+                // No command
+                "bad-ls": { "args": [] },
+            },
+        });
+        let servers = ServerConfig::from_settings(&settings);
+        assert_eq!(1, servers.len());
+        let config = &servers["ok-ls"];
+        assert_eq!("ok-ls", config.command);
+        assert_eq!(vec!["--stdio".to_string()], config.args);
+        assert_eq!(vec!["Cargo.toml".to_string()], config.root_markers);
+
+        let server = config.server("ok-ls", None);
+        let workspace = Path::new("/workspace");
+        assert!(server.handles(
+            Some(workspace),
+            "rust",
+            Path::new("/workspace/a.rs")
+        ));
+        assert!(server.handles(
+            Some(workspace),
+            "",
+            Path::new("/workspace/doc/a.md")
+        ));
+        assert!(!server.handles(
+            Some(workspace),
+            "go",
+            Path::new("/workspace/a.go")
+        ));
+
+        assert!(ServerConfig::from_settings(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_server_config_root() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let path = manifest_dir.join("src").join("lsp.rs");
+        let config = |marker: &str| ServerConfig {
+            command: String::new(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            languages: Vec::new(),
+            extensions: Vec::new(),
+            root_markers: vec![marker.to_string()],
+            initialization_options: None,
+        };
+        assert_eq!(
+            Some(manifest_dir.to_path_buf()),
+            config("Cargo.toml").root(&path)
+        );
+        assert_eq!(
+            manifest_dir.parent().map(|dir| dir.to_path_buf()),
+            config("Cargo.lock").root(&path)
+        );
+        assert_eq!(None, config("no-such-marker").root(&path));
+    }
+
+    #[test]
+    fn test_file_operation_filter_matches() {
+        let filter = |glob: &str, matches, ignore_case| FileOperationFilter {
+            scheme: Some("file".to_string()),
+            pattern: FileOperationPattern {
+                glob: glob.to_string(),
+                matches,
+                options: Some(FileOperationPatternOptions {
+                    ignore_case: Some(ignore_case),
+                }),
+            },
+        };
+        let workspace = Path::new("/work/project");
+        let file = workspace.join("src").join("main.rs");
+        let dir = workspace.join("src");
+
+        // Relative to the workspace
+        let src = filter("src/**/*.rs", None, false);
+        assert!(file_operation_filter_matches(
+            &src,
+            Some(workspace),
+            &file,
+            false
+        ));
+        assert!(!file_operation_filter_matches(&src, None, &file, false));
+        let any = filter("**/*.rs", None, false);
+        assert!(file_operation_filter_matches(&any, None, &file, false));
+        assert!(file_operation_filter_matches(
+            &any,
+            Some(Path::new("/elsewhere")),
+            &file,
+            false
+        ));
+
+        // Files or folders only
+        let files = filter("**/src", Some(FileOperationPatternKind::File), false);
+        let folders =
+            filter("**/src", Some(FileOperationPatternKind::Folder), false);
+        assert!(!file_operation_filter_matches(
+            &files,
+            Some(workspace),
+            &dir,
+            true
+        ));
+        assert!(file_operation_filter_matches(
+            &folders,
+            Some(workspace),
+            &dir,
+            true
+        ));
+        assert!(!file_operation_filter_matches(
+            &folders,
+            Some(workspace),
+            &file,
+            false
+        ));
+
+        // Case
+        let upper = filter("SRC/**/*.RS", None, false);
+        let ignore_case = filter("SRC/**/*.RS", None, true);
+        assert!(!file_operation_filter_matches(
+            &upper,
+            Some(workspace),
+            &file,
+            false
+        ));
+        assert!(file_operation_filter_matches(
+            &ignore_case,
+            Some(workspace),
+            &file,
+            false
+        ));
+
+        // Other schemes
+        let mut untitled = filter("**/*.rs", None, false);
+        untitled.scheme = Some("untitled".to_string());
+        assert!(!file_operation_filter_matches(
+            &untitled, None, &file, false
+        ));
+    }
+
+    fn semantic_token(delta_line: u32, delta_start: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length: 3,
+            token_type: 1,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    fn semantic_tokens_cache(data: Vec<SemanticToken>) -> SemanticTokensCache {
+        SemanticTokensCache {
+            rev: 0,
+            result_id: Some("1".to_string()),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_semantic_tokens_of_result() {
+        let full =
+            json!({ "resultId": "1", "data": [0, 4, 3, 1, 0, 1, 2, 3, 1, 0] });
+        let (result_id, data) = semantic_tokens_of_result(full, None).unwrap();
+        assert_eq!(Some("1".to_string()), result_id);
+        assert_eq!(vec![semantic_token(0, 4), semantic_token(1, 2)], data);
+
+        // Edits of whole tokens
+        let previous = semantic_tokens_cache(vec![
+            semantic_token(0, 4),
+            semantic_token(1, 2),
+            semantic_token(2, 0),
+        ]);
+        let delta = json!({
+            "resultId": "2",
+            "edits": [
+                { "start": 10, "deleteCount": 5 },
+                { "start": 0, "deleteCount": 0, "data": [0, 0, 3, 1, 0] },
+            ],
+        });
+        let (result_id, data) =
+            semantic_tokens_of_result(delta, Some(previous)).unwrap();
+        assert_eq!(Some("2".to_string()), result_id);
+        assert_eq!(
+            vec![
+                semantic_token(0, 0),
+                semantic_token(0, 4),
+                semantic_token(1, 2)
+            ],
+            data
+        );
+
+        // Edits of some of the integers of the tokens
+        let previous =
+            semantic_tokens_cache(vec![semantic_token(0, 4), semantic_token(1, 2)]);
+        let delta = json!({
+            "resultId": "2",
+            "edits": [
+                { "start": 6, "deleteCount": 1, "data": [7] },
+                { "start": 1, "deleteCount": 0, "data": [8, 3, 1, 0, 0] },
+            ],
+        });
+        let (_, data) = semantic_tokens_of_result(delta, Some(previous)).unwrap();
+        assert_eq!(
+            vec![
+                semantic_token(0, 8),
+                semantic_token(0, 4),
+                semantic_token(1, 7)
+            ],
+            data
+        );
+
+        // Edits out of the tokens, or that leave a part of a token
+        for edits in [
+            json!([{ "start": 11, "deleteCount": 0, "data": [0, 0, 3, 1, 0] }]),
+            json!([{ "start": 5, "deleteCount": 6 }]),
+            json!([{ "start": 5, "deleteCount": 2 }]),
+        ] {
+            let previous = semantic_tokens_cache(vec![
+                semantic_token(0, 4),
+                semantic_token(1, 2),
+            ]);
+            let delta = json!({ "resultId": "2", "edits": edits });
+            assert!(semantic_tokens_of_result(delta, Some(previous)).is_err());
+        }
+        let delta = json!({ "resultId": "2", "edits": [] });
+        assert!(semantic_tokens_of_result(delta, None).is_err());
+    }
+
+    #[test]
+    fn test_retrigger() {
+        let cancelled =
+            |retrigger| anyhow::Error::new(ServerCancelled { retrigger });
+        assert!(retrigger(&cancelled(true)));
+        assert!(!retrigger(&cancelled(false)));
+        assert!(!retrigger(&anyhow!("request cancelled")));
     }
 }
@@ -445,7 +445,7 @@ fn host_handle_notification(plugin_env: &PluginEnv) {
                         .unwrap()
                         .to_string()
                 };
-                plugin_env.dispatcher.lsp.lock().start_server(
+                plugin_env.dispatcher.lsp.lock().start_plugin_server(
                     &exec_path,
                     &language_id,
                     options,
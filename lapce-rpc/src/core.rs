@@ -1,5 +1,9 @@
-use lsp_types::{ProgressParams, PublishDiagnosticsParams};
+use lsp_types::{
+    LogMessageParams, ProgressParams, PublishDiagnosticsParams, ShowMessageParams,
+    ShowMessageRequestParams, WorkspaceEdit,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
@@ -7,6 +11,14 @@ use crate::{
     terminal::TermId,
 };
 
+/// The characters that trigger the signature help of a language server, and
+/// the ones that trigger it again while it's shown
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureTriggers {
+    pub trigger_characters: Vec<String>,
+    pub retrigger_characters: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "method", content = "params")]
@@ -28,6 +40,37 @@ pub enum CoreNotification {
     WorkDoneProgress {
         progress: ProgressParams,
     },
+    /// A `window/showMessage` from the language server named `title`
+    ShowMessage {
+        title: String,
+        message: ShowMessageParams,
+    },
+    /// A `window/logMessage` from the language server named `server`
+    LogMessage {
+        server: String,
+        message: LogMessageParams,
+    },
+    /// The language server named `name` changed its status
+    LanguageServerStatus {
+        name: String,
+        status: LanguageServerStatus,
+    },
+    /// A message between the proxy and the language server named `server`,
+    /// sent when `[lsp] trace` is on
+    LspTrace {
+        server: String,
+        entry: LspTraceEntry,
+    },
+    /// A language server asked for the semantic tokens and the inlay hints
+    /// of the open documents to be requested again
+    LspRefresh {},
+    /// The signature help triggers of the language server named `server`,
+    /// which handles the document at `path`
+    SignatureHelpTriggers {
+        path: PathBuf,
+        server: String,
+        triggers: SignatureTriggers,
+    },
     HomeDir {
         path: PathBuf,
     },
@@ -55,5 +98,52 @@ pub enum CoreNotification {
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageServerStatus {
+    Starting,
+    Running,
+    /// It exited and is started again after a while
+    Restarting,
+    /// It exited too many times, and is only started again by hand
+    Crashed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LspTraceDirection {
+    /// From the proxy to the server
+    Sent,
+    /// From the server to the proxy
+    Received,
+}
+
+/// A JSON-RPC message of the traffic with a language server
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum CoreRequest {}
+pub struct LspTraceEntry {
+    pub direction: LspTraceDirection,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// The method of the request or notification, or of the request that a
+    /// response answers
+    pub method: Option<String>,
+    /// For a response, the milliseconds since its request was sent
+    pub latency: Option<u64>,
+    pub payload: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreRequest {
+    /// A `workspace/applyEdit` request from a language server, responded to
+    /// with an `ApplyWorkspaceEditResponse`
+    ApplyWorkspaceEdit { edit: WorkspaceEdit },
+    /// A `window/showMessageRequest` from the language server named `title`,
+    /// responded to with the `MessageActionItem` that was picked
+    ShowMessageRequest {
+        title: String,
+        message: ShowMessageRequestParams,
+    },
+}
@@ -76,7 +76,9 @@ pub trait Handler {
     type Request: DeserializeOwned;
 
     fn handle_notification(&mut self, rpc: Self::Notification) -> ControlFlow;
-    fn handle_request(&mut self, rpc: Self::Request) -> Result<Value, Value>;
+    /// Handle the request, the handler responds to it with `RpcHandler::respond`
+    /// once it has the result
+    fn handle_request(&mut self, id: RequestId, rpc: Self::Request);
 }
 
 #[derive(Clone)]
@@ -114,8 +116,7 @@ impl RpcHandler {
             } else {
                 match rpc.into_rpc::<H::Notification, H::Request>() {
                     Ok(Call::Request(id, request)) => {
-                        let result = handler.handle_request(request);
-                        self.respond(id, result);
+                        handler.handle_request(id, request);
                     }
                     Ok(Call::Notification(notification)) => {
                         if handler.handle_notification(notification)
@@ -230,7 +231,7 @@ impl RpcHandler {
         }
     }
 
-    fn respond(&self, id: u64, result: Result<Value, Value>) {
+    pub fn respond(&self, id: u64, result: Result<Value, Value>) {
         let mut response = json!({ "id": id });
         match result {
             Ok(result) => response["result"] = result,
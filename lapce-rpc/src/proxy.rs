@@ -1,7 +1,11 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use lsp_types::{CompletionItem, Position};
+use lsp_types::{
+    CallHierarchyItem, CodeLens, Command, CompletionItem, InlayHint, Position,
+    Range, SignatureHelp,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use xi_rope::RopeDelta;
 
 use crate::{
@@ -22,6 +26,10 @@ pub enum ProxyNotification {
         delta: RopeDelta,
         rev: u64,
     },
+    /// The editor dropped the buffer, so it isn't open anymore
+    CloseBuffer {
+        buffer_id: BufferId,
+    },
     NewTerminal {
         term_id: TermId,
         cwd: Option<PathBuf>,
@@ -66,6 +74,28 @@ pub enum ProxyNotification {
     TerminalClose {
         term_id: TermId,
     },
+    /// The `[lsp]` table of the settings, for the language servers
+    UpdateLspSettings {
+        settings: Value,
+    },
+    RestartLanguageServer {
+        name: String,
+    },
+    /// The editor doesn't need the result of the request with the
+    /// `request_id` anymore, so the language servers can stop working on it
+    CancelRequest {
+        kind: CancellableRequest,
+        request_id: usize,
+    },
+}
+
+/// The requests of the editor with a `request_id` that can be cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancellableRequest {
+    Completion,
+    Hover,
+    SignatureHelp,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,9 +127,15 @@ pub enum ProxyRequest {
         buffer_id: BufferId,
         position: Position,
     },
+    /// The signature help at the position, which is requested again with
+    /// the `active` one while it's shown, and when a character is typed
+    /// with it as the `trigger`
     GetSignature {
+        request_id: usize,
         buffer_id: BufferId,
         position: Position,
+        trigger: Option<String>,
+        active: Option<Box<SignatureHelp>>,
     },
     GetReferences {
         buffer_id: BufferId,
@@ -115,11 +151,31 @@ pub enum ProxyRequest {
         buffer_id: BufferId,
         position: Position,
     },
+    GetImplementation {
+        request_id: usize,
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetDeclaration {
+        request_id: usize,
+        buffer_id: BufferId,
+        position: Position,
+    },
+    /// The inlay hints of the range that the editor shows
     GetInlayHints {
         buffer_id: BufferId,
+        range: Range,
+    },
+    /// Fill in the properties that the server left out of the inlay hint
+    ResolveInlayHint {
+        buffer_id: BufferId,
+        hint: Box<InlayHint>,
     },
+    /// The semantic tokens of the whole document, or only of the range that
+    /// the editor shows when the document is large
     GetSemanticTokens {
         buffer_id: BufferId,
+        range: Range,
     },
     GetCodeActions {
         buffer_id: BufferId,
@@ -137,12 +193,82 @@ pub enum ProxyRequest {
     GetDocumentFormatting {
         buffer_id: BufferId,
     },
+    GetDocumentRangeFormatting {
+        buffer_id: BufferId,
+        range: Range,
+    },
+    GetOnTypeFormatting {
+        buffer_id: BufferId,
+        position: Position,
+        /// The character that was typed
+        ch: String,
+    },
+    PrepareRename {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    Rename {
+        buffer_id: BufferId,
+        position: Position,
+        new_name: String,
+    },
+    PrepareCallHierarchy {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    CallHierarchyIncomingCalls {
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+    },
+    CallHierarchyOutgoingCalls {
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+    },
+    PrepareTypeHierarchy {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    /// Type hierarchy items have the same fields as call hierarchy items
+    TypeHierarchySupertypes {
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+    },
+    TypeHierarchySubtypes {
+        buffer_id: BufferId,
+        item: CallHierarchyItem,
+    },
+    GetDocumentHighlight {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetLinkedEditingRange {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetCodeLens {
+        buffer_id: BufferId,
+    },
+    CodeLensResolve {
+        buffer_id: BufferId,
+        code_lens: CodeLens,
+    },
+    /// Run the command on the language server of the buffer
+    ExecuteCommand {
+        buffer_id: BufferId,
+        command: Command,
+    },
+    /// The names of the running language servers
+    GetLanguageServers {},
     GetFiles {
         path: String,
     },
     ReadDir {
         path: PathBuf,
     },
+    /// The content of a file which isn't open
+    ReadFile {
+        path: PathBuf,
+    },
     Save {
         rev: u64,
         buffer_id: BufferId,
@@ -172,3 +298,8 @@ pub enum ProxyRequest {
 pub struct ReadDirResponse {
     pub items: HashMap<PathBuf, FileNodeItem>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadFileResponse {
+    pub content: String,
+}
@@ -25,4 +25,7 @@ pub struct SemanticStyles {
     pub path: PathBuf,
     pub len: usize,
     pub styles: Vec<LineStyle>,
+    /// The styles are only of the requested range, rather than of the whole
+    /// document
+    pub partial: bool,
 }
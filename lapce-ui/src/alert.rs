@@ -16,6 +16,8 @@ use lapce_data::{
     data::LapceTabData,
 };
 
+use serde_json::Value;
+
 use crate::svg::get_svg;
 
 pub struct AlertBox {
@@ -207,6 +209,9 @@ impl Widget<LapceTabData> for AlertBoxContent {
                 if let CommandKind::Focus(FocusCommand::ModalClose) = &command.kind {
                     let alert = Arc::make_mut(&mut data.alert);
                     alert.active = false;
+                    if let Some(request_id) = alert.message_request.take() {
+                        data.proxy.respond(request_id, Ok(Value::Null));
+                    }
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
                         LapceUICommand::Focus,
@@ -316,6 +316,11 @@ impl Widget<LapceTabData> for CompletionContainer {
                             resp.to_owned(),
                         );
                     }
+                    LapceUICommand::UpdateLocalCompletion(request_id, items) => {
+                        let completion = Arc::make_mut(&mut data.completion);
+                        completion
+                            .receive_local_items(*request_id, items.to_owned());
+                    }
                     LapceUICommand::CancelCompletion(request_id) => {
                         if data.completion.request_id == *request_id {
                             let completion = Arc::make_mut(&mut data.completion);
@@ -67,6 +67,8 @@ pub struct LapceEditor {
     /// of hover info (if there is any)
     mouse_hover_timer: TimerToken,
     drag_timer: TimerToken,
+    /// Where the code lenses were painted, for clicking on them
+    code_lens_rects: Vec<(Rect, lsp_types::Command)>,
 }
 
 impl LapceEditor {
@@ -79,9 +81,17 @@ impl LapceEditor {
             mouse_mods: Modifiers::empty(),
             mouse_hover_timer: TimerToken::INVALID,
             drag_timer: TimerToken::INVALID,
+            code_lens_rects: Vec::new(),
         }
     }
 
+    fn code_lens_at(&self, pos: Point) -> Option<&lsp_types::Command> {
+        self.code_lens_rects
+            .iter()
+            .find(|(rect, _)| rect.contains(pos))
+            .map(|(_, command)| command)
+    }
+
     fn mouse_within_scroll(
         &self,
         editor_data: &LapceEditorBufferData,
@@ -216,6 +226,10 @@ impl LapceEditor {
         ctx.set_handled();
         match mouse_event.button {
             MouseButton::Left => {
+                if let Some(command) = self.code_lens_at(mouse_event.pos) {
+                    editor_data.run_code_lens(ctx, command);
+                    return;
+                }
                 self.left_click(ctx, mouse_event, editor_data, config);
                 editor_data.get_code_actions(ctx);
                 editor_data.cancel_completion();
@@ -295,6 +309,41 @@ impl LapceEditor {
                     data: None,
                 },
             }),
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand {
+                    kind: CommandKind::Focus(FocusCommand::Rename),
+                    data: None,
+                },
+            }),
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand {
+                    kind: CommandKind::Focus(FocusCommand::ShowIncomingCalls),
+                    data: None,
+                },
+            }),
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand {
+                    kind: CommandKind::Focus(FocusCommand::ShowOutgoingCalls),
+                    data: None,
+                },
+            }),
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand {
+                    kind: CommandKind::Focus(FocusCommand::ShowSupertypes),
+                    data: None,
+                },
+            }),
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand {
+                    kind: CommandKind::Focus(FocusCommand::ShowSubtypes),
+                    data: None,
+                },
+            }),
             MenuKind::Separator,
             MenuKind::Item(MenuItem {
                 desc: None,
@@ -408,12 +457,12 @@ impl LapceEditor {
                             .max(data.doc.text_layouts.borrow().max_width)
                             .max(editor_size.width),
                         if data.config.editor.scroll_beyond_last_line {
-                            (line_height * data.doc.buffer().num_lines() as f64
+                            (line_height * data.doc.num_rows(&data.config) as f64
                                 - line_height)
                                 .max(0.0)
                                 + editor_size.height
                         } else {
-                            (line_height * data.doc.buffer().num_lines() as f64)
+                            (line_height * data.doc.num_rows(&data.config) as f64)
                                 .max(editor_size.height)
                         },
                     )
@@ -439,7 +488,9 @@ impl LapceEditor {
                 }
             }
             // Almost the same as the general case below but with less vertical padding
-            BufferContent::Local(LocalBufferKind::PathName) => Size::new(
+            BufferContent::Local(
+                LocalBufferKind::PathName | LocalBufferKind::Rename,
+            ) => Size::new(
                 editor_size.width.max(
                     data.doc
                         .get_text_layout(
@@ -783,19 +834,24 @@ impl LapceEditor {
                 let line_height = Self::line_height(data, env);
 
                 let rect = ctx.region().bounding_box();
-                let start_line = (rect.y0 / line_height).floor() as usize;
-                let end_line = (rect.y1 / line_height).ceil() as usize;
+                let start_row = (rect.y0 / line_height).floor() as usize;
+                let end_row = (rect.y1 / line_height).ceil() as usize;
 
                 let mut lines = Vec::new();
                 let mut info = HashMap::new();
-                for line in start_line..end_line + 1 {
+                for row in start_row..end_row + 1 {
+                    let (line, is_code_lens) =
+                        data.doc.line_of_row(&data.config, row);
+                    if is_code_lens {
+                        continue;
+                    }
                     lines.push(line);
                     info.insert(
                         line,
                         LineInfo {
                             font_size,
                             x: 0.0,
-                            y: line as f64 * line_height + line_padding,
+                            y: row as f64 * line_height + line_padding,
                             line_height,
                         },
                     );
@@ -814,10 +870,15 @@ impl LapceEditor {
 
         Self::paint_current_line(ctx, data, &screen_lines);
         Self::paint_cursor_new(ctx, data, &screen_lines, is_focused, env);
+        Self::paint_occurrences(ctx, data, &screen_lines);
         Self::paint_find(ctx, data, &screen_lines);
         Self::paint_text(ctx, data, &screen_lines, env);
         Self::paint_diagnostics(ctx, data, &screen_lines);
         Self::paint_snippet(ctx, data, &screen_lines);
+        self.paint_code_lenses(ctx, data, env);
+        if let EditorView::Normal = data.editor.view {
+            Self::paint_occurrence_marks(ctx, data);
+        }
 
         if let Some(placeholder) = self.placeholder.as_ref() {
             if data.doc.buffer().is_empty() {
@@ -1371,6 +1432,203 @@ impl LapceEditor {
         }
     }
 
+    fn occurrence_color<'a>(
+        data: &'a LapceEditorBufferData,
+        write: bool,
+    ) -> &'a Color {
+        data.config.get_color_unchecked(if write {
+            LapceTheme::EDITOR_OCCURRENCE_WRITE
+        } else {
+            LapceTheme::EDITOR_OCCURRENCE_READ
+        })
+    }
+
+    /// Fill the occurrences of the symbol under the cursor, with a different
+    /// color for the places it is written to
+    fn paint_occurrences(
+        ctx: &mut PaintCtx,
+        data: &LapceEditorBufferData,
+        screen_lines: &ScreenLines,
+    ) {
+        if !data.config.editor.highlight_occurrences {
+            return;
+        }
+        let occurrences = match data.doc.occurrences.as_ref() {
+            Some(occurrences) => occurrences,
+            None => return,
+        };
+        if screen_lines.lines.is_empty() {
+            return;
+        }
+        let start_line = *screen_lines.lines.first().unwrap();
+        let end_line = *screen_lines.lines.last().unwrap();
+        let start = data.doc.buffer().offset_of_line(start_line);
+        let end = data.doc.buffer().offset_of_line(end_line + 1);
+
+        for occurrence in occurrences.iter() {
+            if occurrence.end < start || occurrence.start > end {
+                continue;
+            }
+            let color = Self::occurrence_color(data, occurrence.write);
+            let (start_line, start_col) =
+                data.doc.buffer().offset_to_line_col(occurrence.start);
+            let (end_line, end_col) =
+                data.doc.buffer().offset_to_line_col(occurrence.end);
+            for line in start_line..end_line + 1 {
+                let info = match screen_lines.info.get(&line) {
+                    Some(info) => info,
+                    None => continue,
+                };
+
+                let left_col = if line == start_line { start_col } else { 0 };
+                let right_col = if line == end_line {
+                    end_col
+                } else {
+                    data.doc.buffer().line_end_col(line, true) + 1
+                };
+
+                let phantom_text = data.doc.line_phantom_text(&data.config, line);
+                let left_col = phantom_text.col_at(left_col);
+                let right_col = phantom_text.col_at(right_col);
+
+                let text_layout = data.doc.get_text_layout(
+                    ctx.text(),
+                    line,
+                    info.font_size,
+                    &data.config,
+                );
+                let x0 = text_layout.text.hit_test_text_position(left_col).point.x;
+                let x1 = text_layout.text.hit_test_text_position(right_col).point.x;
+                let rect = Rect::new(
+                    x0 + info.x,
+                    info.y,
+                    x1 + info.x,
+                    info.y + info.line_height,
+                );
+                ctx.fill(rect, color);
+            }
+        }
+    }
+
+    /// Mark the lines of the occurrences on the right edge of the editor,
+    /// under the vertical scroll bar
+    fn paint_occurrence_marks(ctx: &mut PaintCtx, data: &LapceEditorBufferData) {
+        if !data.config.editor.highlight_occurrences {
+            return;
+        }
+        let occurrences = match data.doc.occurrences.as_ref() {
+            Some(occurrences) => occurrences,
+            None => return,
+        };
+
+        let rect = ctx.region().bounding_box();
+        let width = data.config.ui.scroll_width() as f64;
+        let lines = (data.doc.buffer().last_line() + 1) as f64;
+        for occurrence in occurrences.iter() {
+            let line = data.doc.buffer().line_of_offset(occurrence.start);
+            let y = rect.y0 + rect.height() * line as f64 / lines;
+            let mark = Rect::new(rect.x1 - width, y, rect.x1, y + 2.0);
+            ctx.fill(
+                mark,
+                &Self::occurrence_color(data, occurrence.write)
+                    .clone()
+                    .with_alpha(1.0),
+            );
+        }
+    }
+
+    /// Paint the code lenses on the phantom lines above their lines, aligned
+    /// with the text of the line
+    fn paint_code_lenses(
+        &mut self,
+        ctx: &mut PaintCtx,
+        data: &LapceEditorBufferData,
+        env: &Env,
+    ) {
+        self.code_lens_rects.clear();
+        if !matches!(data.editor.view, EditorView::Normal)
+            || !data.editor.content.is_file()
+            || !data.config.editor.enable_code_lens
+            || data.doc.code_lenses.is_empty()
+        {
+            return;
+        }
+
+        let line_height = Self::line_height(data, env);
+        let font_family = data.config.editor.inlay_hint_font_family();
+        let font_size = data.config.editor.inlay_hint_font_size() as f64;
+        let dim = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+
+        let rect = ctx.region().bounding_box();
+        let start_row = (rect.y0 / line_height).floor() as usize;
+        let end_row = (rect.y1 / line_height).ceil() as usize;
+        for row in start_row..end_row + 1 {
+            let (line, is_code_lens) = data.doc.line_of_row(&data.config, row);
+            if !is_code_lens {
+                continue;
+            }
+
+            let buffer = data.doc.buffer();
+            let indent = buffer.first_non_blank_character_on_line(line)
+                - buffer.offset_of_line(line);
+            let phantom_text = data.doc.line_phantom_text(&data.config, line);
+            let mut x = data
+                .doc
+                .get_text_layout(
+                    ctx.text(),
+                    line,
+                    data.config.editor.font_size,
+                    &data.config,
+                )
+                .text
+                .hit_test_text_position(phantom_text.col_at(indent))
+                .point
+                .x;
+            let y = row as f64 * line_height;
+
+            let commands = data
+                .doc
+                .code_lenses
+                .lenses_of_line(line)
+                .filter_map(|lens| lens.command.as_ref());
+            for (i, command) in commands.enumerate() {
+                if i > 0 {
+                    let separator = ctx
+                        .text()
+                        .new_text_layout(" | ")
+                        .font(font_family.clone(), font_size)
+                        .text_color(dim.clone())
+                        .build()
+                        .unwrap();
+                    let size = separator.size();
+                    ctx.draw_text(
+                        &separator,
+                        Point::new(x, y + (line_height - size.height) / 2.0),
+                    );
+                    x += size.width;
+                }
+
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(command.title.clone())
+                    .font(font_family.clone(), font_size)
+                    .text_color(dim.clone())
+                    .build()
+                    .unwrap();
+                let size = text_layout.size();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(x, y + (line_height - size.height) / 2.0),
+                );
+                self.code_lens_rects.push((
+                    Rect::new(x, y, x + size.width, y + line_height),
+                    command.clone(),
+                ));
+                x += size.width;
+            }
+        }
+    }
+
     fn paint_snippet(
         ctx: &mut PaintCtx,
         data: &LapceEditorBufferData,
@@ -1698,7 +1956,11 @@ impl Widget<LapceTabData> for LapceEditor {
         match event {
             Event::MouseMove(mouse_event) => {
                 ctx.set_handled();
-                ctx.set_cursor(&druid::Cursor::IBeam);
+                if self.code_lens_at(mouse_event.pos).is_some() {
+                    ctx.set_cursor(&druid::Cursor::Pointer);
+                } else {
+                    ctx.set_cursor(&druid::Cursor::IBeam);
+                }
                 let doc = data.main_split.editor_doc(self.view_id);
                 let editor =
                     data.main_split.editors.get(&self.view_id).unwrap().clone();
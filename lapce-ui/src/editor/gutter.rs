@@ -58,7 +58,10 @@ impl Widget<LapceTabData> for LapceEditorGutter {
                                 LapceUICommand::ShowCodeActions(Some(
                                     ctx.to_window(Point::new(
                                         rect.x0,
-                                        (line + 1) as f64 * line_height
+                                        (data.doc.row_of_line(&data.config, line)
+                                            + 1)
+                                            as f64
+                                            * line_height
                                             - data.editor.scroll_offset.y,
                                     )),
                                 )),
@@ -487,7 +490,8 @@ impl LapceEditorGutter {
         let char_width = data.config.editor_char_width(text);
         Size::new(width, height).to_rect().with_origin(Point::new(
             self.width + char_width + 3.0,
-            (line_height - height) / 2.0 + line_height * line as f64
+            (line_height - height) / 2.0
+                + line_height * data.doc.row_of_line(&data.config, line) as f64
                 - data.editor.scroll_offset.y,
         ))
     }
@@ -525,7 +529,8 @@ impl LapceEditorGutter {
             }
             let line_height = data.config.editor.line_height as f64;
             let scroll_offset = data.editor.scroll_offset;
-            let start_line = (scroll_offset.y / line_height).floor() as usize;
+            let start_row = (scroll_offset.y / line_height).floor() as usize;
+            let (start_line, _) = data.doc.line_of_row(&data.config, start_row);
             let num_lines = (ctx.size().height / line_height).floor() as usize;
             let last_line = data.doc.buffer().last_line();
             let current_line = data
@@ -579,7 +584,9 @@ impl LapceEditorGutter {
                 let x = line_label_length as f64 - text_layout.size().width;
 
                 // Vertically centered
-                let y = line_height * line as f64 - scroll_offset.y
+                let y = line_height
+                    * data.doc.row_of_line(&data.config, line) as f64
+                    - scroll_offset.y
                     + (line_height - text_layout.size().height) / 2.0;
 
                 ctx.draw_text(&text_layout, Point::new(x, y));
@@ -631,8 +638,8 @@ impl LapceEditorGutter {
                     if let Some(color) = color.cloned() {
                         let removed_height = 10.0;
                         let x = self.width + char_width;
-                        let mut y =
-                            (line - len) as f64 * line_height - scroll_offset.y;
+                        let row = data.doc.row_of_line(&data.config, line - len);
+                        let mut y = row as f64 * line_height - scroll_offset.y;
                         if len == 0 {
                             y -= removed_height / 2.0;
                         }
@@ -655,7 +662,10 @@ impl LapceEditorGutter {
                                 if len == 0 {
                                     removed_height
                                 } else {
-                                    line_height * len as f64
+                                    // Code lenses inside the change make it taller
+                                    let end_row =
+                                        data.doc.row_of_line(&data.config, line - 1);
+                                    line_height * (end_row + 1 - row) as f64
                                 },
                             ),
                         );
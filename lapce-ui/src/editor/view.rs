@@ -35,6 +35,14 @@ use crate::{
     settings::LapceSettingsPanel,
 };
 
+/// How long the editor has to show the same lines before their semantic
+/// styles and inlay hints are requested
+const LSP_VIEWPORT_DELAY: Duration = Duration::from_millis(150);
+
+/// How long the document has to stay unchanged before its code lenses are
+/// requested
+const CODE_LENS_DELAY: Duration = Duration::from_millis(500);
+
 pub struct LapceEditorView {
     pub view_id: WidgetId,
     pub header: WidgetPod<LapceTabData, LapceEditorHeader>,
@@ -42,6 +50,14 @@ pub struct LapceEditorView {
     pub find: Option<WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>>,
     cursor_blink_timer: TimerToken,
     last_idle_timer: TimerToken,
+    /// Fires when the cursor has settled, to highlight the occurrences of
+    /// the symbol under it
+    occurrences_timer: TimerToken,
+    /// Fires when the shown lines have settled, to request their semantic
+    /// styles and inlay hints, and resolve their code lenses
+    lsp_timer: TimerToken,
+    /// Fires when the document has settled, to request its code lenses
+    code_lens_timer: TimerToken,
     display_border: bool,
     background_color_name: &'static str,
 }
@@ -79,6 +95,9 @@ impl LapceEditorView {
             find,
             cursor_blink_timer: TimerToken::INVALID,
             last_idle_timer: TimerToken::INVALID,
+            occurrences_timer: TimerToken::INVALID,
+            lsp_timer: TimerToken::INVALID,
+            code_lens_timer: TimerToken::INVALID,
             display_border: true,
             background_color_name: LapceTheme::EDITOR_BACKGROUND,
         }
@@ -168,7 +187,7 @@ impl LapceEditorView {
                     data.focus_area = FocusArea::Panel(PanelKind::SourceControl);
                     Arc::make_mut(&mut data.source_control).active = self.view_id;
                 }
-                LocalBufferKind::PathName => {}
+                LocalBufferKind::PathName | LocalBufferKind::Rename => {}
                 LocalBufferKind::Empty => {
                     data.focus_area = FocusArea::Editor;
                     data.main_split.active = Arc::new(Some(self.view_id));
@@ -491,12 +510,12 @@ impl LapceEditorView {
             }
             y
         } else {
-            let line = if let EditorView::Diff(version) = &data.editor.view {
+            let row = if let EditorView::Diff(version) = &data.editor.view {
                 data.doc.history_visual_line(version, line)
             } else {
-                line
+                data.doc.row_of_line(&data.config, line)
             };
-            line as f64 * line_height
+            row as f64 * line_height
         };
 
         Rect::ZERO
@@ -579,6 +598,42 @@ impl Widget<LapceTabData> for LapceEditorView {
                     }
                 }
             }
+            Event::Timer(id) if self.occurrences_timer == *id => {
+                ctx.set_handled();
+                self.occurrences_timer = TimerToken::INVALID;
+                let editor_data = data.editor_view_content(self.view_id);
+                let (start_line, end_line) = editor_data.visible_lines();
+                editor_data.doc.get_occurrences(
+                    editor_data.editor.cursor.offset(),
+                    start_line,
+                    end_line,
+                );
+            }
+            Event::Timer(id) if self.lsp_timer == *id => {
+                ctx.set_handled();
+                self.lsp_timer = TimerToken::INVALID;
+                let editor_data = data.editor_view_content(self.view_id);
+                let (start_line, end_line) = editor_data.visible_lines();
+                editor_data.doc.get_semantic_styles(start_line, end_line);
+                editor_data.doc.get_inlay_hints(start_line, end_line);
+                // The visible lines are rows, which the code lenses move down
+                let doc = &editor_data.doc;
+                doc.resolve_code_lenses(
+                    doc.line_of_row(&data.config, start_line).0,
+                    doc.line_of_row(&data.config, end_line).0,
+                );
+            }
+            Event::Timer(id) if self.code_lens_timer == *id => {
+                ctx.set_handled();
+                self.code_lens_timer = TimerToken::INVALID;
+                let editor_data = data.editor_view_content(self.view_id);
+                let (start_row, end_row) = editor_data.visible_lines();
+                let doc = &editor_data.doc;
+                doc.get_code_lens(
+                    doc.line_of_row(&data.config, start_row).0,
+                    doc.line_of_row(&data.config, end_row).0,
+                );
+            }
             Event::Timer(id) if self.last_idle_timer == *id => {
                 ctx.set_handled();
                 let editor_data = data.editor_view_content(self.view_id);
@@ -697,6 +752,8 @@ impl Widget<LapceTabData> for LapceEditorView {
 
         match event {
             LifeCycle::WidgetAdded => {
+                self.lsp_timer = ctx.request_timer(LSP_VIEWPORT_DELAY, None);
+                self.code_lens_timer = ctx.request_timer(CODE_LENS_DELAY, None);
                 let editor = data.main_split.editors.get(&self.view_id).unwrap();
                 if editor.scroll_offset.x > 0.0 || editor.scroll_offset.y > 0.0 {
                     ctx.submit_command(Command::new(
@@ -734,6 +791,13 @@ impl Widget<LapceTabData> for LapceEditorView {
                                 Target::Auto,
                             ));
                         }
+                        BufferContent::Local(LocalBufferKind::Rename) => {
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::CancelRename,
+                                Target::Widget(data.rename.id),
+                            ));
+                        }
                         _ => {}
                     }
                 } else if editor.content.is_palette()
@@ -822,6 +886,41 @@ impl Widget<LapceTabData> for LapceEditorView {
             }
         }
 
+        if data.config.editor.highlight_occurrences
+            && data.focus == self.view_id
+            && (old_data.focus != self.view_id
+                || editor_data.editor.cursor.offset()
+                    != old_editor_data.editor.cursor.offset()
+                || editor_data.doc.rev() != old_editor_data.doc.rev())
+        {
+            self.occurrences_timer = ctx.request_timer(
+                Duration::from_millis(
+                    data.config.editor.highlight_occurrences_delay,
+                ),
+                None,
+            );
+        }
+
+        if editor_data.doc.content().is_file()
+            && (editor_data.doc.rev() != old_editor_data.doc.rev()
+                || editor_data.doc.loaded() != old_editor_data.doc.loaded()
+                || editor_data.doc.lsp_refresh()
+                    != old_editor_data.doc.lsp_refresh()
+                || editor_data.editor.scroll_offset
+                    != old_editor_data.editor.scroll_offset)
+        {
+            self.lsp_timer = ctx.request_timer(LSP_VIEWPORT_DELAY, None);
+        }
+
+        if editor_data.doc.content().is_file()
+            && (editor_data.doc.rev() != old_editor_data.doc.rev()
+                || editor_data.doc.loaded() != old_editor_data.doc.loaded()
+                || editor_data.doc.lsp_refresh()
+                    != old_editor_data.doc.lsp_refresh())
+        {
+            self.code_lens_timer = ctx.request_timer(CODE_LENS_DELAY, None);
+        }
+
         if old_data.config.lapce.modal != data.config.lapce.modal
             && !editor_data.doc.content().is_input()
         {
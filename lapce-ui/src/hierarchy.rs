@@ -0,0 +1,355 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Cursor, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    LifeCycleCtx, MouseEvent, PaintCtx, Point, RenderContext, Size, Target,
+    UpdateCtx, Widget, WidgetExt, WidgetId,
+};
+use lapce_data::{
+    call_hierarchy::CallHierarchyDirection,
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::LapceTabData,
+    editor::EditorLocation,
+    hierarchy::{HierarchyNode, HierarchyTree},
+    panel::PanelKind,
+    proxy::path_from_url,
+};
+
+use crate::{
+    panel::{LapcePanel, PanelHeaderKind},
+    svg::{get_svg, symbol_svg},
+};
+
+pub fn new_call_hierarchy_panel(data: &LapceTabData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::CallHierarchy,
+        data.call_hierarchy.widget_id,
+        data.call_hierarchy.split_id,
+        vec![(
+            data.call_hierarchy.split_id,
+            PanelHeaderKind::Simple("Call Hierarchy".into()),
+            HierarchyTreeView::new(
+                PanelKind::CallHierarchy,
+                data.call_hierarchy.widget_id,
+            )
+            .boxed(),
+            None,
+        )],
+    )
+}
+
+pub fn new_type_hierarchy_panel(data: &LapceTabData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::TypeHierarchy,
+        data.type_hierarchy.widget_id,
+        data.type_hierarchy.split_id,
+        vec![(
+            data.type_hierarchy.split_id,
+            PanelHeaderKind::Simple("Type Hierarchy".into()),
+            HierarchyTreeView::new(
+                PanelKind::TypeHierarchy,
+                data.type_hierarchy.widget_id,
+            )
+            .boxed(),
+            None,
+        )],
+    )
+}
+
+/// The tree of the call or type hierarchy panel, where the children of
+/// a node are only requested when it is expanded
+struct HierarchyTreeView {
+    kind: PanelKind,
+    widget_id: WidgetId,
+    mouse_pos: Point,
+    content_height: f64,
+}
+
+impl HierarchyTreeView {
+    const INDENT: f64 = 15.0;
+
+    fn new(kind: PanelKind, widget_id: WidgetId) -> Self {
+        Self {
+            kind,
+            widget_id,
+            mouse_pos: Point::ZERO,
+            content_height: 0.0,
+        }
+    }
+
+    fn tree<'a>(&self, data: &'a LapceTabData) -> &'a HierarchyTree {
+        match self.kind {
+            PanelKind::TypeHierarchy => &data.type_hierarchy.tree,
+            _ => &data.call_hierarchy.tree,
+        }
+    }
+
+    /// The description of the hierarchy, shown next to the roots
+    fn title(&self, data: &LapceTabData) -> &'static str {
+        match self.kind {
+            PanelKind::TypeHierarchy => data.type_hierarchy.direction.title(),
+            _ => data.call_hierarchy.direction.title(),
+        }
+    }
+
+    fn mouse_down(
+        &self,
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        data: &mut LapceTabData,
+    ) {
+        let line_height = data.config.editor.line_height as f64;
+        let n = (mouse_event.pos.y / line_height).floor() as usize;
+        let (path, level) = match self.tree(data).rows().get(n) {
+            Some(row) => (row.path.clone(), row.level),
+            None => return,
+        };
+
+        let chevron_end = Self::INDENT * level as f64 + line_height;
+        if mouse_event.pos.x < chevron_end {
+            let event_sink = ctx.get_external_handle();
+            match self.kind {
+                PanelKind::TypeHierarchy => {
+                    Arc::make_mut(&mut data.type_hierarchy).toggle(
+                        &path,
+                        &data.proxy,
+                        event_sink,
+                    );
+                }
+                _ => {
+                    Arc::make_mut(&mut data.call_hierarchy).toggle(
+                        &path,
+                        &data.proxy,
+                        event_sink,
+                    );
+                }
+            }
+            return;
+        }
+
+        if let Some(node) = self.tree(data).node(&path) {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::JumpToLspLocation(None, self.location(node, data)),
+                Target::Widget(data.id),
+            ));
+        }
+    }
+
+    /// Incoming calls jump to the call site in the caller, everything else
+    /// jumps to the item itself
+    fn location(&self, node: &HierarchyNode, data: &LapceTabData) -> EditorLocation {
+        let incoming = self.kind == PanelKind::CallHierarchy
+            && data.call_hierarchy.direction == CallHierarchyDirection::Incoming;
+        let position = match node.ranges.first() {
+            Some(range) if incoming => range.start,
+            _ => node.item.selection_range.start,
+        };
+        EditorLocation {
+            path: path_from_url(&node.item.uri),
+            position: Some(position),
+            scroll_offset: None,
+            history: None,
+        }
+    }
+}
+
+impl Widget<LapceTabData> for HierarchyTreeView {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.widget_id)
+    }
+
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                match command {
+                    LapceUICommand::CallHierarchyCalls {
+                        request_id,
+                        path,
+                        calls,
+                    } => {
+                        Arc::make_mut(&mut data.call_hierarchy).receive_calls(
+                            *request_id,
+                            path,
+                            calls.clone(),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::TypeHierarchyTypes {
+                        request_id,
+                        path,
+                        items,
+                    } => {
+                        Arc::make_mut(&mut data.type_hierarchy).receive_types(
+                            *request_id,
+                            path,
+                            items.clone(),
+                        );
+                        ctx.set_handled();
+                    }
+                    _ => {}
+                }
+            }
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if mouse_event.pos.y < self.content_height {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                self.mouse_down(ctx, mouse_event, data);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        let changed = match self.kind {
+            PanelKind::TypeHierarchy => {
+                !Arc::ptr_eq(&old_data.type_hierarchy, &data.type_hierarchy)
+            }
+            _ => !Arc::ptr_eq(&old_data.call_hierarchy, &data.call_hierarchy),
+        };
+        if changed {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let line_height = data.config.editor.line_height as f64;
+        self.content_height = line_height * self.tree(data).rows().len() as f64;
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = data.config.editor.line_height as f64;
+        let size = ctx.size();
+        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
+        let svg_size = 15.0;
+        let font_family = data.config.ui.font_family();
+        let font_size = data.config.ui.font_size() as f64;
+        let foreground = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND);
+        let dim = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+
+        for (i, row) in self
+            .tree(data)
+            .rows()
+            .iter()
+            .enumerate()
+            .skip(min)
+            .take(max - min)
+        {
+            let y = line_height * i as f64;
+            if ctx.is_hot() && i == mouse_line {
+                ctx.fill(
+                    Size::new(size.width, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(0.0, y)),
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+                );
+            }
+
+            let padding = Self::INDENT * row.level as f64;
+            let svg_y = y + (line_height - svg_size) / 2.0;
+            let node = row.node;
+            // Leaves have nothing to expand
+            let has_children = node.children.as_ref().map(|c| !c.is_empty());
+            if has_children != Some(false) {
+                let icon_name = if node.expanded {
+                    "chevron-down.svg"
+                } else {
+                    "chevron-right.svg"
+                };
+                let rect = Size::new(svg_size, svg_size)
+                    .to_rect()
+                    .with_origin(Point::new(1.0 + padding, svg_y));
+                ctx.draw_svg(&get_svg(icon_name).unwrap(), rect, Some(foreground));
+            }
+
+            let x = padding + svg_size + 5.0;
+            if let Some(svg) = symbol_svg(&node.item.kind) {
+                let rect = Size::new(svg_size, svg_size)
+                    .to_rect()
+                    .with_origin(Point::new(x, svg_y));
+                ctx.draw_svg(&svg, rect, Some(foreground));
+            }
+
+            let x = x + svg_size + 5.0;
+            let text_layout = ctx
+                .text()
+                .new_text_layout(node.item.name.clone())
+                .font(font_family.clone(), font_size)
+                .text_color(foreground.clone())
+                .build()
+                .unwrap();
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(x, text_y));
+
+            let file_name = path_from_url(&node.item.uri)
+                .file_name()
+                .and_then(|f| f.to_str())
+                .unwrap_or("")
+                .to_string();
+            let detail = if row.level == 0 {
+                // The roots are the items that the hierarchy is about
+                self.title(data).to_string()
+            } else {
+                match node.item.detail.as_ref() {
+                    Some(detail) if !detail.is_empty() => {
+                        format!("{detail}  {file_name}")
+                    }
+                    _ => file_name,
+                }
+            };
+            let x = x + text_layout.size().width + 10.0;
+            let text_layout = ctx
+                .text()
+                .new_text_layout(detail)
+                .font(font_family.clone(), font_size)
+                .text_color(dim.clone())
+                .build()
+                .unwrap();
+            ctx.draw_text(&text_layout, Point::new(x, text_y));
+        }
+    }
+}
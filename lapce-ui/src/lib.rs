@@ -5,17 +5,24 @@ pub mod completion;
 pub mod editor;
 pub mod explorer;
 pub mod find;
+pub mod hierarchy;
 pub mod hover;
 pub mod keymap;
 mod logging;
+pub mod lsp_trace;
+pub mod notification;
+pub mod output;
 pub mod palette;
 pub mod panel;
+pub mod peek;
 pub mod picker;
 pub mod plugin;
 pub mod problem;
+pub mod rename;
 pub mod scroll;
 pub mod search;
 pub mod settings;
+pub mod signature;
 pub mod source_control;
 pub mod split;
 pub mod status;
@@ -0,0 +1,343 @@
+use std::sync::Arc;
+
+use chrono::{Local, TimeZone};
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Cursor, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+    WidgetExt,
+};
+use lapce_data::{
+    config::LapceTheme, data::LapceTabData, lsp_trace::LspTraceItem,
+    panel::PanelKind,
+};
+use lapce_rpc::core::LspTraceDirection;
+
+use crate::panel::{LapcePanel, PanelHeaderKind};
+
+pub fn new_lsp_trace_panel(data: &LapceTabData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::LspTrace,
+        data.lsp_trace.widget_id,
+        data.lsp_trace.split_id,
+        vec![
+            (
+                data.lsp_trace.methods_widget_id,
+                PanelHeaderKind::Simple("Methods".into()),
+                LspTraceMethods::new().boxed(),
+                Some(250.0),
+            ),
+            (
+                data.lsp_trace.messages_widget_id,
+                PanelHeaderKind::Simple("Messages".into()),
+                LspTraceMessages::new().boxed(),
+                None,
+            ),
+        ],
+    )
+}
+
+/// The methods of the traced messages, clicking on one shows only its
+/// messages, and clicking on the first line shows them all again
+struct LspTraceMethods {
+    mouse_pos: Point,
+    content_height: f64,
+}
+
+impl LspTraceMethods {
+    fn new() -> Self {
+        Self {
+            mouse_pos: Point::ZERO,
+            content_height: 0.0,
+        }
+    }
+}
+
+impl Widget<LapceTabData> for LspTraceMethods {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if mouse_event.pos.y < self.content_height {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                let line_height = data.config.editor.line_height as f64;
+                let n = (mouse_event.pos.y / line_height).floor() as usize;
+                if n == 0 {
+                    Arc::make_mut(&mut data.lsp_trace).filter = None;
+                } else if let Some((method, _)) =
+                    data.lsp_trace.methods.get_index(n - 1)
+                {
+                    let method = method.clone();
+                    Arc::make_mut(&mut data.lsp_trace).filter = Some(method);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !Arc::ptr_eq(&old_data.lsp_trace, &data.lsp_trace) {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let line_height = data.config.editor.line_height as f64;
+        self.content_height =
+            line_height * (data.lsp_trace.methods.len() + 1) as f64;
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = data.config.editor.line_height as f64;
+        let size = ctx.size();
+        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
+
+        let total = data.lsp_trace.items.len();
+        let lines = std::iter::once((None, total)).chain(
+            data.lsp_trace
+                .methods
+                .iter()
+                .map(|(method, count)| (Some(method), *count)),
+        );
+        for (i, (method, count)) in lines.enumerate() {
+            let y = line_height * i as f64;
+            if method == data.lsp_trace.filter.as_ref() {
+                ctx.fill(
+                    Size::new(size.width, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(0.0, y)),
+                    data.config.get_color_unchecked(LapceTheme::PANEL_CURRENT),
+                );
+            } else if ctx.is_hot() && i == mouse_line {
+                ctx.fill(
+                    Size::new(size.width, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(0.0, y)),
+                    data.config.get_color_unchecked(LapceTheme::PANEL_HOVERED),
+                );
+            }
+
+            let text = format!(
+                "{} ({count})",
+                method.map(|method| method.as_str()).unwrap_or("All")
+            );
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(10.0, text_y));
+        }
+    }
+}
+
+enum TraceRow {
+    /// The message at the index of the trace items
+    Message(usize),
+    /// A line of the pretty printed payload of the expanded message
+    Payload(String),
+}
+
+/// The messages that pass the filter, one per line, with the payload of the
+/// one that is clicked on pretty printed below it
+struct LspTraceMessages {
+    rows: Vec<TraceRow>,
+}
+
+impl LspTraceMessages {
+    fn new() -> Self {
+        Self { rows: Vec::new() }
+    }
+
+    fn message_text(item: &LspTraceItem) -> String {
+        let entry = &item.entry;
+        let time = Local
+            .timestamp_millis(entry.timestamp as i64)
+            .format("%H:%M:%S%.3f");
+        let direction = match entry.direction {
+            LspTraceDirection::Sent => "→",
+            LspTraceDirection::Received => "←",
+        };
+        let id = entry
+            .payload
+            .get("id")
+            .map(|id| format!(" #{id}"))
+            .unwrap_or_default();
+        let latency = entry
+            .latency
+            .map(|latency| format!(" ({latency} ms)"))
+            .unwrap_or_default();
+        format!(
+            "{time} {direction} {} {}{id}{latency}",
+            item.server,
+            item.summary()
+        )
+    }
+}
+
+impl Widget<LapceTabData> for LspTraceMessages {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        let line_height = data.config.editor.line_height as f64;
+        match event {
+            Event::MouseMove(mouse_event) => {
+                let n = (mouse_event.pos.y / line_height).floor() as usize;
+                if let Some(TraceRow::Message(_)) = self.rows.get(n) {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+            Event::MouseDown(mouse_event) => {
+                let n = (mouse_event.pos.y / line_height).floor() as usize;
+                if let Some(TraceRow::Message(index)) = self.rows.get(n) {
+                    if let Some(item) = data.lsp_trace.items.get(*index) {
+                        let id = item.id;
+                        Arc::make_mut(&mut data.lsp_trace).toggle_expanded(id);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !Arc::ptr_eq(&old_data.lsp_trace, &data.lsp_trace) {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let trace = &data.lsp_trace;
+        self.rows.clear();
+        for (index, item) in trace.items.iter().enumerate() {
+            if !trace.is_visible(item) {
+                continue;
+            }
+            self.rows.push(TraceRow::Message(index));
+            if trace.expanded == Some(item.id) {
+                let payload = serde_json::to_string_pretty(&item.entry.payload)
+                    .unwrap_or_default();
+                self.rows.extend(
+                    payload
+                        .lines()
+                        .map(|line| TraceRow::Payload(line.to_string())),
+                );
+            }
+        }
+
+        let line_height = data.config.editor.line_height as f64;
+        let height = line_height * self.rows.len() as f64;
+        Size::new(bc.max().width, height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = data.config.editor.line_height as f64;
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+
+        for (i, row) in self.rows.iter().enumerate().skip(min).take(max - min) {
+            let (text, color, x) = match row {
+                TraceRow::Message(index) => {
+                    let item = match data.lsp_trace.items.get(*index) {
+                        Some(item) => item,
+                        None => continue,
+                    };
+                    let color = if item.is_error() {
+                        LapceTheme::LAPCE_ERROR
+                    } else {
+                        LapceTheme::EDITOR_FOREGROUND
+                    };
+                    (Self::message_text(item), color, 10.0)
+                }
+                TraceRow::Payload(line) => {
+                    (line.clone(), LapceTheme::EDITOR_DIM, 30.0)
+                }
+            };
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(
+                    data.config.editor.font_family(),
+                    data.config.editor.font_size as f64,
+                )
+                .text_color(data.config.get_color_unchecked(color).clone())
+                .build()
+                .unwrap();
+            let y = line_height * i as f64;
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(x, text_y));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use druid::{
+    piet::{PietTextLayout, Text, TextAttribute, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Cursor, Env, Event, EventCtx, FontWeight, LayoutCtx,
+    LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, Target,
+    UpdateCtx, Widget,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::LapceTabData,
+};
+use lsp_types::MessageType;
+
+use crate::svg::get_svg;
+
+struct NotificationLayout {
+    id: usize,
+    kind: MessageType,
+    rect: Rect,
+    close_rect: Rect,
+    title: PietTextLayout,
+    message: PietTextLayout,
+}
+
+/// The notifications in the bottom right corner, above the status bar
+pub struct NotificationList {
+    width: f64,
+    padding: f64,
+    svg_size: f64,
+    items: Vec<NotificationLayout>,
+}
+
+impl NotificationList {
+    pub fn new() -> Self {
+        Self {
+            width: 360.0,
+            padding: 10.0,
+            svg_size: 14.0,
+            items: Vec::new(),
+        }
+    }
+}
+
+impl Default for NotificationList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget<LapceTabData> for NotificationList {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                if self
+                    .items
+                    .iter()
+                    .any(|item| item.close_rect.contains(mouse_event.pos))
+                {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                for item in self.items.iter() {
+                    if item.close_rect.contains(mouse_event.pos) {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::DismissNotification(item.id),
+                            Target::Widget(data.id),
+                        ));
+                    }
+                    if item.rect.contains(mouse_event.pos) {
+                        ctx.set_handled();
+                    }
+                }
+            }
+            Event::MouseUp(mouse_event) => {
+                if self
+                    .items
+                    .iter()
+                    .any(|item| item.rect.contains(mouse_event.pos))
+                {
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !old_data.notification.items.ptr_eq(&data.notification.items) {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        _bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let font_family = data.config.ui.font_family();
+        let font_size = data.config.ui.font_size() as f64;
+        let foreground = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            .clone();
+        let text_x = self.padding * 2.0 + self.svg_size;
+        let text_width = self.width - text_x - self.padding * 2.0 - self.svg_size;
+
+        self.items.clear();
+        let mut y = 0.0;
+        for notification in data.notification.items.iter() {
+            let title = ctx
+                .text()
+                .new_text_layout(notification.title.clone())
+                .font(font_family.clone(), font_size)
+                .default_attribute(TextAttribute::Weight(FontWeight::BOLD))
+                .max_width(text_width)
+                .text_color(foreground.clone())
+                .build()
+                .unwrap();
+            let message = ctx
+                .text()
+                .new_text_layout(notification.message.clone())
+                .font(font_family.clone(), font_size)
+                .max_width(text_width)
+                .text_color(foreground.clone())
+                .build()
+                .unwrap();
+            let height = self.padding * 2.0
+                + title.size().height
+                + self.padding / 2.0
+                + message.size().height;
+            let rect = Rect::new(0.0, y, self.width, y + height);
+            let close_rect = Size::new(self.svg_size, self.svg_size)
+                .to_rect()
+                .with_origin(Point::new(
+                    self.width - self.padding - self.svg_size,
+                    y + self.padding,
+                ))
+                .inflate(2.0, 2.0);
+            self.items.push(NotificationLayout {
+                id: notification.id,
+                kind: notification.kind,
+                rect,
+                close_rect,
+                title,
+                message,
+            });
+            y += height + self.padding;
+        }
+
+        if self.items.is_empty() {
+            Size::ZERO
+        } else {
+            Size::new(self.width, y - self.padding)
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let shadow_width = data.config.ui.drop_shadow_width() as f64;
+        let foreground = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND);
+        for item in self.items.iter() {
+            if shadow_width > 0.0 {
+                ctx.blurred_rect(
+                    item.rect,
+                    shadow_width,
+                    data.config
+                        .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+                );
+            } else {
+                ctx.stroke(
+                    item.rect.inflate(0.5, 0.5),
+                    data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                    1.0,
+                );
+            }
+            ctx.fill(
+                item.rect,
+                data.config
+                    .get_color_unchecked(LapceTheme::HOVER_BACKGROUND),
+            );
+
+            let title_height = item.title.size().height;
+            let svg_y =
+                item.rect.y0 + self.padding + (title_height - self.svg_size) / 2.0;
+            let icon = match item.kind {
+                MessageType::ERROR => Some(("error.svg", LapceTheme::LAPCE_ERROR)),
+                MessageType::WARNING => {
+                    Some(("warning.svg", LapceTheme::LAPCE_WARN))
+                }
+                _ => None,
+            };
+            if let Some((svg, color)) = icon {
+                let rect = Size::new(self.svg_size, self.svg_size)
+                    .to_rect()
+                    .with_origin(Point::new(self.padding, svg_y));
+                ctx.draw_svg(
+                    &get_svg(svg).unwrap(),
+                    rect,
+                    Some(data.config.get_color_unchecked(color)),
+                );
+            }
+
+            let text_x = self.padding * 2.0 + self.svg_size;
+            ctx.draw_text(
+                &item.title,
+                Point::new(text_x, item.rect.y0 + self.padding),
+            );
+            ctx.draw_text(
+                &item.message,
+                Point::new(text_x, item.rect.y0 + self.padding * 1.5 + title_height),
+            );
+
+            ctx.draw_svg(
+                &get_svg("close.svg").unwrap(),
+                item.close_rect.inflate(-2.0, -2.0),
+                Some(foreground),
+            );
+        }
+    }
+}
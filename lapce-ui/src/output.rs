@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Cursor, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+    WidgetExt,
+};
+use lapce_data::{config::LapceTheme, data::LapceTabData, panel::PanelKind};
+use lsp_types::MessageType;
+
+use crate::panel::{LapcePanel, PanelHeaderKind};
+
+pub fn new_output_panel(data: &LapceTabData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::Output,
+        data.output.widget_id,
+        data.output.split_id,
+        vec![
+            (
+                data.output.servers_widget_id,
+                PanelHeaderKind::Simple("Servers".into()),
+                OutputServers::new().boxed(),
+                Some(200.0),
+            ),
+            (
+                data.output.log_widget_id,
+                PanelHeaderKind::Simple("Log".into()),
+                OutputLog::new().boxed(),
+                None,
+            ),
+        ],
+    )
+}
+
+/// The language servers that have logged something, clicking on one shows
+/// its log
+struct OutputServers {
+    mouse_pos: Point,
+    content_height: f64,
+}
+
+impl OutputServers {
+    fn new() -> Self {
+        Self {
+            mouse_pos: Point::ZERO,
+            content_height: 0.0,
+        }
+    }
+}
+
+impl Widget<LapceTabData> for OutputServers {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                if mouse_event.pos.y < self.content_height {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                let line_height = data.config.editor.line_height as f64;
+                let n = (mouse_event.pos.y / line_height).floor() as usize;
+                if n < data.output.logs.len() {
+                    Arc::make_mut(&mut data.output).active = n;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if old_data.output.logs.len() != data.output.logs.len()
+            || old_data.output.active != data.output.active
+        {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let line_height = data.config.editor.line_height as f64;
+        self.content_height = line_height * data.output.logs.len() as f64;
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = data.config.editor.line_height as f64;
+        let size = ctx.size();
+        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
+
+        for (i, server) in data.output.logs.keys().enumerate() {
+            let y = line_height * i as f64;
+            if i == data.output.active {
+                ctx.fill(
+                    Size::new(size.width, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(0.0, y)),
+                    data.config.get_color_unchecked(LapceTheme::PANEL_CURRENT),
+                );
+            } else if ctx.is_hot() && i == mouse_line {
+                ctx.fill(
+                    Size::new(size.width, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(0.0, y)),
+                    data.config.get_color_unchecked(LapceTheme::PANEL_HOVERED),
+                );
+            }
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(server.clone())
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(10.0, text_y));
+        }
+    }
+}
+
+/// The log of the server that is picked in `OutputServers`
+struct OutputLog {}
+
+impl OutputLog {
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Widget<LapceTabData> for OutputLog {
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        _event: &Event,
+        _data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !Arc::ptr_eq(&old_data.output, &data.output) {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let line_height = data.config.editor.line_height as f64;
+        let lines = data
+            .output
+            .active_log()
+            .map(|(_, lines)| lines.len())
+            .unwrap_or(0);
+        let height = line_height * lines as f64;
+        Size::new(bc.max().width, height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let lines = match data.output.active_log() {
+            Some((_, lines)) => lines,
+            None => return,
+        };
+        let line_height = data.config.editor.line_height as f64;
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+
+        for (i, line) in lines.iter().enumerate().skip(min).take(max - min) {
+            let color = match line.kind {
+                MessageType::ERROR => LapceTheme::LAPCE_ERROR,
+                MessageType::WARNING => LapceTheme::LAPCE_WARN,
+                MessageType::LOG => LapceTheme::EDITOR_DIM,
+                _ => LapceTheme::EDITOR_FOREGROUND,
+            };
+            let text_layout = ctx
+                .text()
+                .new_text_layout(line.text.clone())
+                .font(
+                    data.config.editor.font_family(),
+                    data.config.editor.font_size as f64,
+                )
+                .text_color(data.config.get_color_unchecked(color).clone())
+                .build()
+                .unwrap();
+            let y = line_height * i as f64;
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(10.0, text_y));
+        }
+    }
+}
@@ -653,7 +653,8 @@ impl PaletteContent {
                     "".to_string(),
                     vec![],
                 ),
-                PaletteItemContent::Language(name) => (
+                PaletteItemContent::Language(name)
+                | PaletteItemContent::LanguageServer(name) => (
                     None,
                     name.to_string(),
                     indices.to_vec(),
@@ -920,6 +920,14 @@ impl PanelSwitcher {
             PanelKind::Terminal => LapceWorkbenchCommand::ToggleTerminalVisual,
             PanelKind::Search => LapceWorkbenchCommand::ToggleSearchVisual,
             PanelKind::Problem => LapceWorkbenchCommand::ToggleProblemVisual,
+            PanelKind::CallHierarchy => {
+                LapceWorkbenchCommand::ToggleCallHierarchyVisual
+            }
+            PanelKind::TypeHierarchy => {
+                LapceWorkbenchCommand::ToggleTypeHierarchyVisual
+            }
+            PanelKind::Output => LapceWorkbenchCommand::ToggleOutputVisual,
+            PanelKind::LspTrace => LapceWorkbenchCommand::ToggleLspTraceVisual,
         };
         (
             *kind,
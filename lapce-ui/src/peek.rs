@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget, WidgetExt, WidgetId,
+    WidgetPod,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::LapceTabData,
+    peek::PeekStatus,
+    proxy::path_from_url,
+};
+
+use crate::editor::view::LapceEditorView;
+
+/// A header line with the location that is shown, above a read-only editor
+/// on it
+pub struct PeekContainer {
+    id: WidgetId,
+    editor: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+}
+
+impl PeekContainer {
+    pub fn new(data: &LapceTabData) -> Self {
+        let editor =
+            LapceEditorView::new(data.peek.editor_id, WidgetId::next(), None)
+                .hide_header();
+        Self {
+            id: data.peek.id,
+            editor: WidgetPod::new(editor.boxed()),
+        }
+    }
+
+    /// The kind of the peek, the path of the location relative to the
+    /// workspace with its line, and the position among the locations
+    fn header_text(data: &LapceTabData) -> String {
+        let peek = &data.peek;
+        let location = match peek.location() {
+            Some(location) => location,
+            None => return peek.kind.title().to_string(),
+        };
+        let path = path_from_url(&location.uri);
+        let path = data
+            .workspace
+            .path
+            .as_ref()
+            .and_then(|workspace| path.strip_prefix(workspace).ok())
+            .unwrap_or(&path);
+        format!(
+            "{}  {}:{}  {}/{}",
+            peek.kind.title(),
+            path.to_string_lossy(),
+            location.range.start.line + 1,
+            peek.index + 1,
+            peek.len()
+        )
+    }
+}
+
+impl Widget<LapceTabData> for PeekContainer {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.id)
+    }
+
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                if let LapceUICommand::UpdatePeek {
+                    request_id,
+                    locations,
+                } = command
+                {
+                    let peek = Arc::make_mut(&mut data.peek);
+                    if peek.receive(*request_id, locations.clone()) {
+                        peek.show(ctx);
+                    }
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            // The editor is read-only, so clicks don't reach it and a double
+            // click opens the location instead
+            Event::MouseDown(mouse_event) => {
+                if mouse_event.count == 2 {
+                    Arc::make_mut(&mut data.peek).open(ctx);
+                }
+                ctx.set_handled();
+                return;
+            }
+            Event::MouseUp(_) | Event::MouseMove(_) => {
+                ctx.set_handled();
+                return;
+            }
+            _ => {}
+        }
+        self.editor.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.editor.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        let old_peek = &old_data.peek;
+        let peek = &data.peek;
+
+        if peek.status != PeekStatus::Inactive {
+            let old_editor =
+                old_data.main_split.editors.get(&old_peek.editor_view_id);
+            let editor = data.main_split.editors.get(&peek.editor_view_id);
+            if let (Some(old_editor), Some(editor)) = (old_editor, editor) {
+                if old_editor.window_origin != editor.window_origin
+                    || old_editor.scroll_offset != editor.scroll_offset
+                {
+                    ctx.request_layout();
+                }
+            }
+        }
+
+        if old_peek.request_id != peek.request_id
+            || old_peek.status != peek.status
+            || old_peek.index != peek.index
+            || !Arc::ptr_eq(&old_peek.locations, &peek.locations)
+        {
+            ctx.request_layout();
+        }
+
+        self.editor.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        let size = bc.max();
+        let header_height = data.config.editor.line_height as f64;
+        let editor_size =
+            Size::new(size.width, (size.height - header_height).max(0.0));
+        self.editor
+            .layout(ctx, &BoxConstraints::tight(editor_size), data, env);
+        self.editor
+            .set_origin(ctx, data, env, Point::new(0.0, header_height));
+        ctx.set_paint_insets((10.0, 10.0, 10.0, 10.0));
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        if data.peek.status != PeekStatus::Done {
+            return;
+        }
+
+        let size = ctx.size();
+        let rect = size.to_rect();
+        let shadow_width = data.config.ui.drop_shadow_width() as f64;
+        if shadow_width > 0.0 {
+            ctx.blurred_rect(
+                rect,
+                shadow_width,
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+            );
+        } else {
+            ctx.stroke(
+                rect.inflate(0.5, 0.5),
+                data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                1.0,
+            );
+        }
+
+        let header_height = data.config.editor.line_height as f64;
+        ctx.fill(
+            Size::new(size.width, header_height).to_rect(),
+            data.config
+                .get_color_unchecked(LapceTheme::PANEL_BACKGROUND),
+        );
+        let text_layout = ctx
+            .text()
+            .new_text_layout(Self::header_text(data))
+            .font(
+                data.config.ui.font_family(),
+                data.config.ui.font_size() as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(10.0, (header_height - text_layout.size().height) / 2.0),
+        );
+
+        self.editor.paint(ctx, data, env);
+    }
+}
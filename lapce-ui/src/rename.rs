@@ -0,0 +1,337 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Data, Env, Event, EventCtx, KbKey, LayoutCtx,
+    LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, Target,
+    UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::{LapceEditorData, LapceTabData},
+    document::{BufferContent, LocalBufferKind},
+    rename::RenameStatus,
+};
+
+use crate::editor::view::LapceEditorView;
+
+/// The inline input for renaming a symbol, along with the preview of the
+/// workspace edit that the language server sent back for it
+pub struct RenameContainer {
+    id: WidgetId,
+    input: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    input_height: f64,
+}
+
+impl RenameContainer {
+    const WIDTH: f64 = 400.0;
+    const PADDING: f64 = 5.0;
+    /// The maximum number of preview rows (files and hunks) to show
+    const MAX_ROWS: usize = 15;
+
+    pub fn new(data: &mut LapceTabData) -> Self {
+        let editor = LapceEditorData::new(
+            Some(data.rename.input_view_id),
+            None,
+            None,
+            BufferContent::Local(LocalBufferKind::Rename),
+            &data.config,
+        );
+        let input = LapceEditorView::new(editor.view_id, editor.editor_id, None)
+            .hide_header()
+            .hide_gutter()
+            .hide_border()
+            .set_background_color(LapceTheme::EDITOR_BACKGROUND);
+        data.main_split
+            .editors
+            .insert(editor.view_id, Arc::new(editor));
+
+        Self {
+            id: data.rename.id,
+            input: WidgetPod::new(input.boxed()),
+            input_height: 0.0,
+        }
+    }
+
+    /// Request the edit for the name in the input, or apply the previewed
+    /// edit if the name hasn't changed since it was requested
+    fn submit(&self, ctx: &mut EventCtx, data: &mut LapceTabData) {
+        let new_name = data
+            .main_split
+            .local_docs
+            .get(&LocalBufferKind::Rename)
+            .unwrap()
+            .buffer()
+            .text()
+            .to_string();
+        let new_name = new_name.trim().to_string();
+
+        let rename = Arc::make_mut(&mut data.rename);
+        match rename.status {
+            RenameStatus::Preview if new_name == rename.new_name => {
+                rename.apply(ctx, &mut data.main_split);
+                self.focus_editor(ctx, data);
+            }
+            RenameStatus::Input | RenameStatus::Preview if !new_name.is_empty() => {
+                rename.request(&data.proxy, new_name, ctx.get_external_handle());
+            }
+            _ => {}
+        }
+    }
+
+    fn focus_editor(&self, ctx: &mut EventCtx, data: &LapceTabData) {
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(data.rename.editor_view_id),
+        ));
+    }
+
+    fn num_rows(data: &LapceTabData) -> usize {
+        data.rename
+            .preview
+            .iter()
+            .map(|file| file.hunks.len() + 1)
+            .sum::<usize>()
+            .min(Self::MAX_ROWS + 1)
+    }
+
+    fn paint_preview(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
+        let line_height = data.config.editor.line_height as f64;
+        let font_family = data.config.ui.font_family();
+        let font_size = data.config.ui.font_size() as f64;
+        let foreground = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            .clone();
+        let dim = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_DIM)
+            .clone();
+
+        let mut rows = Vec::new();
+        for file in data.rename.preview.iter() {
+            let path = data
+                .workspace
+                .path
+                .as_ref()
+                .and_then(|workspace| file.path.strip_prefix(workspace).ok())
+                .unwrap_or(&file.path);
+            rows.push((
+                None,
+                format!("{} ({})", path.to_string_lossy(), file.edits.len()),
+            ));
+            for hunk in file.hunks.iter() {
+                rows.push((
+                    Some(format!("{}", hunk.line + 1)),
+                    hunk.after.trim().to_string(),
+                ));
+            }
+        }
+        let total = rows.len();
+
+        let mut y = self.input_height + Self::PADDING;
+        for (i, (line, text)) in rows.into_iter().enumerate() {
+            if i == Self::MAX_ROWS && total > Self::MAX_ROWS + 1 {
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(format!("... {} more", total - i))
+                    .font(font_family.clone(), font_size)
+                    .text_color(dim.clone())
+                    .build()
+                    .unwrap();
+                let text_y = y + (line_height - text_layout.size().height) / 2.0;
+                ctx.draw_text(&text_layout, Point::new(Self::PADDING, text_y));
+                break;
+            }
+
+            let x = match line {
+                Some(line) => {
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout(line)
+                        .font(font_family.clone(), font_size)
+                        .text_color(dim.clone())
+                        .build()
+                        .unwrap();
+                    let text_y = y + (line_height - text_layout.size().height) / 2.0;
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(Self::PADDING * 4.0, text_y),
+                    );
+                    Self::PADDING * 4.0 + text_layout.size().width + 10.0
+                }
+                None => Self::PADDING,
+            };
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(font_family.clone(), font_size)
+                .text_color(foreground.clone())
+                .build()
+                .unwrap();
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(x, text_y));
+
+            y += line_height;
+        }
+    }
+}
+
+impl Widget<LapceTabData> for RenameContainer {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.id)
+    }
+
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                match command {
+                    LapceUICommand::RenamePreview { request_id, edit } => {
+                        let rename = Arc::make_mut(&mut data.rename);
+                        rename.receive(
+                            *request_id,
+                            edit.as_ref(),
+                            &data.main_split,
+                            &data.proxy,
+                            ctx.get_external_handle(),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RenamePreviewFile {
+                        request_id,
+                        path,
+                        content,
+                    } => {
+                        Arc::make_mut(&mut data.rename).receive_file(
+                            *request_id,
+                            path,
+                            content,
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::CancelRename => {
+                        Arc::make_mut(&mut data.rename).cancel();
+                        ctx.set_handled();
+                    }
+                    _ => {}
+                }
+            }
+            Event::KeyDown(key_ev) if self.input.has_focus() => {
+                if key_ev.key == KbKey::Enter {
+                    self.submit(ctx, data);
+                    ctx.set_handled();
+                    return;
+                } else if key_ev.key == KbKey::Escape {
+                    Arc::make_mut(&mut data.rename).cancel();
+                    self.focus_editor(ctx, data);
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            _ => {}
+        }
+        self.input.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.input.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        if old_data.rename.status != data.rename.status
+            || !old_data.rename.preview.same(&data.rename.preview)
+        {
+            ctx.request_layout();
+        }
+
+        if data.rename.status != RenameStatus::Inactive {
+            self.input.update(ctx, data, env);
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        let input_bc =
+            BoxConstraints::tight(Size::new(Self::WIDTH, bc.max().height)).loosen();
+        let input_size = self.input.layout(ctx, &input_bc, data, env);
+        self.input.set_origin(ctx, data, env, Point::ZERO);
+        self.input_height = input_size.height;
+
+        let rows = Self::num_rows(data);
+        let preview_height = if rows > 0 {
+            rows as f64 * data.config.editor.line_height as f64 + Self::PADDING * 2.0
+        } else {
+            0.0
+        };
+
+        ctx.set_paint_insets((10.0, 10.0, 10.0, 10.0));
+        Size::new(Self::WIDTH, self.input_height + preview_height)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        if data.rename.status == RenameStatus::Inactive {
+            return;
+        }
+
+        let rect = ctx.size().to_rect();
+        let shadow_width = data.config.ui.drop_shadow_width() as f64;
+        if shadow_width > 0.0 {
+            ctx.blurred_rect(
+                rect,
+                shadow_width,
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+            );
+        } else {
+            ctx.stroke(
+                rect.inflate(0.5, 0.5),
+                data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                1.0,
+            );
+        }
+        ctx.fill(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::HOVER_BACKGROUND),
+        );
+
+        self.input.paint(ctx, data, env);
+
+        ctx.with_save(|ctx| {
+            ctx.clip(Rect::new(
+                0.0,
+                self.input_height,
+                rect.width(),
+                rect.height(),
+            ));
+            self.paint_preview(ctx, data);
+        });
+    }
+}
@@ -0,0 +1,323 @@
+use std::sync::Arc;
+
+use druid::{
+    kurbo::Line, theme, ArcStr, BoxConstraints, Command, Env, Event, EventCtx,
+    FontDescriptor, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point,
+    RenderContext, Size, Target, TextLayout, UpdateCtx, Widget, WidgetId, WidgetPod,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::LapceTabData,
+    rich_text::RichText,
+    signature::{SignatureData, SignatureStatus},
+};
+
+use crate::scroll::{LapceIdentityWrapper, LapceScroll};
+
+pub struct SignatureContainer {
+    id: WidgetId,
+    scroll_id: WidgetId,
+    signature: WidgetPod<
+        LapceTabData,
+        LapceIdentityWrapper<LapceScroll<LapceTabData, Signature>>,
+    >,
+    content_size: Size,
+}
+
+impl SignatureContainer {
+    pub fn new(data: &SignatureData) -> Self {
+        let signature = LapceIdentityWrapper::wrap(
+            LapceScroll::new(Signature::new()).vertical(),
+            data.scroll_id,
+        );
+        Self {
+            id: data.id,
+            scroll_id: data.scroll_id,
+            signature: WidgetPod::new(signature),
+            content_size: Size::ZERO,
+        }
+    }
+}
+
+impl Widget<LapceTabData> for SignatureContainer {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.id)
+    }
+
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                if let LapceUICommand::UpdateSignature { request_id, help } = command
+                {
+                    let signature = Arc::make_mut(&mut data.signature);
+                    signature.receive(*request_id, help.clone());
+                    ctx.request_paint();
+                }
+            }
+            _ => {}
+        }
+        self.signature.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.signature.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        let old_signature = &old_data.signature;
+        let signature = &data.signature;
+
+        if signature.status != SignatureStatus::Inactive {
+            let old_editor = old_data
+                .main_split
+                .editors
+                .get(&old_signature.editor_view_id);
+            let editor = data.main_split.editors.get(&signature.editor_view_id);
+            if let (Some(old_editor), Some(editor)) = (old_editor, editor) {
+                if old_editor.window_origin != editor.window_origin
+                    || old_editor.scroll_offset != editor.scroll_offset
+                {
+                    ctx.request_layout();
+                }
+            }
+        }
+
+        if old_signature.request_id != signature.request_id
+            || old_signature.status != signature.status
+            || old_signature.offset != signature.offset
+            || old_signature.active_signature != signature.active_signature
+        {
+            ctx.request_layout();
+        }
+
+        if old_signature.status == SignatureStatus::Inactive
+            && signature.status != SignatureStatus::Inactive
+        {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ResetFade,
+                Target::Widget(self.scroll_id),
+            ));
+        }
+
+        self.signature.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        _bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        let size = data.signature.size;
+        let bc = BoxConstraints::new(Size::ZERO, size);
+        self.content_size = self.signature.layout(ctx, &bc, data, env);
+        *data.signature.content_size.borrow_mut() = self.content_size;
+        self.signature.set_origin(ctx, data, env, Point::ZERO);
+        ctx.set_paint_insets((10.0, 10.0, 10.0, 10.0));
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        if data.signature.status == SignatureStatus::Done {
+            let rect = self.content_size.to_rect();
+            let shadow_width = data.config.ui.drop_shadow_width() as f64;
+            if shadow_width > 0.0 {
+                ctx.blurred_rect(
+                    rect,
+                    shadow_width,
+                    data.config
+                        .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+                );
+            } else {
+                ctx.stroke(
+                    rect.inflate(0.5, 0.5),
+                    data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                    1.0,
+                );
+            }
+            self.signature.paint(ctx, data, env);
+        }
+    }
+}
+
+/// The label of the overload, and below a separator the documentation of
+/// the active parameter and of the overload
+struct Signature {
+    label_layout: TextLayout<RichText>,
+    documentation_layouts: Vec<TextLayout<RichText>>,
+}
+
+impl Signature {
+    const STARTING_Y: f64 = 5.0;
+    const STARTING_X: f64 = 10.0;
+
+    fn new() -> Self {
+        let mut label_layout = TextLayout::new();
+        label_layout.set_text(RichText::new(ArcStr::from("")));
+        Self {
+            label_layout,
+            documentation_layouts: Vec::new(),
+        }
+    }
+}
+
+impl Widget<LapceTabData> for Signature {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        _data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        if let Event::MouseMove(_) = event {
+            ctx.set_handled();
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        let old_signature = &old_data.signature;
+        let signature = &data.signature;
+        let help_changed = match (&old_signature.help, &signature.help) {
+            (Some(old_help), Some(help)) => !Arc::ptr_eq(old_help, help),
+            (None, None) => false,
+            _ => true,
+        };
+        if !help_changed
+            && old_signature.active_signature == signature.active_signature
+        {
+            return;
+        }
+
+        let font = FontDescriptor::new(data.config.editor.font_family())
+            .with_size(data.config.editor.font_size as f64);
+        let doc_font = FontDescriptor::new(data.config.ui.hover_font_family())
+            .with_size(data.config.ui.hover_font_size() as f64);
+        let text_color = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            .clone();
+
+        self.label_layout.set_text(signature.label(&data.config));
+        self.label_layout.set_font(font);
+        self.label_layout.set_text_color(text_color.clone());
+
+        self.documentation_layouts = signature
+            .documentation(&data.config)
+            .into_iter()
+            .map(|documentation| {
+                let mut layout = TextLayout::new();
+                layout.set_text(documentation);
+                layout.set_font(doc_font.clone());
+                layout.set_text_color(text_color.clone());
+                layout
+            })
+            .collect();
+
+        ctx.request_layout();
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        let width = bc.max().width;
+        let max_width = width
+            - Signature::STARTING_X
+            - env.get(theme::SCROLLBAR_WIDTH)
+            - env.get(theme::SCROLLBAR_PAD);
+
+        self.label_layout.set_wrap_width(max_width);
+        self.label_layout.rebuild_if_needed(ctx.text(), env);
+        let mut height = self.label_layout.size().height;
+
+        if !self.documentation_layouts.is_empty() {
+            height += data.config.editor.line_height as f64;
+        }
+        for layout in self.documentation_layouts.iter_mut() {
+            layout.set_wrap_width(max_width);
+            layout.rebuild_if_needed(ctx.text(), env);
+            height += layout.size().height;
+        }
+
+        Size::new(width, height + Signature::STARTING_Y * 2.0)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        if data.signature.status != SignatureStatus::Done {
+            return;
+        }
+
+        let rect = ctx.region().bounding_box();
+        ctx.fill(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::HOVER_BACKGROUND),
+        );
+
+        let origin = Point::new(Self::STARTING_X, Self::STARTING_Y);
+        self.label_layout.draw(ctx, origin);
+        if self.documentation_layouts.is_empty() {
+            return;
+        }
+
+        // Separate the documentation from the label
+        let line_height = data.config.editor.line_height as f64;
+        let side_margin =
+            env.get(theme::SCROLLBAR_WIDTH) + env.get(theme::SCROLLBAR_PAD);
+        let y = origin.y + self.label_layout.size().height + line_height / 2.0;
+        ctx.stroke(
+            Line::new(
+                Point::new(rect.x0 + side_margin, y),
+                Point::new(rect.x1 - side_margin, y),
+            ),
+            data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+            1.0,
+        );
+
+        let mut origin = Point::new(origin.x, y + line_height / 2.0);
+        for layout in self.documentation_layouts.iter() {
+            layout.draw(ctx, origin);
+            origin.y += layout.size().height;
+        }
+    }
+}
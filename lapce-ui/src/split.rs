@@ -3,7 +3,7 @@ use crate::{
     settings::LapceSettingsPanel,
     terminal::LapceTerminalView,
 };
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use crate::svg::logo_svg;
 use druid::{
@@ -523,6 +523,42 @@ impl LapceSplit {
         ctx.children_changed();
     }
 
+    /// Open a new terminal at the end of the split that runs the command
+    pub fn run_in_terminal(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        cwd: Option<PathBuf>,
+        command: &str,
+    ) {
+        let terminal_data = Arc::new(LapceTerminalData::new_with_command(
+            data.workspace.clone(),
+            self.split_id,
+            ctx.get_external_handle(),
+            data.proxy.clone(),
+            &data.config,
+            cwd,
+            command.to_string(),
+        ));
+        let terminal = LapceTerminalView::new(&terminal_data);
+        let terminal_panel = Arc::make_mut(&mut data.terminal);
+        terminal_panel.active = terminal_data.widget_id;
+        terminal_panel.active_term_id = terminal_data.term_id;
+        terminal_panel
+            .terminals
+            .insert(terminal_data.term_id, terminal_data.clone());
+
+        self.insert_flex_child(
+            self.children.len(),
+            terminal.boxed(),
+            Some(terminal_data.widget_id),
+            1.0,
+        );
+        self.even_flex_children();
+        ctx.children_changed();
+        data.show_panel(ctx, PanelKind::Terminal);
+    }
+
     pub fn split_terminal_close(
         &mut self,
         ctx: &mut EventCtx,
@@ -899,6 +935,9 @@ impl Widget<LapceTabData> for LapceSplit {
                     LapceUICommand::SplitEditorClose(widget_id) => {
                         self.split_editor_close(ctx, data, *widget_id);
                     }
+                    LapceUICommand::RunInTerminal { cwd, command } => {
+                        self.run_in_terminal(ctx, data, cwd.clone(), command);
+                    }
                     LapceUICommand::SplitTerminal(vertical, widget_id) => {
                         self.split_terminal(ctx, data, *vertical, *widget_id);
                     }
@@ -6,11 +6,15 @@ use druid::{
 };
 use lapce_core::mode::Mode;
 use lapce_data::{
-    command::{CommandKind, LapceCommand, LapceWorkbenchCommand, LAPCE_COMMAND},
+    command::{
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_COMMAND, LAPCE_UI_COMMAND,
+    },
     config::{Config, LapceTheme},
     data::{FocusArea, LapceTabData},
     panel::{PanelContainerPosition, PanelKind},
 };
+use lapce_rpc::core::LanguageServerStatus;
 
 use crate::{svg::get_svg, tab::LapceIcon};
 
@@ -273,7 +277,9 @@ impl Widget<LapceTabData> for LapceStatus {
             return;
         }
 
-        if !old_data.progresses.ptr_eq(&data.progresses) {
+        if !old_data.progresses.ptr_eq(&data.progresses)
+            || !old_data.language_servers.ptr_eq(&data.language_servers)
+        {
             ctx.request_paint();
         }
     }
@@ -532,5 +538,46 @@ impl Widget<LapceTabData> for LapceStatus {
                 ),
             ));
         }
+
+        for (name, status) in data.language_servers.iter().rev() {
+            let label = match status {
+                LanguageServerStatus::Running => name.clone(),
+                LanguageServerStatus::Starting => format!("{name} (starting)"),
+                LanguageServerStatus::Restarting => format!("{name} (restarting)"),
+                LanguageServerStatus::Crashed => format!("{name} (crashed)"),
+                LanguageServerStatus::Stopped => continue,
+            };
+            let x1 = right;
+            let (new_right, _, (point, text_layout)) = self
+                .paint_icon_with_label_from_right(
+                    right - 5.0,
+                    size.height,
+                    "",
+                    label,
+                    ctx,
+                    &data.config,
+                );
+            right = new_right;
+            let x0 = right - 5.0;
+            let rect = Rect::ZERO
+                .with_origin(Point::new(x0, 0.0))
+                .with_size(Size::new(x1 - x0, size.height));
+            if rect.contains(self.mouse_pos) {
+                ctx.fill(
+                    rect,
+                    data.config.get_color_unchecked(LapceTheme::PANEL_CURRENT),
+                );
+            }
+            ctx.draw_text(&text_layout, point);
+            // Clicking on a server restarts it
+            self.clickable_items.push((
+                rect,
+                Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RestartLanguageServer(name.clone()),
+                    Target::Widget(data.id),
+                ),
+            ));
+        }
     }
 }
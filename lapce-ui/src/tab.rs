@@ -15,10 +15,11 @@ use lapce_core::{
     selection::Selection,
 };
 use lapce_data::{
+    alert::AlertContentData,
     command::{
         CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
-        PluginLoadingStatus, LAPCE_COMMAND, LAPCE_OPEN_FILE, LAPCE_OPEN_FOLDER,
-        LAPCE_SAVE_FILE_AS, LAPCE_UI_COMMAND,
+        PluginLoadingStatus, LAPCE_COMMAND, LAPCE_EXPORT_LSP_TRACE, LAPCE_OPEN_FILE,
+        LAPCE_OPEN_FOLDER, LAPCE_SAVE_FILE_AS, LAPCE_UI_COMMAND,
     },
     completion::CompletionStatus,
     config::{Config, LapceTheme},
@@ -36,19 +37,40 @@ use lapce_data::{
         PanelContainerPosition, PanelKind, PanelPosition, PanelResizePosition,
         PanelStyle,
     },
+    peek::PeekStatus,
     proxy::path_from_url,
+    rename::RenameStatus,
+    signature::SignatureStatus,
 };
-use lapce_rpc::plugin::PluginDescription;
-use lsp_types::DiagnosticSeverity;
+use lapce_rpc::{core::LanguageServerStatus, plugin::PluginDescription};
+use lsp_types::{ApplyWorkspaceEditResponse, DiagnosticSeverity};
+use serde_json::{json, Value};
 use xi_rope::Rope;
 
 use crate::{
-    alert::AlertBox, completion::CompletionContainer, explorer::FileExplorer,
-    hover::HoverContainer, panel::PanelContainer, picker::FilePicker,
-    plugin::Plugin, problem::new_problem_panel, search::new_search_panel,
-    settings::LapceSettingsPanel, source_control::new_source_control_panel,
-    split::split_data_widget, status::LapceStatus, svg::get_svg,
-    terminal::TerminalPanel, title::Title,
+    alert::AlertBox,
+    completion::CompletionContainer,
+    explorer::FileExplorer,
+    hierarchy::{new_call_hierarchy_panel, new_type_hierarchy_panel},
+    hover::HoverContainer,
+    lsp_trace::new_lsp_trace_panel,
+    notification::NotificationList,
+    output::new_output_panel,
+    panel::PanelContainer,
+    peek::PeekContainer,
+    picker::FilePicker,
+    plugin::Plugin,
+    problem::new_problem_panel,
+    rename::RenameContainer,
+    search::new_search_panel,
+    settings::LapceSettingsPanel,
+    signature::SignatureContainer,
+    source_control::new_source_control_panel,
+    split::split_data_widget,
+    status::LapceStatus,
+    svg::get_svg,
+    terminal::TerminalPanel,
+    title::Title,
 };
 
 pub struct LapceIcon {
@@ -69,10 +91,14 @@ pub struct LapceTab {
     main_split: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     completion: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     hover: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    signature: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    peek: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    rename: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     status: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     picker: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     settings: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     alert: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    notification: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     panel_left: WidgetPod<LapceTabData, PanelContainer>,
     panel_bottom: WidgetPod<LapceTabData, PanelContainer>,
     panel_right: WidgetPod<LapceTabData, PanelContainer>,
@@ -95,6 +121,9 @@ impl LapceTab {
 
         let completion = CompletionContainer::new(&data.completion);
         let hover = HoverContainer::new(&data.hover);
+        let signature = SignatureContainer::new(&data.signature);
+        let peek = PeekContainer::new(data);
+        let rename = RenameContainer::new(data);
         let status = LapceStatus::new();
         let picker = FilePicker::new(data);
 
@@ -102,6 +131,7 @@ impl LapceTab {
             LapceSettingsPanel::new(data, WidgetId::next(), WidgetId::next());
 
         let alert = AlertBox::new(data);
+        let notification = NotificationList::new();
 
         let mut panel_left = PanelContainer::new(PanelContainerPosition::Left);
         let mut panel_bottom = PanelContainer::new(PanelContainerPosition::Bottom);
@@ -157,6 +187,30 @@ impl LapceTab {
                             WidgetPod::new(new_problem_panel(&data.problem).boxed()),
                         );
                     }
+                    PanelKind::CallHierarchy => {
+                        panel.insert_panel(
+                            *kind,
+                            WidgetPod::new(new_call_hierarchy_panel(data).boxed()),
+                        );
+                    }
+                    PanelKind::TypeHierarchy => {
+                        panel.insert_panel(
+                            *kind,
+                            WidgetPod::new(new_type_hierarchy_panel(data).boxed()),
+                        );
+                    }
+                    PanelKind::Output => {
+                        panel.insert_panel(
+                            *kind,
+                            WidgetPod::new(new_output_panel(data).boxed()),
+                        );
+                    }
+                    PanelKind::LspTrace => {
+                        panel.insert_panel(
+                            *kind,
+                            WidgetPod::new(new_lsp_trace_panel(data).boxed()),
+                        );
+                    }
                 }
             }
         }
@@ -167,10 +221,14 @@ impl LapceTab {
             main_split: WidgetPod::new(main_split.boxed()),
             completion: WidgetPod::new(completion.boxed()),
             hover: WidgetPod::new(hover.boxed()),
+            signature: WidgetPod::new(signature.boxed()),
+            peek: WidgetPod::new(peek.boxed()),
+            rename: WidgetPod::new(rename.boxed()),
             picker: WidgetPod::new(picker.boxed()),
             status: WidgetPod::new(status.boxed()),
             settings: WidgetPod::new(settings.boxed()),
             alert: WidgetPod::new(alert.boxed()),
+            notification: WidgetPod::new(notification.boxed()),
             panel_left: WidgetPod::new(panel_left),
             panel_right: WidgetPod::new(panel_right),
             panel_bottom: WidgetPod::new(panel_bottom),
@@ -682,6 +740,14 @@ impl LapceTab {
                     Target::Window(data.window_id),
                 ));
             }
+            Event::Command(cmd) if cmd.is(LAPCE_EXPORT_LSP_TRACE) => {
+                ctx.set_handled();
+                let file = cmd.get_unchecked(LAPCE_EXPORT_LSP_TRACE);
+                if let Err(e) = std::fs::write(&file.path, data.lsp_trace.to_jsonl())
+                {
+                    log::error!("failed to export the lsp trace: {e}");
+                }
+            }
             Event::Command(cmd) if cmd.is(LAPCE_OPEN_FILE) => {
                 ctx.set_handled();
                 let file = cmd.get_unchecked(LAPCE_OPEN_FILE);
@@ -837,9 +903,10 @@ impl LapceTab {
                         version,
                         content,
                     } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        let doc = Arc::make_mut(doc);
-                        doc.load_history(version, content.clone());
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc)
+                                .load_history(version, content.clone());
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateTerminalTitle(term_id, title) => {
@@ -988,11 +1055,10 @@ impl LapceTab {
                                         }
                                     }
                                     lsp_types::WorkDoneProgress::End(_end) => {
-                                        for view_id in data.main_split.editors.keys()
+                                        for (_, doc) in
+                                            data.main_split.open_docs.iter_mut()
                                         {
-                                            let editor_data =
-                                                data.editor_view_content(*view_id);
-                                            editor_data.doc.get_inlay_hints();
+                                            Arc::make_mut(doc).refresh_lsp();
                                         }
                                         for i in data
                                             .progresses
@@ -1088,6 +1154,9 @@ impl LapceTab {
                     }
                     LapceUICommand::ShowAlert(content) => {
                         let alert = Arc::make_mut(&mut data.alert);
+                        if let Some(request_id) = alert.message_request.take() {
+                            data.proxy.respond(request_id, Ok(Value::Null));
+                        }
                         alert.active = true;
                         alert.content = content.to_owned();
                         ctx.submit_command(Command::new(
@@ -1098,22 +1167,24 @@ impl LapceTab {
                         ctx.set_handled();
                     }
                     LapceUICommand::BufferSave(path, rev, exit_widget_id) => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        if doc.rev() == *rev {
-                            Arc::make_mut(doc).buffer_mut().set_pristine();
-                            if let Some(widget_id) = exit_widget_id {
-                                ctx.submit_command(Command::new(
-                                    LAPCE_COMMAND,
-                                    LapceCommand {
-                                        kind: CommandKind::Focus(
-                                            FocusCommand::SplitClose,
-                                        ),
-                                        data: None,
-                                    },
-                                    Target::Widget(*widget_id),
-                                ));
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc).buffer_mut().set_pristine();
+                                if let Some(widget_id) = exit_widget_id {
+                                    ctx.submit_command(Command::new(
+                                        LAPCE_COMMAND,
+                                        LapceCommand {
+                                            kind: CommandKind::Focus(
+                                                FocusCommand::SplitClose,
+                                            ),
+                                            data: None,
+                                        },
+                                        Target::Widget(*widget_id),
+                                    ));
+                                }
                             }
                         }
+                        data.main_split.close_hidden_document(path);
                         ctx.set_handled();
                     }
                     LapceUICommand::LoadBufferAndGoToPosition {
@@ -1306,10 +1377,98 @@ impl LapceTab {
                         }
                         ctx.set_handled();
                     }
-                    LapceUICommand::UpdateInlayHints { path, rev, hints } => {
+                    LapceUICommand::UpdateInlayHints {
+                        path,
+                        rev,
+                        interval,
+                        hints,
+                    } => {
                         if let Some(doc) = data.main_split.open_docs.get_mut(path) {
                             if doc.rev() == *rev {
-                                Arc::make_mut(doc).set_inlay_hints(hints.clone());
+                                Arc::make_mut(doc)
+                                    .set_inlay_hints(*interval, hints.clone());
+                            }
+                        }
+                    }
+                    LapceUICommand::UpdateCodeLens { path, rev, lenses } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc).set_code_lenses(lenses.clone());
+                            }
+                        }
+                    }
+                    LapceUICommand::UpdateResolvedCodeLens { path, rev, lens } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc)
+                                    .set_resolved_code_lens(lens.clone());
+                            }
+                        }
+                    }
+                    LapceUICommand::ApplyWorkspaceEdit { request_id, edit } => {
+                        let proxy = data.proxy.clone();
+                        let request_id = *request_id;
+                        data.main_split.apply_workspace_edit(
+                            ctx,
+                            edit,
+                            move |result| {
+                                let response = match result {
+                                    Ok(()) => ApplyWorkspaceEditResponse {
+                                        applied: true,
+                                        failure_reason: None,
+                                        failed_change: None,
+                                    },
+                                    Err(e) => ApplyWorkspaceEditResponse {
+                                        applied: false,
+                                        failure_reason: Some(e.to_string()),
+                                        failed_change: None,
+                                    },
+                                };
+                                proxy.respond(
+                                    request_id,
+                                    Ok(serde_json::to_value(response).unwrap()),
+                                );
+                            },
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RunInTerminal { cwd, command } => {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::RunInTerminal {
+                                cwd: cwd.clone(),
+                                command: command.clone(),
+                            },
+                            Target::Widget(data.terminal.split_id),
+                        ));
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateOccurrences {
+                        path,
+                        rev,
+                        occurrences,
+                    } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc)
+                                    .set_occurrences(occurrences.clone());
+                            }
+                        }
+                    }
+                    LapceUICommand::UpdateLinkedEditing {
+                        view_id,
+                        path,
+                        rev,
+                        ranges,
+                    } => {
+                        let doc_rev =
+                            data.main_split.open_docs.get(path).map(|doc| doc.rev());
+                        if doc_rev == Some(*rev) {
+                            if let Some(editor) =
+                                data.main_split.editors.get_mut(view_id)
+                            {
+                                Arc::make_mut(editor)
+                                    .set_linked_editing(ranges.clone());
                             }
                         }
                     }
@@ -1360,9 +1519,10 @@ impl LapceTab {
                         ctx.set_handled();
                     }
                     LapceUICommand::OpenFileChanged { path, content } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        let doc = Arc::make_mut(doc);
-                        doc.handle_file_changed(content.to_owned());
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc)
+                                .handle_file_changed(content.to_owned());
+                        }
                     }
                     LapceUICommand::ReloadBuffer { path, rev, content } => {
                         let doc = data.main_split.open_docs.get_mut(path).unwrap();
@@ -1404,14 +1564,31 @@ impl LapceTab {
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateSemanticStyles(_id, path, rev, styles) => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        if doc.rev() == *rev {
-                            let doc = Arc::make_mut(doc);
-                            doc.set_semantic_styles(Some(styles.clone()));
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc)
+                                    .set_semantic_styles(Some(styles.clone()));
+                            }
                         }
 
                         ctx.set_handled();
                     }
+                    LapceUICommand::UpdateSemanticStylesRange {
+                        path,
+                        rev,
+                        interval,
+                        styles,
+                    } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc).merge_semantic_styles(
+                                    *interval,
+                                    styles.clone(),
+                                );
+                            }
+                        }
+                        ctx.set_handled();
+                    }
                     LapceUICommand::Focus => {
                         let dir = data
                             .workspace
@@ -1460,7 +1637,10 @@ impl LapceTab {
                         ctx.set_handled();
                         let doc = match content {
                             BufferContent::File(path) => {
-                                data.main_split.open_docs.get_mut(path).unwrap()
+                                match data.main_split.open_docs.get_mut(path) {
+                                    Some(doc) => doc,
+                                    None => return,
+                                }
                             }
                             BufferContent::Local(kind) => {
                                 data.main_split.local_docs.get_mut(kind).unwrap()
@@ -1504,6 +1684,10 @@ impl LapceTab {
                         }
                         doc.trigger_syntax_change(None);
                     }
+                    LapceUICommand::RestartLanguageServer(name) => {
+                        ctx.set_handled();
+                        data.proxy.restart_language_server(name);
+                    }
                     LapceUICommand::UpdateHistoryChanges {
                         path,
                         rev,
@@ -1512,12 +1696,13 @@ impl LapceTab {
                         ..
                     } => {
                         ctx.set_handled();
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        Arc::make_mut(doc).update_history_changes(
-                            *rev,
-                            history,
-                            changes.clone(),
-                        );
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).update_history_changes(
+                                *rev,
+                                history,
+                                changes.clone(),
+                            );
+                        }
                     }
                     LapceUICommand::UpdateHistoryStyle {
                         path,
@@ -1526,9 +1711,12 @@ impl LapceTab {
                         ..
                     } => {
                         ctx.set_handled();
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        Arc::make_mut(doc)
-                            .update_history_styles(history, highlights.to_owned());
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).update_history_styles(
+                                history,
+                                highlights.to_owned(),
+                            );
+                        }
                     }
                     LapceUICommand::UpdatePickerPwd(path) => {
                         Arc::make_mut(&mut data.picker).pwd = path.clone();
@@ -1656,6 +1844,161 @@ impl LapceTab {
                         );
                         ctx.set_handled();
                     }
+                    LapceUICommand::StartRename {
+                        editor_view_id,
+                        path,
+                        rev,
+                        offset,
+                        response,
+                    } => {
+                        let rename = Arc::make_mut(&mut data.rename);
+                        rename.start(
+                            ctx,
+                            &mut data.main_split,
+                            *editor_view_id,
+                            path,
+                            *rev,
+                            *offset,
+                            response,
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowCallHierarchy {
+                        direction,
+                        buffer_id,
+                        items,
+                    } => {
+                        let call_hierarchy = Arc::make_mut(&mut data.call_hierarchy);
+                        call_hierarchy.start(
+                            *direction,
+                            *buffer_id,
+                            items.clone(),
+                            &data.proxy,
+                            ctx.get_external_handle(),
+                        );
+                        data.show_panel(ctx, PanelKind::CallHierarchy);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowTypeHierarchy {
+                        direction,
+                        buffer_id,
+                        items,
+                    } => {
+                        let type_hierarchy = Arc::make_mut(&mut data.type_hierarchy);
+                        type_hierarchy.start(
+                            *direction,
+                            *buffer_id,
+                            items.clone(),
+                            &data.proxy,
+                            ctx.get_external_handle(),
+                        );
+                        data.show_panel(ctx, PanelKind::TypeHierarchy);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowMessage { title, message } => {
+                        Arc::make_mut(&mut data.notification).push(
+                            message.typ,
+                            title.clone(),
+                            message.message.clone(),
+                        );
+                        Arc::make_mut(&mut data.output).log(
+                            title,
+                            message.typ,
+                            &message.message,
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::DismissNotification(id) => {
+                        Arc::make_mut(&mut data.notification).dismiss(*id);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::LogMessage { server, message } => {
+                        Arc::make_mut(&mut data.output).log(
+                            server,
+                            message.typ,
+                            &message.message,
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::LspTrace { server, entry } => {
+                        Arc::make_mut(&mut data.lsp_trace)
+                            .push(server.clone(), entry.clone());
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::LspRefresh => {
+                        for (_, doc) in data.main_split.open_docs.iter_mut() {
+                            Arc::make_mut(doc).refresh_lsp();
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::SignatureHelpTriggers {
+                        path,
+                        server,
+                        triggers,
+                    } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc)
+                                .set_signature_triggers(server, triggers.clone());
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::LanguageServerStatus { name, status } => {
+                        if *status == LanguageServerStatus::Stopped {
+                            data.language_servers.remove(name);
+                        } else {
+                            data.language_servers.insert(name.clone(), *status);
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowMessageRequest {
+                        request_id,
+                        title,
+                        message,
+                    } => {
+                        let alert = Arc::make_mut(&mut data.alert);
+                        // Only one request can be shown at a time
+                        if let Some(request_id) = alert.message_request.take() {
+                            data.proxy.respond(request_id, Ok(Value::Null));
+                        }
+                        let buttons = message
+                            .actions
+                            .iter()
+                            .flatten()
+                            .map(|action| {
+                                (
+                                    action.title.clone(),
+                                    data.id,
+                                    LapceCommand {
+                                        kind: CommandKind::Workbench(
+                                            LapceWorkbenchCommand::MessageRequestAction,
+                                        ),
+                                        data: Some(json!({
+                                            "request_id": request_id,
+                                            "action": action,
+                                        })),
+                                    },
+                                )
+                            })
+                            .collect();
+                        alert.active = true;
+                        alert.message_request = Some(*request_id);
+                        alert.content = AlertContentData {
+                            title: title.clone(),
+                            msg: message.message.clone(),
+                            buttons,
+                        };
+                        Arc::make_mut(&mut data.output).log(
+                            title,
+                            message.typ,
+                            &message.message,
+                        );
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::Focus,
+                            Target::Widget(alert.widget_id),
+                        ));
+                        ctx.set_handled();
+                    }
                     LapceUICommand::ExplorerEndNaming { apply_naming } => {
                         let file_explorer = Arc::make_mut(&mut data.file_explorer);
                         if *apply_naming {
@@ -1705,11 +2048,26 @@ impl Widget<LapceTabData> for LapceTab {
         {
             self.hover.event(ctx, event, data, env);
         }
+        if data.signature.status == SignatureStatus::Done
+            || event.should_propagate_to_hidden()
+        {
+            self.signature.event(ctx, event, data, env);
+        }
+        if data.peek.status == PeekStatus::Done || event.should_propagate_to_hidden()
+        {
+            self.peek.event(ctx, event, data, env);
+        }
+        if data.rename.status != RenameStatus::Inactive
+            || event.should_propagate_to_hidden()
+        {
+            self.rename.event(ctx, event, data, env);
+        }
 
         if !event.should_propagate_to_hidden() && !ctx.is_handled() {
             self.handle_event(ctx, event, data, env);
         }
 
+        self.notification.event(ctx, event, data, env);
         self.main_split.event(ctx, event, data, env);
         self.status.event(ctx, event, data, env);
         if data.panel.is_container_shown(&PanelContainerPosition::Left)
@@ -1793,9 +2151,13 @@ impl Widget<LapceTabData> for LapceTab {
         self.status.lifecycle(ctx, event, data, env);
         self.completion.lifecycle(ctx, event, data, env);
         self.hover.lifecycle(ctx, event, data, env);
+        self.signature.lifecycle(ctx, event, data, env);
+        self.peek.lifecycle(ctx, event, data, env);
+        self.rename.lifecycle(ctx, event, data, env);
         self.picker.lifecycle(ctx, event, data, env);
         self.settings.lifecycle(ctx, event, data, env);
         self.alert.lifecycle(ctx, event, data, env);
+        self.notification.lifecycle(ctx, event, data, env);
         self.panel_left.lifecycle(ctx, event, data, env);
         self.panel_right.lifecycle(ctx, event, data, env);
         self.panel_bottom.lifecycle(ctx, event, data, env);
@@ -1860,10 +2222,14 @@ impl Widget<LapceTabData> for LapceTab {
         self.main_split.update(ctx, data, env);
         self.completion.update(ctx, data, env);
         self.hover.update(ctx, data, env);
+        self.signature.update(ctx, data, env);
+        self.peek.update(ctx, data, env);
+        self.rename.update(ctx, data, env);
         self.status.update(ctx, data, env);
         self.picker.update(ctx, data, env);
         self.settings.update(ctx, data, env);
         self.alert.update(ctx, data, env);
+        self.notification.update(ctx, data, env);
         self.panel_left.update(ctx, data, env);
         self.panel_right.update(ctx, data, env);
         self.panel_bottom.update(ctx, data, env);
@@ -2002,6 +2368,28 @@ impl Widget<LapceTabData> for LapceTab {
             self.hover.set_origin(ctx, data, env, hover_origin);
         }
 
+        if data.signature.status == SignatureStatus::Done {
+            self.signature.layout(ctx, bc, data, env);
+            let signature_origin =
+                data.signature_origin(ctx.text(), self_size, &data.config);
+            self.signature.set_origin(ctx, data, env, signature_origin);
+        }
+
+        if data.peek.status == PeekStatus::Done {
+            let peek_bc = BoxConstraints::tight(data.peek_size());
+            let peek_size = self.peek.layout(ctx, &peek_bc, data, env);
+            let peek_origin =
+                data.peek_origin(ctx.text(), peek_size.height, &data.config);
+            self.peek.set_origin(ctx, data, env, peek_origin);
+        }
+
+        if data.rename.status != RenameStatus::Inactive {
+            let rename_size = self.rename.layout(ctx, bc, data, env);
+            let rename_origin =
+                data.rename_origin(ctx.text(), self_size, rename_size, &data.config);
+            self.rename.set_origin(ctx, data, env, rename_origin);
+        }
+
         if data.picker.active {
             let picker_size = self.picker.layout(ctx, bc, data, env);
             self.picker.set_origin(
@@ -2015,6 +2403,20 @@ impl Widget<LapceTabData> for LapceTab {
             );
         }
 
+        let notification_size = self.notification.layout(ctx, bc, data, env);
+        self.notification.set_origin(
+            ctx,
+            data,
+            env,
+            Point::new(
+                self_size.width - notification_size.width - 10.0,
+                self_size.height
+                    - status_size.height
+                    - notification_size.height
+                    - 10.0,
+            ),
+        );
+
         if data.alert.active {
             self.alert.layout(ctx, bc, data, env);
             self.alert.set_origin(ctx, data, env, Point::ZERO);
@@ -2106,8 +2508,14 @@ impl Widget<LapceTabData> for LapceTab {
         }
         self.title.paint(ctx, data, env);
         self.status.paint(ctx, data, env);
+        self.notification.paint(ctx, data, env);
+        self.peek.paint(ctx, data, env);
+        self.signature.paint(ctx, data, env);
         self.completion.paint(ctx, data, env);
         self.hover.paint(ctx, data, env);
+        if data.rename.status != RenameStatus::Inactive {
+            self.rename.paint(ctx, data, env);
+        }
         self.picker.paint(ctx, data, env);
         self.settings.paint(ctx, data, env);
         ctx.incr_alpha_depth();